@@ -1,4 +1,5 @@
-import type {RunnerScript, ScriptMetadata} from "@/shared/types/common.ts";
+import type {RunnerScript, RunScriptResult, ScriptMetadata} from "@/shared/types/common.ts";
+import type {BatchItem} from "@/entities/scripts/types.ts";
 
 export const mapRunnerScript = (script: ScriptMetadata): RunnerScript => {
     return {
@@ -16,4 +17,23 @@ export const mapRunnerScript = (script: ScriptMetadata): RunnerScript => {
             loading: false,
         },
     };
-};
\ No newline at end of file
+};
+
+// Ошибка, таймаут и пропуск показываются как stderr, как и ошибка одиночного запуска
+export const mapBatchItem = (item: BatchItem): RunScriptResult => {
+    switch (item.status) {
+        case 'ok':
+            return item.result;
+        case 'timeout':
+            return {
+                exit_code: item.result?.exit_code ?? 0,
+                stdout: item.result?.stdout ?? '',
+                stderr: item.result?.stderr || item.error.message,
+                timed_out: true,
+            };
+        case 'error':
+            return { exit_code: 0, stdout: '', stderr: item.error.message, timed_out: false };
+        case 'skipped':
+            return { exit_code: 0, stdout: '', stderr: item.reason, timed_out: false };
+    }
+};
@@ -1,8 +1,8 @@
 import HttpClient from "@/services/httpClient.ts";
 import type {RunScriptsResponse, ScriptsRequestParams} from "@/entities/scripts/types.ts";
-import type {RunScriptParams, ScriptMetadata} from "@/shared/types/common.ts";
+import type {RunScriptParams, RunScriptResult, ScriptMetadata} from "@/shared/types/common.ts";
 import {scriptsRoutesMap} from "@/entities/scripts/routes.map.ts";
-import {mapRunnerScript} from "@/entities/mappers/script.map.ts";
+import {mapBatchItem, mapRunnerScript} from "@/entities/mappers/script.map.ts";
 
 const defaultParams: ScriptsRequestParams = {
     sort_by: 'created',
@@ -30,5 +30,10 @@ export const runScripts = async (
         DEFAULT_PARAMS
     );
 
-    return data;
+    const results: Record<string, RunScriptResult> = {};
+    for (const [name, item] of Object.entries(data.results)) {
+        results[name] = mapBatchItem(item);
+    }
+
+    return { results };
 }
\ No newline at end of file
@@ -7,6 +7,18 @@ export interface ScriptsRequestParams {
 }
 
 
+export interface BatchError {
+    code: string;
+    message: string;
+}
+
+// Результат скрипта в пакетном запуске, различается по `status`
+export type BatchItem =
+    | { status: 'ok'; result: RunScriptResult }
+    | { status: 'error'; error: BatchError }
+    | { status: 'timeout'; error: BatchError; result?: RunScriptResult }
+    | { status: 'skipped'; reason: string };
+
 export interface RunScriptsResponse {
-    results: Record<string, RunScriptResult>;
+    results: Record<string, BatchItem>;
 }
\ No newline at end of file
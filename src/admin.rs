@@ -0,0 +1,85 @@
+use crate::{
+    app_state::AppState,
+    error::AppError,
+    models::{AdminConfig, AdminConfigPatch},
+};
+use std::{fmt::Display, sync::Arc, time::Duration};
+use tracing::info;
+
+/// Действующие значения настроек, изменяемых без перезапуска
+pub fn current(state: &AppState) -> AdminConfig {
+    let (rate_limit, rate_burst) = state.rate_limiter.limits();
+    AdminConfig {
+        cache_ttl_secs: state.cache_ttl().as_secs(),
+        default_timeout_secs: state.script_timeout().as_secs(),
+        max_output_bytes: state.max_output_bytes(),
+        rate_limit,
+        rate_burst,
+        max_concurrent: state.max_concurrent(),
+    }
+}
+
+/// Применяет изменения, только если допустимы все значения; каждое
+/// изменение пишется в лог. Новые значения действуют со следующего запуска.
+pub fn apply(state: &Arc<AppState>, user: &str, patch: AdminConfigPatch) -> Result<AdminConfig, AppError> {
+    validate(state, &patch)?;
+
+    if let Some(secs) = patch.cache_ttl_secs {
+        let old = state.set_cache_ttl(Duration::from_secs(secs));
+        log_change(user, "cache_ttl_secs", old.as_secs(), secs);
+    }
+    if let Some(secs) = patch.default_timeout_secs {
+        let old = state.set_script_timeout(Duration::from_secs(secs));
+        log_change(user, "default_timeout_secs", old.as_secs(), secs);
+    }
+    if let Some(bytes) = patch.max_output_bytes {
+        let old = state.set_max_output_bytes(bytes);
+        log_change(user, "max_output_bytes", old, bytes);
+    }
+    if patch.rate_limit.is_some() || patch.rate_burst.is_some() {
+        let (old_limit, old_burst) = state.rate_limiter.limits();
+        state.rate_limiter.set_limits(
+            patch.rate_limit.unwrap_or(old_limit),
+            patch.rate_burst.unwrap_or(old_burst),
+        );
+        let (limit, burst) = state.rate_limiter.limits();
+        log_change(user, "rate_limit", old_limit, limit);
+        log_change(user, "rate_burst", old_burst, burst);
+    }
+    if let Some(limit) = patch.max_concurrent {
+        let old = state.set_max_concurrent(limit);
+        log_change(user, "max_concurrent", old, limit);
+    }
+    Ok(current(state))
+}
+
+fn validate(state: &AppState, patch: &AdminConfigPatch) -> Result<(), AppError> {
+    if let Some(secs) = patch.default_timeout_secs {
+        let max = state.max_script_timeout.as_secs();
+        if secs == 0 || secs > max {
+            return Err(AppError::BadRequest(format!(
+                "default_timeout_secs must be between 1 and {} (RUNNER_MAX_SCRIPT_TIMEOUT_SECS)",
+                max
+            )));
+        }
+    }
+    if patch.max_output_bytes == Some(0) {
+        return Err(AppError::BadRequest("max_output_bytes must be greater than 0".to_string()));
+    }
+    // Лишние слоты забираются у семафора одним запросом не больше u32::MAX
+    if let Some(limit) = patch.max_concurrent {
+        if limit == 0 || limit >= u32::MAX as usize {
+            return Err(AppError::BadRequest(format!(
+                "max_concurrent must be between 1 and {}",
+                u32::MAX - 1
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn log_change<T: PartialEq + Display>(user: &str, field: &str, old: T, new: T) {
+    if old != new {
+        info!("{} changed {} at runtime: {} -> {}", user, field, old, new);
+    }
+}
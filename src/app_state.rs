@@ -1,43 +1,426 @@
 use std::{
-    collections::HashMap,
-    path::PathBuf,
+    collections::{HashMap, HashSet, VecDeque},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
     time::{Duration, Instant, SystemTime},
 };
+use chrono::{DateTime, Utc};
 use mongodb::Database;
-use tokio::sync::{Mutex, Semaphore};
+use uuid::Uuid;
+use crate::{audit::AuditLog, auth_middleware::ApiToken, cache::ResultCache, inflight::InFlight, jobs::JobRequest, config::Config, error::AppError, cron::CronExpr, fetch::Fetcher, gc::Gc, events::Events, exec_user::ExecUser, network::{self, IsolationFallback}, history_db::HistoryDb, idempotency::Idempotency, namespace::Namespaces, outputs::RunOutputs, run_log::RunLogs, models::{Artifact, DepsState, EnvironmentReport, HealthCheck, HistoryEntry, JobInfo, RunLimits, Schedule, ScriptConfig, ScriptInterface, ScriptKind, ScriptStats}, quota::Quotas, rate_limit::RateLimiter, sandbox::Sandbox, secrets::SecretStore, script_runner::PipeOutput, shutdown::Shutdown, utils::{ensure_inside, join_name, relative_name, validate_script_name}, webhook::Webhooks, worker_pool::{WorkerPool, POOL_EXTENSION}};
+use tokio::sync::{watch, Mutex, Notify, Semaphore};
 
-#[derive(Clone)]
 pub struct CachedResult {
-    pub stdout: String,
-    pub stderr: String,
+    // Исходные байты вывода: попадание в кэш отдаёт тот же результат
+    pub stdout: PipeOutput,
+    pub stderr: PipeOutput,
+    pub artifacts: Vec<Artifact>,
     pub exit_code: i32,
+    // Время исходного запуска: попадание в кэш сообщает его, а не нулевую длительность
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub duration_ms: u64,
     pub timestamp: Instant,
-    pub script_mtime: SystemTime,
+    // SHA-256 скрипта, с которым получен результат
+    pub script_hash: String,
+}
+
+pub struct JobRecord {
+    pub info: JobInfo,
+    // Момент завершения — от него отсчитывается срок хранения
+    pub finished: Option<Instant>,
+    pub cancel: Arc<Notify>,
+    // Параметры запуска, пока задача не завершена
+    pub request: Option<JobRequest>,
+    // `true` после завершения; будит все запросы, ждущие результата
+    pub done: watch::Sender<bool>,
+}
+
+pub struct ScheduleRecord {
+    pub info: Schedule,
+    pub cron: CronExpr,
+    // Удерживается на время запуска: так обнаруживаются перекрывающиеся запуски
+    pub running: Arc<Mutex<()>>,
+}
+
+/// Известный сканеру скрипт: настройки запуска и SHA-256 содержимого (ETag)
+#[derive(Clone, Default)]
+pub struct ScriptEntry {
+    pub config: ScriptConfig,
+    pub kind: ScriptKind,
+    pub hash: String,
+    // mtime файла (у пакета — самого нового из его файлов), для которого посчитан хэш
+    pub mtime: Option<SystemTime>,
+}
+
+/// Слоты одновременных запусков одного скрипта
+pub struct ScriptLimiter {
+    pub limit: usize,
+    pub semaphore: Arc<Semaphore>,
+    // Запуски, ожидающие слота скрипта, и отклонённые без слота
+    pub queued: AtomicUsize,
+    pub rejections: AtomicU64,
+}
+
+impl ScriptLimiter {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            semaphore: Arc::new(Semaphore::new(limit)),
+            queued: AtomicUsize::new(0),
+            rejections: AtomicU64::new(0),
+        }
+    }
+
+    pub fn running(&self) -> usize {
+        self.limit - self.semaphore.available_permits()
+    }
+}
+
+// Последняя установка зависимостей в виртуальное окружение
+pub struct DepsRecord {
+    // Хэш requirements, для которого шла установка
+    pub hash: String,
+    pub state: DepsState,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub error: Option<String>,
+    pub log: VecDeque<String>,
 }
 
 pub struct AppState {
     pub scripts_dir: PathBuf,
     pub db: Database,
-    pub scripts: Mutex<Vec<PathBuf>>,
+    // Известные сканеру скрипты и их настройки запуска
+    pub scripts: Mutex<HashMap<PathBuf, ScriptEntry>>,
     pub semaphore: Semaphore,
-    pub cache: Mutex<HashMap<String, CachedResult>>,
-    pub cache_ttl: Duration,
+    // Значения, меняющиеся через PATCH /admin/config; читаются методами
+    max_concurrent: AtomicUsize,
+    // Сколько запусков ждёт слота и сколько отклонено после RUNNER_MAX_QUEUE_WAIT_SECS
+    pub queued: AtomicUsize,
+    pub busy_rejections: AtomicU64,
+    pub max_queue_wait: Option<Duration>,
+    // Ограничения запусков отдельных скриптов по имени скрипта
+    pub script_limiters: Mutex<HashMap<String, Arc<ScriptLimiter>>>,
+    pub script_max_concurrent: Option<usize>,
+    pub script_max_queued: usize,
+    // Пул тёплых воркеров для Python-скриптов (RUNNER_POOL_SIZE > 0)
+    pub pool: Option<WorkerPool>,
+    // Ограничения ресурсов процесса по умолчанию (RUNNER_MAX_MEMORY_MB, RUNNER_MAX_CPU_SECS)
+    pub memory_limit_mb: Option<u64>,
+    pub cpu_limit_secs: Option<u64>,
+    // Пользователь процессов скриптов (RUNNER_EXEC_UID, RUNNER_EXEC_GID)
+    pub exec_user: Option<ExecUser>,
+    // Почему процесс скрипта нельзя изолировать от сети; `None` — можно
+    pub network_unavailable: Option<String>,
+    pub network_fallback: IsolationFallback,
+    // Команда песочницы для процессов скриптов (RUNNER_SANDBOX)
+    pub sandbox: Option<Arc<Sandbox>>,
+    pub cache: Mutex<ResultCache>,
+    // Выполняющиеся запуски для объединения одинаковых запросов
+    pub inflight: InFlight,
+    cache_ttl_ms: AtomicU64,
+    script_timeout_ms: AtomicU64,
+    pub max_script_timeout: Duration,
+    pub jobs: Mutex<HashMap<Uuid, JobRecord>>,
+    // Расписания запусков по идентификатору
+    pub schedules: Mutex<HashMap<String, ScheduleRecord>>,
+    pub job_retention: Duration,
+    pub max_job_wait: Duration,
+    // Расширение файла -> команда интерпретатора
+    pub interpreters: HashMap<String, String>,
+    // Интерпретаторы, которые клиент может выбрать в запросе
+    pub allowed_interpreters: HashSet<String>,
+    // Установки зависимостей по пути виртуального окружения
+    pub deps: Mutex<HashMap<PathBuf, DepsRecord>>,
+    pub deps_install_timeout: Duration,
+    // Последняя проверка интерпретаторов для /readyz
+    pub interpreter_checks: Mutex<Option<(Instant, Vec<HealthCheck>)>>,
+    // Отчёты GET /environment по команде интерпретатора
+    pub environments: Mutex<HashMap<String, EnvironmentReport>>,
+    // Разобранные интерфейсы скриптов и хэш содержимого, для которого они получены
+    pub interfaces: Mutex<HashMap<PathBuf, (String, ScriptInterface)>>,
+    // Кольцевой буфер последних запусков
+    pub history: Mutex<VecDeque<HistoryEntry>>,
+    pub history_capacity: usize,
+    // Журнал в SQLite (`RUNNER_HISTORY_DB`); заменяет историю в памяти
+    pub history_db: Option<HistoryDb>,
+    // Счётчики запусков по скриптам; отдельная блокировка от кэша и списка скриптов
+    pub stats: Mutex<HashMap<String, ScriptStats>>,
+    // Поток событий для GET /events
+    pub events: Events,
+    // Учёт запусков для корректной остановки сервера
+    pub shutdown: Shutdown,
+    pub rate_limiter: RateLimiter,
+    // Квоты запусков по скриптам и вызывающим
+    pub quotas: Quotas,
+    // Статические API-токены (в дополнение к JWT)
+    pub api_tokens: Vec<ApiToken>,
+    // Лимит вывода на каждый из потоков stdout/stderr
+    max_output_bytes: AtomicUsize,
+    // Лимит тела запроса с произвольными данными (raw-запуск, импорт архива)
+    pub max_raw_body_bytes: usize,
+    // Лимит кода скрипта при создании и изменении через API
+    pub max_script_bytes: usize,
+    // Лимит тела JSON-запроса на запуск
+    pub max_run_body_bytes: usize,
+    // Ограничения аргументов и data в запросах на запуск
+    pub run_limits: RunLimits,
+    // Сколько предыдущих версий хранить для каждого скрипта
+    pub max_script_versions: usize,
+    // Файлы-результаты запусков по run_id и срок их хранения
+    pub artifacts_dir: PathBuf,
+    pub max_artifact_bytes: u64,
+    pub artifact_retention: Duration,
+    // Разрешён ли POST /eval (RUNNER_ALLOW_EVAL)
+    pub allow_eval: bool,
+    // Скрипты нельзя изменять через API (RUNNER_READ_ONLY)
+    pub read_only: bool,
+    // Уведомления о завершении запусков на callback_url
+    pub webhooks: Webhooks,
+    pub namespaces: Namespaces,
+    // Очистка устаревших данных запусков и итог последней
+    pub gc: Gc,
+    // stdout недавних запусков для input_from
+    pub outputs: RunOutputs,
+    // Ответы на запросы с Idempotency-Key
+    pub idempotency: Idempotency,
+    // Объединённые логи stdout и stderr последних запусков
+    pub run_logs: RunLogs,
+    pub fetcher: Fetcher,
+    // Журнал аудита изменений скриптов через API
+    pub audit: AuditLog,
+    // Пользователи, которым доступно управление секретами (RUNNER_ADMIN_USERS)
+    pub admin_users: Vec<String>,
+    pub secrets: SecretStore,
 }
 
 impl AppState {
     pub fn new(
-        scripts_dir: PathBuf,
+        config: &Config,
         db: Database,
-        max_concurrent: usize,
-        cache_ttl: Duration,
+        history_db: Option<HistoryDb>,
+        audit: AuditLog,
+        secrets: SecretStore,
     ) -> Self {
+        let idempotency = Idempotency::new(config, history_db.as_ref());
         Self {
-            scripts_dir,
+            scripts_dir: config.scripts_dir.clone(),
             db,
-            scripts: Mutex::new(Vec::new()),
-            semaphore: Semaphore::new(max_concurrent),
-            cache: Mutex::new(HashMap::new()),
-            cache_ttl,
+            scripts: Mutex::new(HashMap::new()),
+            semaphore: Semaphore::new(config.max_concurrent),
+            max_concurrent: AtomicUsize::new(config.max_concurrent),
+            queued: AtomicUsize::new(0),
+            busy_rejections: AtomicU64::new(0),
+            max_queue_wait: config.max_queue_wait,
+            script_limiters: Mutex::new(HashMap::new()),
+            script_max_concurrent: config.script_max_concurrent,
+            script_max_queued: config.script_max_queued,
+            pool: match config.interpreters.get(POOL_EXTENSION) {
+                Some(interpreter) if config.pool_size > 0 => Some(WorkerPool::start(
+                    interpreter,
+                    config.pool_size,
+                    config.pool_max_task_memory_mb,
+                )),
+                _ => None,
+            },
+            memory_limit_mb: config.memory_limit_mb,
+            cpu_limit_secs: config.cpu_limit_secs,
+            exec_user: config.exec_user,
+            network_unavailable: network::probe(config.exec_user).err(),
+            network_fallback: config.network_fallback,
+            sandbox: config.sandbox.clone().map(Arc::new),
+            cache: Mutex::new(ResultCache::new(config.cache_max_entries)),
+            inflight: InFlight::default(),
+            cache_ttl_ms: AtomicU64::new(config.cache_ttl.as_millis() as u64),
+            script_timeout_ms: AtomicU64::new(config.script_timeout.as_millis() as u64),
+            max_script_timeout: config.max_script_timeout,
+            jobs: Mutex::new(HashMap::new()),
+            schedules: Mutex::new(HashMap::new()),
+            job_retention: config.job_retention,
+            max_job_wait: config.max_job_wait,
+            interpreters: config.interpreters.clone(),
+            allowed_interpreters: config.allowed_interpreters.clone(),
+            deps: Mutex::new(HashMap::new()),
+            deps_install_timeout: config.deps_install_timeout,
+            interpreter_checks: Mutex::new(None),
+            environments: Mutex::new(HashMap::new()),
+            interfaces: Mutex::new(HashMap::new()),
+            history: Mutex::new(VecDeque::with_capacity(config.history_size)),
+            history_capacity: config.history_size,
+            history_db,
+            stats: Mutex::new(HashMap::new()),
+            events: Events::default(),
+            shutdown: Shutdown::default(),
+            rate_limiter: RateLimiter::new(
+                config.rate_limit,
+                config.rate_burst,
+                config.rate_limit_exempt.clone(),
+            ),
+            quotas: Quotas::new(config.script_quota, config.caller_quota, config.quota_count_cache_hits),
+            api_tokens: config.api_tokens.clone(),
+            max_output_bytes: AtomicUsize::new(config.max_output_bytes),
+            run_limits: config.run_limits,
+            max_raw_body_bytes: config.max_raw_body_bytes,
+            max_script_bytes: config.max_script_bytes,
+            max_run_body_bytes: config.max_run_body_bytes,
+            max_script_versions: config.max_script_versions,
+            artifacts_dir: config.artifacts_dir.clone(),
+            max_artifact_bytes: config.max_artifact_bytes,
+            artifact_retention: config.artifact_retention,
+            allow_eval: config.allow_eval,
+            read_only: config.read_only,
+            webhooks: Webhooks::new(config.callback_hosts.clone()),
+            fetcher: Fetcher::new(config.fetch_hosts.clone()),
+            namespaces: Namespaces::load(&config.scripts_dir, config.namespace_defaults),
+            gc: Gc::new(config),
+            outputs: RunOutputs::new(config.output_store_bytes, config.output_retention),
+            idempotency,
+            run_logs: RunLogs::new(config.run_logs_per_script),
+            audit,
+            admin_users: config.admin_users.clone(),
+            secrets,
+        }
+    }
+
+    /// Команда интерпретатора для скрипта по расширению его файла
+    pub fn interpreter_for(&self, script_name: &str) -> Result<&str, AppError> {
+        Path::new(script_name)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| self.interpreters.get(ext))
+            .map(String::as_str)
+            .ok_or_else(|| AppError::UnsupportedScriptType(script_name.to_string()))
+    }
+
+    /// Проверяет интерпретатор, запрошенный клиентом, по `RUNNER_ALLOWED_INTERPRETERS`
+    pub fn check_interpreter(&self, interpreter: &str) -> Result<(), AppError> {
+        if self.allowed_interpreters.contains(interpreter) {
+            Ok(())
+        } else {
+            Err(AppError::InterpreterNotAllowed(interpreter.to_string()))
+        }
+    }
+
+    /// Отклоняет изменение скриптов, если сервер запущен с `RUNNER_READ_ONLY`
+    pub fn check_writable(&self) -> Result<(), AppError> {
+        if self.read_only {
+            Err(AppError::Forbidden(
+                "Server is read-only (RUNNER_READ_ONLY): scripts cannot be modified via the API".to_string(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Поддерживается ли расширение файла
+    pub fn is_supported_script(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| self.interpreters.contains_key(ext))
+            .unwrap_or(false)
+    }
+
+    /// Путь к файлу скрипта по имени
+    pub fn script_path(&self, name: &str) -> PathBuf {
+        join_name(&self.scripts_dir, name)
+    }
+
+    /// Путь к файлу скрипта для чтения и записи через API: имя проверено, и
+    /// с учётом символических ссылок путь не выходит за директорию скриптов
+    pub async fn checked_script_path(&self, name: &str) -> Result<PathBuf, AppError> {
+        validate_script_name(name)?;
+        let path = self.script_path(name);
+        ensure_inside(&self.scripts_dir, &path).await?;
+        Ok(path)
+    }
+
+    /// Имя скрипта по пути к файлу — путь относительно директории скриптов
+    /// через `/`. `None` для файлов вне директории, скрытых и вложенных
+    /// глубже `MAX_SCRIPT_DEPTH`.
+    pub fn script_name(&self, path: &Path) -> Option<String> {
+        let name = relative_name(&self.scripts_dir, path)?;
+        validate_script_name(&name).ok()?;
+        Some(name)
+    }
+
+    /// Таймаут выполнения с учётом запрошенного клиентом значения,
+    /// ограниченный сверху серверным максимумом.
+    pub fn effective_timeout(&self, requested_secs: Option<u64>) -> Duration {
+        match requested_secs {
+            Some(secs) => Duration::from_secs(secs.max(1)).min(self.max_script_timeout),
+            None => self.script_timeout(),
         }
     }
+
+    /// Сколько ждать слота: запрошенное клиентом значение не больше
+    /// `max_queue_wait`. Без запроса задания ждут сколько угодно.
+    pub fn queue_wait(&self, requested_secs: Option<u64>, job: bool) -> Option<Duration> {
+        match (requested_secs, self.max_queue_wait) {
+            (Some(secs), Some(max)) => Some(Duration::from_secs(secs).min(max)),
+            (Some(secs), None) => Some(Duration::from_secs(secs)),
+            (None, _) if job => None,
+            (None, max) => max,
+        }
+    }
+
+    /// Слотов одновременных запусков
+    pub fn max_concurrent(&self) -> usize {
+        self.max_concurrent.load(Ordering::Relaxed)
+    }
+
+    /// Запусков, занявших слот
+    pub fn running(&self) -> usize {
+        self.max_concurrent().saturating_sub(self.semaphore.available_permits())
+    }
+
+    /// Меняет число слотов, возвращает прежнее. Если свободных слотов меньше,
+    /// чем нужно убрать, остальные забираются по мере завершения запусков;
+    /// до этого новые запуски ждут.
+    pub fn set_max_concurrent(self: &Arc<Self>, limit: usize) -> usize {
+        let old = self.max_concurrent.swap(limit, Ordering::Relaxed);
+        if limit > old {
+            self.semaphore.add_permits(limit - old);
+        } else if limit < old {
+            let excess = old - limit;
+            let pending = excess - self.semaphore.forget_permits(excess);
+            if pending > 0 {
+                let state = Arc::clone(self);
+                tokio::spawn(async move {
+                    if let Ok(permits) = state.semaphore.acquire_many(pending as u32).await {
+                        permits.forget();
+                    }
+                });
+            }
+        }
+        old
+    }
+
+    pub fn cache_ttl(&self) -> Duration {
+        Duration::from_millis(self.cache_ttl_ms.load(Ordering::Relaxed))
+    }
+
+    pub fn set_cache_ttl(&self, ttl: Duration) -> Duration {
+        Duration::from_millis(self.cache_ttl_ms.swap(ttl.as_millis() as u64, Ordering::Relaxed))
+    }
+
+    /// Таймаут запуска, если клиент и настройки скрипта его не задали
+    pub fn script_timeout(&self) -> Duration {
+        Duration::from_millis(self.script_timeout_ms.load(Ordering::Relaxed))
+    }
+
+    pub fn set_script_timeout(&self, timeout: Duration) -> Duration {
+        Duration::from_millis(self.script_timeout_ms.swap(timeout.as_millis() as u64, Ordering::Relaxed))
+    }
+
+    pub fn max_output_bytes(&self) -> usize {
+        self.max_output_bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn set_max_output_bytes(&self, bytes: usize) -> usize {
+        self.max_output_bytes.swap(bytes, Ordering::Relaxed)
+    }
 }
\ No newline at end of file
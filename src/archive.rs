@@ -0,0 +1,248 @@
+use crate::{
+    app_state::AppState,
+    audit::{Actor, Snapshot},
+    db,
+    error::AppError,
+    history,
+    history_db::ScriptChange,
+    models::{ImportFileResult, ImportMode, ImportStatus, ScriptKind},
+    package,
+    script_runner,
+    utils::{relative_name, validate_script_name, write_atomic},
+    versions,
+};
+use chrono::{DateTime, Utc};
+use mongodb::bson::{doc, Bson, DateTime as BsonDateTime};
+use std::{
+    io::{Cursor, Read, Write},
+    path::{Component, Path},
+    time::SystemTime,
+};
+use tokio::fs;
+use tracing::info;
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+fn zip_error(e: zip::result::ZipError) -> AppError {
+    AppError::BadRequest(format!("Invalid zip archive: {}", e))
+}
+
+/// Zip-архив всех известных сканеру скриптов; пути в архиве совпадают
+/// с именами скриптов (`etl/extract.py`), пакеты попадают в него своими файлами
+pub async fn export_scripts(state: &AppState) -> Result<Vec<u8>, AppError> {
+    let mut scripts: Vec<_> = state
+        .scripts
+        .lock()
+        .await
+        .iter()
+        .map(|(path, entry)| (path.clone(), entry.kind))
+        .collect();
+    scripts.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut paths = Vec::with_capacity(scripts.len());
+    for (path, kind) in scripts {
+        match kind {
+            ScriptKind::File => paths.push(path),
+            // Пакет мог быть удалён после последнего сканирования
+            ScriptKind::Package => match package::snapshot(&path).await {
+                Ok(snapshot) => paths.extend(snapshot.files),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            },
+        }
+    }
+
+    let mut files = Vec::with_capacity(paths.len());
+    for path in paths {
+        let Some(name) = relative_name(&state.scripts_dir, &path) else {
+            continue;
+        };
+        // Файл мог быть удалён после последнего сканирования
+        match fs::read(&path).await {
+            Ok(content) => files.push((name, content)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    info!("Exporting {} scripts", files.len());
+    tokio::task::spawn_blocking(move || {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        for (name, content) in files {
+            writer.start_file(name, options).map_err(zip_error)?;
+            writer.write_all(&content)?;
+        }
+        Ok(writer.finish().map_err(zip_error)?.into_inner())
+    })
+    .await
+    .map_err(|e| AppError::Internal(format!("Export task failed: {}", e)))?
+}
+
+// Путь записи с `..`, абсолютный или с обратными слешами может указывать
+// за пределы директории скриптов
+fn is_unsafe_entry(name: &str) -> bool {
+    name.contains('\\')
+        || Path::new(name)
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+// Записи архива: имя и содержимое; каталоги пропускаются
+fn read_entries(bytes: Vec<u8>, max_file_bytes: usize) -> Result<Vec<(String, Vec<u8>)>, AppError> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).map_err(zip_error)?;
+    let mut entries = Vec::with_capacity(archive.len());
+    for i in 0..archive.len() {
+        let file = archive.by_index(i).map_err(zip_error)?;
+        let name = file.name().to_string();
+        if is_unsafe_entry(&name) {
+            return Err(AppError::BadRequest(format!(
+                "Archive entry '{}' points outside the scripts directory",
+                name
+            )));
+        }
+        if file.is_dir() {
+            continue;
+        }
+        // Размер из заголовка не доверяем: читаем не больше лимита + 1 байт
+        let mut content = Vec::new();
+        file.take(max_file_bytes as u64 + 1).read_to_end(&mut content)?;
+        if content.len() > max_file_bytes {
+            return Err(AppError::BadRequest(format!(
+                "Archive entry '{}' exceeds {} bytes",
+                name, max_file_bytes
+            )));
+        }
+        entries.push((name, content));
+    }
+    Ok(entries)
+}
+
+/// Импортирует скрипты из zip-архива. Архив с опасными путями отклоняется
+/// целиком; записи с недопустимыми именами пропускаются с пометкой `invalid`.
+pub async fn import_scripts(
+    state: &AppState,
+    actor: &Actor,
+    bytes: Vec<u8>,
+    mode: ImportMode,
+    max_file_bytes: usize,
+) -> Result<Vec<ImportFileResult>, AppError> {
+    let entries = tokio::task::spawn_blocking(move || read_entries(bytes, max_file_bytes))
+        .await
+        .map_err(|e| AppError::Internal(format!("Import task failed: {}", e)))??;
+
+    // В режиме fail конфликт проверяется до записи первого файла
+    if mode == ImportMode::Fail {
+        for (name, _) in &entries {
+            if validate_entry(state, name).is_ok() && state.script_path(name).exists() {
+                return Err(AppError::ScriptAlreadyExists(name.clone()));
+            }
+        }
+    }
+
+    let mut results = Vec::with_capacity(entries.len());
+    for (name, content) in entries {
+        let result = match import_file(state, actor, &name, content, mode).await {
+            Ok(status) => ImportFileResult {
+                name,
+                status,
+                error: None,
+            },
+            Err(e @ (AppError::InvalidScriptName(_)
+            | AppError::UnsupportedScriptType(_)
+            | AppError::Utf8(_))) => ImportFileResult {
+                name,
+                status: ImportStatus::Invalid,
+                error: Some(e.to_string()),
+            },
+            Err(e) => return Err(e),
+        };
+        results.push(result);
+    }
+
+    info!("Imported scripts archive: {} entries", results.len());
+    Ok(results)
+}
+
+fn validate_entry(state: &AppState, name: &str) -> Result<(), AppError> {
+    validate_script_name(name)?;
+    state.interpreter_for(name)?;
+    Ok(())
+}
+
+async fn import_file(
+    state: &AppState,
+    actor: &Actor,
+    name: &str,
+    content: Vec<u8>,
+    mode: ImportMode,
+) -> Result<ImportStatus, AppError> {
+    validate_entry(state, name)?;
+    let code = String::from_utf8(content)?;
+
+    let path = state.checked_script_path(name).await?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).await?;
+    }
+    let exists = path.exists();
+    let mut before = None;
+    let status = match (exists, mode) {
+        (true, ImportMode::Skip) => return Ok(ImportStatus::Skipped),
+        (true, ImportMode::Fail) => return Err(AppError::ScriptAlreadyExists(name.to_string())),
+        (true, ImportMode::Overwrite) => {
+            before = Snapshot::read(&path).await;
+            versions::save_version(state, name).await?;
+            write_atomic(&path, code.as_bytes(), true).await?;
+            ImportStatus::Overwritten
+        }
+        (false, _) => {
+            match write_atomic(&path, code.as_bytes(), false).await {
+                Ok(()) => {}
+                // Файл появился между проверкой и записью
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    return Ok(ImportStatus::Skipped)
+                }
+                Err(e) => return Err(e.into()),
+            }
+            ImportStatus::Created
+        }
+    };
+
+    let after = Snapshot::of(code.as_bytes());
+    history::record_change(state, actor, name, ScriptChange::Import, before, Some(after)).await;
+
+    let meta = fs::metadata(&path).await?;
+    let modified: DateTime<Utc> = meta
+        .modified()
+        .unwrap_or_else(|_| SystemTime::now())
+        .into();
+    let modified = BsonDateTime::from_millis(modified.timestamp_millis());
+
+    // Описание и результат существующего документа сохраняются
+    if db::get_script_by_name(&state.db, name).await?.is_some() {
+        let update = doc! {
+            "code": &code,
+            "size": meta.len() as i64,
+            "modified": modified,
+            "origin": Bson::Null,
+        };
+        db::update_script(&state.db, name, update).await?;
+    } else {
+        let doc = db::ScriptDoc {
+            id: None,
+            name: name.to_string(),
+            code,
+            description: None,
+            result: None,
+            size: meta.len(),
+            created: modified,
+            modified,
+            origin: None,
+        };
+        db::replace_script(&state.db, doc).await?;
+    }
+
+    script_runner::invalidate_script_cache(state, name).await;
+    script_runner::register_script(state, path).await;
+
+    Ok(status)
+}
@@ -0,0 +1,138 @@
+use crate::error::AppError;
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Utc,
+};
+
+const OPEN: &str = "{{";
+const CLOSE: &str = "}}";
+
+// `\{{` в аргументе — буквальные `{{`, а не начало подстановки
+const ESCAPE: char = '\\';
+
+const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Значения подстановок одного запуска
+pub struct Vars<'a> {
+    pub script: &'a str,
+    pub run_id: &'a str,
+    pub now: DateTime<Utc>,
+}
+
+/// Проверяет подстановки в аргументах при приёме запроса, чтобы ошибка
+/// в шаблоне давала 400 сразу, а не при запуске задачи или расписания
+pub fn validate(args: &[String]) -> Result<(), AppError> {
+    let vars = Vars {
+        script: "",
+        run_id: "",
+        now: Utc::now(),
+    };
+    expand(args, &vars).map(|_| ())
+}
+
+/// Раскрывает `{{date:%Y-%m-%d}}`, `{{run_id}}`, `{{script}}` и
+/// `{{now_epoch}}`; дата и время — в UTC. Неизвестная подстановка — ошибка.
+pub fn expand(args: &[String], vars: &Vars) -> Result<Vec<String>, AppError> {
+    args.iter()
+        .map(|arg| {
+            expand_arg(arg, vars)
+                .map_err(|e| AppError::BadRequest(format!("Invalid template in argument '{}': {}", arg, e)))
+        })
+        .collect()
+}
+
+fn expand_arg(arg: &str, vars: &Vars) -> Result<String, String> {
+    let mut expanded = String::with_capacity(arg.len());
+    let mut rest = arg;
+    while let Some(start) = rest.find(OPEN) {
+        let (before, after) = (&rest[..start], &rest[start + OPEN.len()..]);
+        if let Some(literal) = before.strip_suffix(ESCAPE) {
+            expanded.push_str(literal);
+            expanded.push_str(OPEN);
+            rest = after;
+            continue;
+        }
+        expanded.push_str(before);
+        let end = after.find(CLOSE).ok_or("unclosed '{{'")?;
+        expanded.push_str(&placeholder(after[..end].trim(), vars)?);
+        rest = &after[end + CLOSE.len()..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+fn placeholder(spec: &str, vars: &Vars) -> Result<String, String> {
+    let (name, format) = match spec.split_once(':') {
+        Some((name, format)) => (name, Some(format)),
+        None => (spec, None),
+    };
+    match (name, format) {
+        ("date", format) => format_date(vars.now, format.unwrap_or(DEFAULT_DATE_FORMAT)),
+        ("run_id", None) => Ok(vars.run_id.to_string()),
+        ("script", None) => Ok(vars.script.to_string()),
+        ("now_epoch", None) => Ok(vars.now.timestamp().to_string()),
+        _ => Err(format!("unknown placeholder '{{{{{}}}}}'", spec)),
+    }
+}
+
+// Некорректный формат chrono выдаёт ошибку только при выводе, поэтому
+// он проверяется заранее
+fn format_date(now: DateTime<Utc>, format: &str) -> Result<String, String> {
+    let items: Vec<Item> = StrftimeItems::new(format).collect();
+    if items.iter().any(|item| matches!(item, Item::Error)) {
+        return Err(format!("invalid date format '{}'", format));
+    }
+    Ok(now.format_with_items(items.into_iter()).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn vars() -> Vars<'static> {
+        Vars {
+            script: "etl/extract.py",
+            run_id: "run-1",
+            now: Utc.with_ymd_and_hms(2024, 3, 5, 14, 30, 0).unwrap(),
+        }
+    }
+
+    fn expand_one(arg: &str) -> Result<String, AppError> {
+        expand(&[arg.to_string()], &vars()).map(|mut args| args.remove(0))
+    }
+
+    #[test]
+    fn expands_placeholders() {
+        let cases = [
+            ("{{date}}", "2024-03-05"),
+            ("--day={{date:%d.%m.%Y}}", "--day=05.03.2024"),
+            ("{{ run_id }}", "run-1"),
+            ("{{script}}", "etl/extract.py"),
+            ("{{now_epoch}}", "1709649000"),
+            ("{{script}}:{{run_id}}", "etl/extract.py:run-1"),
+            ("plain", "plain"),
+        ];
+        for (arg, expected) in cases {
+            assert_eq!(expand_one(arg).expect(arg), expected, "{}", arg);
+        }
+    }
+
+    #[test]
+    fn unknown_or_malformed_placeholder_is_bad_request() {
+        for arg in ["{{user}}", "{{run_id:x}}", "{{date", "{{date:%Q}}"] {
+            let error = expand_one(arg).expect_err(arg);
+            assert!(matches!(error, AppError::BadRequest(_)), "{}: {:?}", arg, error);
+            assert_eq!(error.status(), axum::http::StatusCode::BAD_REQUEST);
+        }
+        assert!(validate(&["ok".to_string(), "{{nope}}".to_string()]).is_err());
+    }
+
+    #[test]
+    fn escaped_braces_are_kept_literally() {
+        assert_eq!(expand_one(r"\{{date}}").unwrap(), "{{date}}");
+        assert_eq!(expand_one(r"a\{{b}} {{run_id}}").unwrap(), "a{{b}} run-1");
+        // Закрывающие скобки без открывающих подстановкой не считаются
+        assert_eq!(expand_one("}}").unwrap(), "}}");
+    }
+}
@@ -0,0 +1,180 @@
+use crate::{
+    app_state::AppState,
+    error::AppError,
+    gc::Swept,
+    models::Artifact,
+    utils::{dir_size, is_windows_file_name},
+    workdir::RunDir,
+};
+use std::{
+    collections::HashSet,
+    path::{Component, Path, PathBuf},
+    time::SystemTime,
+};
+use tokio::fs;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+// Сколько файлов одного запуска сохранять
+const MAX_ARTIFACTS: usize = 1000;
+
+// Файл в директории артефактов запуска с именем скрипта, которому они
+// принадлежат; по нему проверяется доступ к пространству имён. Сам он
+// как артефакт не отдаётся.
+const OWNER_FILE: &str = ".script";
+
+/// Копирует файлы, оставленные скриптом в `artifacts` (и в прежней `output`),
+/// в `RUNNER_ARTIFACTS_DIR/<run_id>`, пока их общий размер не превышает
+/// `RUNNER_MAX_ARTIFACT_BYTES`. Не поместившиеся файлы пропускаются; символические
+/// ссылки не копируются; из одноимённых файлов сохраняется файл из `artifacts`.
+/// Рядом записывается имя скрипта `script_name`.
+pub async fn store(state: &AppState, run_id: &str, script_name: &str, run_dir: &RunDir) -> Vec<Artifact> {
+    if state.max_artifact_bytes == 0 {
+        return Vec::new();
+    }
+    let target = state.artifacts_dir.join(run_id);
+    let mut artifacts = Vec::new();
+    let mut stored = HashSet::new();
+    let mut total = 0;
+    for root in run_dir.artifact_dirs() {
+        let mut pending = vec![root.clone()];
+        while let Some(dir) = pending.pop() {
+            let Ok(mut entries) = fs::read_dir(&dir).await else {
+                continue;
+            };
+            while let Ok(Some(entry)) = entries.next_entry().await {
+                let path = entry.path();
+                let (Ok(file_type), Ok(meta)) = (entry.file_type().await, entry.metadata().await) else {
+                    continue;
+                };
+                if file_type.is_dir() {
+                    pending.push(path);
+                    continue;
+                }
+                if !file_type.is_file() {
+                    continue;
+                }
+                let Ok(relative) = path.strip_prefix(&root) else {
+                    continue;
+                };
+                if relative == Path::new(OWNER_FILE) {
+                    warn!("Skipping artifact {} of run {}: reserved name", OWNER_FILE, run_id);
+                    continue;
+                }
+                // Имя артефакта в URL — через `/` на любой платформе
+                let name = relative
+                    .iter()
+                    .map(|segment| segment.to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                if stored.contains(&name) {
+                    warn!("Skipping artifact {} of run {}: already stored from artifacts", name, run_id);
+                    continue;
+                }
+                if artifacts.len() >= MAX_ARTIFACTS || total + meta.len() > state.max_artifact_bytes {
+                    warn!("Skipping artifact {} of run {}: artifact limit reached", name, run_id);
+                    continue;
+                }
+                if let Err(e) = copy_file(&path, &target.join(relative)).await {
+                    warn!("Failed to store artifact {} of run {}: {}", name, run_id, e);
+                    continue;
+                }
+                total += meta.len();
+                stored.insert(name.clone());
+                artifacts.push(Artifact {
+                    url: format!("/runs/{}/artifacts/{}", run_id, encode_path(&name)),
+                    name,
+                    size: meta.len(),
+                });
+            }
+        }
+    }
+    if !artifacts.is_empty() {
+        if let Err(e) = fs::write(target.join(OWNER_FILE), script_name).await {
+            warn!("Failed to record owner of artifacts of run {}: {}", run_id, e);
+        }
+    }
+    artifacts.sort_by(|a, b| a.name.cmp(&b.name));
+    artifacts
+}
+
+/// Имя скрипта, запуску которого принадлежат артефакты; `None`, если
+/// артефактов нет или владелец не записан
+pub async fn owner(state: &AppState, run_id: &str) -> Option<String> {
+    if Uuid::parse_str(run_id).is_err() {
+        return None;
+    }
+    let path = state.artifacts_dir.join(run_id).join(OWNER_FILE);
+    fs::read_to_string(path).await.ok()
+}
+
+async fn copy_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    fs::copy(from, to).await?;
+    Ok(())
+}
+
+/// Путь к сохранённому файлу запуска. Идентификатор запуска должен быть UUID,
+/// а имя — относительным путём без `..`, поэтому выйти за пределы директории
+/// артефактов запуска нельзя.
+pub fn artifact_path(state: &AppState, run_id: &str, name: &str) -> Result<PathBuf, AppError> {
+    if Uuid::parse_str(run_id).is_err() {
+        return Err(AppError::BadRequest(format!("Invalid run id '{}'", run_id)));
+    }
+    let relative = Path::new(name);
+    let valid = !name.is_empty()
+        && name != OWNER_FILE
+        && !name.contains('\\')
+        && relative.components().all(|c| matches!(c, Component::Normal(_)))
+        && (!cfg!(windows) || name.split('/').all(is_windows_file_name));
+    if !valid {
+        return Err(AppError::BadRequest(format!("Invalid artifact name '{}'", name)));
+    }
+    Ok(state.artifacts_dir.join(run_id).join(relative))
+}
+
+/// Удаляет файлы запусков старше `RUNNER_ARTIFACT_RETENTION_SECS`.
+/// Директории выполняющихся запусков не трогаются.
+pub async fn prune(state: &AppState) -> Swept {
+    let mut swept = Swept::default();
+    let Ok(mut entries) = fs::read_dir(&state.artifacts_dir).await else {
+        return swept;
+    };
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let expired = entry
+            .metadata()
+            .await
+            .and_then(|meta| meta.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .is_some_and(|age| age > state.artifact_retention);
+        let active = entry.file_name().to_str().is_some_and(|run_id| state.gc.is_active(run_id));
+        if !expired || active {
+            continue;
+        }
+        let size = dir_size(&entry.path()).await;
+        match fs::remove_dir_all(entry.path()).await {
+            Ok(()) => swept.add(size),
+            Err(e) => warn!("Failed to remove artifacts {}: {}", entry.path().display(), e),
+        }
+    }
+    if swept.items > 0 {
+        info!("Removed artifacts of {} expired runs", swept.items);
+    }
+    swept
+}
+
+// Процентное кодирование всего, кроме незарезервированных символов и `/`
+fn encode_path(name: &str) -> String {
+    let mut encoded = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
@@ -0,0 +1,157 @@
+use crate::{
+    auth_middleware::TokenAccess,
+    jwt::Claims,
+    models::{AuditEntry, AuditQuery},
+    utils::sha256_hex,
+};
+use anyhow::Context;
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::request::Parts,
+};
+use std::{
+    collections::VecDeque,
+    convert::Infallible,
+    io::{BufRead, BufReader},
+    net::{IpAddr, SocketAddr},
+    path::{Path, PathBuf},
+};
+use tokio::{fs, io::AsyncWriteExt, sync::Mutex};
+use tracing::{error, info, warn};
+
+const DEFAULT_AUDIT_LIMIT: usize = 100;
+
+// Пользователь статического токена из RUNNER_API_TOKENS: у токенов нет имён
+const API_TOKEN_PRINCIPAL: &str = "api_token";
+
+/// Кто выполняет запрос: адрес клиента и пользователь JWT или API-токен
+#[derive(Debug, Clone, Default)]
+pub struct Actor {
+    pub ip: Option<IpAddr>,
+    pub principal: Option<String>,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Actor {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Infallible> {
+        let principal = match parts.extensions.get::<Claims>() {
+            Some(claims) => Some(claims.sub.clone()),
+            None => parts
+                .extensions
+                .get::<TokenAccess>()
+                .map(|_| API_TOKEN_PRINCIPAL.to_string()),
+        };
+        Ok(Self {
+            ip: parts
+                .extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip()),
+            principal,
+        })
+    }
+}
+
+/// Размер и SHA-256 содержимого скрипта
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub hash: String,
+    pub size: u64,
+}
+
+impl Snapshot {
+    pub fn of(content: &[u8]) -> Self {
+        Self {
+            hash: sha256_hex(content),
+            size: content.len() as u64,
+        }
+    }
+
+    /// Текущее содержимое файла; `None`, если файла нет
+    pub async fn read(path: &Path) -> Option<Self> {
+        fs::read(path).await.ok().map(|content| Self::of(&content))
+    }
+}
+
+/// Журнал изменений скриптов через API: последние `RUNNER_AUDIT_SIZE` записей
+/// в памяти и, с `RUNNER_AUDIT_LOG`, все записи в JSONL-файле, который только
+/// дописывается. При старте последние записи читаются из файла.
+pub struct AuditLog {
+    entries: Mutex<VecDeque<AuditEntry>>,
+    capacity: usize,
+    file: Option<PathBuf>,
+}
+
+impl AuditLog {
+    pub fn open(capacity: usize, file: Option<PathBuf>) -> anyhow::Result<Self> {
+        let mut entries = VecDeque::with_capacity(capacity);
+        if let Some(path) = &file {
+            match std::fs::File::open(path) {
+                Ok(f) => {
+                    let mut invalid = 0;
+                    for line in BufReader::new(f).lines() {
+                        let line = line.with_context(|| format!("Failed to read audit log {}", path.display()))?;
+                        match serde_json::from_str(&line) {
+                            Ok(entry) => push_bounded(&mut entries, entry, capacity),
+                            Err(_) => invalid += 1,
+                        }
+                    }
+                    if invalid > 0 {
+                        warn!("Skipped {} malformed lines in audit log {}", invalid, path.display());
+                    }
+                    info!("Restored {} audit entries from {}", entries.len(), path.display());
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    return Err(e).with_context(|| format!("Failed to open audit log {}", path.display()))
+                }
+            }
+        }
+        Ok(Self {
+            entries: Mutex::new(entries),
+            capacity,
+            file,
+        })
+    }
+
+    /// Добавляет запись; файл дописывается под той же блокировкой, поэтому
+    /// порядок строк в нём совпадает с порядком в памяти
+    pub async fn record(&self, entry: AuditEntry) {
+        let mut entries = self.entries.lock().await;
+        if let Some(path) = &self.file {
+            if let Err(e) = append_line(path, &entry).await {
+                error!("Failed to write audit entry for {} to {}: {}", entry.script, path.display(), e);
+            }
+        }
+        push_bounded(&mut entries, entry, self.capacity);
+    }
+
+    /// Записи по фильтру, от новых к старым
+    pub async fn query(&self, query: &AuditQuery) -> Vec<AuditEntry> {
+        let entries = self.entries.lock().await;
+        entries
+            .iter()
+            .rev()
+            .filter(|e| query.script.as_deref().is_none_or(|s| e.script == s))
+            .filter(|e| query.since.is_none_or(|since| e.timestamp >= since))
+            .filter(|e| query.until.is_none_or(|until| e.timestamp < until))
+            .take(query.limit.unwrap_or(DEFAULT_AUDIT_LIMIT))
+            .cloned()
+            .collect()
+    }
+}
+
+fn push_bounded(entries: &mut VecDeque<AuditEntry>, entry: AuditEntry, capacity: usize) {
+    entries.push_back(entry);
+    while entries.len() > capacity {
+        entries.pop_front();
+    }
+}
+
+async fn append_line(path: &Path, entry: &AuditEntry) -> std::io::Result<()> {
+    let mut line = serde_json::to_vec(entry)?;
+    line.push(b'\n');
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(path).await?;
+    file.write_all(&line).await?;
+    file.flush().await
+}
@@ -1,12 +1,49 @@
 use axum::{
-    extract::Request,
-    http::{header, StatusCode},
+    extract::{Request, State},
+    http::{header, Method},
     middleware::Next,
     response::Response,
 };
-use crate::jwt;
+use std::{collections::HashSet, sync::Arc};
+use subtle::ConstantTimeEq;
+use crate::{app_state::AppState, error::AppError, jwt, namespace::NamespaceAccess, quota::Caller};
 
-pub async fn auth_middleware(mut req: Request, next: Next) -> Result<Response, StatusCode> {
+/// Права статического API-токена
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenAccess {
+    /// Только чтение: GET/HEAD-запросы
+    ReadOnly,
+    ReadWrite,
+}
+
+/// Статический токен из `RUNNER_API_TOKENS`
+#[derive(Debug, Clone)]
+pub struct ApiToken {
+    pub token: String,
+    pub access: TokenAccess,
+    /// Пространства имён, к которым привязан токен; `None` — все
+    pub namespaces: Option<HashSet<String>>,
+}
+
+// Сравнение с каждым токеном за постоянное время, без раннего выхода
+fn find_api_token<'a>(tokens: &'a [ApiToken], candidate: &str) -> Option<&'a ApiToken> {
+    let mut found = None;
+    for api_token in tokens {
+        if bool::from(api_token.token.as_bytes().ct_eq(candidate.as_bytes())) {
+            found = Some(api_token);
+        }
+    }
+    found
+}
+
+/// Принимает JWT, выданный `/login`, или статический API-токен.
+/// Токены только для чтения допускаются лишь к GET/HEAD-запросам, кроме
+/// `GET /run/{name}`, который запускает скрипт.
+pub async fn auth_middleware(
+    State(state): State<Arc<AppState>>,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
     let auth_header = req
         .headers()
         .get(header::AUTHORIZATION)
@@ -14,14 +51,31 @@ pub async fn auth_middleware(mut req: Request, next: Next) -> Result<Response, S
 
     let token = match auth_header {
         Some(header) if header.starts_with("Bearer ") => header[7..].to_string(),
-        _ => return Err(StatusCode::UNAUTHORIZED),
+        _ => return Err(AppError::Unauthorized("Missing bearer token".to_string())),
     };
 
-    match jwt::validate_token(&token) {
-        Ok(claims) => {
+    let access = match find_api_token(&state.api_tokens, &token) {
+        Some(api_token) => {
+            req.extensions_mut().insert(Caller::token(&token));
+            req.extensions_mut().insert(NamespaceAccess::token(api_token));
+            api_token.access
+        }
+        None => {
+            let claims = jwt::validate_token(&token)
+                .map_err(|_| AppError::Unauthorized("Invalid or expired token".to_string()))?;
+            req.extensions_mut().insert(Caller::user(&claims.sub));
             req.extensions_mut().insert(claims);
-            Ok(next.run(req).await)
+            TokenAccess::ReadWrite
         }
-        Err(_) => Err(StatusCode::UNAUTHORIZED),
+    };
+
+    let runs_script = req.uri().path().starts_with("/run/");
+    if access == TokenAccess::ReadOnly
+        && (runs_script || !matches!(*req.method(), Method::GET | Method::HEAD))
+    {
+        return Err(AppError::Forbidden("Read-only token cannot modify or run scripts".to_string()));
     }
-}
\ No newline at end of file
+
+    req.extensions_mut().insert(access);
+    Ok(next.run(req).await)
+}
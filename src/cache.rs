@@ -0,0 +1,185 @@
+use crate::{app_state::CachedResult, cache_db::CacheDb, models::CacheStats};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+    time::Duration,
+};
+
+struct Entry {
+    value: Arc<CachedResult>,
+    last_used: u64,
+}
+
+/// Кэш результатов с ограничением числа записей и вытеснением давно
+/// не использованных (LRU). Значения хранятся в `Arc`, чтобы при попадании
+/// под блокировкой копировался только указатель.
+pub struct ResultCache {
+    entries: HashMap<String, Entry>,
+    // Порядок использования: счётчик -> ключ
+    order: BTreeMap<u64, String>,
+    counter: u64,
+    max_entries: usize,
+    memory_bytes: usize,
+    hits: u64,
+    misses: u64,
+    // Копия на диске; без неё кэш живёт только в памяти
+    store: Option<CacheDb>,
+}
+
+fn entry_size(key: &str, value: &CachedResult) -> usize {
+    key.len() + value.stdout.bytes.len() + value.stderr.bytes.len()
+}
+
+impl ResultCache {
+    pub fn new(max_entries: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            counter: 0,
+            max_entries,
+            memory_bytes: 0,
+            hits: 0,
+            misses: 0,
+            store: None,
+        }
+    }
+
+    /// Подключает копию на диске и добавляет восстановленные из неё записи
+    pub fn attach_store(&mut self, store: CacheDb, entries: Vec<(String, CachedResult)>) {
+        if self.max_entries == 0 {
+            store.clear();
+            return;
+        }
+        // Сверх `max_entries` вытесняются самые старые, в том числе с диска
+        self.store = Some(store);
+        for (key, value) in entries {
+            self.insert_entry(key, Arc::new(value));
+        }
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.counter += 1;
+        self.counter
+    }
+
+    /// Возвращает запись и отмечает её как недавно использованную
+    pub fn get(&mut self, key: &str) -> Option<Arc<CachedResult>> {
+        let tick = self.next_tick();
+        let entry = self.entries.get_mut(key)?;
+        self.order.remove(&entry.last_used);
+        entry.last_used = tick;
+        self.order.insert(tick, key.to_string());
+        Some(Arc::clone(&entry.value))
+    }
+
+    pub fn insert(&mut self, key: String, value: CachedResult) {
+        if self.max_entries == 0 {
+            return;
+        }
+        let value = Arc::new(value);
+        if let Some(store) = &self.store {
+            store.put(&key, &value);
+        }
+        self.insert_entry(key, value);
+    }
+
+    fn insert_entry(&mut self, key: String, value: Arc<CachedResult>) {
+        self.remove_entry(&key);
+        while self.entries.len() >= self.max_entries {
+            match self.order.pop_first() {
+                Some((_, oldest)) => {
+                    if let Some(entry) = self.entries.remove(&oldest) {
+                        self.memory_bytes -= entry_size(&oldest, &entry.value);
+                    }
+                    if let Some(store) = &self.store {
+                        store.remove(&oldest);
+                    }
+                }
+                None => break,
+            }
+        }
+
+        let tick = self.next_tick();
+        self.memory_bytes += entry_size(&key, &value);
+        self.order.insert(tick, key.clone());
+        self.entries.insert(
+            key,
+            Entry {
+                value,
+                last_used: tick,
+            },
+        );
+    }
+
+    pub fn remove(&mut self, key: &str) -> bool {
+        let removed = self.remove_entry(key);
+        if removed {
+            if let Some(store) = &self.store {
+                store.remove(key);
+            }
+        }
+        removed
+    }
+
+    fn remove_entry(&mut self, key: &str) -> bool {
+        match self.entries.remove(key) {
+            Some(entry) => {
+                self.order.remove(&entry.last_used);
+                self.memory_bytes -= entry_size(key, &entry.value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Оставляет только записи, для которых `keep` вернул true; возвращает число удалённых
+    pub fn retain<F>(&mut self, mut keep: F) -> usize
+    where
+        F: FnMut(&str, &CachedResult) -> bool,
+    {
+        let doomed: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|(key, entry)| !keep(key, &entry.value))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in &doomed {
+            self.remove(key);
+        }
+        doomed.len()
+    }
+
+    /// Удаляет записи старше `ttl`
+    pub fn evict_expired(&mut self, ttl: Duration) -> usize {
+        self.retain(|_, value| value.timestamp.elapsed() < ttl)
+    }
+
+    pub fn clear(&mut self) -> usize {
+        let removed = self.entries.len();
+        self.entries.clear();
+        self.order.clear();
+        self.memory_bytes = 0;
+        if let Some(store) = &self.store {
+            store.clear();
+        }
+        removed
+    }
+
+    pub fn record_hit(&mut self) {
+        self.hits += 1;
+    }
+
+    pub fn record_miss(&mut self) {
+        self.misses += 1;
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self.entries.len(),
+            max_entries: self.max_entries,
+            hits: self.hits,
+            misses: self.misses,
+            memory_bytes: self.memory_bytes,
+        }
+    }
+}
@@ -0,0 +1,258 @@
+use crate::{app_state::CachedResult, script_runner::PipeOutput};
+use anyhow::Context;
+use chrono::DateTime;
+use rusqlite::{params, Connection, Row};
+use std::{
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+// Версия схемы в `PRAGMA user_version`; файл другой версии пересоздаётся
+const SCHEMA_VERSION: i64 = 3;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS results (
+    key TEXT PRIMARY KEY,
+    stdout BLOB NOT NULL,
+    stdout_truncated INTEGER NOT NULL,
+    stderr BLOB NOT NULL,
+    stderr_truncated INTEGER NOT NULL,
+    artifacts TEXT NOT NULL,
+    exit_code INTEGER NOT NULL,
+    started_at INTEGER NOT NULL,
+    finished_at INTEGER NOT NULL,
+    duration_ms INTEGER NOT NULL,
+    stored_at INTEGER NOT NULL,
+    script_hash TEXT NOT NULL
+);
+";
+
+// Сколько изменений сохранять одной транзакцией
+const MAX_BATCH: usize = 256;
+
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+enum Command {
+    Put {
+        key: String,
+        value: Arc<CachedResult>,
+        stored_at: SystemTime,
+    },
+    Remove(String),
+    Clear,
+}
+
+/// Копия кэша результатов в SQLite (`RUNNER_CACHE_DB`), из которой кэш
+/// восстанавливается после перезапуска. Запись идёт в отдельном потоке.
+pub struct CacheDb {
+    tx: mpsc::UnboundedSender<Command>,
+}
+
+impl CacheDb {
+    /// Открывает файл кэша и читает записи моложе `ttl` (от старых к новым).
+    /// Повреждённый или несовместимый файл удаляется с предупреждением;
+    /// если и новый не создаётся, кэш остаётся только в памяти (`None`).
+    pub fn open(path: &Path, ttl: Duration) -> Option<(Self, Vec<(String, CachedResult)>)> {
+        let (conn, entries) = match load(path, ttl) {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                warn!("Discarding cache file {}: {:#}", path.display(), e);
+                remove_files(path);
+                match load(path, ttl) {
+                    Ok(loaded) => loaded,
+                    Err(e) => {
+                        warn!("Result cache is kept in memory only: {:#}", e);
+                        return None;
+                    }
+                }
+            }
+        };
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        if let Err(e) = std::thread::Builder::new()
+            .name("cache-db".to_string())
+            .spawn(move || write_loop(conn, rx))
+        {
+            warn!("Failed to start cache writer, result cache is kept in memory only: {}", e);
+            return None;
+        }
+        info!("Restored {} cached results from {}", entries.len(), path.display());
+        Some((Self { tx }, entries))
+    }
+
+    pub fn put(&self, key: &str, value: &Arc<CachedResult>) {
+        // Момент записи по часам: Instant не переносится между процессами
+        let stored_at = SystemTime::now()
+            .checked_sub(value.timestamp.elapsed())
+            .unwrap_or_else(SystemTime::now);
+        self.send(Command::Put {
+            key: key.to_string(),
+            value: Arc::clone(value),
+            stored_at,
+        });
+    }
+
+    pub fn remove(&self, key: &str) {
+        self.send(Command::Remove(key.to_string()));
+    }
+
+    pub fn clear(&self) {
+        self.send(Command::Clear);
+    }
+
+    fn send(&self, command: Command) {
+        if self.tx.send(command).is_err() {
+            warn!("Cache writer has stopped, change not persisted");
+        }
+    }
+}
+
+fn remove_files(path: &Path) {
+    for suffix in ["", "-wal", "-shm"] {
+        let mut file = path.as_os_str().to_owned();
+        file.push(suffix);
+        let _ = std::fs::remove_file(file);
+    }
+}
+
+fn load(path: &Path, ttl: Duration) -> anyhow::Result<(Connection, Vec<(String, CachedResult)>)> {
+    let conn = Connection::open(path)
+        .with_context(|| format!("Failed to open cache database {}", path.display()))?;
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))
+        .context("Failed to enable WAL")?;
+
+    let version: i64 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    if version != SCHEMA_VERSION {
+        if version != 0 {
+            warn!(
+                "Cache file {} has schema version {}, expected {}; starting empty",
+                path.display(),
+                version,
+                SCHEMA_VERSION
+            );
+        }
+        conn.execute_batch("DROP TABLE IF EXISTS results;")?;
+        conn.execute_batch(SCHEMA).context("Failed to create cache table")?;
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+    }
+
+    let now = SystemTime::now();
+    let expired_before = to_millis(now.checked_sub(ttl).unwrap_or(UNIX_EPOCH));
+    conn.execute("DELETE FROM results WHERE stored_at < ?1", [expired_before])?;
+
+    let entries = {
+        let mut statement = conn.prepare(
+            "SELECT key, stdout, stdout_truncated, stderr, stderr_truncated, artifacts, \
+             exit_code, started_at, finished_at, duration_ms, stored_at, script_hash \
+             FROM results ORDER BY stored_at",
+        )?;
+        let rows = statement.query_map([], |row| read_entry(row, now))?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+            .context("Failed to read cached results")?
+            .into_iter()
+            .flatten()
+            .collect()
+    };
+    Ok((conn, entries))
+}
+
+// Запись, чей возраст не представим через Instant, пропускается
+fn read_entry(row: &Row, now: SystemTime) -> rusqlite::Result<Option<(String, CachedResult)>> {
+    let artifacts: String = row.get(5)?;
+    let artifacts = serde_json::from_str(&artifacts).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(5, rusqlite::types::Type::Text, Box::new(e))
+    })?;
+    let stored_at = from_millis(row.get(10)?);
+    let age = now.duration_since(stored_at).unwrap_or_default();
+    let Some(timestamp) = Instant::now().checked_sub(age) else {
+        return Ok(None);
+    };
+    let value = CachedResult {
+        stdout: PipeOutput {
+            bytes: row.get(1)?,
+            truncated: row.get(2)?,
+        },
+        stderr: PipeOutput {
+            bytes: row.get(3)?,
+            truncated: row.get(4)?,
+        },
+        artifacts,
+        exit_code: row.get(6)?,
+        started_at: DateTime::from_timestamp_millis(row.get(7)?).unwrap_or_default(),
+        finished_at: DateTime::from_timestamp_millis(row.get(8)?).unwrap_or_default(),
+        duration_ms: row.get::<_, i64>(9)? as u64,
+        timestamp,
+        script_hash: row.get(11)?,
+    };
+    Ok(Some((row.get(0)?, value)))
+}
+
+fn to_millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as i64
+}
+
+fn from_millis(millis: i64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(millis.max(0) as u64)
+}
+
+fn write_loop(mut conn: Connection, mut rx: mpsc::UnboundedReceiver<Command>) {
+    while let Some(command) = rx.blocking_recv() {
+        let mut batch = vec![command];
+        while batch.len() < MAX_BATCH {
+            match rx.try_recv() {
+                Ok(command) => batch.push(command),
+                Err(_) => break,
+            }
+        }
+        let count = batch.len();
+        if let Err(e) = write_batch(&mut conn, batch) {
+            warn!("Failed to persist {} cache changes: {}", count, e);
+        }
+    }
+}
+
+fn write_batch(conn: &mut Connection, batch: Vec<Command>) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    for command in batch {
+        match command {
+            Command::Put {
+                key,
+                value,
+                stored_at,
+            } => {
+                tx.prepare_cached(
+                    "INSERT OR REPLACE INTO results (key, stdout, stdout_truncated, stderr, \
+                     stderr_truncated, artifacts, exit_code, started_at, finished_at, \
+                     duration_ms, stored_at, script_hash) \
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+                )?
+                .execute(params![
+                    key,
+                    value.stdout.bytes,
+                    value.stdout.truncated,
+                    value.stderr.bytes,
+                    value.stderr.truncated,
+                    serde_json::to_string(&value.artifacts).unwrap_or_default(),
+                    value.exit_code,
+                    value.started_at.timestamp_millis(),
+                    value.finished_at.timestamp_millis(),
+                    value.duration_ms as i64,
+                    to_millis(stored_at),
+                    value.script_hash,
+                ])?;
+            }
+            Command::Remove(key) => {
+                tx.prepare_cached("DELETE FROM results WHERE key = ?1")?
+                    .execute([key])?;
+            }
+            Command::Clear => {
+                tx.execute("DELETE FROM results", [])?;
+            }
+        }
+    }
+    tx.commit()
+}
@@ -0,0 +1,407 @@
+use crate::{
+    models::{
+        ErrorResponse, HistoryEntry, RunRequest, ScriptMetadata, ScriptResult, StreamExit,
+        UploadResponse, UploadStatus,
+    },
+    utils::decode_output,
+};
+use anyhow::{anyhow, bail, Context};
+use clap::{Args, Parser, Subcommand};
+use futures::StreamExt;
+use reqwest::{multipart, Method, RequestBuilder, Response, StatusCode, Url};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+// Код возврата клиента при ошибке API или соединения
+const EXIT_ERROR: i32 = 1;
+// Код возврата при таймауте скрипта, как у `timeout` из coreutils
+const EXIT_TIMEOUT: i32 = 124;
+
+/// Аргументы командной строки; без подкоманды запускается сервер
+#[derive(Debug, Parser)]
+#[command(version, about = "Сервер запуска скриптов и клиент его API")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Клиент API работающего сервера
+    Client(ClientArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct ClientArgs {
+    /// Адрес сервера
+    #[arg(long, env = "RUNNER_SERVER", default_value = "http://localhost:3000")]
+    server: String,
+    /// Токен API или JWT для заголовка Authorization
+    #[arg(long, env = "RUNNER_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+    #[command(subcommand)]
+    command: ClientCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum ClientCommand {
+    /// Список скриптов
+    List {
+        /// Только скрипты с этим тегом
+        #[arg(long)]
+        tag: Option<String>,
+    },
+    /// Вывести код скрипта
+    Get { name: String },
+    /// Загрузить файл или директорию скриптов
+    Push {
+        path: PathBuf,
+        /// Заменить существующие скрипты
+        #[arg(long)]
+        overwrite: bool,
+        /// Не проверять синтаксис перед сохранением
+        #[arg(long)]
+        no_validate: bool,
+    },
+    /// Запустить скрипт; код возврата клиента — код возврата скрипта
+    Run {
+        name: String,
+        /// Аргументы скрипта, после `--`
+        #[arg(last = true)]
+        args: Vec<String>,
+        /// JSON, передаваемый в stdin
+        #[arg(long)]
+        data: Option<String>,
+        /// Таймаут выполнения (секунды)
+        #[arg(long)]
+        timeout: Option<u64>,
+        /// Выполнить заново, не читая кэш
+        #[arg(long)]
+        no_cache: bool,
+        /// Дождаться результата целиком вместо построчного вывода
+        #[arg(long)]
+        no_stream: bool,
+    },
+    /// Последние запуски из истории
+    Logs {
+        /// Только запуски этого скрипта
+        name: Option<String>,
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+}
+
+/// Выполняет команду клиента и возвращает код возврата процесса
+pub async fn run(args: ClientArgs) -> i32 {
+    let result = match ApiClient::new(&args.server, args.token) {
+        Ok(client) => execute(&client, args.command).await,
+        Err(e) => Err(e),
+    };
+    result.unwrap_or_else(|e| {
+        eprintln!("error: {:#}", e);
+        EXIT_ERROR
+    })
+}
+
+struct ApiClient {
+    http: reqwest::Client,
+    server: Url,
+    token: Option<String>,
+}
+
+impl ApiClient {
+    fn new(server: &str, token: Option<String>) -> anyhow::Result<Self> {
+        let server = Url::parse(server).with_context(|| format!("Invalid server address '{}'", server))?;
+        if server.cannot_be_a_base() {
+            bail!("Invalid server address '{}'", server);
+        }
+        Ok(Self {
+            http: reqwest::Client::new(),
+            server,
+            token,
+        })
+    }
+
+    // Каждый сегмент кодируется: `etl/extract.py` уходит как `etl%2Fextract.py`
+    fn request(&self, method: Method, segments: &[&str]) -> RequestBuilder {
+        let mut url = self.server.clone();
+        url.path_segments_mut()
+            .expect("checked in ApiClient::new")
+            .pop_if_empty()
+            .extend(segments);
+        let request = self.http.request(method, url);
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+}
+
+async fn execute(client: &ApiClient, command: ClientCommand) -> anyhow::Result<i32> {
+    match command {
+        ClientCommand::List { tag } => list(client, tag).await,
+        ClientCommand::Get { name } => get(client, &name).await,
+        ClientCommand::Push {
+            path,
+            overwrite,
+            no_validate,
+        } => push(client, &path, overwrite, !no_validate).await,
+        ClientCommand::Run {
+            name,
+            args,
+            data,
+            timeout,
+            no_cache,
+            no_stream,
+        } => {
+            let data = data
+                .map(|data| serde_json::from_str(&data).context("--data is not valid JSON"))
+                .transpose()?;
+            let request = RunRequest {
+                data,
+                args: Some(args),
+                timeout_secs: timeout,
+                cache: no_cache.then_some(false),
+                ..Default::default()
+            };
+            // Поток не читает кэш, поэтому --no-cache его не требует
+            if no_stream {
+                run_once(client, &name, &request).await
+            } else {
+                run_streamed(client, &name, &request).await
+            }
+        }
+        ClientCommand::Logs { name, limit } => logs(client, name, limit).await,
+    }
+}
+
+// Ответ с ошибкой превращается в сообщение с кодом из тела ошибки API
+async fn check(response: Response) -> anyhow::Result<Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let text = response.text().await.unwrap_or_default();
+    match serde_json::from_str::<ErrorResponse>(&text) {
+        Ok(ErrorResponse { error }) => Err(anyhow!("{} ({}): {}", status, error.code, error.message)),
+        Err(_) => Err(anyhow!("{}: {}", status, text.trim())),
+    }
+}
+
+async fn list(client: &ApiClient, tag: Option<String>) -> anyhow::Result<i32> {
+    let mut request = client.request(Method::GET, &["scripts"]);
+    if let Some(tag) = &tag {
+        request = request.query(&[("tag", tag)]);
+    }
+    let scripts: Vec<ScriptMetadata> = check(request.send().await?).await?.json().await?;
+    for script in scripts {
+        let disabled = if script.disabled { " (disabled)" } else { "" };
+        match script.description {
+            Some(description) => println!("{}{}\t{}", script.name, disabled, description),
+            None => println!("{}{}", script.name, disabled),
+        }
+    }
+    Ok(0)
+}
+
+async fn get(client: &ApiClient, name: &str) -> anyhow::Result<i32> {
+    let response = check(client.request(Method::GET, &["scripts", name]).send().await?).await?;
+    std::io::stdout().write_all(&response.bytes().await?)?;
+    Ok(0)
+}
+
+async fn push(client: &ApiClient, path: &Path, overwrite: bool, validate: bool) -> anyhow::Result<i32> {
+    let files = if path.is_dir() {
+        let mut files = Vec::new();
+        collect_files(path, path, &mut files)?;
+        files
+    } else {
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| anyhow!("Invalid file name '{}'", path.display()))?;
+        vec![(name.to_string(), path.to_path_buf())]
+    };
+    if files.is_empty() {
+        bail!("No files to upload in '{}'", path.display());
+    }
+
+    let mut form = multipart::Form::new().text("overwrite", overwrite.to_string());
+    for (name, file) in files {
+        let content = std::fs::read(&file).with_context(|| format!("Cannot read '{}'", file.display()))?;
+        form = form.part("file", multipart::Part::bytes(content).file_name(name));
+    }
+    let response = client
+        .request(Method::POST, &["scripts"])
+        .query(&[("validate", validate)])
+        .multipart(form)
+        .send()
+        .await?;
+    let upload: UploadResponse = check(response).await?.json().await?;
+
+    let mut failed = false;
+    for file in upload.files {
+        match (file.status, file.error) {
+            (UploadStatus::Error, Some(error)) => {
+                failed = true;
+                println!("{}\terror\t{}: {}", file.name, error.code, error.message);
+            }
+            (status, _) => {
+                let status = serde_json::to_value(status)?;
+                println!("{}\t{}", file.name, status.as_str().unwrap_or_default());
+            }
+        }
+    }
+    Ok(if failed { EXIT_ERROR } else { 0 })
+}
+
+// Файлы директории с именами относительно неё; скрытые файлы и директории
+// (`.venvs`, `.git`) пропускаются
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<(String, PathBuf)>) -> anyhow::Result<()> {
+    let mut entries = std::fs::read_dir(dir)
+        .with_context(|| format!("Cannot read '{}'", dir.display()))?
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let path = entry.path();
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        if path.is_dir() {
+            collect_files(root, &path, files)?;
+            continue;
+        }
+        let name = path
+            .strip_prefix(root)?
+            .components()
+            .map(|c| c.as_os_str().to_str())
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| anyhow!("Invalid file name '{}'", path.display()))?
+            .join("/");
+        files.push((name, path));
+    }
+    Ok(())
+}
+
+// Код возврата клиента по итогу запуска
+fn exit_code(exit_code: i32, timed_out: bool, signal: Option<i32>) -> i32 {
+    match (timed_out, signal) {
+        (true, _) => EXIT_TIMEOUT,
+        (false, Some(signal)) => 128 + signal,
+        (false, None) if (0..=255).contains(&exit_code) => exit_code,
+        _ => EXIT_ERROR,
+    }
+}
+
+async fn run_once(client: &ApiClient, name: &str, request: &RunRequest) -> anyhow::Result<i32> {
+    let response = client.request(Method::POST, &["run", name]).json(request).send().await?;
+    let result: ScriptResult = check(response).await?.json().await?;
+    std::io::stdout().write_all(&decode_output(&result.stdout, result.stdout_encoding))?;
+    std::io::stderr().write_all(&decode_output(&result.stderr, result.stderr_encoding))?;
+    Ok(exit_code(result.exit_code, result.timed_out, result.signal))
+}
+
+// Построчный вывод через SSE; сервер без `/run/{name}/stream` отвечает 404
+// или 405 без тела ошибки API — тогда результат запрашивается целиком
+async fn run_streamed(client: &ApiClient, name: &str, request: &RunRequest) -> anyhow::Result<i32> {
+    let response = client
+        .request(Method::POST, &["run", name, "stream"])
+        .json(request)
+        .send()
+        .await?;
+    let status = response.status();
+    if matches!(status, StatusCode::NOT_FOUND | StatusCode::METHOD_NOT_ALLOWED) {
+        let text = response.text().await.unwrap_or_default();
+        if let Ok(ErrorResponse { error }) = serde_json::from_str::<ErrorResponse>(&text) {
+            bail!("{} ({}): {}", status, error.code, error.message);
+        }
+        return run_once(client, name, request).await;
+    }
+    let response = check(response).await?;
+
+    let mut body = response.bytes_stream();
+    let mut buffer = Vec::new();
+    let mut event = SseEvent::default();
+    while let Some(chunk) = body.next().await {
+        buffer.extend_from_slice(&chunk?);
+        while let Some(end) = buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+            if !line.is_empty() {
+                event.push_line(line);
+                continue;
+            }
+            // Пустая строка завершает событие
+            match std::mem::take(&mut event).dispatch()? {
+                Some(code) => return Ok(code),
+                None => continue,
+            }
+        }
+    }
+    bail!("Stream ended before the script finished")
+}
+
+#[derive(Default)]
+struct SseEvent {
+    name: String,
+    data: Vec<String>,
+}
+
+impl SseEvent {
+    fn push_line(&mut self, line: &str) {
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "event" => self.name = value.to_string(),
+            "data" => self.data.push(value.to_string()),
+            // Комментарии keep-alive и прочие поля не нужны
+            _ => {}
+        }
+    }
+
+    // Печатает вывод; после `exit` возвращает код возврата
+    fn dispatch(self) -> anyhow::Result<Option<i32>> {
+        let data = self.data.join("\n");
+        match self.name.as_str() {
+            "stdout" => println!("{}", data),
+            "stderr" => eprintln!("{}", data),
+            "exit" => {
+                let exit: StreamExit = serde_json::from_str(&data)?;
+                return Ok(Some(exit_code(exit.exit_code, exit.timed_out, exit.signal)));
+            }
+            "error" => bail!("{}", data),
+            _ => {}
+        }
+        Ok(None)
+    }
+}
+
+async fn logs(client: &ApiClient, name: Option<String>, limit: usize) -> anyhow::Result<i32> {
+    let mut request = client
+        .request(Method::GET, &["history"])
+        .query(&[("limit", limit)]);
+    if let Some(name) = &name {
+        request = request.query(&[("script", name)]);
+    }
+    let entries: Vec<HistoryEntry> = check(request.send().await?).await?.json().await?;
+    for entry in entries {
+        let status = serde_json::to_value(entry.status)?;
+        let exit_code = entry.exit_code.map(|code| code.to_string()).unwrap_or_else(|| "-".to_string());
+        println!(
+            "{}\t{}\t{}\texit={}\t{}ms\t{}",
+            entry.started_at.to_rfc3339(),
+            entry.script,
+            status.as_str().unwrap_or_default(),
+            exit_code,
+            entry.duration_ms,
+            entry.run_id,
+        );
+        if let Some(error) = entry.error {
+            println!("\t{}", error);
+        }
+    }
+    Ok(0)
+}
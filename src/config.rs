@@ -0,0 +1,605 @@
+use anyhow::{anyhow, Context};
+use crate::{
+    auth_middleware::{ApiToken, TokenAccess},
+    exec_user::ExecUser,
+    network::IsolationFallback,
+    limits,
+    sandbox::Sandbox,
+    listener::{Bind, TlsFiles},
+    models::RunLimits,
+    namespace::NamespaceDefaults,
+    quota::QuotaLimits,
+    utils::is_valid_dir_name,
+};
+use std::{collections::{HashMap, HashSet}, net::{IpAddr, SocketAddr}, path::PathBuf, str::FromStr, time::Duration};
+
+// Интерпретаторы по умолчанию: расширение файла -> команда
+#[cfg(not(windows))]
+const DEFAULT_INTERPRETERS: &[(&str, &str)] = &[
+    ("py", "python3 -u"),
+    ("sh", "bash"),
+    ("js", "node"),
+    ("rb", "ruby"),
+];
+
+// На Windows нет `python3`: у установщика python.org и Microsoft Store
+// есть `python`; для лаунчера `py -3` задайте RUNNER_INTERPRETERS
+#[cfg(windows)]
+const DEFAULT_INTERPRETERS: &[(&str, &str)] = &[
+    ("py", "python -u"),
+    ("ps1", "powershell -NoProfile -ExecutionPolicy Bypass -File"),
+    ("sh", "bash"),
+    ("js", "node"),
+    ("rb", "ruby"),
+];
+
+/// Настройки сервера, читаются из переменных окружения при старте.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub bind: Bind,
+    pub socket_mode: u32,
+    pub tls: Option<TlsFiles>,
+    pub scripts_dir: PathBuf,
+    pub max_concurrent: usize,
+    pub cache_ttl: Duration,
+    pub cache_max_entries: usize,
+    pub cache_db: Option<PathBuf>,
+    pub script_timeout: Duration,
+    pub max_script_timeout: Duration,
+    pub job_retention: Duration,
+    pub max_job_wait: Duration,
+    pub output_store_bytes: usize,
+    pub output_retention: Duration,
+    pub run_logs_per_script: usize,
+    pub idempotency_store_bytes: usize,
+    pub idempotency_ttl: Duration,
+    pub idempotency_persist: bool,
+    pub interpreters: HashMap<String, String>,
+    pub allowed_interpreters: HashSet<String>,
+    pub deps_install_timeout: Duration,
+    pub history_size: usize,
+    pub history_db: Option<PathBuf>,
+    pub audit_size: usize,
+    pub audit_log: Option<PathBuf>,
+    pub history_retention: Option<Duration>,
+    pub scan_interval: Duration,
+    pub max_raw_body_bytes: usize,
+    pub max_script_bytes: usize,
+    pub max_run_body_bytes: usize,
+    pub shutdown_timeout: Duration,
+    pub rate_limit: u32,
+    pub rate_burst: u32,
+    pub rate_limit_exempt: HashSet<IpAddr>,
+    pub script_quota: QuotaLimits,
+    pub caller_quota: QuotaLimits,
+    pub namespace_defaults: NamespaceDefaults,
+    pub quota_count_cache_hits: bool,
+    pub api_tokens: Vec<ApiToken>,
+    pub admin_users: Vec<String>,
+    pub secrets_key: Option<String>,
+    pub secrets_file: PathBuf,
+    pub max_output_bytes: usize,
+    pub run_limits: RunLimits,
+    pub max_script_versions: usize,
+    pub trash_retention: Option<Duration>,
+    pub version_retention: Option<Duration>,
+    pub gc_interval: Duration,
+    pub artifacts_dir: PathBuf,
+    pub max_artifact_bytes: u64,
+    pub artifact_retention: Duration,
+    pub max_queue_wait: Option<Duration>,
+    pub script_max_concurrent: Option<usize>,
+    pub script_max_queued: usize,
+    pub pool_size: usize,
+    pub pool_max_task_memory_mb: u64,
+    pub memory_limit_mb: Option<u64>,
+    pub cpu_limit_secs: Option<u64>,
+    pub exec_user: Option<ExecUser>,
+    pub network_fallback: IsolationFallback,
+    pub sandbox: Option<Sandbox>,
+    pub allow_eval: bool,
+    pub read_only: bool,
+    pub compression: bool,
+    pub compression_min_bytes: u16,
+    pub callback_hosts: HashSet<String>,
+    pub fetch_hosts: HashSet<String>,
+}
+
+impl Config {
+    pub fn from_env() -> anyhow::Result<Self> {
+        // TCP-адрес или `unix:<путь>` для Unix-сокета
+        let bind = match std::env::var("RUNNER_BIND") {
+            Ok(value) => value
+                .trim()
+                .parse()
+                .with_context(|| format!("Invalid value for RUNNER_BIND: '{}'", value))?,
+            Err(_) => Bind::Tcp("0.0.0.0:3000".parse::<SocketAddr>()?),
+        };
+        // Права файла Unix-сокета, восьмеричные
+        let socket_mode = match std::env::var("RUNNER_SOCKET_MODE") {
+            Ok(value) => u32::from_str_radix(value.trim(), 8)
+                .ok()
+                .filter(|mode| *mode <= 0o777)
+                .ok_or_else(|| anyhow!("Invalid value for RUNNER_SOCKET_MODE: '{}', expected octal like 660", value))?,
+            Err(_) => 0o660,
+        };
+        let tls = match (std::env::var("RUNNER_TLS_CERT"), std::env::var("RUNNER_TLS_KEY")) {
+            (Ok(cert), Ok(key)) => Some(TlsFiles {
+                cert: PathBuf::from(cert),
+                key: PathBuf::from(key),
+            }),
+            (Err(_), Err(_)) => None,
+            _ => return Err(anyhow!("RUNNER_TLS_CERT and RUNNER_TLS_KEY must be set together")),
+        };
+        if tls.is_some() && matches!(bind, Bind::Unix(_)) {
+            return Err(anyhow!("TLS is supported only when RUNNER_BIND is a TCP address"));
+        }
+        let scripts_dir = std::env::var("RUNNER_SCRIPTS_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| PathBuf::from("./scripts"));
+
+        let max_concurrent: usize = env_parse("RUNNER_MAX_CONCURRENT", 4)?;
+        if max_concurrent == 0 {
+            return Err(anyhow!("RUNNER_MAX_CONCURRENT must be greater than 0"));
+        }
+
+        let cache_ttl = Duration::from_secs(env_parse("RUNNER_CACHE_TTL_SECS", 30)?);
+        let cache_max_entries: usize = env_parse("RUNNER_CACHE_MAX_ENTRIES", 1000)?;
+        // Копия кэша в SQLite; без неё кэш теряется при перезапуске
+        let cache_db = std::env::var("RUNNER_CACHE_DB").ok().map(PathBuf::from);
+        let script_timeout = env_secs("RUNNER_SCRIPT_TIMEOUT_SECS", 30)?;
+        let max_script_timeout = env_secs("RUNNER_MAX_SCRIPT_TIMEOUT_SECS", 300)?;
+        if script_timeout > max_script_timeout {
+            return Err(anyhow!(
+                "RUNNER_SCRIPT_TIMEOUT_SECS must not exceed RUNNER_MAX_SCRIPT_TIMEOUT_SECS"
+            ));
+        }
+
+        let job_retention = env_secs("RUNNER_JOB_RETENTION_SECS", 3600)?;
+        // Предел ожидания завершения задачи в GET /jobs/{id}?wait=
+        let max_job_wait = Duration::from_secs(env_parse("RUNNER_MAX_JOB_WAIT_SECS", 60)?);
+        // stdout недавних запусков для input_from; 0 — не хранить
+        let output_store_bytes: usize = env_parse("RUNNER_OUTPUT_STORE_BYTES", 64 * 1024 * 1024)?;
+        let output_retention = env_secs("RUNNER_OUTPUT_RETENTION_SECS", 3600)?;
+        // Объединённые логи последних запусков каждого скрипта; 0 — не вести
+        let run_logs_per_script: usize = env_parse("RUNNER_RUN_LOGS_PER_SCRIPT", 10)?;
+
+        let interpreters = match std::env::var("RUNNER_INTERPRETERS") {
+            Ok(value) => parse_interpreters(&value)?,
+            Err(_) => DEFAULT_INTERPRETERS
+                .iter()
+                .map(|(ext, cmd)| (ext.to_string(), cmd.to_string()))
+                .collect(),
+        };
+        // Интерпретатор Python для всех `.py`, если на сервере несколько версий
+        let mut interpreters = interpreters;
+        if let Ok(python) = std::env::var("RUNNER_PYTHON") {
+            let python = python.trim();
+            if python.is_empty() {
+                return Err(anyhow!("RUNNER_PYTHON must not be empty"));
+            }
+            interpreters.insert("py".to_string(), format!("{} -u", python));
+        }
+
+        // Интерпретаторы, которые можно выбрать полем `interpreter` запроса
+        let allowed_interpreters = match std::env::var("RUNNER_ALLOWED_INTERPRETERS") {
+            Ok(value) => value
+                .split(',')
+                .map(str::trim)
+                .filter(|cmd| !cmd.is_empty())
+                .map(str::to_string)
+                .collect(),
+            Err(_) => HashSet::new(),
+        };
+
+        // Хосты, с которых разрешено загружать скрипты; пусто — /scripts/fetch и /scripts/sync выключены
+        let fetch_hosts = match std::env::var("RUNNER_FETCH_HOSTS") {
+            Ok(value) => value
+                .split(',')
+                .map(|host| host.trim().to_ascii_lowercase())
+                .filter(|host| !host.is_empty())
+                .collect(),
+            Err(_) => HashSet::new(),
+        };
+
+        // Установка зависимостей из requirements.txt (venv и pip) целиком
+        let deps_install_timeout = env_secs("RUNNER_DEPS_INSTALL_TIMEOUT_SECS", 900)?;
+
+        let history_size: usize = env_parse("RUNNER_HISTORY_SIZE", 1000)?;
+
+        // Журнал запусков в SQLite; без него история хранится только в памяти
+        let history_db = std::env::var("RUNNER_HISTORY_DB").ok().map(PathBuf::from);
+
+        // Ответы на запросы с Idempotency-Key; 0 — заголовок не поддерживается
+        let idempotency_store_bytes: usize = env_parse("RUNNER_IDEMPOTENCY_STORE_BYTES", 64 * 1024 * 1024)?;
+        let idempotency_ttl = env_secs("RUNNER_IDEMPOTENCY_TTL_SECS", 24 * 60 * 60)?;
+        // Копия ключей в базе журнала, чтобы повтор после перезапуска не выполнил скрипт заново
+        let idempotency_persist: bool = env_parse("RUNNER_IDEMPOTENCY_PERSIST", false)?;
+        if idempotency_persist && history_db.is_none() {
+            return Err(anyhow!("RUNNER_IDEMPOTENCY_PERSIST requires RUNNER_HISTORY_DB"));
+        }
+        // Журнал аудита изменений скриптов: записей в памяти и копия в JSONL
+        let audit_size: usize = env_parse("RUNNER_AUDIT_SIZE", 1000)?;
+        let audit_log = std::env::var("RUNNER_AUDIT_LOG").ok().map(PathBuf::from);
+        // 0 — хранить записи журнала без ограничения
+        let history_retention = match env_parse::<u64>("RUNNER_HISTORY_RETENTION_DAYS", 90)? {
+            0 => None,
+            days => Some(Duration::from_secs(days * 24 * 60 * 60)),
+        };
+
+        let scan_interval = env_secs("RUNNER_SCAN_INTERVAL_SECS", 60)?;
+
+        let max_raw_body_bytes: usize = env_parse("RUNNER_MAX_RAW_BODY_BYTES", 10 * 1024 * 1024)?;
+
+        let shutdown_timeout = env_secs("RUNNER_SHUTDOWN_TIMEOUT_SECS", 30)?;
+
+        // 0 отключает ограничение частоты запусков
+        let rate_limit: u32 = env_parse("RUNNER_RATE_LIMIT", 120)?;
+        let rate_burst: u32 = env_parse("RUNNER_RATE_BURST", 20)?;
+        let rate_limit_exempt = match std::env::var("RUNNER_RATE_LIMIT_EXEMPT") {
+            Ok(value) => value
+                .split(',')
+                .map(str::trim)
+                .filter(|ip| !ip.is_empty())
+                .map(|ip| {
+                    ip.parse()
+                        .with_context(|| format!("Invalid IP in RUNNER_RATE_LIMIT_EXEMPT: '{}'", ip))
+                })
+                .collect::<anyhow::Result<_>>()?,
+            Err(_) => HashSet::new(),
+        };
+
+        // Квоты за скользящий час для каждого скрипта и каждого вызывающего
+        // (API-токена или пользователя JWT): запуски и секунды выполнения
+        let script_quota = QuotaLimits {
+            runs_per_hour: env_limit("RUNNER_SCRIPT_QUOTA_RUNS_PER_HOUR")?,
+            exec_secs_per_hour: env_limit("RUNNER_SCRIPT_QUOTA_EXEC_SECS_PER_HOUR")?,
+        };
+        let caller_quota = QuotaLimits {
+            runs_per_hour: env_limit("RUNNER_CALLER_QUOTA_RUNS_PER_HOUR")?,
+            exec_secs_per_hour: env_limit("RUNNER_CALLER_QUOTA_EXEC_SECS_PER_HOUR")?,
+        };
+        // Ограничения пространств имён, созданных без своих значений
+        let namespace_defaults = NamespaceDefaults {
+            max_concurrent: env_parse("RUNNER_NAMESPACE_MAX_CONCURRENT", 0)?,
+            quota: QuotaLimits {
+                runs_per_hour: env_limit("RUNNER_NAMESPACE_QUOTA_RUNS_PER_HOUR")?,
+                exec_secs_per_hour: env_limit("RUNNER_NAMESPACE_QUOTA_EXEC_SECS_PER_HOUR")?,
+            },
+        };
+        // Ответы из кэша по умолчанию квоту не расходуют
+        let quota_count_cache_hits: bool = env_parse("RUNNER_QUOTA_COUNT_CACHE_HITS", false)?;
+
+        let max_output_bytes: usize = env_parse("RUNNER_MAX_OUTPUT_BYTES", 4 * 1024 * 1024)?;
+        if max_output_bytes == 0 {
+            return Err(anyhow!("RUNNER_MAX_OUTPUT_BYTES must be greater than 0"));
+        }
+
+        // Ограничения запроса на запуск: число и длина аргументов, размер data
+        let run_limits = RunLimits {
+            max_args: env_parse("RUNNER_MAX_ARGS", 256)?,
+            max_arg_bytes: env_parse("RUNNER_MAX_ARG_BYTES", 4096)?,
+            max_data_bytes: env_parse("RUNNER_MAX_DATA_BYTES", 1024 * 1024)?,
+            max_batch_scripts: env_parse("RUNNER_MAX_BATCH_SCRIPTS", 25)?,
+        };
+        if run_limits.max_arg_bytes == 0 || run_limits.max_data_bytes == 0 {
+            return Err(anyhow!(
+                "RUNNER_MAX_ARG_BYTES and RUNNER_MAX_DATA_BYTES must be greater than 0"
+            ));
+        }
+        if run_limits.max_batch_scripts == 0 {
+            return Err(anyhow!("RUNNER_MAX_BATCH_SCRIPTS must be greater than 0"));
+        }
+        // По умолчанию тело JSON-запроса на запуск вмещает data и аргументы в пределах ограничений
+        let max_run_body_bytes: usize =
+            env_parse("RUNNER_MAX_RUN_BODY_BYTES", limits::max_json_body_bytes(&run_limits))?;
+        let max_script_bytes: usize = env_parse("RUNNER_MAX_SCRIPT_BYTES", 1024 * 1024)?;
+        if max_run_body_bytes == 0 || max_script_bytes == 0 {
+            return Err(anyhow!(
+                "RUNNER_MAX_RUN_BODY_BYTES and RUNNER_MAX_SCRIPT_BYTES must be greater than 0"
+            ));
+        }
+
+        // 0 — ждать свободного слота без ограничения
+        let max_queue_wait = match env_parse::<u64>("RUNNER_MAX_QUEUE_WAIT_SECS", 30)? {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        };
+
+        // Ограничение одновременных запусков одного скрипта по умолчанию; 0 — нет
+        let script_max_concurrent = match env_parse::<usize>("RUNNER_SCRIPT_MAX_CONCURRENT", 0)? {
+            0 => None,
+            max => Some(max),
+        };
+        // Сколько запусков скрипта ждёт его слота; 0 — сразу отклонять с 429
+        let script_max_queued: usize = env_parse("RUNNER_SCRIPT_MAX_QUEUED", 100)?;
+
+        // Пул тёплых воркеров для Python-скриптов; 0 — выключен
+        let pool_size: usize = env_parse("RUNNER_POOL_SIZE", 0)?;
+        // Воркер пула запускает задачи через fork
+        if pool_size > 0 && !cfg!(unix) {
+            return Err(anyhow!("RUNNER_POOL_SIZE is supported only on Unix"));
+        }
+        let pool_max_task_memory_mb: u64 = env_parse("RUNNER_POOL_MAX_TASK_MEMORY_MB", 512)?;
+
+        // Ограничения памяти и процессорного времени процесса скрипта; 0 — нет
+        let memory_limit_mb = match env_parse::<u64>("RUNNER_MAX_MEMORY_MB", 0)? {
+            0 => None,
+            mb => Some(mb),
+        };
+        let cpu_limit_secs = match env_parse::<u64>("RUNNER_MAX_CPU_SECS", 0)? {
+            0 => None,
+            secs => Some(secs),
+        };
+
+        // Пользователь и группа процессов скриптов; без них — пользователь сервера
+        let exec_user = match (std::env::var("RUNNER_EXEC_UID"), std::env::var("RUNNER_EXEC_GID")) {
+            (Ok(uid), Ok(gid)) => Some(ExecUser {
+                uid: uid
+                    .trim()
+                    .parse()
+                    .with_context(|| format!("Invalid value for RUNNER_EXEC_UID: '{}'", uid))?,
+                gid: gid
+                    .trim()
+                    .parse()
+                    .with_context(|| format!("Invalid value for RUNNER_EXEC_GID: '{}'", gid))?,
+            }),
+            (Err(_), Err(_)) => None,
+            _ => return Err(anyhow!("RUNNER_EXEC_UID and RUNNER_EXEC_GID must be set together")),
+        };
+        if exec_user.is_some() && !cfg!(unix) {
+            return Err(anyhow!("RUNNER_EXEC_UID is supported only on Unix"));
+        }
+        // Воркеры пула запущены от имени сервера, и задачи наследуют его права
+        if exec_user.is_some() && pool_size > 0 {
+            return Err(anyhow!("RUNNER_POOL_SIZE cannot be used together with RUNNER_EXEC_UID"));
+        }
+        // Запуски с network=false, если изоляция от сети недоступна
+        let network_fallback = match std::env::var("RUNNER_NETWORK_ISOLATION_FALLBACK") {
+            Ok(value) => match value.trim() {
+                "fail" => IsolationFallback::Fail,
+                "warn" => IsolationFallback::Warn,
+                _ => {
+                    return Err(anyhow!(
+                        "Invalid value for RUNNER_NETWORK_ISOLATION_FALLBACK: '{}', expected fail or warn",
+                        value
+                    ))
+                }
+            },
+            Err(_) => IsolationFallback::default(),
+        };
+
+        // Песочница, в которой запускаются процессы скриптов; без неё — обычный запуск
+        let sandbox = match std::env::var("RUNNER_SANDBOX") {
+            Ok(value) if !value.trim().is_empty() => Some(
+                Sandbox::parse(&value, &scripts_dir)
+                    .map_err(|e| anyhow!("Invalid value for RUNNER_SANDBOX: {}", e))?,
+            ),
+            _ => None,
+        };
+        if sandbox.is_some() && !cfg!(unix) {
+            return Err(anyhow!("RUNNER_SANDBOX is supported only on Unix"));
+        }
+        // Воркеры пула выполняют задачи вне песочницы
+        if sandbox.is_some() && pool_size > 0 {
+            return Err(anyhow!("RUNNER_POOL_SIZE cannot be used together with RUNNER_SANDBOX"));
+        }
+
+        // Выполнение произвольного кода через POST /eval; по умолчанию выключено
+        let allow_eval: bool = env_parse("RUNNER_ALLOW_EVAL", false)?;
+
+        // Запрет изменения скриптов через API (скрипты выкладываются извне)
+        let read_only: bool = env_parse("RUNNER_READ_ONLY", false)?;
+
+        // Сжатие ответов запуска и истории; меньшие ответы отдаются как есть
+        let compression: bool = env_parse("RUNNER_COMPRESSION", true)?;
+        let compression_min_bytes: u16 = env_parse("RUNNER_COMPRESSION_MIN_BYTES", 1024)?;
+
+        // Хосты, на которые разрешено отправлять уведомления; пусто — callback_url не принимается
+        let callback_hosts = match std::env::var("RUNNER_CALLBACK_HOSTS") {
+            Ok(value) => value
+                .split(',')
+                .map(|host| host.trim().to_ascii_lowercase())
+                .filter(|host| !host.is_empty())
+                .collect(),
+            Err(_) => HashSet::new(),
+        };
+
+        // 0 отключает хранение предыдущих версий скриптов
+        let max_script_versions: usize = env_parse("RUNNER_MAX_SCRIPT_VERSIONS", 20)?;
+
+        // Сколько дней удалённые скрипты хранятся в корзине; 0 — не очищать
+        let trash_days: u64 = env_parse("RUNNER_TRASH_RETENTION_DAYS", 30)?;
+        let trash_retention = (trash_days > 0).then(|| Duration::from_secs(trash_days * 86400));
+        // Сколько дней хранить предыдущие версии скриптов; 0 — пока их не вытеснят новые
+        let version_days: u64 = env_parse("RUNNER_VERSION_RETENTION_DAYS", 0)?;
+        let version_retention = (version_days > 0).then(|| Duration::from_secs(version_days * 86400));
+        // Как часто удалять данные старше их срока хранения
+        let gc_interval = env_secs("RUNNER_GC_INTERVAL_SECS", 60)?;
+
+        // Файлы-результаты запусков: где хранить, сколько байт на запуск (0 — не
+        // сохранять) и сколько секунд
+        let artifacts_dir = std::env::var("RUNNER_ARTIFACTS_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| std::env::temp_dir().join("script-runner-artifacts"));
+        let max_artifact_bytes: u64 = env_parse("RUNNER_MAX_ARTIFACT_BYTES", 100 * 1024 * 1024)?;
+        let artifact_retention = env_secs("RUNNER_ARTIFACT_RETENTION_SECS", 86400)?;
+
+        let api_tokens = match std::env::var("RUNNER_API_TOKENS") {
+            Ok(value) => parse_api_tokens(&value)?,
+            Err(_) => Vec::new(),
+        };
+        // Пользователи JWT, которым доступно управление секретами;
+        // по умолчанию — суперадминистратор
+        let admin_users: Vec<String> = std::env::var("RUNNER_ADMIN_USERS")
+            .or_else(|_| std::env::var("SUPER_ADMIN_NAME"))
+            .map(|value| {
+                value
+                    .split(',')
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        // Хранилище секретов: ключ шифрования (base64, 32 байта) и файл
+        let secrets_key = std::env::var("RUNNER_SECRETS_KEY").ok();
+        let secrets_file = std::env::var("RUNNER_SECRETS_FILE")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| scripts_dir.join(".secrets.json"));
+
+        Ok(Self {
+            bind,
+            socket_mode,
+            tls,
+            scripts_dir,
+            max_concurrent,
+            cache_ttl,
+            cache_max_entries,
+            cache_db,
+            script_timeout,
+            max_script_timeout,
+            job_retention,
+            max_job_wait,
+            output_store_bytes,
+            output_retention,
+            run_logs_per_script,
+            idempotency_store_bytes,
+            idempotency_ttl,
+            idempotency_persist,
+            interpreters,
+            allowed_interpreters,
+            deps_install_timeout,
+            history_size,
+            history_db,
+            audit_size,
+            audit_log,
+            history_retention,
+            scan_interval,
+            max_raw_body_bytes,
+            max_script_bytes,
+            max_run_body_bytes,
+            shutdown_timeout,
+            rate_limit,
+            rate_burst,
+            rate_limit_exempt,
+            script_quota,
+            caller_quota,
+            namespace_defaults,
+            quota_count_cache_hits,
+            api_tokens,
+            admin_users,
+            secrets_key,
+            secrets_file,
+            max_output_bytes,
+            run_limits,
+            max_script_versions,
+            trash_retention,
+            version_retention,
+            gc_interval,
+            artifacts_dir,
+            max_artifact_bytes,
+            artifact_retention,
+            max_queue_wait,
+            script_max_concurrent,
+            script_max_queued,
+            pool_size,
+            pool_max_task_memory_mb,
+            memory_limit_mb,
+            cpu_limit_secs,
+            exec_user,
+            network_fallback,
+            sandbox,
+            allow_eval,
+            read_only,
+            compression,
+            compression_min_bytes,
+            callback_hosts,
+            fetch_hosts,
+        })
+    }
+}
+
+// Значение переменной окружения или значение по умолчанию, если она не задана
+fn env_parse<T>(key: &str, default: T) -> anyhow::Result<T>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    match std::env::var(key) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .with_context(|| format!("Invalid value for {}: '{}'", key, value)),
+        Err(_) => Ok(default),
+    }
+}
+
+// Положительное число секунд
+fn env_secs(key: &str, default: u64) -> anyhow::Result<Duration> {
+    let secs: u64 = env_parse(key, default)?;
+    if secs == 0 {
+        return Err(anyhow!("{} must be greater than 0", key));
+    }
+    Ok(Duration::from_secs(secs))
+}
+
+// Необязательное ограничение; 0 или отсутствие переменной — без ограничения
+fn env_limit(key: &str) -> anyhow::Result<Option<u64>> {
+    Ok(Some(env_parse::<u64>(key, 0)?).filter(|limit| *limit > 0))
+}
+
+// Формат: "py=python3 -u,sh=bash,js=node"
+fn parse_interpreters(value: &str) -> anyhow::Result<HashMap<String, String>> {
+    let mut interpreters = HashMap::new();
+    for entry in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (ext, cmd) = entry
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Invalid RUNNER_INTERPRETERS entry '{}': expected ext=command", entry))?;
+        let ext = ext.trim().trim_start_matches('.');
+        let cmd = cmd.trim();
+        if ext.is_empty() || cmd.is_empty() {
+            return Err(anyhow!("Invalid RUNNER_INTERPRETERS entry '{}'", entry));
+        }
+        interpreters.insert(ext.to_string(), cmd.to_string());
+    }
+    if interpreters.is_empty() {
+        return Err(anyhow!("RUNNER_INTERPRETERS must contain at least one entry"));
+    }
+    Ok(interpreters)
+}
+
+// Формат: "token1:rw,token2:ro,token3,token4:rw@team-a|team-b" (без суффикса —
+// чтение и запись; после `@` — пространства имён, к которым привязан токен)
+fn parse_api_tokens(value: &str) -> anyhow::Result<Vec<ApiToken>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (rest, namespaces) = match entry.rsplit_once('@') {
+                Some((rest, list)) => {
+                    let namespaces: HashSet<String> = list.split('|').map(str::to_string).collect();
+                    if namespaces.iter().any(|namespace| !is_valid_dir_name(namespace)) {
+                        return Err(anyhow!("Invalid namespace list in RUNNER_API_TOKENS entry '{}'", entry));
+                    }
+                    (rest, Some(namespaces))
+                }
+                None => (entry, None),
+            };
+            let (token, access) = match rest.rsplit_once(':') {
+                Some((token, "ro")) => (token, TokenAccess::ReadOnly),
+                Some((token, "rw")) => (token, TokenAccess::ReadWrite),
+                _ => (rest, TokenAccess::ReadWrite),
+            };
+            if token.is_empty() {
+                return Err(anyhow!("Invalid RUNNER_API_TOKENS entry '{}'", entry));
+            }
+            Ok(ApiToken {
+                token: token.to_string(),
+                access,
+                namespaces,
+            })
+        })
+        .collect()
+}
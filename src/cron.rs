@@ -0,0 +1,133 @@
+use chrono::{DateTime, Datelike, TimeDelta, TimeZone, Timelike, Utc};
+use std::str::FromStr;
+
+// Насколько далеко искать следующий запуск: покрывает 29 февраля
+const SEARCH_LIMIT_DAYS: i64 = 366 * 5;
+
+/// Cron-выражение из пяти полей (минута, час, день месяца, месяц, день недели)
+/// или сокращение `@hourly`, `@daily`, `@weekly`, `@monthly`, `@yearly`.
+/// Поля: `*`, число, диапазон `a-b`, шаг `*/n` или `a-b/n`, списки через запятую.
+/// Время — UTC.
+#[derive(Debug, Clone)]
+pub struct CronExpr {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    // Если ограничены и день месяца, и день недели, достаточно совпадения любого
+    day_or: bool,
+}
+
+impl FromStr for CronExpr {
+    type Err = String;
+
+    fn from_str(expr: &str) -> Result<Self, Self::Err> {
+        let expr = match expr.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(format!("expected 5 fields, got {}", fields.len()));
+        }
+
+        let mut weekdays = parse_field(fields[4], 0, 7)?;
+        // 7 — тоже воскресенье
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            minutes: parse_field(fields[0], 0, 59)?,
+            hours: parse_field(fields[1], 0, 23)?,
+            days: parse_field(fields[2], 1, 31)?,
+            months: parse_field(fields[3], 1, 12)?,
+            weekdays,
+            day_or: !fields[2].starts_with('*') && !fields[4].starts_with('*'),
+        })
+    }
+}
+
+impl CronExpr {
+    /// Первая подходящая минута строго после `from`; `None`, если выражение
+    /// не срабатывает никогда (например, `0 0 31 2 *`)
+    pub fn next_after(&self, from: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut t = from.with_second(0)?.with_nanosecond(0)? + TimeDelta::minutes(1);
+        let limit = from + TimeDelta::days(SEARCH_LIMIT_DAYS);
+        // Несовпадающие месяцы, дни и часы пропускаются целиком
+        while t < limit {
+            if !has(self.months, t.month()) {
+                let (year, month) = if t.month() == 12 {
+                    (t.year() + 1, 1)
+                } else {
+                    (t.year(), t.month() + 1)
+                };
+                t = Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).single()?;
+            } else if !self.day_matches(&t) {
+                t = t.date_naive().succ_opt()?.and_hms_opt(0, 0, 0)?.and_utc();
+            } else if !has(self.hours, t.hour()) {
+                t = t.with_minute(0)? + TimeDelta::hours(1);
+            } else if !has(self.minutes, t.minute()) {
+                t += TimeDelta::minutes(1);
+            } else {
+                return Some(t);
+            }
+        }
+        None
+    }
+
+    fn day_matches(&self, t: &DateTime<Utc>) -> bool {
+        let day = has(self.days, t.day());
+        let weekday = has(self.weekdays, t.weekday().num_days_from_sunday());
+        if self.day_or {
+            day || weekday
+        } else {
+            day && weekday
+        }
+    }
+}
+
+fn has(mask: u64, value: u32) -> bool {
+    mask & (1 << value) != 0
+}
+
+// Битовая маска допустимых значений поля
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => match step.parse::<u32>() {
+                Ok(step) if step > 0 => (range, step),
+                _ => return Err(format!("invalid step in '{}'", part)),
+            },
+            None => (part, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (parse_value(start, min, max)?, parse_value(end, min, max)?)
+        } else {
+            let value = parse_value(range, min, max)?;
+            // `5/15` — с 5 до конца диапазона с шагом 15
+            (value, if part.contains('/') { max } else { value })
+        };
+        if start > end {
+            return Err(format!("invalid range '{}'", part));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+fn parse_value(value: &str, min: u32, max: u32) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(value) if (min..=max).contains(&value) => Ok(value),
+        _ => Err(format!("value '{}' is out of range {}-{}", value, min, max)),
+    }
+}
@@ -8,7 +8,7 @@ use mongodb::{
 use serde::{Deserialize, Serialize};
 use bcrypt::{hash, verify, DEFAULT_COST};
 use crate::error::AppError;
-use crate::models::User;
+use crate::models::{ScriptOrigin, User};
 use tracing::info;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -22,6 +22,8 @@ pub struct ScriptDoc {
     pub size: u64,
     pub created: BsonDateTime,
     pub modified: BsonDateTime,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<ScriptOrigin>,
 }
 
 pub async fn init_db(uri: &str, db_name: &str) -> Result<Database, mongodb::error::Error> {
@@ -52,6 +54,19 @@ pub async fn insert_script(
     Ok(())
 }
 
+// Заменяет документ скрипта целиком или создаёт его
+pub async fn replace_script(
+    db: &Database,
+    doc: ScriptDoc,
+) -> Result<(), mongodb::error::Error> {
+    let collection = scripts_collection(db);
+    collection
+        .replace_one(doc! { "name": doc.name.as_str() }, &doc)
+        .upsert(true)
+        .await?;
+    Ok(())
+}
+
 pub async fn update_script(
     db: &Database,
     name: &str,
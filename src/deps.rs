@@ -0,0 +1,347 @@
+use crate::{
+    app_state::{AppState, DepsRecord},
+    error::AppError,
+    models::{DepsState, DepsStatus},
+    utils::{join_name, sha256_hex},
+};
+use chrono::Utc;
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::Arc,
+};
+use tokio::{
+    fs,
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
+    process::Command,
+    time::timeout,
+};
+use tracing::{debug, info, info_span, warn, Instrument};
+
+/// Директория виртуальных окружений внутри директории скриптов; сканер её пропускает
+pub const VENVS_DIR: &str = ".venvs";
+
+// Зависимости одного скрипта: `report.py` -> `report.py.requirements.txt`
+const REQUIREMENTS_SUFFIX: &str = ".requirements.txt";
+
+// Общие зависимости всех скриптов директории и её поддиректорий
+const REQUIREMENTS_FILE: &str = "requirements.txt";
+
+// Окружение директории; скрытое имя не совпадёт с именем скрипта
+const SHARED_VENV: &str = ".shared";
+
+// Хэш requirements, с которым собрано окружение
+const MARKER_FILE: &str = ".requirements.sha256";
+
+// Сколько последних строк вывода установки хранится для `GET .../deps`
+const MAX_LOG_LINES: usize = 500;
+
+/// Файл зависимостей, который относится к скрипту, и окружение для него
+struct Requirements {
+    file: PathBuf,
+    venv: PathBuf,
+    // Префикс ключей кэша скриптов, которые используют это окружение
+    cache_prefix: String,
+}
+
+// Ближайший к скрипту файл зависимостей: собственный, затем `requirements.txt`
+// в его директории и выше вплоть до директории скриптов
+async fn find(state: &AppState, script_name: &str) -> Option<Requirements> {
+    if !script_name.ends_with(".py") {
+        return None;
+    }
+    let venvs = state.scripts_dir.join(VENVS_DIR);
+    let own = requirements_path(&state.script_path(script_name));
+    if fs::try_exists(&own).await.unwrap_or(false) {
+        return Some(Requirements {
+            file: own,
+            venv: join_name(&venvs, script_name),
+            cache_prefix: format!("{}:", script_name),
+        });
+    }
+
+    let mut dir = script_name.rsplit_once('/').map(|(dir, _)| dir);
+    loop {
+        let (file, venv, cache_prefix) = match dir {
+            Some(dir) => (
+                state.script_path(dir).join(REQUIREMENTS_FILE),
+                join_name(&venvs, dir).join(SHARED_VENV),
+                format!("{}/", dir),
+            ),
+            None => (
+                state.scripts_dir.join(REQUIREMENTS_FILE),
+                venvs.join(SHARED_VENV),
+                String::new(),
+            ),
+        };
+        if fs::try_exists(&file).await.unwrap_or(false) {
+            return Some(Requirements { file, venv, cache_prefix });
+        }
+        dir = match dir {
+            Some(current) => current.rsplit_once('/').map(|(parent, _)| parent),
+            None => return None,
+        };
+    }
+}
+
+/// Путь к собственному файлу зависимостей скрипта
+pub fn requirements_path(script_path: &Path) -> PathBuf {
+    let mut path = script_path.as_os_str().to_owned();
+    path.push(REQUIREMENTS_SUFFIX);
+    PathBuf::from(path)
+}
+
+fn venv_python(venv: &Path) -> PathBuf {
+    if cfg!(windows) {
+        venv.join("Scripts").join("python.exe")
+    } else {
+        venv.join("bin").join("python")
+    }
+}
+
+// Команда интерпретатора окружения, как у запуска скрипта
+fn venv_command(venv: &Path) -> String {
+    format!("{} -u", venv_python(venv).display())
+}
+
+fn relative(state: &AppState, path: &Path) -> String {
+    path.strip_prefix(&state.scripts_dir)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+// Состояние окружения для текущего содержимого файла зависимостей
+async fn current_state(state: &AppState, req: &Requirements, hash: &str) -> DepsState {
+    if let Some(record) = state.deps.lock().await.get(&req.venv) {
+        match record.state {
+            DepsState::Installing => return DepsState::Installing,
+            // Повторная установка после ошибки — только явным запросом
+            DepsState::Failed if record.hash == hash => return DepsState::Failed,
+            _ => {}
+        }
+    }
+    match fs::read_to_string(req.venv.join(MARKER_FILE)).await {
+        Ok(installed) if installed.trim() == hash => DepsState::Ready,
+        Ok(_) => DepsState::Outdated,
+        Err(_) => DepsState::Missing,
+    }
+}
+
+/// Интерпретатор виртуального окружения для скрипта с зависимостями.
+/// `None` — у скрипта нет requirements. Если окружение ещё не собрано или
+/// requirements изменились, запускается установка, а запуск отклоняется.
+pub async fn ensure(
+    state: &Arc<AppState>,
+    script_name: &str,
+    base_interpreter: &str,
+) -> Result<Option<String>, AppError> {
+    let Some(req) = find(state, script_name).await else {
+        return Ok(None);
+    };
+    let hash = sha256_hex(&fs::read(&req.file).await?);
+    let not_ready = |reason: &str| {
+        AppError::DependenciesNotReady(script_name.to_string(), reason.to_string())
+    };
+    match current_state(state, &req, &hash).await {
+        DepsState::Ready => Ok(Some(venv_command(&req.venv))),
+        DepsState::Installing => Err(not_ready("installation is in progress")),
+        DepsState::Failed => Err(not_ready("installation failed, see deps status")),
+        DepsState::Missing | DepsState::Outdated => {
+            start_install(state, req, hash, base_interpreter).await;
+            Err(not_ready("installation has started"))
+        }
+        DepsState::None => Ok(None),
+    }
+}
+
+/// Виртуальное окружение скрипта с requirements: путь относительно директории
+/// скриптов, состояние и команда его python
+pub async fn venv(state: &AppState, script_name: &str) -> Result<Option<(String, DepsState, String)>, AppError> {
+    let Some(req) = find(state, script_name).await else {
+        return Ok(None);
+    };
+    let hash = sha256_hex(&fs::read(&req.file).await?);
+    let deps_state = current_state(state, &req, &hash).await;
+    Ok(Some((relative(state, &req.venv), deps_state, venv_command(&req.venv))))
+}
+
+/// Состояние зависимостей скрипта и вывод последней установки
+pub async fn status(state: &AppState, script_name: &str) -> Result<DepsStatus, AppError> {
+    let Some(req) = find(state, script_name).await else {
+        return Ok(DepsStatus {
+            requirements: None,
+            state: DepsState::None,
+            started_at: None,
+            finished_at: None,
+            error: None,
+            log: Vec::new(),
+        });
+    };
+    let hash = sha256_hex(&fs::read(&req.file).await?);
+    let deps_state = current_state(state, &req, &hash).await;
+    let deps = state.deps.lock().await;
+    let record = deps.get(&req.venv);
+    Ok(DepsStatus {
+        requirements: Some(relative(state, &req.file)),
+        state: deps_state,
+        started_at: record.map(|r| r.started_at),
+        finished_at: record.and_then(|r| r.finished_at),
+        error: record.and_then(|r| r.error.clone()),
+        log: record.map(|r| r.log.iter().cloned().collect()).unwrap_or_default(),
+    })
+}
+
+/// Запускает установку зависимостей скрипта; с `force` окружение
+/// пересобирается, даже если оно актуально
+pub async fn install(
+    state: &Arc<AppState>,
+    script_name: &str,
+    base_interpreter: &str,
+    force: bool,
+) -> Result<DepsStatus, AppError> {
+    let req = find(state, script_name).await.ok_or_else(|| {
+        AppError::BadRequest(format!("Script '{}' has no requirements file", script_name))
+    })?;
+    let hash = sha256_hex(&fs::read(&req.file).await?);
+    match current_state(state, &req, &hash).await {
+        DepsState::Installing => {}
+        DepsState::Ready if !force => {}
+        _ => start_install(state, req, hash, base_interpreter).await,
+    }
+    status(state, script_name).await
+}
+
+// Ставит установку в фон, если для этого окружения она ещё не идёт
+async fn start_install(state: &Arc<AppState>, req: Requirements, hash: String, base_interpreter: &str) {
+    {
+        let mut deps = state.deps.lock().await;
+        if deps.get(&req.venv).is_some_and(|r| r.state == DepsState::Installing) {
+            return;
+        }
+        deps.insert(
+            req.venv.clone(),
+            DepsRecord {
+                hash: hash.clone(),
+                state: DepsState::Installing,
+                started_at: Utc::now(),
+                finished_at: None,
+                error: None,
+                log: VecDeque::new(),
+            },
+        );
+    }
+
+    let python = base_interpreter
+        .split_whitespace()
+        .next()
+        .unwrap_or(base_interpreter)
+        .to_string();
+    let span = info_span!("deps", requirements = %relative(state, &req.file));
+    let state = Arc::clone(state);
+    tokio::spawn(
+        async move {
+            info!("Installing dependencies into {}", req.venv.display());
+            // Процессы установки завершаются при сбросе future (`kill_on_drop`)
+            let result = match timeout(state.deps_install_timeout, build_venv(&state, &req, &python)).await {
+                Ok(result) => result,
+                Err(_) => Err(format!(
+                    "installation timed out after {:?}",
+                    state.deps_install_timeout
+                )),
+            };
+            let result = match result {
+                Ok(()) => fs::write(req.venv.join(MARKER_FILE), &hash)
+                    .await
+                    .map_err(|e| format!("failed to write {}: {}", MARKER_FILE, e)),
+                Err(e) => Err(e),
+            };
+
+            match &result {
+                Ok(()) => {
+                    info!("Dependencies installed into {}", req.venv.display());
+                    // Результаты, полученные со старым набором пакетов, больше не годятся
+                    state
+                        .cache
+                        .lock()
+                        .await
+                        .retain(|key, _| !key.starts_with(&req.cache_prefix));
+                    // и отчёт об окружении со старым списком пакетов тоже
+                    state.environments.lock().await.remove(&venv_command(&req.venv));
+                }
+                Err(e) => warn!("Failed to install dependencies into {}: {}", req.venv.display(), e),
+            }
+            if let Some(record) = state.deps.lock().await.get_mut(&req.venv) {
+                record.state = if result.is_ok() { DepsState::Ready } else { DepsState::Failed };
+                record.error = result.err();
+                record.finished_at = Some(Utc::now());
+            }
+        }
+        .instrument(span),
+    );
+}
+
+// Окружение создаётся заново, чтобы в нём не осталось удалённых из requirements пакетов
+async fn build_venv(state: &AppState, req: &Requirements, python: &str) -> Result<(), String> {
+    if fs::try_exists(&req.venv).await.unwrap_or(false) {
+        fs::remove_dir_all(&req.venv)
+            .await
+            .map_err(|e| format!("failed to remove old environment: {}", e))?;
+    }
+    if let Some(parent) = req.venv.parent() {
+        fs::create_dir_all(parent)
+            .await
+            .map_err(|e| format!("failed to create {}: {}", VENVS_DIR, e))?;
+    }
+
+    let mut venv = Command::new(python);
+    venv.arg("-m").arg("venv").arg(&req.venv);
+    run_logged(state, &req.venv, venv).await?;
+
+    let mut pip = Command::new(venv_python(&req.venv));
+    pip.args(["-m", "pip", "install", "--disable-pip-version-check", "--no-input", "-r"])
+        .arg(&req.file);
+    run_logged(state, &req.venv, pip).await
+}
+
+// Выполняет команду, построчно сохраняя её stdout и stderr в журнал установки
+async fn run_logged(state: &AppState, venv: &Path, mut command: Command) -> Result<(), String> {
+    let program = command.as_std().get_program().to_string_lossy().into_owned();
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("failed to start '{}': {}", program, e))?;
+
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+    let (status, _, _) = tokio::join!(
+        child.wait(),
+        log_lines(state, venv, stdout),
+        log_lines(state, venv, stderr),
+    );
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(format!("'{}' exited with {}", program, status)),
+        Err(e) => Err(format!("failed to wait for '{}': {}", program, e)),
+    }
+}
+
+async fn log_lines<R: AsyncRead + Unpin>(state: &AppState, venv: &Path, reader: Option<R>) {
+    let Some(reader) = reader else {
+        return;
+    };
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        debug!("{}", line);
+        if let Some(record) = state.deps.lock().await.get_mut(venv) {
+            if record.log.len() == MAX_LOG_LINES {
+                record.log.pop_front();
+            }
+            record.log.push_back(line);
+        }
+    }
+}
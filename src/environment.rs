@@ -0,0 +1,194 @@
+use crate::{
+    app_state::AppState,
+    deps,
+    error::AppError,
+    models::{DepsState, EnvironmentReport, InstalledPackage, ScriptKind},
+    script_runner,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use std::{collections::BTreeMap, process::Stdio, time::Duration};
+use tokio::{process::Command, time::timeout};
+use tracing::warn;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(15);
+
+// Расширение, интерпретатор которого описывает `GET /environment`
+const PYTHON_EXTENSION: &str = "py";
+
+// Каждая часть отчёта собирается отдельно: ошибка одной не теряет остальные
+const PROBE: &str = r#"
+import json, os, platform, sys
+report = {"errors": []}
+try:
+    report["executable"] = sys.executable
+    report["version"] = platform.python_version()
+    report["platform"] = platform.platform()
+except Exception as e:
+    report["errors"].append("interpreter: %s" % e)
+try:
+    from importlib import metadata
+    report["packages"] = [
+        {"name": d.metadata["Name"], "version": d.version}
+        for d in metadata.distributions()
+        if d.metadata["Name"]
+    ]
+except Exception as e:
+    report["errors"].append("packages: %s" % e)
+report["env"] = dict(os.environ)
+print(json.dumps(report))
+"#;
+
+// Переменные, имя которых содержит одно из этих слов, не попадают в отчёт:
+// в них обычно лежат токены, пароли и строки подключения с учётными данными
+const ENV_BLOCKLIST: &[&str] = &[
+    "TOKEN", "SECRET", "PASS", "KEY", "CREDENTIAL", "AUTH", "PRIVATE", "COOKIE", "SESSION", "DSN", "URI",
+    "URL", "JWT",
+];
+
+#[derive(Deserialize, Default)]
+struct ProbeOutput {
+    executable: Option<String>,
+    version: Option<String>,
+    platform: Option<String>,
+    #[serde(default)]
+    packages: Vec<InstalledPackage>,
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+    #[serde(default)]
+    errors: Vec<String>,
+}
+
+/// Окружение интерпретатора `.py`, которым выполняются скрипты
+pub async fn report(state: &AppState, refresh: bool) -> EnvironmentReport {
+    match state.interpreters.get(PYTHON_EXTENSION) {
+        Some(interpreter) => cached_probe(state, interpreter, None, refresh).await,
+        None => failed(String::new(), None, "No interpreter is configured for .py scripts".to_string()),
+    }
+}
+
+/// Окружение, в котором выполняется скрипт: python его виртуального
+/// окружения, если оно собрано, иначе интерпретатор из настроек скрипта
+pub async fn script_report(state: &AppState, script_name: &str, refresh: bool) -> Result<EnvironmentReport, AppError> {
+    let (_, entry) = script_runner::resolve_script(state, script_name).await?;
+    let interpreter = script_runner::base_interpreter(state, script_name, &entry)?;
+    let is_python = entry.kind == ScriptKind::Package || script_name.ends_with(&format!(".{}", PYTHON_EXTENSION));
+    if !is_python {
+        return Ok(failed(
+            interpreter,
+            None,
+            "Environment probe is only available for Python scripts".to_string(),
+        ));
+    }
+    match deps::venv(state, script_name).await? {
+        Some((venv, DepsState::Ready, python)) => Ok(cached_probe(state, &python, Some(venv), refresh).await),
+        // Окружение не собрано: скрипт пока не запустится, отчёт — о базовом интерпретаторе
+        Some((venv, deps_state, _)) => {
+            let mut report = cached_probe(state, &interpreter, None, refresh).await;
+            report.errors.push(format!(
+                "Virtual environment {} is {}, reporting the base interpreter",
+                venv,
+                format!("{:?}", deps_state).to_lowercase()
+            ));
+            Ok(report)
+        }
+        None => Ok(cached_probe(state, &interpreter, None, refresh).await),
+    }
+}
+
+// Отчёт из кэша или новая проверка; кэш — до `refresh` или переустановки окружения
+async fn cached_probe(state: &AppState, interpreter: &str, venv: Option<String>, refresh: bool) -> EnvironmentReport {
+    let mut cached = state.environments.lock().await;
+    if !refresh {
+        if let Some(report) = cached.get(interpreter) {
+            return EnvironmentReport {
+                cached: true,
+                ..report.clone()
+            };
+        }
+    }
+    let report = probe(interpreter, venv).await;
+    cached.insert(interpreter.to_string(), report.clone());
+    report
+}
+
+// Запускает проверку тем же интерпретатором, что и скрипты, но без песочницы
+// и ограничений; stdout — JSON-отчёт
+async fn probe(interpreter: &str, venv: Option<String>) -> EnvironmentReport {
+    let mut parts = interpreter.split_whitespace();
+    let Some(program) = parts.next() else {
+        return failed(interpreter.to_string(), venv, "Interpreter command is empty".to_string());
+    };
+    let output = Command::new(program)
+        .args(parts)
+        .arg("-c")
+        .arg(PROBE)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    let output = match timeout(PROBE_TIMEOUT, output).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => {
+            return failed(interpreter.to_string(), venv, format!("Failed to spawn '{}': {}", program, e));
+        }
+        Err(_) => {
+            return failed(
+                interpreter.to_string(),
+                venv,
+                format!("Probe timed out after {:?}", PROBE_TIMEOUT),
+            );
+        }
+    };
+
+    let mut errors = Vec::new();
+    let mut probed = match serde_json::from_slice::<ProbeOutput>(&output.stdout) {
+        Ok(probed) => probed,
+        Err(e) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let last_line = stderr.lines().last().unwrap_or_default();
+            warn!("Environment probe with '{}' failed: {}", interpreter, last_line);
+            errors.push(format!(
+                "Probe exited with {} and no report: {}",
+                output.status,
+                if last_line.is_empty() { e.to_string() } else { last_line.to_string() }
+            ));
+            ProbeOutput::default()
+        }
+    };
+    errors.append(&mut probed.errors);
+    probed.packages.sort_by_key(|package| package.name.to_lowercase());
+    probed.env.retain(|name, _| !is_blocked(name));
+
+    EnvironmentReport {
+        interpreter: interpreter.to_string(),
+        executable: probed.executable,
+        version: probed.version,
+        platform: probed.platform,
+        venv,
+        packages: probed.packages,
+        env: probed.env,
+        probed_at: Utc::now(),
+        cached: false,
+        errors,
+    }
+}
+
+fn is_blocked(name: &str) -> bool {
+    let name = name.to_ascii_uppercase();
+    ENV_BLOCKLIST.iter().any(|word| name.contains(word))
+}
+
+fn failed(interpreter: String, venv: Option<String>, error: String) -> EnvironmentReport {
+    EnvironmentReport {
+        interpreter,
+        executable: None,
+        version: None,
+        platform: None,
+        venv,
+        packages: Vec::new(),
+        env: BTreeMap::new(),
+        probed_at: Utc::now(),
+        cached: false,
+        errors: vec![error],
+    }
+}
@@ -1,15 +1,45 @@
 use axum::{
-    http::StatusCode,
+    http::{header, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
+    Json,
 };
 use thiserror::Error;
+use tracing::error;
+use crate::{
+    limits::Limit,
+    models::{ErrorBody, ErrorResponse, QuotaScope, QuotaUsage, ScriptResult, TimeoutBudget},
+};
 
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("Script '{0}' not found")]
     ScriptNotFound(String),
+    #[error("Version '{1}' of script '{0}' not found")]
+    VersionNotFound(String, u64),
+    #[error("Script '{0}' already exists")]
+    ScriptAlreadyExists(String),
+    #[error("Script '{0}' is disabled")]
+    ScriptDisabled(String),
     #[error("Script name invalid: {0}")]
     InvalidScriptName(String),
+    #[error("Unsupported script type: {0}")]
+    UnsupportedScriptType(String),
+    #[error("Bad request: {0}")]
+    BadRequest(String),
+    #[error("Interpreter '{0}' is not installed")]
+    InterpreterNotFound(String),
+    #[error("Network isolation is unavailable: {0}")]
+    NetworkIsolationUnavailable(String),
+    #[error("Sandbox is unavailable: {0}")]
+    SandboxUnavailable(String),
+    #[error("Interpreter '{0}' is not allowed")]
+    InterpreterNotAllowed(String),
+    #[error("Dependencies of '{0}' are not ready: {1}")]
+    DependenciesNotReady(String, String),
+    #[error("Precondition failed: {0}")]
+    PreconditionFailed(String),
+    #[error("Syntax error: {0}")]
+    SyntaxError(String),
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
     #[error("JSON error: {0}")]
@@ -18,45 +48,424 @@ pub enum AppError {
     Utf8(#[from] std::string::FromUtf8Error),
     #[error("MongoDB error: {0}")]
     Mongo(#[from] mongodb::error::Error),
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("Artifact '{1}' of run '{0}' not found")]
+    ArtifactNotFound(String, String),
+    #[error("Log of run '{0}' not found")]
+    RunLogNotFound(String),
+    #[error("Secret '{0}' not found")]
+    SecretNotFound(String),
+    #[error("Trash item '{0}' not found")]
+    TrashItemNotFound(String),
+    #[error("Job '{0}' not found")]
+    JobNotFound(String),
+    #[error("Schedule '{0}' not found")]
+    ScheduleNotFound(String),
+    #[error("Job '{0}' is already {1}")]
+    JobNotCancellable(String, &'static str),
+    #[error("Idempotency key '{0}' was already used for a different request")]
+    IdempotencyKeyReused(String),
+    #[error("Request with idempotency key '{0}' is still in progress")]
+    IdempotencyKeyInProgress(String),
     #[error("Script execution timed out")]
     Timeout,
+    #[error("Script execution cancelled")]
+    Cancelled(Option<Box<ScriptResult>>),
+    #[error("Server is shutting down")]
+    ShuttingDown,
+    #[error("{1}")]
+    LimitExceeded(Limit, String),
+    #[error("{}", payload_too_large_message(.limit, .size, .max))]
+    PayloadTooLarge {
+        limit: Limit,
+        size: Option<usize>,
+        max: usize,
+    },
+    #[error("Too many requests, retry after {0}s")]
+    RateLimited(u64),
+    #[error("All execution slots are busy, retry after {0}s")]
+    Busy(u64),
+    #[error("Script '{script}' is at its concurrency limit, retry after {retry_after}s")]
+    ScriptBusy {
+        script: String,
+        retry_after: u64,
+        /// Слот не освободился за время ожидания (а не переполнена очередь)
+        queue_timeout: bool,
+    },
+    #[error("Quota of '{}' is exhausted, retry after {retry_after}s", .usage.subject)]
+    QuotaExceeded {
+        usage: Box<QuotaUsage>,
+        retry_after: u64,
+    },
+    #[error("Namespace '{0}' not found")]
+    NamespaceNotFound(String),
+    #[error("Namespace '{0}' already exists")]
+    NamespaceAlreadyExists(String),
+    #[error("Namespace '{0}' still contains {1} files")]
+    NamespaceNotEmpty(String, usize),
+    #[error("Output of {0} is not available")]
+    SourceOutputNotFound(String),
+    #[error("Fetch failed: {0}")]
+    FetchFailed(String),
     #[error("Internal error: {0}")]
     Internal(String),
     #[error("Unauthorized: {0}")]
     Unauthorized(String),
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
     #[error("User already exists: {0}")]
     UserAlreadyExists(String),
 }
 
+impl AppError {
+    /// HTTP-статус ответа
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::ScriptNotFound(_)
+            | AppError::VersionNotFound(..)
+            | AppError::ArtifactNotFound(..)
+            | AppError::RunLogNotFound(_)
+            | AppError::TrashItemNotFound(_)
+            | AppError::SecretNotFound(_)
+            | AppError::JobNotFound(_)
+            | AppError::ScheduleNotFound(_)
+            | AppError::NamespaceNotFound(_)
+            | AppError::SourceOutputNotFound(_) => StatusCode::NOT_FOUND,
+            AppError::InvalidScriptName(_)
+            | AppError::UnsupportedScriptType(_)
+            | AppError::BadRequest(_)
+            | AppError::InterpreterNotAllowed(_)
+            | AppError::Json(_) => StatusCode::BAD_REQUEST,
+            AppError::JobNotCancellable(..)
+            | AppError::ScriptAlreadyExists(_)
+            | AppError::NamespaceAlreadyExists(_)
+            | AppError::NamespaceNotEmpty(..)
+            | AppError::Cancelled(_)
+            | AppError::IdempotencyKeyInProgress(_)
+            | AppError::UserAlreadyExists(_) => StatusCode::CONFLICT,
+            AppError::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            AppError::ScriptDisabled(_) => StatusCode::LOCKED,
+            AppError::SyntaxError(_) | AppError::IdempotencyKeyReused(_) => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+            AppError::LimitExceeded(limit, _) => limit.status(),
+            AppError::PayloadTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::Timeout => StatusCode::GATEWAY_TIMEOUT,
+            AppError::RateLimited(_)
+            | AppError::ScriptBusy { .. }
+            | AppError::QuotaExceeded { .. } => StatusCode::TOO_MANY_REQUESTS,
+            AppError::ShuttingDown
+            | AppError::Busy(_)
+            | AppError::InterpreterNotFound(_)
+            | AppError::NetworkIsolationUnavailable(_)
+            | AppError::SandboxUnavailable(_)
+            | AppError::DependenciesNotReady(..) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::FetchFailed(_) => StatusCode::BAD_GATEWAY,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::Io(_)
+            | AppError::Utf8(_)
+            | AppError::Mongo(_)
+            | AppError::Sqlite(_)
+            | AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Стабильный машиночитаемый код ошибки
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::ScriptNotFound(_) => "script_not_found",
+            AppError::VersionNotFound(..) => "version_not_found",
+            AppError::ScriptAlreadyExists(_) => "script_already_exists",
+            AppError::ScriptDisabled(_) => "script_disabled",
+            AppError::InvalidScriptName(_) => "invalid_script_name",
+            AppError::UnsupportedScriptType(_) => "unsupported_script_type",
+            AppError::BadRequest(_) => "bad_request",
+            AppError::InterpreterNotFound(_) => "interpreter_not_found",
+            AppError::NetworkIsolationUnavailable(_) => "network_isolation_unavailable",
+            AppError::SandboxUnavailable(_) => "sandbox_unavailable",
+            AppError::InterpreterNotAllowed(_) => "interpreter_not_allowed",
+            AppError::DependenciesNotReady(..) => "dependencies_not_ready",
+            AppError::PreconditionFailed(_) => "precondition_failed",
+            AppError::SyntaxError(_) => "syntax_error",
+            AppError::Io(_) => "io_error",
+            AppError::Json(_) => "invalid_json",
+            AppError::Utf8(_) => "invalid_utf8",
+            AppError::Mongo(_) | AppError::Sqlite(_) => "database_error",
+            AppError::ArtifactNotFound(..) => "artifact_not_found",
+            AppError::RunLogNotFound(_) => "run_log_not_found",
+            AppError::TrashItemNotFound(_) => "trash_item_not_found",
+            AppError::SecretNotFound(_) => "secret_not_found",
+            AppError::JobNotFound(_) => "job_not_found",
+            AppError::JobNotCancellable(..) => "job_not_cancellable",
+            AppError::ScheduleNotFound(_) => "schedule_not_found",
+            AppError::IdempotencyKeyReused(_) => "idempotency_key_reused",
+            AppError::IdempotencyKeyInProgress(_) => "idempotency_key_in_progress",
+            AppError::Timeout => "timeout",
+            AppError::Cancelled(_) => "cancelled",
+            AppError::ShuttingDown => "shutting_down",
+            AppError::LimitExceeded(..) | AppError::PayloadTooLarge { .. } => "limit_exceeded",
+            AppError::RateLimited(_) => "rate_limited",
+            AppError::Busy(_) => "busy",
+            AppError::ScriptBusy { .. } => "script_busy",
+            AppError::QuotaExceeded { .. } => "quota_exceeded",
+            AppError::NamespaceNotFound(_) => "namespace_not_found",
+            AppError::NamespaceAlreadyExists(_) => "namespace_already_exists",
+            AppError::NamespaceNotEmpty(..) => "namespace_not_empty",
+            AppError::SourceOutputNotFound(_) => "source_output_not_found",
+            AppError::FetchFailed(_) => "fetch_failed",
+            AppError::Internal(_) => "internal_error",
+            AppError::Unauthorized(_) => "unauthorized",
+            AppError::Forbidden(_) => "forbidden",
+            AppError::UserAlreadyExists(_) => "user_already_exists",
+        }
+    }
+
+    /// Какой из бюджетов времени исчерпан: ожидание слота или выполнение
+    pub fn timeout_budget(&self) -> Option<TimeoutBudget> {
+        match self {
+            AppError::Timeout => Some(TimeoutBudget::Exec),
+            AppError::Busy(_) | AppError::ScriptBusy { queue_timeout: true, .. } => {
+                Some(TimeoutBudget::Queue)
+            }
+            _ => None,
+        }
+    }
+
+    // Текст для клиента: без путей файловой системы и деталей подключения к БД
+    fn public_message(&self) -> String {
+        match self {
+            AppError::ScriptNotFound(name) => format!("Script '{}' not found", name),
+            AppError::UnsupportedScriptType(name) => format!("Unsupported script type: '{}'", name),
+            AppError::Io(e) => format!("IO error: {}", e.kind()),
+            AppError::Json(e) => format!("Invalid JSON: {}", e),
+            AppError::Utf8(e) => format!("Invalid UTF-8: {}", e.utf8_error()),
+            AppError::Mongo(_) | AppError::Sqlite(_) => "Database error".to_string(),
+            AppError::InterpreterNotFound(program) => format!(
+                "Interpreter '{}' is not installed or not on PATH",
+                program
+            ),
+            AppError::JobNotCancellable(id, status) => {
+                format!("Job '{}' is already {} and cannot be cancelled", id, status)
+            }
+            AppError::NamespaceNotEmpty(name, files) => format!(
+                "Namespace '{}' still contains {} files; delete its scripts first",
+                name, files
+            ),
+            AppError::InvalidScriptName(msg)
+            | AppError::BadRequest(msg)
+            | AppError::SyntaxError(msg)
+            | AppError::LimitExceeded(_, msg)
+            | AppError::FetchFailed(msg)
+            | AppError::Internal(msg)
+            | AppError::Unauthorized(msg)
+            | AppError::Forbidden(msg)
+            | AppError::UserAlreadyExists(msg) => msg.clone(),
+            _ => self.to_string(),
+        }
+    }
+
+    /// Тело ответа об ошибке; то же, что возвращается клиенту отдельно
+    /// и в результатах пакетного запуска
+    pub fn into_body(self) -> ErrorBody {
+        let mut body = ErrorBody {
+            code: self.code().to_string(),
+            message: self.public_message(),
+            script: None,
+            job_id: None,
+            limit: None,
+            size: None,
+            budget: self.timeout_budget(),
+            result: None,
+            quota: None,
+        };
+        match self {
+            AppError::ScriptNotFound(name)
+            | AppError::ScriptAlreadyExists(name)
+            | AppError::ScriptDisabled(name)
+            | AppError::UnsupportedScriptType(name)
+            | AppError::VersionNotFound(name, _)
+            | AppError::DependenciesNotReady(name, _)
+            | AppError::ScriptBusy { script: name, .. } => {
+                body.script = Some(name);
+            }
+            AppError::JobNotFound(id) | AppError::JobNotCancellable(id, _) => {
+                body.job_id = Some(id);
+            }
+            AppError::LimitExceeded(limit, _) => body.limit = Some(limit.as_str().to_string()),
+            AppError::PayloadTooLarge { limit, size, .. } => {
+                body.limit = Some(limit.as_str().to_string());
+                body.size = size;
+            }
+            AppError::Cancelled(partial) => body.result = partial.map(|r| *r),
+            AppError::QuotaExceeded { usage, .. } => {
+                if usage.scope == QuotaScope::Script {
+                    body.script = Some(usage.subject.clone());
+                }
+                body.quota = Some(*usage);
+            }
+            _ => {}
+        }
+        body
+    }
+}
+
+// Размер неизвестен, если тело без Content-Length оборвано при чтении
+fn payload_too_large_message(limit: &Limit, size: &Option<usize>, max: &usize) -> String {
+    match size {
+        Some(size) => format!("Payload is {} bytes, {} is {} bytes", size, limit.as_str(), max),
+        None => format!("Payload exceeds {} of {} bytes", limit.as_str(), max),
+    }
+}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let (status, msg) = match self {
-            AppError::ScriptNotFound(name) => (
+        let status = self.status();
+        if status.is_server_error() {
+            error!("{}", self);
+        }
+        let retry_after = match self {
+            AppError::RateLimited(secs)
+            | AppError::Busy(secs)
+            | AppError::ScriptBusy { retry_after: secs, .. }
+            | AppError::QuotaExceeded { retry_after: secs, .. } => Some(secs),
+            _ => None,
+        };
+
+        let body = self.into_body();
+        let mut response = (status, Json(ErrorResponse { error: body })).into_response();
+        if let Some(secs) = retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(secs));
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    async fn response_parts(error: AppError) -> (StatusCode, Option<String>, Value) {
+        let response = error.into_response();
+        let status = response.status();
+        let retry_after = response
+            .headers()
+            .get(header::RETRY_AFTER)
+            .map(|value| value.to_str().expect("ascii header").to_string());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("read body");
+        (status, retry_after, serde_json::from_slice(&body).expect("JSON body"))
+    }
+
+    #[tokio::test]
+    async fn error_responses_have_documented_shape() {
+        let cases = [
+            (
+                AppError::ScriptNotFound("etl/extract.py".to_string()),
                 StatusCode::NOT_FOUND,
-                format!("Script '{}' not found", name),
+                None,
+                json!({"code": "script_not_found", "message": "Script 'etl/extract.py' not found", "script": "etl/extract.py"}),
             ),
-            AppError::InvalidScriptName(msg) => (StatusCode::BAD_REQUEST, msg),
-            AppError::Io(e) => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("IO error: {}", e),
+            (
+                AppError::ScriptDisabled("hello.py".to_string()),
+                StatusCode::LOCKED,
+                None,
+                json!({"code": "script_disabled", "message": "Script 'hello.py' is disabled", "script": "hello.py"}),
             ),
-            AppError::Json(e) => (StatusCode::BAD_REQUEST, format!("Invalid JSON: {}", e)),
-            AppError::Utf8(e) => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("UTF-8 error: {}", e),
+            (
+                AppError::BadRequest("Missing field".to_string()),
+                StatusCode::BAD_REQUEST,
+                None,
+                json!({"code": "bad_request", "message": "Missing field"}),
             ),
-            AppError::Mongo(e) => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Database error: {}", e),
+            (
+                AppError::JobNotCancellable("42".to_string(), "completed"),
+                StatusCode::CONFLICT,
+                None,
+                json!({"code": "job_not_cancellable", "message": "Job '42' is already completed and cannot be cancelled", "job_id": "42"}),
+            ),
+            (
+                AppError::LimitExceeded(Limit::Args, "Too many arguments".to_string()),
+                StatusCode::BAD_REQUEST,
+                None,
+                json!({"code": "limit_exceeded", "message": "Too many arguments", "limit": "max_args"}),
+            ),
+            (
+                AppError::PayloadTooLarge {
+                    limit: Limit::ScriptBytes,
+                    size: Some(2048),
+                    max: 1024,
+                },
+                StatusCode::PAYLOAD_TOO_LARGE,
+                None,
+                json!({"code": "limit_exceeded", "message": "Payload is 2048 bytes, max_script_bytes is 1024 bytes", "limit": "max_script_bytes", "size": 2048}),
             ),
-            AppError::Timeout => (
+            (
+                AppError::Timeout,
                 StatusCode::GATEWAY_TIMEOUT,
-                "Script execution timed out".to_string(),
+                None,
+                json!({"code": "timeout", "message": "Script execution timed out", "budget": "exec"}),
             ),
-            AppError::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
-            AppError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, msg),
-            AppError::UserAlreadyExists(msg) => (StatusCode::CONFLICT, msg),
-        };
-        (status, msg).into_response()
+            (
+                AppError::Busy(5),
+                StatusCode::SERVICE_UNAVAILABLE,
+                Some("5"),
+                json!({"code": "busy", "message": "All execution slots are busy, retry after 5s", "budget": "queue"}),
+            ),
+            (
+                AppError::ScriptBusy {
+                    script: "hello.py".to_string(),
+                    retry_after: 2,
+                    queue_timeout: false,
+                },
+                StatusCode::TOO_MANY_REQUESTS,
+                Some("2"),
+                json!({"code": "script_busy", "message": "Script 'hello.py' is at its concurrency limit, retry after 2s", "script": "hello.py"}),
+            ),
+            (
+                AppError::RateLimited(1),
+                StatusCode::TOO_MANY_REQUESTS,
+                Some("1"),
+                json!({"code": "rate_limited", "message": "Too many requests, retry after 1s"}),
+            ),
+            (
+                AppError::Cancelled(None),
+                StatusCode::CONFLICT,
+                None,
+                json!({"code": "cancelled", "message": "Script execution cancelled"}),
+            ),
+            (
+                AppError::NamespaceNotEmpty("team".to_string(), 3),
+                StatusCode::CONFLICT,
+                None,
+                json!({"code": "namespace_not_empty", "message": "Namespace 'team' still contains 3 files; delete its scripts first"}),
+            ),
+            // Пути и подробности ошибок базы клиенту не передаются
+            (
+                AppError::Io(std::io::Error::new(std::io::ErrorKind::NotFound, "/srv/scripts/hello.py")),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                None,
+                json!({"code": "io_error", "message": "IO error: entity not found"}),
+            ),
+            (
+                AppError::Sqlite(rusqlite::Error::QueryReturnedNoRows),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                None,
+                json!({"code": "database_error", "message": "Database error"}),
+            ),
+        ];
+
+        for (error, status, retry_after, expected) in cases {
+            let name = format!("{:?}", error);
+            let (actual_status, actual_retry_after, body) = response_parts(error).await;
+            assert_eq!(actual_status, status, "{}", name);
+            assert_eq!(actual_retry_after.as_deref(), retry_after, "{}", name);
+            assert_eq!(body, json!({ "error": expected }), "{}", name);
+        }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,83 @@
+use crate::{
+    error::AppError,
+    models::{EventData, ServerEvent},
+};
+use chrono::Utc;
+use tokio::sync::broadcast;
+
+// Сколько событий хранится для отстающего подписчика; более старые он пропускает
+const CHANNEL_CAPACITY: usize = 1024;
+
+/// Группа событий для фильтра `GET /events?filter=...`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    Run,
+    Crud,
+    Scan,
+}
+
+impl EventKind {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "run" => Some(EventKind::Run),
+            "crud" => Some(EventKind::Crud),
+            "scan" => Some(EventKind::Scan),
+            _ => None,
+        }
+    }
+
+    pub fn of(data: &EventData) -> Self {
+        match data {
+            EventData::RunStarted { .. }
+            | EventData::RunFinished { .. }
+            | EventData::RunTimedOut { .. } => EventKind::Run,
+            EventData::ScriptChanged { .. } => EventKind::Crud,
+            EventData::ScanCompleted { .. } => EventKind::Scan,
+        }
+    }
+}
+
+/// Группы из `filter` через запятую; пустой фильтр — все события
+pub fn parse_filter(filter: Option<&str>) -> Result<Vec<EventKind>, AppError> {
+    filter
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|kind| !kind.is_empty())
+        .map(|kind| {
+            EventKind::parse(kind).ok_or_else(|| {
+                AppError::BadRequest(format!(
+                    "Unknown event filter '{}', expected run, crud or scan",
+                    kind
+                ))
+            })
+        })
+        .collect()
+}
+
+/// Рассылка событий сервера подписчикам `GET /events`. Публикация не ждёт
+/// подписчиков: отставший получает отметку о пропущенных событиях.
+pub struct Events {
+    tx: broadcast::Sender<ServerEvent>,
+}
+
+impl Default for Events {
+    fn default() -> Self {
+        let (tx, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self { tx }
+    }
+}
+
+impl Events {
+    pub fn publish(&self, data: EventData) {
+        // Ошибка означает лишь отсутствие подписчиков
+        let _ = self.tx.send(ServerEvent {
+            time: Utc::now(),
+            data,
+        });
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ServerEvent> {
+        self.tx.subscribe()
+    }
+}
@@ -0,0 +1,93 @@
+use crate::{error::AppError, workdir::RunDir};
+use std::path::Path;
+use tokio::process::Command;
+
+// Переменные окружения сервера, которые процесс скрипта получает и при
+// запуске от другого пользователя; остальные (токены, пути к секретам) — нет
+#[cfg(unix)]
+const INHERITED_ENV: &[&str] = &["PATH", "LANG", "LC_ALL", "TZ"];
+
+// Файлы, созданные скриптом, доступны только ему (и серверу, запущенному от root)
+#[cfg(unix)]
+const EXEC_UMASK: libc::mode_t = 0o077;
+
+/// Пользователь и группа, от имени которых запускаются скрипты
+/// (`RUNNER_EXEC_UID`, `RUNNER_EXEC_GID`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExecUser {
+    pub uid: u32,
+    pub gid: u32,
+}
+
+/// Запускает процесс от имени `user` с umask 077 и чистым окружением:
+/// только `INHERITED_ENV`, `HOME` и `TMPDIR` в рабочей директории.
+/// Переменные запроса добавляются вызывающим после этого вызова.
+#[cfg(unix)]
+pub fn apply(command: &mut Command, user: Option<ExecUser>, workdir: &Path) {
+    let Some(user) = user else {
+        return;
+    };
+    command.env_clear();
+    for key in INHERITED_ENV {
+        if let Some(value) = std::env::var_os(key) {
+            command.env(key, value);
+        }
+    }
+    command
+        .env("HOME", workdir)
+        .env("TMPDIR", workdir)
+        .uid(user.uid)
+        .gid(user.gid);
+    // SAFETY: между fork и exec вызывается только umask, он async-signal-safe
+    unsafe {
+        command.pre_exec(|| {
+            libc::umask(EXEC_UMASK);
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(unix))]
+pub fn apply(_command: &mut Command, _user: Option<ExecUser>, _workdir: &Path) {}
+
+/// Передаёт рабочую директорию запуска пользователю скрипта; без этого
+/// скрипт не сможет писать в неё и в подпапки артефактов
+#[cfg(unix)]
+pub fn prepare_dir(run_dir: &RunDir, user: Option<ExecUser>) -> Result<(), AppError> {
+    let Some(user) = user else {
+        return Ok(());
+    };
+    let [artifacts, output] = run_dir.artifact_dirs();
+    for path in [run_dir.path().to_path_buf(), artifacts, output] {
+        std::os::unix::fs::chown(&path, Some(user.uid), Some(user.gid))
+            .map_err(|e| refused(user, e))?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn prepare_dir(_run_dir: &RunDir, _user: Option<ExecUser>) -> Result<(), AppError> {
+    Ok(())
+}
+
+/// Серверу не хватило прав сменить пользователя процесса (EPERM)
+#[cfg(unix)]
+pub fn is_refused(e: &std::io::Error) -> bool {
+    e.raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+pub fn is_refused(_e: &std::io::Error) -> bool {
+    false
+}
+
+/// Запуск отклонён: скрипт не выполняется от имени сервера, если сменить
+/// пользователя не удалось
+pub fn refused(user: ExecUser, e: std::io::Error) -> AppError {
+    AppError::Internal(format!(
+        "Cannot run script as uid {} gid {}: {}",
+        user.uid,
+        user.gid,
+        e.kind()
+    ))
+}
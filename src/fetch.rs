@@ -0,0 +1,385 @@
+use crate::{
+    app_state::AppState,
+    audit::{Actor, Snapshot},
+    db,
+    error::AppError,
+    history,
+    history_db::ScriptChange,
+    limits::{self, Limit},
+    models::{ScriptOrigin, SyncFileResult, SyncResponse, SyncScriptsRequest, SyncStatus},
+    script_runner,
+    utils::{relative_name, validate_script_name, write_atomic},
+    versions,
+};
+use chrono::{DateTime, Utc};
+use mongodb::bson::{self, doc, DateTime as BsonDateTime};
+use reqwest::{header, redirect, Client, Url};
+use std::{
+    collections::HashSet,
+    path::{Component, Path, PathBuf},
+    process::Stdio,
+    time::{Duration, SystemTime},
+};
+use tokio::{fs, process::Command};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Клонирование репозитория целиком: fetch и checkout
+const GIT_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Загрузка скриптов по HTTPS с хостов из разрешённого списка
+/// (`RUNNER_FETCH_HOSTS`)
+pub struct Fetcher {
+    client: Client,
+    allowed_hosts: HashSet<String>,
+}
+
+impl Fetcher {
+    pub fn new(allowed_hosts: HashSet<String>) -> Self {
+        // Перенаправления не выполняются: иначе разрешённый хост мог бы
+        // увести запрос на внутренний адрес
+        let client = Client::builder()
+            .redirect(redirect::Policy::none())
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .expect("Failed to build HTTP client");
+        Self { client, allowed_hosts }
+    }
+
+    /// Проверяет адрес: только https и хост из разрешённого списка
+    pub fn validate(&self, url: &str) -> Result<Url, AppError> {
+        let url = Url::parse(url).map_err(|e| AppError::BadRequest(format!("Invalid url: {}", e)))?;
+        if url.scheme() != "https" {
+            return Err(AppError::BadRequest("url must use https".to_string()));
+        }
+        let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+        if !self.allowed_hosts.contains(&host) {
+            return Err(AppError::BadRequest(format!(
+                "Host '{}' is not allowed (RUNNER_FETCH_HOSTS)",
+                host
+            )));
+        }
+        Ok(url)
+    }
+
+    /// Скачивает файл не больше `max_bytes`; Content-Type не проверяется
+    pub async fn download(&self, url: Url, max_bytes: usize) -> Result<Vec<u8>, AppError> {
+        let host = url.host_str().unwrap_or_default().to_string();
+        let failed = |e: reqwest::Error| AppError::FetchFailed(format!("Request to {} failed: {}", host, e.without_url()));
+        let mut response = self.client.get(url.clone()).send().await.map_err(failed)?;
+        if !response.status().is_success() {
+            return Err(AppError::FetchFailed(format!(
+                "{} responded with {}",
+                host,
+                response.status()
+            )));
+        }
+        let too_large = |size| AppError::PayloadTooLarge {
+            limit: Limit::ScriptBytes,
+            size,
+            max: max_bytes,
+        };
+        // Content-Length проверяется заранее, но не заменяет подсчёт байтов
+        let declared = response
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok()?.parse::<usize>().ok());
+        if let Some(size) = declared.filter(|size| *size > max_bytes) {
+            return Err(too_large(Some(size)));
+        }
+        let mut content = Vec::new();
+        while let Some(chunk) = response.chunk().await.map_err(failed)? {
+            if content.len() + chunk.len() > max_bytes {
+                return Err(too_large(None));
+            }
+            content.extend_from_slice(&chunk);
+        }
+        info!("Downloaded {} bytes from {}", content.len(), host);
+        Ok(content)
+    }
+}
+
+/// Адрес без логина и пароля: он сохраняется в источнике скрипта и попадает в ошибки
+pub fn redacted(url: &Url) -> String {
+    let mut url = url.clone();
+    let _ = url.set_username("");
+    let _ = url.set_password(None);
+    url.to_string()
+}
+
+// Рабочая копия репозитория во временной директории; удаляется при drop
+struct Staging(PathBuf);
+
+impl Drop for Staging {
+    fn drop(&mut self) {
+        let path = std::mem::take(&mut self.0);
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = std::fs::remove_dir_all(&path) {
+                warn!("Failed to remove sync staging {}: {}", path.display(), e);
+            }
+        });
+    }
+}
+
+// Ветка, тег или хеш коммита; двоеточие превратило бы ref в refspec
+fn validate_ref(git_ref: &str) -> Result<(), AppError> {
+    let invalid = git_ref.is_empty()
+        || git_ref.starts_with('-')
+        || git_ref.contains(':')
+        || git_ref.chars().any(|c| c.is_whitespace() || c.is_control());
+    if invalid {
+        return Err(AppError::BadRequest(format!("Invalid ref '{}'", git_ref)));
+    }
+    Ok(())
+}
+
+// Поддиректория репозитория: только обычные сегменты пути
+fn validate_subdir(subdir: &str) -> Result<(), AppError> {
+    let invalid = subdir.contains('\\')
+        || Path::new(subdir)
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir));
+    if invalid {
+        return Err(AppError::BadRequest(format!("Invalid subdir '{}'", subdir)));
+    }
+    Ok(())
+}
+
+// Команда git без системных настроек и запроса пароля. Разрешён только
+// https без перенаправлений; символические ссылки извлекаются обычными файлами.
+fn git(dir: &Path) -> Command {
+    let mut command = Command::new("git");
+    command
+        .arg("-C")
+        .arg(dir)
+        .args([
+            "-c", "protocol.allow=never",
+            "-c", "protocol.https.allow=always",
+            "-c", "http.followRedirects=false",
+            "-c", "core.symlinks=false",
+            "-c", "core.hooksPath=/dev/null",
+        ])
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .stdin(Stdio::null())
+        .kill_on_drop(true);
+    command
+}
+
+// Выполняет git и возвращает stdout; в тексте ошибки адрес без учётных данных
+async fn run_git(mut command: Command, url: &Url) -> Result<String, AppError> {
+    let output = command.output().await.map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => AppError::FetchFailed("git is not installed".to_string()),
+        _ => AppError::Io(e),
+    })?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).replace(url.as_str(), &redacted(url));
+        return Err(AppError::FetchFailed(format!("git failed: {}", stderr.trim())));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+// Неглубокий клон нужного ref; возвращает хеш коммита
+async fn clone_repo(dir: &Path, url: &Url, git_ref: Option<&str>) -> Result<String, AppError> {
+    let mut init = git(dir);
+    init.args(["init", "-q"]);
+    run_git(init, url).await?;
+
+    let mut fetch = git(dir);
+    fetch
+        .args(["fetch", "-q", "--depth", "1", "--no-tags", "--"])
+        .arg(url.as_str())
+        .arg(git_ref.unwrap_or("HEAD"));
+    run_git(fetch, url).await?;
+
+    let mut checkout = git(dir);
+    checkout.args(["checkout", "-q", "--detach", "FETCH_HEAD"]);
+    run_git(checkout, url).await?;
+
+    let mut rev_parse = git(dir);
+    rev_parse.args(["rev-parse", "HEAD"]);
+    run_git(rev_parse, url).await
+}
+
+// Обычные файлы под `root`, кроме скрытых директорий (`.git`), с путями
+// относительно `root`
+async fn list_files(root: &Path) -> std::io::Result<Vec<(String, PathBuf)>> {
+    let mut files = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let mut entries = fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            let file_type = entry.file_type().await?;
+            if file_type.is_dir() {
+                if !entry.file_name().to_string_lossy().starts_with('.') {
+                    pending.push(path);
+                }
+            } else if file_type.is_file() {
+                if let Some(name) = relative_name(root, &path) {
+                    files.push((name, path));
+                }
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Клонирует репозиторий во временную директорию и копирует скрипты из
+/// `subdir` в директорию скриптов. Файлы с неподдерживаемым расширением
+/// пропускаются; файлы с недопустимым именем, не в UTF-8 и больше
+/// `RUNNER_MAX_SCRIPT_BYTES` получают статус `invalid`.
+pub async fn sync_scripts(
+    state: &AppState,
+    actor: &Actor,
+    request: SyncScriptsRequest,
+) -> Result<SyncResponse, AppError> {
+    let url = state.fetcher.validate(&request.git_url)?;
+    if let Some(git_ref) = &request.git_ref {
+        validate_ref(git_ref)?;
+    }
+    let subdir = request.subdir.as_deref().unwrap_or_default().trim_matches('/');
+    validate_subdir(subdir)?;
+
+    let staging = Staging(
+        std::env::temp_dir()
+            .join("script-runner-sync")
+            .join(Uuid::new_v4().to_string()),
+    );
+    fs::create_dir_all(&staging.0).await?;
+    let commit = tokio::time::timeout(
+        GIT_TIMEOUT,
+        clone_repo(&staging.0, &url, request.git_ref.as_deref()),
+    )
+    .await
+    .map_err(|_| AppError::FetchFailed(format!("git did not finish in {:?}", GIT_TIMEOUT)))??;
+    info!("Synchronizing scripts from {} at {}", redacted(&url), commit);
+
+    let root = staging.0.join(subdir);
+    let files = match list_files(&root).await {
+        Ok(files) => files,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(AppError::BadRequest(format!(
+                "Directory '{}' not found in the repository",
+                subdir
+            )))
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut results = Vec::new();
+    for (name, path) in files {
+        // В репозитории обычно есть README, настройки CI и т. п.
+        if state.interpreter_for(&name).is_err() {
+            continue;
+        }
+        let origin = ScriptOrigin {
+            url: redacted(&url),
+            git_ref: request.git_ref.clone(),
+            commit: Some(commit.clone()),
+            path: Some(if subdir.is_empty() { name.clone() } else { format!("{}/{}", subdir, name) }),
+            fetched: Utc::now(),
+        };
+        let result = match sync_file(state, actor, &name, &path, origin).await {
+            Ok(status) => SyncFileResult {
+                name,
+                status,
+                error: None,
+            },
+            Err(e @ (AppError::InvalidScriptName(_)
+            | AppError::PayloadTooLarge { .. }
+            | AppError::Utf8(_))) => SyncFileResult {
+                name,
+                status: SyncStatus::Invalid,
+                error: Some(e.to_string()),
+            },
+            Err(e) => return Err(e),
+        };
+        results.push(result);
+    }
+
+    info!("Synchronized {} scripts from {}", results.len(), redacted(&url));
+    Ok(SyncResponse { commit, files: results })
+}
+
+async fn sync_file(
+    state: &AppState,
+    actor: &Actor,
+    name: &str,
+    source: &Path,
+    origin: ScriptOrigin,
+) -> Result<SyncStatus, AppError> {
+    validate_script_name(name)?;
+    state.interpreter_for(name)?;
+    let code = String::from_utf8(fs::read(source).await?)?;
+    limits::check_script(state.max_script_bytes, code.as_bytes())?;
+
+    let path = state.checked_script_path(name).await?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).await?;
+    }
+    let current = match fs::read(&path).await {
+        Ok(content) => Some(content),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    let status = match current {
+        Some(content) if content == code.as_bytes() => SyncStatus::Unchanged,
+        Some(content) => {
+            let before = Some(Snapshot::of(&content));
+            versions::save_version(state, name).await?;
+            write_atomic(&path, code.as_bytes(), true).await?;
+            let after = Snapshot::of(code.as_bytes());
+            history::record_change(state, actor, name, ScriptChange::Sync, before, Some(after)).await;
+            SyncStatus::Updated
+        }
+        None => {
+            write_atomic(&path, code.as_bytes(), true).await?;
+            let after = Snapshot::of(code.as_bytes());
+            history::record_change(state, actor, name, ScriptChange::Sync, None, Some(after)).await;
+            SyncStatus::Created
+        }
+    };
+
+    let meta = fs::metadata(&path).await?;
+    let modified: DateTime<Utc> = meta
+        .modified()
+        .unwrap_or_else(|_| SystemTime::now())
+        .into();
+    let modified = BsonDateTime::from_millis(modified.timestamp_millis());
+
+    // Коммит обновляется и у неизменённых файлов; описание сохраняется
+    if db::get_script_by_name(&state.db, name).await?.is_some() {
+        let origin = bson::to_bson(&origin)
+            .map_err(|e| AppError::Internal(format!("Failed to encode script origin: {}", e)))?;
+        let update = doc! {
+            "code": &code,
+            "size": meta.len() as i64,
+            "modified": modified,
+            "origin": origin,
+        };
+        db::update_script(&state.db, name, update).await?;
+    } else {
+        let doc = db::ScriptDoc {
+            id: None,
+            name: name.to_string(),
+            code,
+            description: None,
+            result: None,
+            size: meta.len(),
+            created: modified,
+            modified,
+            origin: Some(origin),
+        };
+        db::replace_script(&state.db, doc).await?;
+    }
+
+    if status != SyncStatus::Unchanged {
+        script_runner::invalidate_script_cache(state, name).await;
+        script_runner::register_script(state, path).await;
+    }
+    Ok(status)
+}
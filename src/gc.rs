@@ -0,0 +1,143 @@
+use crate::{
+    app_state::AppState,
+    artifacts,
+    config::Config,
+    jobs,
+    models::{GcRemoved, GcReport},
+    trash,
+    versions,
+};
+use chrono::Utc;
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::info;
+
+/// Удалённые файлы одной категории и их размер
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Swept {
+    pub items: usize,
+    pub bytes: u64,
+}
+
+impl Swept {
+    pub fn add(&mut self, bytes: u64) {
+        self.items += 1;
+        self.bytes += bytes;
+    }
+}
+
+/// Очистка устаревших данных запусков: завершённых задач, артефактов,
+/// записей журнала, корзины и версий скриптов. Сроки хранения задач и
+/// артефактов берутся из состояния, остальные — `None`, если не ограничены.
+pub struct Gc {
+    trash_retention: Option<Duration>,
+    history_retention: Option<Duration>,
+    version_retention: Option<Duration>,
+    // Одновременно идёт одна очистка: ручная дожидается фоновой
+    sweeping: tokio::sync::Mutex<()>,
+    last: Mutex<Option<GcReport>>,
+    // Запуски, артефакты которых ещё записываются или понадобятся
+    active_runs: Mutex<HashSet<String>>,
+}
+
+/// Снимает защиту артефактов запуска при удалении
+pub struct ActiveRun<'a> {
+    gc: &'a Gc,
+    run_id: String,
+}
+
+impl Drop for ActiveRun<'_> {
+    fn drop(&mut self) {
+        self.gc.active_runs.lock().unwrap().remove(&self.run_id);
+    }
+}
+
+impl Gc {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            trash_retention: config.trash_retention,
+            history_retention: config.history_retention,
+            version_retention: config.version_retention,
+            sweeping: tokio::sync::Mutex::new(()),
+            last: Mutex::new(None),
+            active_runs: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Защищает артефакты запуска от очистки, пока жив возвращённый guard
+    pub fn track_run(&self, run_id: &str) -> ActiveRun<'_> {
+        self.active_runs.lock().unwrap().insert(run_id.to_string());
+        ActiveRun {
+            gc: self,
+            run_id: run_id.to_string(),
+        }
+    }
+
+    pub fn is_active(&self, run_id: &str) -> bool {
+        self.active_runs.lock().unwrap().contains(run_id)
+    }
+
+    /// Итог последней очистки
+    pub fn last(&self) -> Option<GcReport> {
+        self.last.lock().unwrap().clone()
+    }
+}
+
+/// Удаляет всё, что старше своего срока хранения, и запоминает итог
+pub async fn sweep(state: &AppState) -> GcReport {
+    let gc = &state.gc;
+    let _sweeping = gc.sweeping.lock().await;
+    let started_at = Utc::now();
+    let started = Instant::now();
+
+    let jobs = jobs::prune_jobs(state).await;
+    let artifacts = artifacts::prune(state).await;
+    let history = match (&state.history_db, gc.history_retention) {
+        (Some(history_db), Some(retention)) => history_db.purge(retention).await,
+        _ => 0,
+    };
+    state.idempotency.prune(state.history_db.as_ref());
+    let trash = match gc.trash_retention {
+        Some(retention) => trash::purge(state, retention).await,
+        None => Swept::default(),
+    };
+    let versions = match gc.version_retention {
+        Some(retention) => versions::prune(state, retention).await,
+        None => Swept::default(),
+    };
+
+    let report = GcReport {
+        started_at,
+        duration_ms: started.elapsed().as_millis() as u64,
+        removed: GcRemoved {
+            jobs,
+            artifacts: artifacts.items,
+            history,
+            trash: trash.items,
+            versions: versions.items,
+        },
+        reclaimed_bytes: artifacts.bytes + trash.bytes + versions.bytes,
+    };
+    if report.reclaimed_bytes > 0 || jobs + history > 0 {
+        info!(
+            "GC removed {} jobs, {} artifact dirs, {} history records, {} trash items, {} versions; reclaimed {} bytes",
+            jobs, artifacts.items, history, trash.items, versions.items, report.reclaimed_bytes
+        );
+    }
+    *gc.last.lock().unwrap() = Some(report.clone());
+    report
+}
+
+/// Фоновая очистка раз в `RUNNER_GC_INTERVAL_SECS`
+pub fn spawn(state: Arc<AppState>, interval: Duration) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            sweep(&state).await;
+        }
+    });
+}
@@ -1,23 +1,71 @@
 use crate::{
+    admin,
     app_state::AppState,
+    arg_template,
+    archive,
+    artifacts,
+    audit::{Actor, Snapshot},
     db,
+    deps,
     jwt,
+    environment,
     error::AppError,
+    events::{self, EventKind},
+    fetch,
+    gc,
+    health,
+    history,
+    history_db::ScriptChange,
+    idempotency::{self, Begin},
+    interface,
+    jobs,
+    limits,
     models::*,
-    script_runner,
+    namespace::{Namespace, NamespaceAccess},
+    pipeline,
+    quota::{self, Caller},
+    run_log,
+    schedules,
+    script_config,
+    script_runner::{self, RunOptions, StreamEvent},
+    secrets,
+    stats,
+    trash,
+    utils::{extract_description, sha256_hex, stdin_bytes, validate_env, validate_script_name, write_atomic},
+    validation,
+    versions,
 };
 use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
+    extract::{
+        multipart::{Multipart, MultipartError},
+        Extension, FromRequest, Path, Query, Request, State,
+    },
+    http::{header, HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
     Json,
 };
 use bytes::Bytes;
 use chrono::{DateTime, Utc};
-use futures::future::join_all;
-use mongodb::bson::{doc, DateTime as BsonDateTime};
-use std::{collections::HashMap, sync::Arc, time::SystemTime};
-use tokio::fs;
-use tracing::info;
+use futures::{future::join_all, stream, Stream, StreamExt};
+use globset::{GlobBuilder, GlobMatcher};
+use indexmap::IndexMap;
+use mongodb::bson::{doc, Bson, DateTime as BsonDateTime};
+use serde_json::json;
+use std::{
+    collections::HashSet,
+    convert::Infallible,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
+use tokio::{fs, sync::broadcast::error::RecvError};
+use tracing::{info, info_span, Instrument};
 
 fn bson_to_chrono(bson: BsonDateTime) -> DateTime<Utc> {
     let millis = bson.timestamp_millis();
@@ -31,7 +79,7 @@ fn bson_to_chrono(bson: BsonDateTime) -> DateTime<Utc> {
     params(SearchQuery),
     responses(
         (status = 200, description = "Список скриптов", body = Vec<ScriptMetadata>),
-        (status = 401, description = "Не авторизован")
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
     ),
     security(
         ("bearer_auth" = [])
@@ -40,14 +88,19 @@ fn bson_to_chrono(bson: BsonDateTime) -> DateTime<Utc> {
 )]
 pub async fn list_scripts(
     State(state): State<Arc<AppState>>,
+    namespace: Namespace,
     Query(search_query): Query<SearchQuery>,
 ) -> Result<Json<Vec<ScriptMetadata>>, AppError> {
     info!("Listing scripts with metadata (including code)");
 
     let docs = db::get_all_scripts(&state.db).await?;
+    let packages = script_runner::package_names(&state).await;
+    let disabled = script_runner::disabled_names(&state).await;
     let mut metadatas: Vec<ScriptMetadata> = docs
         .into_iter()
         .map(|doc| ScriptMetadata {
+            kind: if packages.contains(&doc.name) { ScriptKind::Package } else { ScriptKind::File },
+            disabled: disabled.contains(&doc.name),
             name: doc.name,
             code: Some(doc.code),
             description: doc.description,
@@ -55,8 +108,19 @@ pub async fn list_scripts(
             size: doc.size,
             created: bson_to_chrono(doc.created),
             modified: bson_to_chrono(doc.modified),
+            details: None,
+            origin: doc.origin,
         })
         .collect();
+    // Скрипты других пространств имён в список не попадают
+    metadatas.retain(|m| state.namespaces.contains(&namespace.0, &m.name));
+
+    if let Some(tag) = &search_query.tag {
+        let tags = parse_tags(tag)?;
+        let tagged: HashSet<String> =
+            script_runner::scripts_with_tags(&state, &tags, TagMatch::Any).await.into_iter().collect();
+        metadatas.retain(|m| tagged.contains(&m.name));
+    }
 
     // Фильтрация по поисковому запросу
     if let Some(query) = &search_query.query {
@@ -95,6 +159,19 @@ pub async fn list_scripts(
         }
     });
 
+    // Сведения с диска собираются параллельно для всех скриптов
+    if search_query.detail == Some(true) {
+        let details = join_all(
+            metadatas
+                .iter()
+                .map(|m| load_file_details(&state, &m.name)),
+        )
+        .await;
+        for (metadata, details) in metadatas.iter_mut().zip(details) {
+            metadata.details = details;
+        }
+    }
+
     Ok(Json(metadatas))
 }
 
@@ -103,12 +180,14 @@ pub async fn list_scripts(
     get,
     path = "/scripts/{name}",
     params(
-        ("name" = String, Path, description = "Имя файла скрипта")
+        ("name" = String, Path, description = "Имя скрипта, может включать поддиректории (`etl/extract.py`)"),
+        GetScriptQuery
     ),
     responses(
-        (status = 200, description = "Данные скрипта", body = ScriptMetadata),
-        (status = 404, description = "Скрипт не найден"),
-        (status = 401, description = "Не авторизован")
+        (status = 200, description = "Код скрипта (text/plain); при format=json — данные скрипта (ScriptMetadata). Заголовок ETag — хэш содержимого", body = String, content_type = "text/plain"),
+        (status = 400, description = "Некорректное имя скрипта", body = ErrorResponse),
+        (status = 404, description = "Скрипт не найден", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
     ),
     security(
         ("bearer_auth" = [])
@@ -118,37 +197,144 @@ pub async fn list_scripts(
 pub async fn get_script(
     State(state): State<Arc<AppState>>,
     Path(name): Path<String>,
-) -> Result<Json<ScriptMetadata>, AppError> {
+    Query(query): Query<GetScriptQuery>,
+) -> Result<Response, AppError> {
     info!("Get script {}", name);
 
-    let doc = db::get_script_by_name(&state.db, &name)
+    validate_script_name(&name)?;
+    state.interpreter_for(&name)?;
+
+    if query.format.as_deref() == Some("json") {
+        let metadata = load_script_metadata(&state, &name).await?;
+        let etag = etag(metadata.code.as_deref().unwrap_or_default().as_bytes());
+        return Ok(([(header::ETAG, etag)], Json(metadata)).into_response());
+    }
+
+    let code = read_script_code(&state, &name).await?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/plain; charset=utf-8".to_string()),
+            (header::ETAG, etag(code.as_bytes())),
+        ],
+        code,
+    )
+        .into_response())
+}
+
+// ETag скрипта — SHA-256 его содержимого в кавычках
+fn etag(content: &[u8]) -> String {
+    format!("\"{}\"", sha256_hex(content))
+}
+
+// Условие `If-Match` для изменения скрипта: `*` или один из ETag текущего
+// содержимого. Без заголовка изменение выполняется безусловно.
+async fn check_if_match(headers: &HeaderMap, path: &std::path::Path) -> Result<(), AppError> {
+    let Some(value) = headers.get(header::IF_MATCH) else {
+        return Ok(());
+    };
+    let value = value
+        .to_str()
+        .map_err(|_| AppError::BadRequest("Invalid If-Match header".to_string()))?;
+    let current = match fs::read(path).await {
+        Ok(content) => Some(etag(&content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    // Слабые ETag (`W/"..."`) при If-Match не совпадают ни с чем
+    let matches = current.as_deref().is_some_and(|current| {
+        value
+            .split(',')
+            .map(str::trim)
+            .any(|tag| tag == "*" || tag == current)
+    });
+    if matches {
+        Ok(())
+    } else {
+        Err(AppError::PreconditionFailed(format!(
+            "If-Match {} does not match the current script",
+            value
+        )))
+    }
+}
+
+// Читает код скрипта с диска; отсутствие файла — ScriptNotFound
+async fn read_script_code(state: &AppState, name: &str) -> Result<String, AppError> {
+    let path = state.checked_script_path(name).await?;
+    fs::read_to_string(&path).await.map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => AppError::ScriptNotFound(name.to_string()),
+        _ => AppError::Io(e),
+    })
+}
+
+// Метаданные скрипта из БД вместе с актуальным кодом с диска
+async fn load_script_metadata(state: &AppState, name: &str) -> Result<ScriptMetadata, AppError> {
+    let doc = db::get_script_by_name(&state.db, name)
         .await?
-        .ok_or_else(|| AppError::ScriptNotFound(name.clone()))?;
+        .ok_or_else(|| AppError::ScriptNotFound(name.to_string()))?;
 
-    let path = state.scripts_dir.join(&name);
-    let code = fs::read_to_string(&path).await?;
+    let code = read_script_code(state, name).await?;
+    let disabled = script_runner::is_disabled(state, name).await;
 
-    Ok(Json(ScriptMetadata {
+    Ok(ScriptMetadata {
         name: doc.name,
+        kind: ScriptKind::File,
         code: Some(code),
         description: doc.description,
         result: doc.result,
         size: doc.size,
         created: bson_to_chrono(doc.created),
         modified: bson_to_chrono(doc.modified),
-    }))
+        details: None,
+        origin: doc.origin,
+        disabled,
+    })
+}
+
+// Сведения о файле скрипта; недоступный файл пропускается
+async fn load_file_details(state: &AppState, name: &str) -> Option<ScriptFileDetails> {
+    let path = state.script_path(name);
+    let (meta, content) = tokio::join!(fs::metadata(&path), fs::read(&path));
+    let (meta, content) = (meta.ok()?, content.ok()?);
+    let code = String::from_utf8_lossy(&content);
+    let config = state
+        .scripts
+        .lock()
+        .await
+        .get(&path)
+        .map(|entry| entry.config.clone())
+        .unwrap_or_default();
+
+    Some(ScriptFileDetails {
+        size_bytes: meta.len(),
+        modified: meta.modified().map(DateTime::<Utc>::from).ok()?,
+        line_count: code.lines().count(),
+        description: extract_description(&code),
+        config,
+        etag: etag(&content),
+    })
 }
 
-/// Создать новый скрипт
+/// Создать новый скрипт: JSON с кодом или multipart с файлами
 #[utoipa::path(
     post,
     path = "/scripts",
-    request_body = CreateScriptRequest,
+    params(SaveScriptQuery),
+    request_body(
+        description = "JSON с кодом или multipart: файлы скриптов (имя скрипта — имя файла) и необязательное поле `overwrite`",
+        content(
+            (CreateScriptRequest = "application/json"),
+            (Vec<u8> = "multipart/form-data")
+        )
+    ),
     responses(
         (status = 201, description = "Скрипт создан"),
-        (status = 400, description = "Некорректное имя скрипта"),
-        (status = 409, description = "Скрипт уже существует"),
-        (status = 401, description = "Не авторизован")
+        (status = 200, description = "Результат по каждому файлу multipart-запроса", body = UploadResponse),
+        (status = 400, description = "Некорректное имя скрипта или multipart-запрос без файлов", body = ErrorResponse),
+        (status = 409, description = "Скрипт уже существует (без overwrite)", body = ErrorResponse),
+        (status = 422, description = "Синтаксическая ошибка в коде", body = ErrorResponse),
+        (status = 413, description = "Код превышает RUNNER_MAX_SCRIPT_BYTES или multipart-запрос — RUNNER_MAX_RAW_BODY_BYTES", body = ErrorResponse),
+        (status = 403, description = "Сервер в режиме только для чтения", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
     ),
     security(
         ("bearer_auth" = [])
@@ -157,23 +343,173 @@ pub async fn get_script(
 )]
 pub async fn create_script(
     State(state): State<Arc<AppState>>,
-    Json(payload): Json<CreateScriptRequest>,
-) -> Result<StatusCode, AppError> {
+    actor: Actor,
+    namespace: Namespace,
+    access: NamespaceAccess,
+    Query(save_query): Query<SaveScriptQuery>,
+    request: Request,
+) -> Result<Response, AppError> {
+    state.check_writable()?;
+    let validate = save_query.validate != Some(false);
+
+    if limits::is_multipart(request.headers()) {
+        let multipart = match Multipart::from_request(request, &state).await {
+            Ok(multipart) => multipart,
+            Err(rejection) => return Ok(rejection.into_response()),
+        };
+        let files = upload_scripts(&state, &actor, &namespace, &access, multipart, validate).await?;
+        return Ok(Json(UploadResponse { files }).into_response());
+    }
+
+    let mut payload = match Json::<CreateScriptRequest>::from_request(request, &state).await {
+        Ok(Json(payload)) => payload,
+        Err(rejection) => return Ok(rejection.into_response()),
+    };
+    payload.name = namespace.qualify(&payload.name);
+    state.namespaces.check_access(&access, &payload.name)?;
     info!("Creating script {}", payload.name);
 
-    if payload.name.contains('/') || payload.name.contains('\\') || !payload.name.ends_with(".py") {
-        return Err(AppError::InvalidScriptName(
-            "Name must be a simple .py filename".to_string(),
-        ));
+    save_new_script(&state, &actor, payload.into(), validate, None).await?;
+    Ok(StatusCode::CREATED.into_response())
+}
+
+// Файлы multipart-запроса сохраняются по одному; ошибка клиента в одном файле
+// попадает в его результат, не прерывая остальные
+async fn upload_scripts(
+    state: &AppState,
+    actor: &Actor,
+    namespace: &Namespace,
+    access: &NamespaceAccess,
+    mut multipart: Multipart,
+    validate: bool,
+) -> Result<Vec<UploadFileResult>, AppError> {
+    let bad_part = |e: MultipartError| AppError::BadRequest(format!("Invalid multipart body: {}", e.body_text()));
+
+    // Поле overwrite может идти после файлов, поэтому сначала читаются все части;
+    // их общий размер уже ограничен RUNNER_MAX_RAW_BODY_BYTES
+    let mut files = Vec::new();
+    let mut overwrite = false;
+    while let Some(field) = multipart.next_field().await.map_err(bad_part)? {
+        match field.file_name().map(str::to_string) {
+            Some(file_name) => files.push((file_name, field.bytes().await.map_err(bad_part)?)),
+            None if field.name() == Some("overwrite") => {
+                let value = field.text().await.map_err(bad_part)?;
+                overwrite = value.trim().parse().map_err(|_| {
+                    AppError::BadRequest(format!("Invalid overwrite value '{}': expected true or false", value))
+                })?;
+            }
+            None => {}
+        }
+    }
+    if files.is_empty() {
+        return Err(AppError::BadRequest("Multipart body contains no files".to_string()));
     }
+    info!("Uploading {} scripts (overwrite: {})", files.len(), overwrite);
 
-    let path = state.scripts_dir.join(&payload.name);
-    if path.exists() {
-        return Err(AppError::Internal("Script already exists".into()));
+    let mut results = Vec::with_capacity(files.len());
+    for (file_name, content) in files {
+        let name = namespace.qualify(&file_name);
+        let script = NewScript {
+            name: name.clone(),
+            content: content.to_vec(),
+            description: None,
+            result: None,
+            overwrite,
+        };
+        let existed = overwrite && state.script_path(&name).exists();
+        let saved = match state.namespaces.check_access(access, &name) {
+            Ok(()) => save_new_script(state, actor, script, validate, None).await,
+            Err(e) => Err(e),
+        };
+        let result = match saved {
+            Ok(()) => UploadFileResult {
+                name,
+                status: if existed { UploadStatus::Overwritten } else { UploadStatus::Created },
+                error: None,
+            },
+            Err(AppError::ScriptAlreadyExists(_)) => UploadFileResult {
+                name,
+                status: UploadStatus::Skipped,
+                error: None,
+            },
+            Err(e) if e.status().is_client_error() => UploadFileResult {
+                name,
+                status: UploadStatus::Error,
+                error: Some(e.into_body()),
+            },
+            Err(e) => return Err(e),
+        };
+        results.push(result);
+    }
+    Ok(results)
+}
+
+// Новый скрипт; содержимое записывается на диск байт в байт
+struct NewScript {
+    name: String,
+    content: Vec<u8>,
+    description: Option<String>,
+    result: Option<String>,
+    overwrite: bool,
+}
+
+impl From<CreateScriptRequest> for NewScript {
+    fn from(payload: CreateScriptRequest) -> Self {
+        Self {
+            name: payload.name,
+            content: payload.code.into_bytes(),
+            description: payload.description,
+            result: payload.result,
+            overwrite: payload.overwrite == Some(true),
+        }
+    }
+}
+
+// Запись нового скрипта или замена существующего (overwrite): общая часть
+// `POST /scripts` и `POST /scripts/fetch`
+async fn save_new_script(
+    state: &AppState,
+    actor: &Actor,
+    payload: NewScript,
+    validate: bool,
+    origin: Option<ScriptOrigin>,
+) -> Result<(), AppError> {
+    limits::check_script(state.max_script_bytes, &payload.content)?;
+    let path = state.checked_script_path(&payload.name).await?;
+    state.interpreter_for(&payload.name)?;
+
+    let overwrite = payload.overwrite;
+    if !overwrite && path.exists() {
+        return Err(AppError::ScriptAlreadyExists(payload.name));
     }
 
-    // Сохраняем файл
-    fs::write(&path, &payload.code).await?;
+    if validate {
+        validation::check_syntax(state, &payload.name, &payload.content).await?;
+    }
+
+    let before = if overwrite { Snapshot::read(&path).await } else { None };
+    if overwrite {
+        versions::save_version(state, &payload.name).await?;
+    }
+    // Поддиректории вложенного скрипта создаются при первом сохранении
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).await?;
+    }
+
+    // Сохраняем файл; без overwrite существующий файл не заменяется
+    write_atomic(&path, &payload.content, overwrite)
+        .await
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::AlreadyExists => AppError::ScriptAlreadyExists(payload.name.clone()),
+            _ => AppError::Io(e),
+        })?;
+    let change = match (&origin, overwrite) {
+        (Some(_), _) => ScriptChange::Fetch,
+        (None, true) => ScriptChange::Update,
+        (None, false) => ScriptChange::Create,
+    };
+    let after = Snapshot::of(&payload.content);
+    history::record_change(state, actor, &payload.name, change, before, Some(after)).await;
 
     // Метаданные файла
     let meta = fs::metadata(&path).await?;
@@ -187,38 +523,134 @@ pub async fn create_script(
         .into();
 
     // Документ в БД – преобразуем chrono в bson
+    let name = payload.name;
     let doc = db::ScriptDoc {
         id: None,
-        name: payload.name,
-        code: payload.code,
+        name: name.clone(),
+        // В БД хранится текст; файл на диске остаётся байт в байт
+        code: String::from_utf8_lossy(&payload.content).into_owned(),
         description: payload.description,
         result: payload.result,
         size: meta.len(),
         created: mongodb::bson::DateTime::from_millis(created.timestamp_millis()),
         modified: mongodb::bson::DateTime::from_millis(modified.timestamp_millis()),
+        origin,
     };
 
-    db::insert_script(&state.db, doc).await?;
+    // Документ мог уже появиться после сканирования нового файла — заменяем его
+    db::replace_script(&state.db, doc).await?;
+    if overwrite {
+        script_runner::invalidate_script_cache(state, &name).await;
+    }
 
     // Обновляем список в памяти
-    let mut scripts = state.scripts.lock().await;
-    scripts.push(path);
+    script_runner::register_script(state, path).await;
 
+    Ok(())
+}
+
+/// Загрузить скрипт по HTTPS-адресу
+#[utoipa::path(
+    post,
+    path = "/scripts/fetch",
+    params(SaveScriptQuery),
+    request_body = FetchScriptRequest,
+    responses(
+        (status = 201, description = "Скрипт загружен"),
+        (status = 400, description = "Некорректное имя скрипта, адрес или хост не из RUNNER_FETCH_HOSTS", body = ErrorResponse),
+        (status = 409, description = "Скрипт уже существует (без overwrite)", body = ErrorResponse),
+        (status = 422, description = "Синтаксическая ошибка в коде", body = ErrorResponse),
+        (status = 413, description = "Файл превышает RUNNER_MAX_SCRIPT_BYTES", body = ErrorResponse),
+        (status = 502, description = "Не удалось скачать файл", body = ErrorResponse),
+        (status = 403, description = "Сервер в режиме только для чтения", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "scripts"
+)]
+pub async fn fetch_script(
+    State(state): State<Arc<AppState>>,
+    actor: Actor,
+    Query(save_query): Query<SaveScriptQuery>,
+    Json(payload): Json<FetchScriptRequest>,
+) -> Result<StatusCode, AppError> {
+    state.check_writable()?;
+    let url = state.fetcher.validate(&payload.url)?;
+    info!("Fetching script {} from {}", payload.name, fetch::redacted(&url));
+
+    // Имя проверяется до загрузки, чтобы не скачивать файл впустую
+    validate_script_name(&payload.name)?;
+    state.interpreter_for(&payload.name)?;
+    let overwrite = payload.overwrite == Some(true);
+    if !overwrite && state.checked_script_path(&payload.name).await?.exists() {
+        return Err(AppError::ScriptAlreadyExists(payload.name));
+    }
+
+    let content = state.fetcher.download(url.clone(), state.max_script_bytes).await?;
+    let origin = ScriptOrigin {
+        url: fetch::redacted(&url),
+        git_ref: None,
+        commit: None,
+        path: None,
+        fetched: Utc::now(),
+    };
+    let request = CreateScriptRequest {
+        name: payload.name,
+        code: String::from_utf8(content)?,
+        description: payload.description,
+        result: None,
+        overwrite: payload.overwrite,
+    };
+    let validate = save_query.validate != Some(false);
+    save_new_script(&state, &actor, request.into(), validate, Some(origin)).await?;
     Ok(StatusCode::CREATED)
 }
 
+/// Синхронизировать скрипты из Git-репозитория
+#[utoipa::path(
+    post,
+    path = "/scripts/sync",
+    request_body = SyncScriptsRequest,
+    responses(
+        (status = 200, description = "Коммит и результат по каждому файлу", body = SyncResponse),
+        (status = 400, description = "Некорректный адрес, ref или subdir; хост не из RUNNER_FETCH_HOSTS", body = ErrorResponse),
+        (status = 502, description = "Не удалось получить репозиторий", body = ErrorResponse),
+        (status = 403, description = "Сервер в режиме только для чтения", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "scripts"
+)]
+pub async fn sync_scripts(
+    State(state): State<Arc<AppState>>,
+    actor: Actor,
+    Json(payload): Json<SyncScriptsRequest>,
+) -> Result<Json<SyncResponse>, AppError> {
+    state.check_writable()?;
+    Ok(Json(fetch::sync_scripts(&state, &actor, payload).await?))
+}
+
 /// Обновить существующий скрипт
 #[utoipa::path(
     put,
     path = "/scripts/{name}",
     params(
-        ("name" = String, Path, description = "Имя файла скрипта")
+        ("name" = String, Path, description = "Имя скрипта, может включать поддиректории (`etl/extract.py`)"),
+        SaveScriptQuery
     ),
     request_body = UpdateScriptRequest,
     responses(
         (status = 200, description = "Обновлённые данные скрипта", body = ScriptMetadata),
-        (status = 404, description = "Скрипт не найден"),
-        (status = 401, description = "Не авторизован")
+        (status = 404, description = "Скрипт не найден", body = ErrorResponse),
+        (status = 412, description = "Содержимое скрипта не совпадает с If-Match", body = ErrorResponse),
+        (status = 422, description = "Синтаксическая ошибка в коде", body = ErrorResponse),
+        (status = 413, description = "Код превышает RUNNER_MAX_SCRIPT_BYTES", body = ErrorResponse),
+        (status = 403, description = "Сервер в режиме только для чтения", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
     ),
     security(
         ("bearer_auth" = [])
@@ -227,19 +659,37 @@ pub async fn create_script(
 )]
 pub async fn update_script(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Path(name): Path<String>,
+    Query(save_query): Query<SaveScriptQuery>,
+    headers: HeaderMap,
     Json(payload): Json<UpdateScriptRequest>,
 ) -> Result<Json<ScriptMetadata>, AppError> {
+    state.check_writable()?;
     info!("Updating script {}", name);
 
-    let path = state.scripts_dir.join(&name);
+    if let Some(code) = &payload.code {
+        limits::check_script(state.max_script_bytes, code.as_bytes())?;
+    }
+    let path = state.checked_script_path(&name).await?;
     if !path.exists() {
         return Err(AppError::ScriptNotFound(name));
     }
+    check_if_match(&headers, &path).await?;
 
-    // Если передан code, обновляем файл
+    // Если передан code, проверяем и обновляем файл
     if let Some(ref code) = payload.code {
-        fs::write(&path, code).await?;
+        if save_query.validate != Some(false) {
+            validation::check_syntax(&state, &name, code.as_bytes()).await?;
+        }
+        let before = Snapshot::read(&path).await;
+        versions::save_version(&state, &name).await?;
+        write_atomic(&path, code.as_bytes(), true).await?;
+        let after = Snapshot::of(code.as_bytes());
+        history::record_change(&state, &actor, &name, ScriptChange::Update, before, Some(after)).await;
+        script_runner::invalidate_script_cache(&state, &name).await;
+        // Настройки в комментарии `runner:` могли измениться
+        script_runner::register_script(&state, path.clone()).await;
     }
 
     // Метаданные файла (всегда обновляем размер и mtime)
@@ -256,6 +706,8 @@ pub async fn update_script(
 
     if let Some(code) = payload.code {
         update_doc.insert("code", code);
+        // Код больше не совпадает с загруженным из источника
+        update_doc.insert("origin", Bson::Null);
     }
     if let Some(desc) = payload.description {
         update_doc.insert(
@@ -272,159 +724,2408 @@ pub async fn update_script(
 
     db::update_script(&state.db, &name, update_doc).await?;
 
-    // Если нужно будет, чтобы запрос возвращал измененный скрипт
-    get_script(State(state), Path(name)).await
-    // Ok(StatusCode::OK)
+    // Возвращаем изменённый скрипт
+    Ok(Json(load_script_metadata(&state, &name).await?))
 }
 
-/// Удалить скрипт
+/// Выгрузить все скрипты одним zip-архивом
 #[utoipa::path(
-    delete,
-    path = "/scripts/{name}",
+    get,
+    path = "/scripts/export",
+    responses(
+        (status = 200, description = "Zip-архив со скриптами", body = Vec<u8>, content_type = "application/zip"),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "scripts"
+)]
+pub async fn export_scripts(State(state): State<Arc<AppState>>) -> Result<Response, AppError> {
+    let archive = archive::export_scripts(&state).await?;
+    let file_name = format!("scripts-{}.zip", Utc::now().format("%Y%m%d-%H%M%S"));
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/zip".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", file_name),
+            ),
+        ],
+        archive,
+    )
+        .into_response())
+}
+
+/// Загрузить скрипты из zip-архива
+#[utoipa::path(
+    post,
+    path = "/scripts/import",
+    params(ImportQuery),
+    request_body(content = Vec<u8>, description = "Zip-архив со скриптами в корне", content_type = "application/zip"),
+    responses(
+        (status = 200, description = "Результат по каждому файлу", body = ImportResponse),
+        (status = 400, description = "Некорректный архив или запись с путём вне директории скриптов", body = ErrorResponse),
+        (status = 409, description = "Скрипт уже существует (mode=fail)", body = ErrorResponse),
+        (status = 413, description = "Архив превышает RUNNER_MAX_RAW_BODY_BYTES", body = ErrorResponse),
+        (status = 403, description = "Сервер в режиме только для чтения", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "scripts"
+)]
+pub async fn import_scripts(
+    State(state): State<Arc<AppState>>,
+    actor: Actor,
+    Query(query): Query<ImportQuery>,
+    body: Bytes,
+) -> Result<Json<ImportResponse>, AppError> {
+    state.check_writable()?;
+    let mode = query.mode.unwrap_or_default();
+    info!("Importing scripts archive ({} bytes, mode {:?})", body.len(), mode);
+
+    // Распакованный файл не может быть больше лимита на загрузку
+    let max_file_bytes = state.max_raw_body_bytes;
+    let files = archive::import_scripts(&state, &actor, body.to_vec(), mode, max_file_bytes).await?;
+    Ok(Json(ImportResponse { files }))
+}
+
+/// Список сохранённых версий скрипта
+#[utoipa::path(
+    get,
+    path = "/scripts/{name}/versions",
     params(
-        ("name" = String, Path, description = "Имя файла скрипта")
+        ("name" = String, Path, description = "Имя скрипта, может включать поддиректории (`etl/extract.py`)")
     ),
     responses(
-        (status = 204, description = "Скрипт удалён"),
-        (status = 404, description = "Скрипт не найден"),
-        (status = 401, description = "Не авторизован")
+        (status = 200, description = "Версии от новых к старым", body = [ScriptVersion]),
+        (status = 400, description = "Некорректное имя скрипта", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
     ),
     security(
         ("bearer_auth" = [])
     ),
     tag = "scripts"
 )]
-pub async fn delete_script(
+pub async fn list_script_versions(
     State(state): State<Arc<AppState>>,
     Path(name): Path<String>,
-) -> Result<StatusCode, AppError> {
-    info!("Deleting script {}", name);
+) -> Result<Json<Vec<ScriptVersion>>, AppError> {
+    validate_script_name(&name)?;
+    Ok(Json(versions::list_versions(&state, &name).await?))
+}
 
-    let path = state.scripts_dir.join(&name);
-    if path.exists() {
-        fs::remove_file(&path).await?;
+/// Получить код сохранённой версии скрипта
+#[utoipa::path(
+    get,
+    path = "/scripts/{name}/versions/{id}",
+    params(
+        ("name" = String, Path, description = "Имя скрипта, может включать поддиректории (`etl/extract.py`)"),
+        ("id" = u64, Path, description = "Номер версии")
+    ),
+    responses(
+        (status = 200, description = "Код версии", body = String, content_type = "text/plain"),
+        (status = 404, description = "Версия не найдена", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "scripts"
+)]
+pub async fn get_script_version(
+    State(state): State<Arc<AppState>>,
+    Path((name, id)): Path<(String, u64)>,
+) -> Result<Response, AppError> {
+    validate_script_name(&name)?;
+    let code = versions::read_version(&state, &name, id).await?;
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+        code,
+    )
+        .into_response())
+}
+
+/// Восстановить скрипт из сохранённой версии
+#[utoipa::path(
+    post,
+    path = "/scripts/{name}/rollback/{id}",
+    params(
+        ("name" = String, Path, description = "Имя скрипта, может включать поддиректории (`etl/extract.py`)"),
+        ("id" = u64, Path, description = "Номер версии")
+    ),
+    responses(
+        (status = 200, description = "Восстановленный скрипт", body = ScriptMetadata),
+        (status = 404, description = "Скрипт или версия не найдены", body = ErrorResponse),
+        (status = 403, description = "Сервер в режиме только для чтения", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "scripts"
+)]
+pub async fn rollback_script(
+    State(state): State<Arc<AppState>>,
+    actor: Actor,
+    Path((name, id)): Path<(String, u64)>,
+) -> Result<Json<ScriptMetadata>, AppError> {
+    state.check_writable()?;
+    info!("Rolling back script {} to version {}", name, id);
+
+    let path = state.checked_script_path(&name).await?;
+    if !path.exists() {
+        return Err(AppError::ScriptNotFound(name));
     }
+    let code = versions::read_version(&state, &name, id).await?;
 
-    db::delete_script(&state.db, &name).await?;
+    // Текущее содержимое тоже сохраняется, так что откат можно отменить
+    let before = Snapshot::read(&path).await;
+    versions::save_version(&state, &name).await?;
+    write_atomic(&path, code.as_bytes(), true).await?;
+    let after = Snapshot::of(code.as_bytes());
+    history::record_change(&state, &actor, &name, ScriptChange::Rollback, before, Some(after)).await;
+    script_runner::invalidate_script_cache(&state, &name).await;
+    script_runner::register_script(&state, path.clone()).await;
 
-    let mut scripts = state.scripts.lock().await;
-    scripts.retain(|p| p != &path);
+    let meta = fs::metadata(&path).await?;
+    let modified: DateTime<Utc> = meta
+        .modified()
+        .unwrap_or_else(|_| SystemTime::now())
+        .into();
+    let update_doc = doc! {
+        "code": code,
+        "size": meta.len() as i64,
+        "modified": BsonDateTime::from_millis(modified.timestamp_millis()),
+        "origin": Bson::Null,
+    };
+    db::update_script(&state.db, &name, update_doc).await?;
 
-    Ok(StatusCode::NO_CONTENT)
+    Ok(Json(load_script_metadata(&state, &name).await?))
 }
 
-/// Запустить несколько скриптов (по именам) с одинаковыми данными
+/// Заменить теги скрипта; они сохраняются в `<имя>.toml`
+#[utoipa::path(
+    put,
+    path = "/scripts/{name}/tags",
+    params(
+        ("name" = String, Path, description = "Имя скрипта, может включать поддиректории (`etl/extract.py`)")
+    ),
+    request_body = ScriptTags,
+    responses(
+        (status = 200, description = "Теги скрипта после изменения", body = ScriptTags),
+        (status = 400, description = "Некорректный тег или файл настроек", body = ErrorResponse),
+        (status = 404, description = "Скрипт не найден", body = ErrorResponse),
+        (status = 403, description = "Сервер в режиме только для чтения", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "scripts"
+)]
+pub async fn update_script_tags(
+    State(state): State<Arc<AppState>>,
+    actor: Actor,
+    Path(name): Path<String>,
+    Json(payload): Json<ScriptTags>,
+) -> Result<Json<ScriptTags>, AppError> {
+    state.check_writable()?;
+    info!("Updating tags of script {}", name);
+
+    let path = state.checked_script_path(&name).await?;
+    if !path.exists() {
+        return Err(AppError::ScriptNotFound(name));
+    }
+    let tags = script_config::normalize_tags(&payload.tags).map_err(AppError::BadRequest)?;
+    script_config::save_tags(&path, &tags).await?;
+    // Код не меняется: в журнале аудита хэш до и после совпадает
+    let snapshot = Snapshot::read(&path).await;
+    history::record_change(&state, &actor, &name, ScriptChange::Tags, snapshot.clone(), snapshot).await;
+    script_runner::register_script(&state, path).await;
+    Ok(Json(ScriptTags { tags }))
+}
+
+/// Отключить запуски скрипта, не удаляя его
 #[utoipa::path(
     post,
-    path = "/run",
-    params(RunQuery),
-    request_body = RunRequest,
+    path = "/scripts/{name}/disable",
+    params(
+        ("name" = String, Path, description = "Имя скрипта, может включать поддиректории (`etl/extract.py`)")
+    ),
     responses(
-        (status = 200, description = "Результаты выполнения", body = RunResponse),
-        (status = 401, description = "Не авторизован")
+        (status = 200, description = "Скрипт отключён", body = ScriptAvailability),
+        (status = 400, description = "Некорректное имя скрипта или файл настроек", body = ErrorResponse),
+        (status = 404, description = "Скрипт не найден", body = ErrorResponse),
+        (status = 403, description = "Сервер в режиме только для чтения", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
     ),
     security(
         ("bearer_auth" = [])
     ),
-    tag = "execution"
+    tag = "scripts"
 )]
-pub async fn run_scripts(
+pub async fn disable_script(
     State(state): State<Arc<AppState>>,
-    Query(query): Query<RunQuery>,
-    Json(payload): Json<RunRequest>,
-) -> Result<Json<RunResponse>, AppError> {
-    info!("Running scripts with data");
+    actor: Actor,
+    Path(name): Path<String>,
+) -> Result<Json<ScriptAvailability>, AppError> {
+    info!("Disabling script {}", name);
+    Ok(Json(set_script_disabled(&state, &actor, name, true).await?))
+}
 
-    let target_names: Vec<String> = match query.names {
-        Some(names_str) => names_str
-            .split(',')
-            .map(|s| s.trim().to_string())
-            .filter(|s| !s.is_empty())
-            .collect(),
-        None => {
-            let scripts = state.scripts.lock().await;
-            scripts
-                .iter()
-                .filter_map(|p| p.file_name().and_then(|n| n.to_str()).map(String::from))
-                .collect()
-        }
-    };
+/// Снова разрешить запуски скрипта
+#[utoipa::path(
+    post,
+    path = "/scripts/{name}/enable",
+    params(
+        ("name" = String, Path, description = "Имя скрипта, может включать поддиректории (`etl/extract.py`)")
+    ),
+    responses(
+        (status = 200, description = "Скрипт включён", body = ScriptAvailability),
+        (status = 400, description = "Некорректное имя скрипта или файл настроек", body = ErrorResponse),
+        (status = 404, description = "Скрипт не найден", body = ErrorResponse),
+        (status = 403, description = "Сервер в режиме только для чтения", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "scripts"
+)]
+pub async fn enable_script(
+    State(state): State<Arc<AppState>>,
+    actor: Actor,
+    Path(name): Path<String>,
+) -> Result<Json<ScriptAvailability>, AppError> {
+    info!("Enabling script {}", name);
+    Ok(Json(set_script_disabled(&state, &actor, name, false).await?))
+}
 
-    if target_names.is_empty() {
-        return Ok(Json(RunResponse {
-            results: HashMap::new(),
-        }));
+// Отметка хранится в `<имя>.toml`; кэш сбрасывается, чтобы после включения
+// не вернулись результаты, полученные до отключения
+async fn set_script_disabled(
+    state: &AppState,
+    actor: &Actor,
+    name: String,
+    disabled: bool,
+) -> Result<ScriptAvailability, AppError> {
+    state.check_writable()?;
+    let path = state.checked_script_path(&name).await?;
+    if !path.exists() {
+        return Err(AppError::ScriptNotFound(name));
     }
+    script_config::save_disabled(&path, disabled).await?;
+    script_runner::invalidate_script_cache(state, &name).await;
+    let snapshot = Snapshot::read(&path).await;
+    let change = if disabled { ScriptChange::Disable } else { ScriptChange::Enable };
+    history::record_change(state, actor, &name, change, snapshot.clone(), snapshot).await;
+    script_runner::register_script(state, path).await;
+    Ok(ScriptAvailability { name, disabled })
+}
 
-    let input_bytes = Bytes::from(serde_json::to_vec(&payload.data)?);
-    let args = payload.args.unwrap_or_default();
+// Непустые элементы списка через запятую
+fn split_names(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
 
-    let state = Arc::clone(&state);
-    let futures = target_names.into_iter().map(move |name| {
-        let state = Arc::clone(&state);
-        let input_bytes = input_bytes.clone();
-        let args = args.clone();
-        async move {
-            let result = script_runner::run_script(state, &name, args, input_bytes).await;
-            (name, result)
-        }
-    });
+fn is_glob(name: &str) -> bool {
+    name.contains(['*', '?', '[', '{'])
+}
 
-    let results_vec = join_all(futures).await;
-    let mut results = HashMap::new();
-    for (name, res) in results_vec {
-        match res {
-            Ok(r) => {
-                results.insert(name, r);
-            }
-            Err(e) => {
-                results.insert(
-                    name,
-                    ScriptResult {
-                        stdout: String::new(),
-                        stderr: format!("Error: {}", e),
-                        exit_code: -1,
-                        timed_out: false,
-                    },
-                );
-            }
-        }
-    }
+// `*` и `?` не пересекают `/`: `etl/*.py` не включает `etl/daily/load.py`, для этого есть `**`
+fn glob_matcher(pattern: &str) -> Result<GlobMatcher, AppError> {
+    GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()
+        .map(|glob| glob.compile_matcher())
+        .map_err(|e| AppError::BadRequest(format!("Invalid pattern '{}': {}", pattern, e.kind())))
+}
 
-    Ok(Json(RunResponse { results }))
+// Теги через запятую из query-строки
+fn parse_tags(value: &str) -> Result<Vec<String>, AppError> {
+    script_config::normalize_tags(value.split(',')).map_err(AppError::BadRequest)
 }
 
-/// Запустить один скрипт по имени
+/// Удалить скрипт
 #[utoipa::path(
-    post,
-    path = "/run/{name}",
+    delete,
+    path = "/scripts/{name}",
     params(
-        ("name" = String, Path, description = "Имя файла скрипта")
+        ("name" = String, Path, description = "Имя скрипта, может включать поддиректории (`etl/extract.py`)"),
+        DeleteScriptQuery
     ),
-    request_body = RunRequest,
     responses(
-        (status = 200, description = "Результат выполнения", body = ScriptResult),
-        (status = 404, description = "Скрипт не найден"),
-        (status = 401, description = "Не авторизован")
+        (status = 204, description = "Скрипт перемещён в корзину или удалён"),
+        (status = 400, description = "Некорректное имя скрипта", body = ErrorResponse),
+        (status = 404, description = "Скрипт не найден", body = ErrorResponse),
+        (status = 412, description = "Содержимое скрипта не совпадает с If-Match", body = ErrorResponse),
+        (status = 403, description = "Сервер в режиме только для чтения", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
     ),
     security(
         ("bearer_auth" = [])
     ),
-    tag = "execution"
+    tag = "scripts"
 )]
-pub async fn run_single_script(
+pub async fn delete_script(
     State(state): State<Arc<AppState>>,
+    actor: Actor,
     Path(name): Path<String>,
-    Json(payload): Json<RunRequest>,
-) -> Result<Json<ScriptResult>, AppError> {
-    info!("Running single script {}", name);
+    Query(query): Query<DeleteScriptQuery>,
+    headers: HeaderMap,
+) -> Result<StatusCode, AppError> {
+    state.check_writable()?;
+    info!("Deleting script {}", name);
 
-    let input_bytes = Bytes::from(serde_json::to_vec(&payload.data)?);
-    let args = payload.args.unwrap_or_default();
-    let result = script_runner::run_script(state, &name, args, input_bytes).await?;
+    let path = state.checked_script_path(&name).await?;
+    check_if_match(&headers, &path).await?;
+    if path.exists() {
+        let before = Snapshot::read(&path).await;
+        if query.permanent == Some(true) {
+            fs::remove_file(&path).await?;
+        } else {
+            trash::move_to_trash(&state, &name).await?;
+        }
+        history::record_change(&state, &actor, &name, ScriptChange::Delete, before, None).await;
+        if query.remove_empty_dirs == Some(true) {
+            remove_empty_dirs(&state, &path).await;
+        }
+    }
+
+    db::delete_script(&state.db, &name).await?;
+
+    state.scripts.lock().await.remove(&path);
+    script_runner::invalidate_script_cache(&state, &name).await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Удаляет опустевшие поддиректории удалённого скрипта, поднимаясь к корню.
+// remove_dir не удаляет непустую директорию, поэтому гонки с записью безопасны.
+async fn remove_empty_dirs(state: &AppState, path: &std::path::Path) {
+    let mut dir = path.parent();
+    while let Some(current) = dir {
+        if current == state.scripts_dir || fs::remove_dir(current).await.is_err() {
+            break;
+        }
+        info!("Removed empty directory {}", current.display());
+        dir = current.parent();
+    }
+}
+
+/// Список удалённых скриптов в корзине
+#[utoipa::path(
+    get,
+    path = "/scripts/trash",
+    responses(
+        (status = 200, description = "Скрипты в корзине, от недавно удалённых", body = [TrashItem]),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "scripts"
+)]
+pub async fn list_trash(State(state): State<Arc<AppState>>) -> Json<Vec<TrashItem>> {
+    Json(trash::list(&state).await)
+}
+
+/// Восстановить скрипт из корзины
+#[utoipa::path(
+    post,
+    path = "/scripts/trash/{id}/restore",
+    params(
+        ("id" = String, Path, description = "Идентификатор из списка корзины")
+    ),
+    responses(
+        (status = 201, description = "Восстановленный скрипт", body = ScriptMetadata),
+        (status = 404, description = "В корзине нет такого скрипта", body = ErrorResponse),
+        (status = 409, description = "Скрипт с этим именем уже существует", body = ErrorResponse),
+        (status = 403, description = "Сервер в режиме только для чтения", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "scripts"
+)]
+pub async fn restore_script(
+    State(state): State<Arc<AppState>>,
+    actor: Actor,
+    Path(id): Path<String>,
+) -> Result<(StatusCode, Json<ScriptMetadata>), AppError> {
+    state.check_writable()?;
+    let name = trash::restore(&state, &id).await?;
+    let path = state.script_path(&name);
+    let code = read_script_code(&state, &name).await?;
+    let after = Snapshot::of(code.as_bytes());
+    history::record_change(&state, &actor, &name, ScriptChange::Restore, None, Some(after)).await;
+
+    save_script_doc(&state, &path, &name, code, None, false).await?;
+    script_runner::invalidate_script_cache(&state, &name).await;
+    script_runner::register_script(&state, path).await;
+
+    let metadata = load_script_metadata(&state, &name).await?;
+    Ok((StatusCode::CREATED, Json(metadata)))
+}
+
+/// Скопировать скрипт под новым именем
+#[utoipa::path(
+    post,
+    path = "/scripts/{name}/copy",
+    params(
+        ("name" = String, Path, description = "Имя скрипта, может включать поддиректории (`etl/extract.py`)")
+    ),
+    request_body = ScriptTargetRequest,
+    responses(
+        (status = 201, description = "Копия создана", body = ScriptMetadata),
+        (status = 400, description = "Некорректное имя скрипта", body = ErrorResponse),
+        (status = 404, description = "Скрипт не найден", body = ErrorResponse),
+        (status = 409, description = "Скрипт с новым именем уже существует", body = ErrorResponse),
+        (status = 403, description = "Сервер в режиме только для чтения", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "scripts"
+)]
+pub async fn copy_script(
+    State(state): State<Arc<AppState>>,
+    actor: Actor,
+    namespace: Namespace,
+    access: NamespaceAccess,
+    Path(name): Path<String>,
+    Json(mut payload): Json<ScriptTargetRequest>,
+) -> Result<(StatusCode, Json<ScriptMetadata>), AppError> {
+    state.check_writable()?;
+    payload.new_name = namespace.qualify(&payload.new_name);
+    state.namespaces.check_access(&access, &payload.new_name)?;
+    info!("Copying script {} to {}", name, payload.new_name);
+
+    let (path, new_path) = prepare_target(&state, &name, &payload.new_name).await?;
+    let code = read_script_code(&state, &name).await?;
+    write_atomic(&new_path, code.as_bytes(), false)
+        .await
+        .map_err(|e| target_error(e, &payload.new_name))?;
+    for (from, to) in companion_files(&path, &new_path) {
+        if fs::try_exists(&from).await.unwrap_or(false) {
+            fs::copy(&from, &to).await?;
+        }
+    }
+    let after = Snapshot::of(code.as_bytes());
+    history::record_change(&state, &actor, &payload.new_name, ScriptChange::Copy, None, Some(after)).await;
+
+    let source = db::get_script_by_name(&state.db, &name).await?;
+    save_script_doc(&state, &new_path, &payload.new_name, code, source, false).await?;
+    script_runner::invalidate_script_cache(&state, &payload.new_name).await;
+    script_runner::register_script(&state, new_path).await;
+
+    let metadata = load_script_metadata(&state, &payload.new_name).await?;
+    Ok((StatusCode::CREATED, Json(metadata)))
+}
+
+/// Переименовать скрипт
+#[utoipa::path(
+    post,
+    path = "/scripts/{name}/rename",
+    params(
+        ("name" = String, Path, description = "Имя скрипта, может включать поддиректории (`etl/extract.py`)")
+    ),
+    request_body = ScriptTargetRequest,
+    responses(
+        (status = 200, description = "Скрипт под новым именем", body = ScriptMetadata),
+        (status = 400, description = "Некорректное имя скрипта", body = ErrorResponse),
+        (status = 404, description = "Скрипт не найден", body = ErrorResponse),
+        (status = 409, description = "Скрипт с новым именем уже существует", body = ErrorResponse),
+        (status = 403, description = "Сервер в режиме только для чтения", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "scripts"
+)]
+pub async fn rename_script(
+    State(state): State<Arc<AppState>>,
+    actor: Actor,
+    namespace: Namespace,
+    access: NamespaceAccess,
+    Path(name): Path<String>,
+    Json(mut payload): Json<ScriptTargetRequest>,
+) -> Result<Json<ScriptMetadata>, AppError> {
+    state.check_writable()?;
+    payload.new_name = namespace.qualify(&payload.new_name);
+    state.namespaces.check_access(&access, &payload.new_name)?;
+    info!("Renaming script {} to {}", name, payload.new_name);
+
+    let (path, new_path) = prepare_target(&state, &name, &payload.new_name).await?;
+    // Жёсткая ссылка не заменяет существующий файл, в отличие от rename.
+    // Уже идущий запуск дорабатывает со своим открытым файлом.
+    fs::hard_link(&path, &new_path)
+        .await
+        .map_err(|e| target_error(e, &payload.new_name))?;
+    fs::remove_file(&path).await?;
+    for (from, to) in companion_files(&path, &new_path) {
+        if fs::try_exists(&from).await.unwrap_or(false) {
+            fs::rename(&from, &to).await?;
+        }
+    }
+    versions::rename_versions(&state, &name, &payload.new_name).await;
+
+    // Список в памяти меняется под одной блокировкой: скрипт не пропадает между именами
+    let entry = script_runner::load_entry(&new_path).await;
+    {
+        let mut scripts = state.scripts.lock().await;
+        scripts.remove(&path);
+        scripts.insert(new_path.clone(), entry);
+    }
+    script_runner::invalidate_script_cache(&state, &name).await;
+    script_runner::invalidate_script_cache(&state, &payload.new_name).await;
+
+    let code = read_script_code(&state, &payload.new_name).await?;
+    let after = Snapshot::of(code.as_bytes());
+    history::record_change(&state, &actor, &payload.new_name, ScriptChange::Rename, None, Some(after)).await;
+    let source = db::get_script_by_name(&state.db, &name).await?;
+    save_script_doc(&state, &new_path, &payload.new_name, code, source, true).await?;
+    db::delete_script(&state.db, &name).await?;
+
+    Ok(Json(load_script_metadata(&state, &payload.new_name).await?))
+}
+
+// Проверяет исходный скрипт и новое имя по тем же правилам, что и создание
+async fn prepare_target(
+    state: &AppState,
+    name: &str,
+    new_name: &str,
+) -> Result<(PathBuf, PathBuf), AppError> {
+    let path = state.checked_script_path(name).await?;
+    let new_path = state.checked_script_path(new_name).await?;
+    state.interpreter_for(new_name)?;
+    if !fs::try_exists(&path).await? {
+        return Err(AppError::ScriptNotFound(name.to_string()));
+    }
+    if fs::try_exists(&new_path).await? {
+        return Err(AppError::ScriptAlreadyExists(new_name.to_string()));
+    }
+    if let Some(dir) = new_path.parent() {
+        fs::create_dir_all(dir).await?;
+    }
+    Ok((path, new_path))
+}
+
+fn target_error(e: std::io::Error, new_name: &str) -> AppError {
+    match e.kind() {
+        std::io::ErrorKind::AlreadyExists => AppError::ScriptAlreadyExists(new_name.to_string()),
+        _ => AppError::Io(e),
+    }
+}
+
+// Файлы настроек и зависимостей, которые следуют за скриптом
+fn companion_files(path: &std::path::Path, new_path: &std::path::Path) -> [(PathBuf, PathBuf); 2] {
+    [
+        (script_config::sidecar_path(path), script_config::sidecar_path(new_path)),
+        (deps::requirements_path(path), deps::requirements_path(new_path)),
+    ]
+}
+
+// Документ нового скрипта с описанием исходного; заменяет документ,
+// который мог успеть создать сканер. Переименованный скрипт сохраняет время создания.
+async fn save_script_doc(
+    state: &AppState,
+    path: &std::path::Path,
+    name: &str,
+    code: String,
+    source: Option<db::ScriptDoc>,
+    keep_created: bool,
+) -> Result<(), AppError> {
+    let meta = fs::metadata(path).await?;
+    let modified: DateTime<Utc> = meta
+        .modified()
+        .unwrap_or_else(|_| SystemTime::now())
+        .into();
+    let created = source
+        .as_ref()
+        .filter(|_| keep_created)
+        .map(|doc| doc.created)
+        .unwrap_or_else(BsonDateTime::now);
+    // Источник переносится только при переименовании: копия — новый скрипт
+    let origin = source
+        .as_ref()
+        .filter(|_| keep_created)
+        .and_then(|doc| doc.origin.clone());
+    let doc = db::ScriptDoc {
+        id: None,
+        name: name.to_string(),
+        code,
+        description: source.as_ref().and_then(|doc| doc.description.clone()),
+        result: source.and_then(|doc| doc.result),
+        size: meta.len(),
+        created,
+        modified: BsonDateTime::from_millis(modified.timestamp_millis()),
+        origin,
+    };
+    db::replace_script(&state.db, doc).await?;
+    Ok(())
+}
+
+/// Запустить несколько скриптов (по именам) с одинаковыми данными
+#[utoipa::path(
+    post,
+    path = "/run",
+    params(RunQuery),
+    request_body = RunRequest,
+    responses(
+        (status = 400, description = "Недопустимые переменные окружения, не указаны `names`, `tags` или `all=true`, превышен `max_batch_scripts`", body = ErrorResponse),
+        (status = 200, description = "Результаты выполнения (с `legacy=true` — LegacyRunResponse)", body = RunResponse),
+        (status = 207, description = "Хотя бы один скрипт не выполнен: ошибка, таймаут или пропуск", body = RunResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "execution"
+)]
+pub async fn run_scripts(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    access: NamespaceAccess,
+    Query(query): Query<RunQuery>,
+    payload: Option<Json<RunRequest>>,
+) -> Result<Response, AppError> {
+    // Тело запроса необязательно: без него скрипт запускается без stdin и аргументов
+    let payload = payload.map(|Json(payload)| payload).unwrap_or_default();
+    info!("Running scripts with data");
+
+    let names: Option<Vec<String>> = query.names.as_deref().map(split_names);
+    let tags = query.tags.as_deref().map(parse_tags).transpose()?;
+    let exclude = query
+        .exclude
+        .as_deref()
+        .map(|value| split_names(value).iter().map(|p| glob_matcher(p)).collect::<Result<Vec<_>, _>>())
+        .transpose()?
+        .unwrap_or_default();
+    let mut expanded = IndexMap::new();
+    let mut target_names: Vec<String> = match (names, tags) {
+        // Запуск всей директории должен быть явным
+        (None, None) if query.all != Some(true) => {
+            return Err(AppError::BadRequest(
+                "Specify names or tags, or pass all=true to run every script".to_string(),
+            ));
+        }
+        (None, None) => {
+            let scripts = state.scripts.lock().await;
+            scripts.keys().filter_map(|p| state.script_name(p)).collect()
+        }
+        // Скрипты с тегами идут после явно перечисленных, без повторов
+        (names, tags) => {
+            let mut target_names = Vec::new();
+            for name in names.unwrap_or_default() {
+                // Имя без метасимволов запускается как есть, даже если такого
+                // скрипта нет: в результатах будет ошибка script_not_found
+                if !is_glob(&name) {
+                    if !target_names.contains(&name) {
+                        target_names.push(name);
+                    }
+                    continue;
+                }
+                let matched = script_runner::scripts_matching(&state, &glob_matcher(&name)?).await;
+                for script in &matched {
+                    if !target_names.contains(script) {
+                        target_names.push(script.clone());
+                    }
+                }
+                expanded.insert(name, matched);
+            }
+            if let Some(tags) = tags {
+                let tag_match = query.tag_match.unwrap_or_default();
+                for name in script_runner::scripts_with_tags(&state, &tags, tag_match).await {
+                    if !target_names.contains(&name) {
+                        target_names.push(name);
+                    }
+                }
+            }
+            target_names
+        }
+    };
+    target_names.retain(|name| !exclude.iter().any(|glob| glob.is_match(name)));
+    limits::check_batch(&state.run_limits, target_names.len())?;
+
+    let legacy = query.legacy == Some(true);
+    if target_names.is_empty() {
+        return Ok(batch_response(Vec::new(), expanded, legacy));
+    }
+
+    let sequential = payload.sequential == Some(true);
+    let fail_fast = payload.fail_fast == Some(true);
+    let (input_bytes, options) = prepare_run(&state, caller, &access, payload)?;
+
+    let runs = target_names
+        .into_iter()
+        .map(|name| BatchRun {
+            key: name.clone(),
+            name,
+            input_bytes: input_bytes.clone(),
+            options: options.clone(),
+        })
+        .collect();
+    let outcomes = run_batch(&state, runs, sequential, fail_fast).await;
+
+    Ok(batch_response(outcomes, expanded, legacy))
+}
+
+/// Запустить пакет скриптов с отдельными аргументами и данными для каждого
+#[utoipa::path(
+    post,
+    path = "/run/batch",
+    params(BatchQuery),
+    request_body = BatchRunRequest,
+    responses(
+        (status = 200, description = "Результаты по id записи (или её индексу)", body = RunResponse),
+        (status = 207, description = "Хотя бы одна запись не выполнена", body = RunResponse),
+        (status = 400, description = "Повторяющиеся id, недопустимые переменные окружения или превышен `max_batch_scripts`", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "execution"
+)]
+pub async fn run_batch_entries(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    access: NamespaceAccess,
+    Query(query): Query<BatchQuery>,
+    Json(payload): Json<BatchRunRequest>,
+) -> Result<Response, AppError> {
+    info!("Running batch of {} entries", payload.entries.len());
+    limits::check_batch(&state.run_limits, payload.entries.len())?;
+
+    let mut keys = HashSet::new();
+    let mut runs = Vec::with_capacity(payload.entries.len());
+    for (index, entry) in payload.entries.into_iter().enumerate() {
+        let key = entry.id.unwrap_or_else(|| index.to_string());
+        if !keys.insert(key.clone()) {
+            return Err(AppError::BadRequest(format!("Duplicate batch entry id '{}'", key)));
+        }
+
+        let (input_bytes, options) = prepare_run(&state, caller.clone(), &access, RunRequest {
+            data: entry.data,
+            args: entry.args,
+            timeout_secs: entry.timeout_secs.or(payload.timeout_secs),
+            cache: payload.cache,
+            env: entry.env,
+            keep_workdir: None,
+            sequential: None,
+            fail_fast: None,
+            callback_url: None,
+            partial_on_timeout: payload.partial_on_timeout,
+            interpreter: entry.interpreter,
+            ..Default::default()
+        })?;
+        runs.push(BatchRun {
+            key,
+            name: entry.name,
+            input_bytes,
+            options,
+        });
+    }
+
+    let sequential = payload.sequential == Some(true);
+    let fail_fast = payload.fail_fast == Some(true);
+    let outcomes = run_batch(&state, runs, sequential, fail_fast).await;
+
+    Ok(batch_response(outcomes, IndexMap::new(), query.legacy == Some(true)))
+}
+
+// Один запуск пакета: ключ в ответе, скрипт и его входные данные
+struct BatchRun {
+    key: String,
+    name: String,
+    input_bytes: Bytes,
+    options: RunOptions,
+}
+
+// Итог одного запуска пакета до преобразования в формат ответа
+enum BatchOutcome {
+    Done(Result<Box<ScriptResult>, AppError>),
+    Skipped(&'static str),
+}
+
+const SKIPPED_REASON: &str = "A previous script failed";
+const DISABLED_REASON: &str = "Script is disabled";
+
+// 200, если все скрипты выполнены, иначе 207; в прежнем формате — всегда 200
+fn batch_response(
+    outcomes: Vec<(String, BatchOutcome)>,
+    expanded: IndexMap<String, Vec<String>>,
+    legacy: bool,
+) -> Response {
+    if legacy {
+        let results = outcomes
+            .into_iter()
+            .map(|(key, outcome)| (key, legacy_result(outcome)))
+            .collect();
+        return Json(LegacyRunResponse { results, expanded }).into_response();
+    }
+
+    let mut summary = BatchSummary::default();
+    let results: IndexMap<String, BatchItem> = outcomes
+        .into_iter()
+        .map(|(key, outcome)| {
+            let item = batch_item(outcome);
+            match item {
+                BatchItem::Ok { .. } => summary.ok += 1,
+                BatchItem::Error { .. } => summary.error += 1,
+                BatchItem::Timeout { .. } => summary.timeout += 1,
+                BatchItem::Skipped { .. } => summary.skipped += 1,
+            }
+            (key, item)
+        })
+        .collect();
+    let status = if summary.ok == results.len() {
+        StatusCode::OK
+    } else {
+        StatusCode::MULTI_STATUS
+    };
+    (status, Json(RunResponse { results, summary, expanded })).into_response()
+}
+
+fn batch_item(outcome: BatchOutcome) -> BatchItem {
+    match outcome {
+        // Частичный результат по таймауту (partial_on_timeout)
+        BatchOutcome::Done(Ok(result)) if result.timed_out => BatchItem::Timeout {
+            error: AppError::Timeout.into_body(),
+            result: Some(result),
+        },
+        BatchOutcome::Done(Ok(result)) => BatchItem::Ok { result },
+        BatchOutcome::Done(Err(e)) if e.timeout_budget().is_some() => BatchItem::Timeout {
+            error: e.into_body(),
+            result: None,
+        },
+        BatchOutcome::Done(Err(e)) => BatchItem::Error { error: e.into_body() },
+        BatchOutcome::Skipped(reason) => BatchItem::Skipped {
+            reason: reason.to_string(),
+        },
+    }
+}
+
+fn legacy_result(outcome: BatchOutcome) -> BatchResult {
+    match outcome {
+        BatchOutcome::Done(Ok(result)) => BatchResult::Completed(result),
+        BatchOutcome::Done(Err(e)) => BatchResult::Completed(Box::new(ScriptResult::from_error(&e))),
+        BatchOutcome::Skipped(reason) => BatchResult::Skipped(SkippedResult {
+            skipped: true,
+            reason: reason.to_string(),
+        }),
+    }
+}
+
+// Выполняет пакет; результаты возвращаются в порядке запроса
+async fn run_batch(
+    state: &Arc<AppState>,
+    runs: Vec<BatchRun>,
+    sequential: bool,
+    fail_fast: bool,
+) -> Vec<(String, BatchOutcome)> {
+    // Сколько скриптов планируется одновременно: при fail_fast — не больше
+    // слотов семафора, чтобы после ошибки следующие скрипты не запускались
+    let concurrency = if sequential {
+        1
+    } else if fail_fast {
+        state.max_concurrent()
+    } else {
+        runs.len().max(1)
+    };
+
+    let span = info_span!("batch", size = runs.len());
+    let failed = AtomicBool::new(false);
+    let runs = stream::iter(runs).map(|run| {
+        let state = Arc::clone(state);
+        let failed = &failed;
+        async move {
+            if fail_fast && failed.load(Ordering::Acquire) {
+                return (run.key, BatchOutcome::Skipped(SKIPPED_REASON));
+            }
+
+            let result = script_runner::run_script(state, &run.name, run.input_bytes, run.options).await;
+            // Отключённый скрипт пропускается и не считается ошибкой для fail_fast
+            if let Err(AppError::ScriptDisabled(_)) = result {
+                return (run.key, BatchOutcome::Skipped(DISABLED_REASON));
+            }
+            if !matches!(&result, Ok(r) if r.exit_code == 0) {
+                failed.store(true, Ordering::Release);
+            }
+            (run.key, BatchOutcome::Done(result.map(Box::new)))
+        }
+    });
+
+    // buffered сохраняет порядок запроса в ответе; span каждого скрипта
+    // вложен в span пакета
+    runs.buffered(concurrency).collect().instrument(span).await
+}
+
+/// Запустить цепочку скриптов: stdout каждого шага передаётся в stdin следующего
+#[utoipa::path(
+    post,
+    path = "/pipeline",
+    request_body = PipelineRequest,
+    responses(
+        (status = 200, description = "Результаты выполненных шагов", body = PipelineResponse),
+        (status = 400, description = "Пустой пайплайн или недопустимые переменные окружения", body = ErrorResponse),
+        (status = 404, description = "Скрипт одного из шагов не найден", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "execution"
+)]
+pub async fn run_pipeline(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    Json(payload): Json<PipelineRequest>,
+) -> Result<Json<PipelineResponse>, AppError> {
+    info!("Running pipeline of {} steps", payload.steps.len());
+    let span = info_span!("pipeline", steps = payload.steps.len());
+    Ok(Json(pipeline::run_pipeline(state, caller, payload).instrument(span).await?))
+}
+
+/// Выполнить код без сохранения скрипта (только при RUNNER_ALLOW_EVAL=true)
+#[utoipa::path(
+    post,
+    path = "/eval",
+    request_body = EvalRequest,
+    responses(
+        (status = 200, description = "Результат выполнения", body = ScriptResult),
+        (status = 400, description = "Неподдерживаемый язык или недопустимые переменные окружения", body = ErrorResponse),
+        (status = 403, description = "Выполнение произвольного кода отключено", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "execution"
+)]
+pub async fn eval_code(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<EvalRequest>,
+) -> Result<Json<ScriptResult>, AppError> {
+    if !state.allow_eval {
+        return Err(AppError::Forbidden(
+            "Code evaluation is disabled (RUNNER_ALLOW_EVAL)".to_string(),
+        ));
+    }
+    let language = payload.language.as_deref().unwrap_or("py");
+    // Расширение попадает в имя временного файла
+    if language.is_empty() || !language.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(AppError::BadRequest(format!("Invalid language '{}'", language)));
+    }
+
+    let args = payload.args.unwrap_or_default();
+    limits::check_args(&state.run_limits, &args)?;
+    let input_bytes = stdin_bytes(payload.data.as_ref())?;
+    limits::check_data(&state.run_limits, &input_bytes)?;
+    let env = payload.env.unwrap_or_default().into_iter().collect();
+    validate_env(&env)?;
+    let options = RunOptions {
+        args,
+        env,
+        timeout_secs: payload.timeout_secs,
+        partial_on_timeout: payload.partial_on_timeout == Some(true),
+        network: payload.network,
+        ..Default::default()
+    };
+    let result = script_runner::run_code(&state, language, &payload.code, input_bytes, options).await?;
     Ok(Json(result))
 }
 
+// stdout запуска-источника для `input_from`. Он становится stdin, поэтому
+// входит в ключ кэша, как и `data`. Токен с привязкой к пространствам имён
+// читает вывод только их скриптов.
+fn source_input(state: &AppState, access: &NamespaceAccess, source: &InputFrom) -> Result<Bytes, AppError> {
+    if let InputFrom::Latest { latest: false, .. } = source {
+        return Err(AppError::BadRequest("input_from.latest must be true".to_string()));
+    }
+    let (script, stdout) = state.outputs.lookup(source)?;
+    state.namespaces.check_access(access, &script)?;
+    Ok(stdout)
+}
+
+// Данные для stdin и параметры запуска из JSON-запроса
+fn prepare_run(
+    state: &AppState,
+    caller: Caller,
+    access: &NamespaceAccess,
+    payload: RunRequest,
+) -> Result<(Bytes, RunOptions), AppError> {
+    let args = payload.args.unwrap_or_default();
+    limits::check_args(&state.run_limits, &args)?;
+    arg_template::validate(&args)?;
+    let input_bytes = match &payload.input_from {
+        Some(_) if payload.data.is_some() => {
+            return Err(AppError::BadRequest("Use either data or input_from, not both".to_string()));
+        }
+        Some(source) => source_input(state, access, source)?,
+        None => {
+            let input_bytes = stdin_bytes(payload.data.as_ref())?;
+            limits::check_data(&state.run_limits, &input_bytes)?;
+            input_bytes
+        }
+    };
+    let env = payload.env.unwrap_or_default().into_iter().collect();
+    validate_env(&env)?;
+    let callback_url = payload
+        .callback_url
+        .map(|url| state.webhooks.validate(&url))
+        .transpose()?;
+    if let Some(interpreter) = &payload.interpreter {
+        state.check_interpreter(interpreter)?;
+    }
+    let secrets = payload.secrets.unwrap_or_default();
+    for name in &secrets {
+        secrets::validate_secret_name(name)?;
+        if env.contains_key(name) {
+            return Err(AppError::BadRequest(format!("Secret '{}' conflicts with env variable", name)));
+        }
+    }
+    let options = RunOptions {
+        args,
+        env,
+        timeout_secs: payload.timeout_secs,
+        queue_timeout_secs: payload.queue_timeout_secs,
+        no_cache: payload.cache == Some(false),
+        keep_workdir: payload.keep_workdir == Some(true),
+        callback_url,
+        partial_on_timeout: payload.partial_on_timeout == Some(true),
+        interpreter: payload.interpreter,
+        parse_output: payload.parse_output,
+        omit_stdout: payload.omit_stdout == Some(true),
+        parse_traceback: payload.parse_traceback,
+        network: payload.network,
+        secrets,
+        caller: caller.0,
+        ..Default::default()
+    };
+    Ok((input_bytes, options))
+}
+
+/// Запустить один скрипт по имени
+#[utoipa::path(
+    post,
+    path = "/run/{name}",
+    params(
+        ("name" = String, Path, description = "Имя скрипта, может включать поддиректории (`etl/extract.py`)"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Повтор с тем же ключом получает сохранённый ответ без нового запуска")
+    ),
+    request_body = RunRequest,
+    responses(
+        (status = 400, description = "Недопустимые переменные окружения", body = ErrorResponse),
+        (status = 200, description = "Результат выполнения", body = ScriptResult),
+        (status = 409, description = "Запрос с тем же Idempotency-Key ещё выполняется", body = ErrorResponse),
+        (status = 422, description = "Idempotency-Key уже использован для другого запроса", body = ErrorResponse),
+        (status = 304, description = "Результат из кэша совпадает с If-None-Match или скрипт не менялся с `if_script_changed_since`"),
+        (status = 404, description = "Скрипт не найден", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "execution"
+)]
+pub async fn run_single_script(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    caller: Caller,
+    access: NamespaceAccess,
+    headers: HeaderMap,
+    payload: Option<Json<RunRequest>>,
+) -> Result<Response, AppError> {
+    let payload = payload.map(|Json(payload)| payload).unwrap_or_default();
+    // Проверка до разбора запроса и ожидания слота: неизменный скрипт не запускается
+    if let Some(known) = &payload.if_script_changed_since {
+        let (_, entry) = script_runner::resolve_script(&state, &name).await?;
+        let known = known.trim().trim_start_matches("W/").trim_matches('"');
+        if known.eq_ignore_ascii_case(&entry.hash) {
+            info!("Script {} unchanged since {}, not running", name, entry.hash);
+            let etag = format!("\"{}\"", entry.hash);
+            return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response());
+        }
+    }
+    let fingerprint = idempotency::fingerprint("run", &name, &payload);
+    let pending = match idempotency::key_from_headers(&state, &headers)? {
+        Some(key) => match idempotency::begin(&state, &caller, &key, fingerprint)? {
+            Begin::Replay(stored) => return Ok(stored.into_response()),
+            Begin::Run(pending) => Some(pending),
+        },
+        None => None,
+    };
+    info!("Running single script {}", name);
+
+    let (input_bytes, options) = prepare_run(&state, caller, &access, payload)?;
+    let result = script_runner::run_script(Arc::clone(&state), &name, input_bytes, options).await;
+    // Сохраняется итог выполнения, в том числе таймаут; ошибки до запуска освобождают ключ
+    if let Some(pending) = pending {
+        match &result {
+            Ok(result) => pending.complete(StatusCode::OK, result),
+            Err(AppError::Timeout) => pending.complete(
+                StatusCode::GATEWAY_TIMEOUT,
+                &ErrorResponse {
+                    error: AppError::Timeout.into_body(),
+                },
+            ),
+            Err(_) => {}
+        }
+    }
+    Ok(run_result_response(&headers, result?))
+}
+
+// Результат из кэша отдаётся с ETag и Cache-Control на оставшийся срок
+// жизни записи, а при совпадении If-None-Match — как 304 без тела.
+// Результат нового запуска клиенту кэшировать нельзя.
+fn run_result_response(headers: &HeaderMap, result: ScriptResult) -> Response {
+    let quota_remaining = result.quota_remaining;
+    with_quota_header(cached_result_response(headers, result), quota_remaining)
+}
+
+fn cached_result_response(headers: &HeaderMap, result: ScriptResult) -> Response {
+    let Some(freshness) = result.freshness.clone() else {
+        return ([(header::CACHE_CONTROL, "no-store")], Json(result)).into_response();
+    };
+    let cache_headers = [
+        (header::ETAG, freshness.etag.clone()),
+        (
+            header::CACHE_CONTROL,
+            format!("private, max-age={}", freshness.max_age.as_secs()),
+        ),
+    ];
+    if if_none_match(headers, &freshness.etag) {
+        return (StatusCode::NOT_MODIFIED, cache_headers).into_response();
+    }
+    (cache_headers, Json(result)).into_response()
+}
+
+// Остаток квот после запуска, если для скрипта или вызывающего они заданы
+fn with_quota_header(mut response: Response, remaining: Option<QuotaRemaining>) -> Response {
+    if let Some(value) = remaining.and_then(quota::header_value) {
+        response.headers_mut().insert(quota::QUOTA_REMAINING_HEADER, value);
+    }
+    response
+}
+
+// If-None-Match сравнивается слабо: префикс `W/` не учитывается
+fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+        })
+}
+
+/// Запустить скрипт без входных данных, с параметрами в query-строке
+#[utoipa::path(
+    get,
+    path = "/run/{name}",
+    params(
+        ("name" = String, Path, description = "Имя скрипта, может включать поддиректории (`etl/extract.py`)"),
+        ("args" = Option<String>, Query, description = "Аргументы через запятую: `a,b,c`"),
+        ("arg" = Option<String>, Query, description = "Один аргумент, можно повторять: `arg=a&arg=b`"),
+        ("timeout_secs" = Option<u64>, Query, description = "Таймаут выполнения в секундах"),
+        ("queue_timeout_secs" = Option<u64>, Query, description = "Сколько ждать свободного слота в секундах"),
+        ("cache" = Option<bool>, Query, description = "`false` — выполнить без кэша"),
+        ("network" = Option<bool>, Query, description = "`false` — выполнить без доступа к сети"),
+        ("env" = Option<String>, Query, description = "Переменная окружения `KEY=VALUE`, можно повторять")
+    ),
+    responses(
+        (status = 200, description = "Результат выполнения", body = ScriptResult),
+        (status = 304, description = "Результат из кэша совпадает с If-None-Match"),
+        (status = 400, description = "Некорректные параметры запроса", body = ErrorResponse),
+        (status = 404, description = "Скрипт не найден", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse),
+        (status = 403, description = "Токен только для чтения", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "execution"
+)]
+pub async fn run_single_script_get(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    caller: Caller,
+    headers: HeaderMap,
+    Query(params): Query<Vec<(String, String)>>,
+) -> Result<Response, AppError> {
+    info!("Running single script {} without input", name);
+
+    let options = raw_run_options(&state, caller, params)?;
+    let result = script_runner::run_script(state, &name, Bytes::new(), options).await?;
+    Ok(run_result_response(&headers, result))
+}
+
+/// Запустить скрипт, передав тело запроса в stdin без изменений
+#[utoipa::path(
+    post,
+    path = "/run/{name}/raw",
+    params(
+        ("name" = String, Path, description = "Имя скрипта, может включать поддиректории (`etl/extract.py`)"),
+        ("args" = Option<String>, Query, description = "Аргументы через запятую: `a,b,c`"),
+        ("arg" = Option<String>, Query, description = "Один аргумент, можно повторять: `arg=a&arg=b`"),
+        ("timeout_secs" = Option<u64>, Query, description = "Таймаут выполнения в секундах"),
+        ("queue_timeout_secs" = Option<u64>, Query, description = "Сколько ждать свободного слота в секундах"),
+        ("cache" = Option<bool>, Query, description = "`false` — выполнить без кэша"),
+        ("network" = Option<bool>, Query, description = "`false` — выполнить без доступа к сети"),
+        ("env" = Option<String>, Query, description = "Переменная окружения `KEY=VALUE`, можно повторять")
+    ),
+    request_body(content = Vec<u8>, description = "Произвольные данные для stdin", content_type = "application/octet-stream"),
+    responses(
+        (status = 200, description = "Результат выполнения", body = ScriptResult),
+        (status = 400, description = "Некорректные параметры запроса", body = ErrorResponse),
+        (status = 404, description = "Скрипт не найден", body = ErrorResponse),
+        (status = 413, description = "Тело запроса превышает RUNNER_MAX_RAW_BODY_BYTES", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "execution"
+)]
+pub async fn run_single_script_raw(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    caller: Caller,
+    Query(params): Query<Vec<(String, String)>>,
+    body: Bytes,
+) -> Result<Response, AppError> {
+    info!("Running single script {} with raw body ({} bytes)", name, body.len());
+
+    let options = raw_run_options(&state, caller, params)?;
+    let result = script_runner::run_script(state, &name, body, options).await?;
+    let quota_remaining = result.quota_remaining;
+    Ok(with_quota_header(Json(result).into_response(), quota_remaining))
+}
+
+// Параметры запуска из query-строки; порядок аргументов сохраняется
+fn raw_run_options(
+    state: &AppState,
+    caller: Caller,
+    params: Vec<(String, String)>,
+) -> Result<RunOptions, AppError> {
+    let mut options = RunOptions {
+        caller: caller.0,
+        ..Default::default()
+    };
+    for (key, value) in params {
+        match key.as_str() {
+            "args" => options
+                .args
+                .extend(value.split(',').filter(|a| !a.is_empty()).map(str::to_string)),
+            "arg" => options.args.push(value),
+            "env" => {
+                let (key, val) = value.split_once('=').ok_or_else(|| {
+                    AppError::BadRequest(format!("Invalid env: '{}', expected KEY=VALUE", value))
+                })?;
+                options.env.insert(key.to_string(), val.to_string());
+            }
+            "timeout_secs" => {
+                let secs = value.parse().map_err(|_| {
+                    AppError::BadRequest(format!("Invalid timeout_secs: '{}'", value))
+                })?;
+                options.timeout_secs = Some(secs);
+            }
+            "queue_timeout_secs" => {
+                let secs = value.parse().map_err(|_| {
+                    AppError::BadRequest(format!("Invalid queue_timeout_secs: '{}'", value))
+                })?;
+                options.queue_timeout_secs = Some(secs);
+            }
+            "cache" => {
+                let cache: bool = value
+                    .parse()
+                    .map_err(|_| AppError::BadRequest(format!("Invalid cache: '{}'", value)))?;
+                options.no_cache = !cache;
+            }
+            "interpreter" => options.interpreter = Some(value),
+            "partial_on_timeout" => {
+                options.partial_on_timeout = value.parse().map_err(|_| {
+                    AppError::BadRequest(format!("Invalid partial_on_timeout: '{}'", value))
+                })?;
+            }
+            "parse_output" => {
+                let parse_output = value.parse().map_err(|_| {
+                    AppError::BadRequest(format!("Invalid parse_output: '{}'", value))
+                })?;
+                options.parse_output = Some(parse_output);
+            }
+            "omit_stdout" => {
+                options.omit_stdout = value.parse().map_err(|_| {
+                    AppError::BadRequest(format!("Invalid omit_stdout: '{}'", value))
+                })?;
+            }
+            "parse_traceback" => {
+                let parse_traceback = value.parse().map_err(|_| {
+                    AppError::BadRequest(format!("Invalid parse_traceback: '{}'", value))
+                })?;
+                options.parse_traceback = Some(parse_traceback);
+            }
+            "network" => {
+                let network = value
+                    .parse()
+                    .map_err(|_| AppError::BadRequest(format!("Invalid network: '{}'", value)))?;
+                options.network = Some(network);
+            }
+            _ => {}
+        }
+    }
+    validate_env(&options.env)?;
+    limits::check_args(&state.run_limits, &options.args)?;
+    arg_template::validate(&options.args)?;
+    if let Some(interpreter) = &options.interpreter {
+        state.check_interpreter(interpreter)?;
+    }
+    Ok(options)
+}
+
+/// Запустить скрипт с потоковой передачей вывода (Server-Sent Events)
+#[utoipa::path(
+    post,
+    path = "/run/{name}/stream",
+    params(
+        ("name" = String, Path, description = "Имя скрипта, может включать поддиректории (`etl/extract.py`)")
+    ),
+    request_body = RunRequest,
+    responses(
+        (status = 400, description = "Недопустимые переменные окружения", body = ErrorResponse),
+        (status = 200, description = "Поток событий stdout/stderr, завершается событием exit", body = String, content_type = "text/event-stream"),
+        (status = 404, description = "Скрипт не найден", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "execution"
+)]
+pub async fn run_single_script_stream(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    caller: Caller,
+    access: NamespaceAccess,
+    payload: Option<Json<RunRequest>>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, AppError> {
+    let payload = payload.map(|Json(payload)| payload).unwrap_or_default();
+    info!("Streaming single script {}", name);
+
+    let (input_bytes, options) = prepare_run(&state, caller, &access, payload)?;
+    if options.callback_url.is_some() {
+        return Err(AppError::BadRequest(
+            "callback_url is not supported for streaming runs".to_string(),
+        ));
+    }
+    let rx = script_runner::run_script_stream(state, &name, input_bytes, options).await?;
+
+    // При отключении клиента поток и получатель удаляются — раннер завершит процесс
+    let stream = stream::unfold(rx, |mut rx| async move {
+        rx.recv()
+            .await
+            .map(|event| (Ok::<_, Infallible>(sse_event(event)), rx))
+    });
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+fn sse_event(event: StreamEvent) -> Event {
+    match event {
+        StreamEvent::Stdout(line) => Event::default().event("stdout").data(line),
+        StreamEvent::Stderr(line) => Event::default().event("stderr").data(line),
+        StreamEvent::Exit {
+            exit_code,
+            timed_out,
+            signal,
+            killed_reason,
+        } => {
+            let exit = StreamExit {
+                exit_code,
+                timed_out,
+                signal,
+                killed_reason,
+            };
+            Event::default().event("exit").data(serde_json::to_string(&exit).unwrap_or_default())
+        }
+        StreamEvent::Error(msg) => Event::default().event("error").data(msg),
+    }
+}
+
+/// Поток событий сервера: запуски, изменения скриптов, сканирование
+#[utoipa::path(
+    get,
+    path = "/events",
+    params(EventsQuery),
+    responses(
+        (status = 200, description = "Поток SSE; данные события — ServerEvent, имя — его `type`. Событие `dropped` сообщает, сколько событий пропущено из-за медленного чтения", content_type = "text/event-stream"),
+        (status = 400, description = "Неизвестная группа событий в filter", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "execution"
+)]
+pub async fn get_events(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<EventsQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, AppError> {
+    let kinds = events::parse_filter(query.filter.as_deref())?;
+    let rx = state.events.subscribe();
+
+    // Поток завершается при остановке сервера, чтобы не задерживать её
+    let stream = stream::unfold((rx, state, kinds), |(mut rx, state, kinds)| async move {
+        loop {
+            let received = tokio::select! {
+                received = rx.recv() => received,
+                _ = state.shutdown.draining() => return None,
+            };
+            let event = match received {
+                Ok(event) if kinds.is_empty() || kinds.contains(&EventKind::of(&event.data)) => {
+                    Event::default()
+                        .event(event.data.name())
+                        .data(serde_json::to_string(&event).unwrap_or_default())
+                }
+                Ok(_) => continue,
+                Err(RecvError::Lagged(dropped)) => Event::default()
+                    .event("dropped")
+                    .data(json!({ "dropped": dropped }).to_string()),
+                Err(RecvError::Closed) => return None,
+            };
+            return Some((Ok::<_, Infallible>(event), (rx, state, kinds)));
+        }
+    });
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+/// Поставить выполнение скрипта в очередь (асинхронно)
+#[utoipa::path(
+    post,
+    path = "/run/{name}/async",
+    params(
+        ("name" = String, Path, description = "Имя скрипта, может включать поддиректории (`etl/extract.py`)"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Повтор с тем же ключом получает ту же задачу, а не новую")
+    ),
+    request_body = RunRequest,
+    responses(
+        (status = 400, description = "Недопустимые переменные окружения", body = ErrorResponse),
+        (status = 202, description = "Задача поставлена в очередь", body = JobInfo),
+        (status = 409, description = "Запрос с тем же Idempotency-Key ещё выполняется", body = ErrorResponse),
+        (status = 422, description = "Idempotency-Key уже использован для другого запроса", body = ErrorResponse),
+        (status = 404, description = "Скрипт не найден", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "execution"
+)]
+pub async fn run_single_script_async(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    caller: Caller,
+    access: NamespaceAccess,
+    headers: HeaderMap,
+    payload: Option<Json<RunRequest>>,
+) -> Result<Response, AppError> {
+    let payload = payload.map(|Json(payload)| payload).unwrap_or_default();
+    let fingerprint = idempotency::fingerprint("run_async", &name, &payload);
+    let pending = match idempotency::key_from_headers(&state, &headers)? {
+        Some(key) => match idempotency::begin(&state, &caller, &key, fingerprint)? {
+            // Повтор получает текущее состояние той же задачи, пока она не удалена
+            Begin::Replay(mut stored) => {
+                let job_id = stored.body.get("job_id").and_then(|id| id.as_str()).map(str::to_string);
+                if let Some(id) = job_id {
+                    if let Ok(job) = jobs::wait_job(&state, &id, Duration::ZERO).await {
+                        stored.body = serde_json::to_value(job)?;
+                    }
+                }
+                return Ok(stored.into_response());
+            }
+            Begin::Run(pending) => Some(pending),
+        },
+        None => None,
+    };
+    info!("Queueing single script {}", name);
+
+    let retry_on_restart = payload.retry_on_restart == Some(true);
+    let (input_bytes, options) = prepare_run(&state, caller, &access, payload)?;
+    let job = jobs::submit_job(Arc::clone(&state), name, input_bytes, options, retry_on_restart).await?;
+    if let Some(pending) = pending {
+        pending.complete(StatusCode::ACCEPTED, &job);
+    }
+    Ok((StatusCode::ACCEPTED, Json(job)).into_response())
+}
+
+/// Получить статус и результат асинхронной задачи; с `wait` — дождаться её завершения
+#[utoipa::path(
+    get,
+    path = "/jobs/{id}",
+    params(
+        ("id" = String, Path, description = "Идентификатор задачи"),
+        JobQuery
+    ),
+    responses(
+        (status = 200, description = "Статус задачи: завершённой или ещё выполняющейся по истечении wait", body = JobInfo),
+        (status = 404, description = "Задача не найдена", body = ErrorResponse),
+        (status = 403, description = "Токен не имеет доступа к пространству имён скрипта задачи", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "execution"
+)]
+pub async fn get_job(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(query): Query<JobQuery>,
+    access: NamespaceAccess,
+) -> Result<Json<JobInfo>, AppError> {
+    check_job_access(&state, &id, &access).await?;
+    let wait = Duration::from_secs(query.wait.unwrap_or(0));
+    Ok(Json(jobs::wait_job(&state, &id, wait).await?))
+}
+
+/// Отменить асинхронную задачу
+#[utoipa::path(
+    delete,
+    path = "/jobs/{id}",
+    params(
+        ("id" = String, Path, description = "Идентификатор задачи")
+    ),
+    responses(
+        (status = 200, description = "Задача отменена", body = JobInfo),
+        (status = 404, description = "Задача не найдена", body = ErrorResponse),
+        (status = 409, description = "Задача уже завершена", body = ErrorResponse),
+        (status = 403, description = "Токен не имеет доступа к пространству имён скрипта задачи", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "execution"
+)]
+pub async fn cancel_job(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    access: NamespaceAccess,
+) -> Result<Json<JobInfo>, AppError> {
+    check_job_access(&state, &id, &access).await?;
+    Ok(Json(jobs::cancel_job(&state, &id).await?))
+}
+
+// Токен с привязкой к пространствам имён видит и отменяет только задачи их скриптов
+async fn check_job_access(state: &AppState, id: &str, access: &NamespaceAccess) -> Result<(), AppError> {
+    if access.0.is_some() {
+        let job = jobs::get_job(state, id).await?;
+        state.namespaces.check_access(access, &job.script)?;
+    }
+    Ok(())
+}
+
+/// Скачать файл, сохранённый скриптом в `./artifacts` (или в прежней `./output`) во время запуска
+#[utoipa::path(
+    get,
+    path = "/runs/{run_id}/artifacts/{filename}",
+    params(
+        ("run_id" = String, Path, description = "Идентификатор запуска (`run_id` результата)"),
+        ("filename" = String, Path, description = "Имя файла относительно `artifacts` (или `output`), может включать поддиректории")
+    ),
+    responses(
+        (status = 200, description = "Содержимое файла; Content-Type по расширению", body = Vec<u8>, content_type = "application/octet-stream"),
+        (status = 400, description = "Некорректный идентификатор запуска или имя файла", body = ErrorResponse),
+        (status = 404, description = "Файл не найден или удалён по сроку хранения", body = ErrorResponse),
+        (status = 403, description = "Токен не имеет доступа к пространству имён скрипта запуска", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "execution"
+)]
+pub async fn get_run_artifact(
+    State(state): State<Arc<AppState>>,
+    Path((run_id, filename)): Path<(String, String)>,
+    access: NamespaceAccess,
+) -> Result<Response, AppError> {
+    let path = artifacts::artifact_path(&state, &run_id, &filename)?;
+    // Токен с привязкой к пространствам имён скачивает артефакты только их
+    // скриптов; запуск без записанного владельца для него не существует
+    if access.0.is_some() {
+        let script = artifacts::owner(&state, &run_id)
+            .await
+            .ok_or_else(|| AppError::ArtifactNotFound(run_id.clone(), filename.clone()))?;
+        state.namespaces.check_access(&access, &script)?;
+    }
+    let content = match fs::read(&path).await {
+        Ok(content) => content,
+        Err(e) if matches!(e.kind(), std::io::ErrorKind::NotFound | std::io::ErrorKind::IsADirectory) => {
+            return Err(AppError::ArtifactNotFound(run_id, filename));
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let content_type = mime_guess::from_path(&path).first_or_octet_stream();
+    Ok(([(header::CONTENT_TYPE, content_type.to_string())], content).into_response())
+}
+
+/// Объединённый лог stdout и stderr запуска в порядке появления строк
+#[utoipa::path(
+    get,
+    path = "/runs/{run_id}/log",
+    params(
+        ("run_id" = String, Path, description = "Идентификатор запуска (`run_id` результата)"),
+        RunLogQuery
+    ),
+    responses(
+        (status = 200, description = "Строки лога текстом или в JSON Lines (`format=jsonl`, объект LogLine в строке)", body = String, content_type = "text/plain"),
+        (status = 400, description = "Неизвестный формат", body = ErrorResponse),
+        (status = 404, description = "Лог не найден: запуск из кэша, воркером пула или вытеснен более новыми", body = ErrorResponse),
+        (status = 403, description = "Токен не имеет доступа к пространству имён скрипта запуска", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "execution"
+)]
+pub async fn get_run_log(
+    State(state): State<Arc<AppState>>,
+    Path(run_id): Path<String>,
+    Query(query): Query<RunLogQuery>,
+    access: NamespaceAccess,
+) -> Result<Response, AppError> {
+    let (script, lines) = state
+        .run_logs
+        .get(&run_id)
+        .ok_or_else(|| AppError::RunLogNotFound(run_id.clone()))?;
+    // Токен с привязкой к пространствам имён читает логи только их скриптов
+    state.namespaces.check_access(&access, &script)?;
+    let (content_type, body) = match query.format.as_deref() {
+        None | Some("text") => ("text/plain; charset=utf-8", run_log::render_text(&lines)),
+        Some("jsonl") => ("application/x-ndjson", run_log::render_jsonl(&lines)),
+        Some(format) => {
+            return Err(AppError::BadRequest(format!(
+                "Invalid format '{}': expected text or jsonl",
+                format
+            )))
+        }
+    };
+    Ok(([(header::CONTENT_TYPE, content_type)], body).into_response())
+}
+
+/// История выполнения скриптов
+#[utoipa::path(
+    get,
+    path = "/history",
+    params(HistoryQuery),
+    responses(
+        (status = 200, description = "Запуски от новых к старым", body = Vec<HistoryEntry>),
+        (status = 500, description = "Ошибка чтения журнала RUNNER_HISTORY_DB", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "execution"
+)]
+pub async fn get_history(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HistoryQuery>,
+) -> Result<Json<Vec<HistoryEntry>>, AppError> {
+    Ok(Json(history::query(&state, &query).await?))
+}
+
+/// Очистить историю выполнения
+#[utoipa::path(
+    delete,
+    path = "/history",
+    responses(
+        (status = 200, description = "История очищена", body = RemovedResponse),
+        (status = 403, description = "Пользователь не администратор или история хранится в RUNNER_HISTORY_DB", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "execution"
+)]
+pub async fn clear_history(
+    State(state): State<Arc<AppState>>,
+    claims: Option<Extension<jwt::Claims>>,
+) -> Result<Json<RemovedResponse>, AppError> {
+    let user = require_admin(&state, claims.as_deref())?;
+    let removed = history::clear(&state).await?;
+    info!("User {} cleared {} history entries", user, removed);
+    Ok(Json(RemovedResponse { removed }))
+}
+
+/// Журнал аудита: кто и когда изменил скрипты через API
+#[utoipa::path(
+    get,
+    path = "/audit",
+    params(AuditQuery),
+    responses(
+        (status = 200, description = "Изменения от новых к старым", body = Vec<AuditEntry>),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "scripts"
+)]
+pub async fn get_audit(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<AuditQuery>,
+) -> Json<Vec<AuditEntry>> {
+    Json(state.audit.query(&query).await)
+}
+
+/// Имена секретов без значений
+#[utoipa::path(
+    get,
+    path = "/secrets",
+    responses(
+        (status = 200, description = "Секреты по алфавиту", body = Vec<SecretInfo>),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "secrets"
+)]
+pub async fn list_secrets(State(state): State<Arc<AppState>>) -> Json<Vec<SecretInfo>> {
+    Json(state.secrets.list().await)
+}
+
+/// Сохранить секрет (только администраторы)
+#[utoipa::path(
+    put,
+    path = "/secrets/{key}",
+    params(
+        ("key" = String, Path, description = "Имя секрета и переменной окружения")
+    ),
+    request_body = SecretValueRequest,
+    responses(
+        (status = 204, description = "Секрет сохранён"),
+        (status = 400, description = "Некорректное имя или хранилище выключено", body = ErrorResponse),
+        (status = 403, description = "Пользователь не администратор", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "secrets"
+)]
+pub async fn put_secret(
+    State(state): State<Arc<AppState>>,
+    claims: Option<Extension<jwt::Claims>>,
+    Path(key): Path<String>,
+    Json(payload): Json<SecretValueRequest>,
+) -> Result<StatusCode, AppError> {
+    require_admin(&state, claims.as_deref())?;
+    state.secrets.set(&key, payload.value).await?;
+    info!("Stored secret {}", key);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Удалить секрет (только администраторы)
+#[utoipa::path(
+    delete,
+    path = "/secrets/{key}",
+    params(
+        ("key" = String, Path, description = "Имя секрета")
+    ),
+    responses(
+        (status = 204, description = "Секрет удалён"),
+        (status = 404, description = "Секрет не найден", body = ErrorResponse),
+        (status = 403, description = "Пользователь не администратор", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "secrets"
+)]
+pub async fn delete_secret(
+    State(state): State<Arc<AppState>>,
+    claims: Option<Extension<jwt::Claims>>,
+    Path(key): Path<String>,
+) -> Result<StatusCode, AppError> {
+    require_admin(&state, claims.as_deref())?;
+    state.secrets.delete(&key).await?;
+    info!("Deleted secret {}", key);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Настройки, изменяемые без перезапуска (только администраторы)
+#[utoipa::path(
+    get,
+    path = "/admin/config",
+    responses(
+        (status = 200, description = "Действующие значения", body = AdminConfig),
+        (status = 403, description = "Пользователь не администратор", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "admin"
+)]
+pub async fn get_admin_config(
+    State(state): State<Arc<AppState>>,
+    claims: Option<Extension<jwt::Claims>>,
+) -> Result<Json<AdminConfig>, AppError> {
+    require_admin(&state, claims.as_deref())?;
+    Ok(Json(admin::current(&state)))
+}
+
+/// Изменить настройки без перезапуска (только администраторы)
+#[utoipa::path(
+    patch,
+    path = "/admin/config",
+    request_body = AdminConfigPatch,
+    responses(
+        (status = 200, description = "Настройки после изменения", body = AdminConfig),
+        (status = 400, description = "Недопустимое значение или неизвестное поле", body = ErrorResponse),
+        (status = 403, description = "Пользователь не администратор", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "admin"
+)]
+pub async fn patch_admin_config(
+    State(state): State<Arc<AppState>>,
+    claims: Option<Extension<jwt::Claims>>,
+    Json(payload): Json<AdminConfigPatch>,
+) -> Result<Json<AdminConfig>, AppError> {
+    let user = require_admin(&state, claims.as_deref())?;
+    Ok(Json(admin::apply(&state, user, payload)?))
+}
+
+/// Пространства имён и их ограничения (только администраторы)
+#[utoipa::path(
+    get,
+    path = "/admin/namespaces",
+    responses(
+        (status = 200, description = "Пространства имён, включая default", body = [NamespaceInfo]),
+        (status = 403, description = "Пользователь не администратор", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "admin"
+)]
+pub async fn list_namespaces(
+    State(state): State<Arc<AppState>>,
+    claims: Option<Extension<jwt::Claims>>,
+) -> Result<Json<Vec<NamespaceInfo>>, AppError> {
+    require_admin(&state, claims.as_deref())?;
+    Ok(Json(state.namespaces.list()))
+}
+
+/// Создать пространство имён и его директорию (только администраторы)
+#[utoipa::path(
+    post,
+    path = "/admin/namespaces",
+    request_body = CreateNamespaceRequest,
+    responses(
+        (status = 201, description = "Пространство создано", body = NamespaceInfo),
+        (status = 400, description = "Недопустимое имя пространства", body = ErrorResponse),
+        (status = 409, description = "Пространство уже существует", body = ErrorResponse),
+        (status = 403, description = "Пользователь не администратор", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "admin"
+)]
+pub async fn create_namespace(
+    State(state): State<Arc<AppState>>,
+    claims: Option<Extension<jwt::Claims>>,
+    Json(payload): Json<CreateNamespaceRequest>,
+) -> Result<(StatusCode, Json<NamespaceInfo>), AppError> {
+    let user = require_admin(&state, claims.as_deref())?;
+    info!("User {} creates namespace {}", user, payload.name);
+    let namespace = state.namespaces.create(&state.scripts_dir, payload).await?;
+    Ok((StatusCode::CREATED, Json(namespace)))
+}
+
+/// Удалить пустое пространство имён (только администраторы)
+#[utoipa::path(
+    delete,
+    path = "/admin/namespaces/{name}",
+    params(
+        ("name" = String, Path, description = "Имя пространства")
+    ),
+    responses(
+        (status = 204, description = "Пустое пространство и его директория удалены"),
+        (status = 400, description = "Пространство default удалить нельзя", body = ErrorResponse),
+        (status = 404, description = "Пространство не найдено", body = ErrorResponse),
+        (status = 409, description = "В директории пространства остались файлы", body = ErrorResponse),
+        (status = 403, description = "Пользователь не администратор", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "admin"
+)]
+pub async fn delete_namespace(
+    State(state): State<Arc<AppState>>,
+    claims: Option<Extension<jwt::Claims>>,
+    Path(name): Path<String>,
+) -> Result<StatusCode, AppError> {
+    let user = require_admin(&state, claims.as_deref())?;
+    state.check_writable()?;
+    info!("User {} deletes namespace {}", user, name);
+    state.namespaces.remove(&state.scripts_dir, &name).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Сразу удалить данные старше их сроков хранения (только администраторы)
+#[utoipa::path(
+    post,
+    path = "/admin/gc",
+    responses(
+        (status = 200, description = "Итог очистки", body = GcReport),
+        (status = 403, description = "Пользователь не администратор", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "admin"
+)]
+pub async fn run_gc(
+    State(state): State<Arc<AppState>>,
+    claims: Option<Extension<jwt::Claims>>,
+) -> Result<Json<GcReport>, AppError> {
+    let user = require_admin(&state, claims.as_deref())?;
+    info!("User {} triggered GC", user);
+    Ok(Json(gc::sweep(&state).await))
+}
+
+// Администраторы — пользователи JWT из RUNNER_ADMIN_USERS; у статических
+// API-токенов нет имени, поэтому им эти операции недоступны
+fn require_admin<'a>(state: &AppState, claims: Option<&'a jwt::Claims>) -> Result<&'a str, AppError> {
+    match claims {
+        Some(claims) if state.admin_users.contains(&claims.sub) => Ok(&claims.sub),
+        _ => Err(AppError::Forbidden("Only administrators can do this".to_string())),
+    }
+}
+
+/// Сводная статистика запусков по всем скриптам
+#[utoipa::path(
+    get,
+    path = "/stats",
+    responses(
+        (status = 200, description = "Счётчики запусков", body = StatsSummary),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "execution"
+)]
+pub async fn get_stats(State(state): State<Arc<AppState>>) -> Json<StatsSummary> {
+    Json(stats::summary(&state).await)
+}
+
+/// Окружение интерпретатора Python: версия, платформа, пакеты и переменные окружения
+#[utoipa::path(
+    get,
+    path = "/environment",
+    params(EnvironmentQuery),
+    responses(
+        (status = 200, description = "Отчёт об окружении; то, что узнать не удалось, перечислено в errors", body = EnvironmentReport),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "execution"
+)]
+pub async fn get_environment(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<EnvironmentQuery>,
+) -> Json<EnvironmentReport> {
+    Json(environment::report(&state, query.refresh == Some(true)).await)
+}
+
+/// Ограничения запросов на запуск
+#[utoipa::path(
+    get,
+    path = "/limits",
+    responses(
+        (status = 200, description = "Действующие ограничения", body = LimitsResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "execution"
+)]
+pub async fn get_limits(State(state): State<Arc<AppState>>) -> Json<LimitsResponse> {
+    let limits = state.run_limits;
+    Json(LimitsResponse {
+        max_args: limits.max_args,
+        max_arg_bytes: limits.max_arg_bytes,
+        max_data_bytes: limits.max_data_bytes,
+        max_batch_scripts: limits.max_batch_scripts,
+        max_raw_body_bytes: state.max_raw_body_bytes,
+        max_script_bytes: state.max_script_bytes,
+        max_run_body_bytes: state.max_run_body_bytes,
+        max_output_bytes: state.max_output_bytes(),
+        default_timeout_secs: state.script_timeout().as_secs(),
+        max_timeout_secs: state.max_script_timeout.as_secs(),
+        read_only: state.read_only,
+    })
+}
+
+/// Статистика запусков одного скрипта
+#[utoipa::path(
+    get,
+    path = "/scripts/{name}/stats",
+    params(
+        ("name" = String, Path, description = "Имя скрипта, может включать поддиректории (`etl/extract.py`)")
+    ),
+    responses(
+        (status = 200, description = "Счётчики запусков скрипта", body = ScriptStats),
+        (status = 404, description = "Скрипт не найден", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "scripts"
+)]
+pub async fn get_script_stats(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> Result<Json<ScriptStats>, AppError> {
+    // Счётчики удалённого скрипта тоже отдаются, пока их не сбросили
+    let stats = stats::get(&state, &name).await;
+    if stats.runs == 0 {
+        script_runner::resolve_script(&state, &name).await?;
+    }
+    Ok(Json(stats))
+}
+
+/// Состояние зависимостей скрипта из requirements.txt
+#[utoipa::path(
+    get,
+    path = "/scripts/{name}/deps",
+    params(
+        ("name" = String, Path, description = "Имя скрипта, может включать поддиректории (`etl/extract.py`)")
+    ),
+    responses(
+        (status = 200, description = "Состояние окружения и журнал последней установки", body = DepsStatus),
+        (status = 404, description = "Скрипт не найден", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "scripts"
+)]
+pub async fn get_script_deps(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> Result<Json<DepsStatus>, AppError> {
+    script_runner::resolve_script(&state, &name).await?;
+    Ok(Json(deps::status(&state, &name).await?))
+}
+
+/// Окружение, в котором выполняется скрипт: пакеты его виртуального окружения, если оно есть
+#[utoipa::path(
+    get,
+    path = "/scripts/{name}/environment",
+    params(
+        ("name" = String, Path, description = "Имя скрипта, может включать поддиректории (`etl/extract.py`)"),
+        EnvironmentQuery
+    ),
+    responses(
+        (status = 200, description = "Отчёт об окружении; то, что узнать не удалось, перечислено в errors", body = EnvironmentReport),
+        (status = 404, description = "Скрипт не найден", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "scripts"
+)]
+pub async fn get_script_environment(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Query(query): Query<EnvironmentQuery>,
+) -> Result<Json<EnvironmentReport>, AppError> {
+    Ok(Json(environment::script_report(&state, &name, query.refresh == Some(true)).await?))
+}
+
+/// Установить зависимости скрипта в его виртуальное окружение
+#[utoipa::path(
+    post,
+    path = "/scripts/{name}/deps/install",
+    params(
+        ("name" = String, Path, description = "Имя скрипта, может включать поддиректории (`etl/extract.py`)"),
+        InstallDepsQuery
+    ),
+    responses(
+        (status = 202, description = "Установка запущена или уже идёт", body = DepsStatus),
+        (status = 400, description = "У скрипта нет файла зависимостей", body = ErrorResponse),
+        (status = 404, description = "Скрипт не найден", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "scripts"
+)]
+pub async fn install_script_deps(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Query(query): Query<InstallDepsQuery>,
+) -> Result<(StatusCode, Json<DepsStatus>), AppError> {
+    info!("Installing dependencies of {}", name);
+    let (_, entry) = script_runner::resolve_script(&state, &name).await?;
+    let interpreter = script_runner::base_interpreter(&state, &name, &entry)?;
+    let status = deps::install(&state, &name, &interpreter, query.force == Some(true)).await?;
+    Ok((StatusCode::ACCEPTED, Json(status)))
+}
+
+/// Докстринг и аргументы argparse Python-скрипта
+#[utoipa::path(
+    get,
+    path = "/scripts/{name}/interface",
+    params(
+        ("name" = String, Path, description = "Имя скрипта, может включать поддиректории (`etl/extract.py`)")
+    ),
+    responses(
+        (status = 200, description = "Интерфейс скрипта; если разобрать его не удалось, причина в поле error", body = ScriptInterface),
+        (status = 404, description = "Скрипт не найден", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "scripts"
+)]
+pub async fn get_script_interface(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> Result<Json<ScriptInterface>, AppError> {
+    Ok(Json(interface::inspect(&state, &name).await?))
+}
+
+/// Сбросить статистику запусков скрипта
+#[utoipa::path(
+    delete,
+    path = "/scripts/{name}/stats",
+    params(
+        ("name" = String, Path, description = "Имя скрипта, может включать поддиректории (`etl/extract.py`)")
+    ),
+    responses(
+        (status = 204, description = "Статистика сброшена"),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "scripts"
+)]
+pub async fn reset_script_stats(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> StatusCode {
+    if stats::reset(&state, &name).await {
+        info!("Reset stats for script {}", name);
+    }
+    StatusCode::NO_CONTENT
+}
+
+/// Статистика кэша результатов
+#[utoipa::path(
+    get,
+    path = "/cache/stats",
+    responses(
+        (status = 200, description = "Статистика кэша", body = CacheStats),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "execution"
+)]
+pub async fn cache_stats(State(state): State<Arc<AppState>>) -> Json<CacheStats> {
+    Json(state.cache.lock().await.stats())
+}
+
+/// Очистить кэш результатов целиком
+#[utoipa::path(
+    delete,
+    path = "/cache",
+    responses(
+        (status = 200, description = "Кэш очищен", body = RemovedResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "execution"
+)]
+pub async fn clear_cache(State(state): State<Arc<AppState>>) -> Json<RemovedResponse> {
+    let removed = script_runner::clear_cache(&state).await;
+    info!("Cleared cache, evicted {} entries", removed);
+    Json(RemovedResponse { removed })
+}
+
+/// Удалить из кэша результаты одного скрипта
+#[utoipa::path(
+    delete,
+    path = "/cache/{script}",
+    params(
+        ("script" = String, Path, description = "Имя скрипта, может включать поддиректории (`etl/extract.py`)")
+    ),
+    responses(
+        (status = 200, description = "Записи скрипта удалены из кэша", body = RemovedResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "execution"
+)]
+pub async fn clear_script_cache(
+    State(state): State<Arc<AppState>>,
+    Path(script): Path<String>,
+) -> Json<RemovedResponse> {
+    let removed = script_runner::invalidate_script_cache(&state, &script).await;
+    info!("Evicted {} cache entries for {}", removed, script);
+    Json(RemovedResponse { removed })
+}
+
+/// Создать расписание запуска скрипта
+#[utoipa::path(
+    post,
+    path = "/schedules",
+    request_body = CreateScheduleRequest,
+    responses(
+        (status = 201, description = "Расписание создано", body = Schedule),
+        (status = 400, description = "Некорректное cron-выражение или параметры", body = ErrorResponse),
+        (status = 404, description = "Скрипт не найден", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "schedules"
+)]
+pub async fn create_schedule(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<CreateScheduleRequest>,
+) -> Result<(StatusCode, Json<Schedule>), AppError> {
+    let schedule = schedules::create(&state, payload).await?;
+    Ok((StatusCode::CREATED, Json(schedule)))
+}
+
+/// Список расписаний
+#[utoipa::path(
+    get,
+    path = "/schedules",
+    responses(
+        (status = 200, description = "Расписания в порядке создания", body = [Schedule]),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "schedules"
+)]
+pub async fn list_schedules(State(state): State<Arc<AppState>>) -> Json<Vec<Schedule>> {
+    Json(schedules::list(&state).await)
+}
+
+/// Удалить расписание
+#[utoipa::path(
+    delete,
+    path = "/schedules/{id}",
+    params(
+        ("id" = String, Path, description = "Идентификатор расписания")
+    ),
+    responses(
+        (status = 204, description = "Расписание удалено"),
+        (status = 404, description = "Расписание не найдено", body = ErrorResponse),
+        (status = 401, description = "Не авторизован", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    tag = "schedules"
+)]
+pub async fn delete_schedule(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    schedules::delete(&state, &id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Проверка живости процесса
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    responses(
+        (status = 200, description = "Сервер запущен")
+    ),
+    tag = "health"
+)]
+pub async fn healthz() -> StatusCode {
+    StatusCode::OK
+}
+
+/// Проверка готовности: директория скриптов и интерпретаторы
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    responses(
+        (status = 200, description = "Сервер готов", body = ReadinessReport),
+        (status = 503, description = "Одна из проверок не прошла", body = ReadinessReport)
+    ),
+    tag = "health"
+)]
+pub async fn readyz(
+    State(state): State<Arc<AppState>>,
+) -> (StatusCode, Json<ReadinessReport>) {
+    let report = health::readiness(&state).await;
+    let status = if report.checks.iter().all(|c| c.ok) {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(report))
+}
+
 /// Регистрация нового пользователя
 #[utoipa::path(
     post,
@@ -432,8 +3133,8 @@ pub async fn run_single_script(
     request_body = RegisterRequest,
     responses(
         (status = 201, description = "Пользователь создан"),
-        (status = 409, description = "Пользователь уже существует"),
-        (status = 400, description = "Некорректные данные")
+        (status = 409, description = "Пользователь уже существует", body = ErrorResponse),
+        (status = 400, description = "Некорректные данные", body = ErrorResponse)
     ),
     tag = "auth"
 )]
@@ -453,7 +3154,7 @@ pub async fn register(
     request_body = LoginRequest,
     responses(
         (status = 200, description = "Успешный вход", body = LoginResponse),
-        (status = 401, description = "Неверные учетные данные")
+        (status = 401, description = "Неверные учетные данные", body = ErrorResponse)
     ),
     tag = "auth"
 )]
@@ -0,0 +1,97 @@
+use crate::{
+    app_state::AppState,
+    models::{HealthCheck, ReadinessReport},
+};
+use std::{
+    collections::BTreeSet,
+    process::Stdio,
+    time::{Duration, Instant},
+};
+use tokio::{fs, process::Command, time::timeout};
+
+// Сколько переиспользовать результат проверки интерпретаторов
+const INTERPRETER_CHECK_TTL: Duration = Duration::from_secs(10);
+const INTERPRETER_CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Проверяет готовность сервера: доступность директории скриптов
+/// и возможность запустить каждый настроенный интерпретатор.
+pub async fn readiness(state: &AppState) -> ReadinessReport {
+    let mut checks = vec![check_scripts_dir(state).await];
+    checks.extend(check_interpreters(state).await);
+    checks.extend(check_sandbox(state));
+
+    let ready = checks.iter().all(|c| c.ok);
+    ReadinessReport {
+        status: if ready { "ok" } else { "unavailable" }.to_string(),
+        checks,
+    }
+}
+
+async fn check_scripts_dir(state: &AppState) -> HealthCheck {
+    let result = fs::read_dir(&state.scripts_dir).await;
+    HealthCheck {
+        name: "scripts_dir".to_string(),
+        ok: result.is_ok(),
+        error: result.err().map(|e| format!("Scripts directory is not readable: {}", e)),
+    }
+}
+
+// Программа песочницы, если она настроена, должна быть установлена
+fn check_sandbox(state: &AppState) -> Option<HealthCheck> {
+    let sandbox = state.sandbox.as_ref()?;
+    let result = sandbox.probe();
+    Some(HealthCheck {
+        name: format!("sandbox:{}", sandbox.program()),
+        ok: result.is_ok(),
+        error: result.err(),
+    })
+}
+
+/// Проверяет все настроенные интерпретаторы (результат кэшируется)
+pub async fn check_interpreters(state: &AppState) -> Vec<HealthCheck> {
+    let mut cached = state.interpreter_checks.lock().await;
+    if let Some((checked_at, checks)) = cached.as_ref() {
+        if checked_at.elapsed() < INTERPRETER_CHECK_TTL {
+            return checks.clone();
+        }
+    }
+
+    let programs: BTreeSet<&str> = state
+        .interpreters
+        .values()
+        .chain(state.allowed_interpreters.iter())
+        .filter_map(|cmd| cmd.split_whitespace().next())
+        .collect();
+
+    let mut checks = Vec::new();
+    for program in programs {
+        checks.push(check_interpreter(program).await);
+    }
+
+    *cached = Some((Instant::now(), checks.clone()));
+    checks
+}
+
+/// Запускает `<program> --version` с коротким таймаутом
+pub async fn check_interpreter(program: &str) -> HealthCheck {
+    let status = Command::new(program)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .status();
+
+    let error = match timeout(INTERPRETER_CHECK_TIMEOUT, status).await {
+        Ok(Ok(status)) if status.success() => None,
+        Ok(Ok(status)) => Some(format!("'{} --version' exited with {}", program, status)),
+        Ok(Err(e)) => Some(format!("Failed to spawn '{}': {}", program, e)),
+        Err(_) => Some(format!("'{} --version' timed out", program)),
+    };
+
+    HealthCheck {
+        name: format!("interpreter:{}", program),
+        ok: error.is_none(),
+        error,
+    }
+}
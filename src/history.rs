@@ -0,0 +1,169 @@
+use crate::{
+    app_state::AppState,
+    audit::{Actor, Snapshot},
+    error::AppError,
+    history_db::ScriptChange,
+    models::{AuditEntry, EventData, HistoryEntry, HistoryQuery, RunStatus, ScriptResult},
+};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+
+// Сколько байт stderr сохранять в записи истории
+const HISTORY_STDERR_LIMIT: usize = 1024;
+const DEFAULT_HISTORY_LIMIT: usize = 100;
+
+impl HistoryEntry {
+    pub fn new(
+        run_id: String,
+        script: &str,
+        args_hash: String,
+        started_at: DateTime<Utc>,
+        duration: Duration,
+        result: Result<(&ScriptResult, bool), &AppError>,
+    ) -> Self {
+        let status = run_status(result.map(|(r, _)| r));
+        let (exit_code, timed_out, cached, stderr, error) = match result {
+            Ok((r, cached)) => (
+                Some(r.exit_code),
+                r.timed_out,
+                cached,
+                truncate(&r.stderr, HISTORY_STDERR_LIMIT),
+                None,
+            ),
+            Err(e) => (None, status == RunStatus::TimedOut, false, String::new(), Some(e.to_string())),
+        };
+        // Ответ из кэша и объединённый с чужим запуском процесс не запускали
+        let invocation = match result {
+            Ok((r, false)) => r.invocation.clone(),
+            _ => None,
+        };
+
+        Self {
+            run_id,
+            script: script.to_string(),
+            args_hash,
+            started_at,
+            duration_ms: duration.as_millis() as u64,
+            status,
+            exit_code,
+            timed_out,
+            cached,
+            stderr,
+            error,
+            invocation,
+        }
+    }
+}
+
+/// Итог запуска для истории и расписаний
+pub fn run_status(result: Result<&ScriptResult, &AppError>) -> RunStatus {
+    match result {
+        Ok(r) if r.timed_out => RunStatus::TimedOut,
+        Ok(r) if r.exit_code == 0 => RunStatus::Success,
+        Ok(_) => RunStatus::Failed,
+        Err(AppError::Timeout) => RunStatus::TimedOut,
+        Err(AppError::Cancelled(_)) => RunStatus::Cancelled,
+        Err(_) => RunStatus::Error,
+    }
+}
+
+// Обрезка по границе символа
+fn truncate(s: &str, max: usize) -> String {
+    if s.len() <= max {
+        return s.to_string();
+    }
+    let mut end = max;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    s[..end].to_string()
+}
+
+/// Добавляет запись; при переполнении вытесняется самая старая.
+/// С `RUNNER_HISTORY_DB` запуск вместо этого пишется в журнал вместе
+/// с аргументами и хэшем входных данных.
+pub async fn record(state: &AppState, entry: HistoryEntry, args: Vec<String>, input: Bytes) {
+    if let Some(history_db) = &state.history_db {
+        history_db.record_run(entry, args, input);
+        return;
+    }
+    if state.history_capacity == 0 {
+        return;
+    }
+    let mut history = state.history.lock().await;
+    while history.len() >= state.history_capacity {
+        history.pop_front();
+    }
+    history.push_back(entry);
+}
+
+/// Записывает изменение скрипта через API в журнал аудита и журнал в SQLite
+/// (если он включён) и сообщает о нём подписчикам `GET /events`.
+/// `before` и `after` — код скрипта до и после изменения.
+pub async fn record_change(
+    state: &AppState,
+    actor: &Actor,
+    script: &str,
+    change: ScriptChange,
+    before: Option<Snapshot>,
+    after: Option<Snapshot>,
+) {
+    state.events.publish(EventData::ScriptChanged {
+        script: script.to_string(),
+        action: change.as_str().to_string(),
+    });
+    if let Some(history_db) = &state.history_db {
+        history_db.record_change(script, change, after.as_ref().map(|s| s.hash.clone()));
+    }
+    let size = |snapshot: &Option<Snapshot>| snapshot.as_ref().map(|s| s.size);
+    let (size_before, size_after) = (size(&before), size(&after));
+    let entry = AuditEntry {
+        timestamp: Utc::now(),
+        script: script.to_string(),
+        action: change.as_str().to_string(),
+        principal: actor.principal.clone(),
+        client_ip: actor.ip.map(|ip| ip.to_string()),
+        size_delta: size_after.unwrap_or(0) as i64 - size_before.unwrap_or(0) as i64,
+        size_before,
+        size_after,
+        hash_before: before.map(|s| s.hash),
+        hash_after: after.map(|s| s.hash),
+    };
+    state.audit.record(entry).await;
+}
+
+/// Записи истории по фильтру, от новых к старым
+pub async fn query(state: &AppState, query: &HistoryQuery) -> Result<Vec<HistoryEntry>, AppError> {
+    let limit = query.limit.unwrap_or(DEFAULT_HISTORY_LIMIT);
+    let offset = query.offset.unwrap_or(0);
+    if let Some(history_db) = &state.history_db {
+        return history_db.query(query, limit, offset).await;
+    }
+    let history = state.history.lock().await;
+    Ok(history
+        .iter()
+        .rev()
+        .filter(|e| query.script.as_deref().is_none_or(|s| e.script == s))
+        .filter(|e| query.status.as_deref().is_none_or(|s| e.status.as_str() == s))
+        .filter(|e| query.since.is_none_or(|since| e.started_at >= since))
+        .skip(offset)
+        .take(limit)
+        .cloned()
+        .collect())
+}
+
+/// Очищает историю, возвращает число удалённых записей. Журнал в SQLite
+/// так не очищается: записи из него удаляются только по сроку хранения.
+pub async fn clear(state: &AppState) -> Result<usize, AppError> {
+    if state.history_db.is_some() {
+        return Err(AppError::Forbidden(
+            "History is kept in RUNNER_HISTORY_DB and is removed only after RUNNER_HISTORY_RETENTION_DAYS"
+                .to_string(),
+        ));
+    }
+    let mut history = state.history.lock().await;
+    let removed = history.len();
+    history.clear();
+    Ok(removed)
+}
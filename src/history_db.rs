@@ -0,0 +1,416 @@
+use crate::{
+    error::AppError,
+    idempotency::{StoredKey, StoredResponse},
+    models::{HistoryEntry, HistoryQuery, Invocation, RunStatus},
+    utils::sha256_hex,
+};
+use anyhow::Context;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, params_from_iter, types::Value, Connection, Row};
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{info, warn};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY,
+    run_id TEXT NOT NULL,
+    script TEXT NOT NULL,
+    args TEXT NOT NULL,
+    args_hash TEXT NOT NULL,
+    input_hash TEXT NOT NULL,
+    started_at INTEGER NOT NULL,
+    duration_ms INTEGER NOT NULL,
+    status TEXT NOT NULL,
+    exit_code INTEGER,
+    timed_out INTEGER NOT NULL,
+    cached INTEGER NOT NULL,
+    stderr TEXT NOT NULL,
+    error TEXT,
+    invocation TEXT
+);
+CREATE INDEX IF NOT EXISTS runs_started_at ON runs (started_at);
+CREATE INDEX IF NOT EXISTS runs_script ON runs (script, started_at);
+CREATE TABLE IF NOT EXISTS script_changes (
+    id INTEGER PRIMARY KEY,
+    script TEXT NOT NULL,
+    action TEXT NOT NULL,
+    code_hash TEXT,
+    changed_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS script_changes_changed_at ON script_changes (changed_at);
+CREATE TABLE IF NOT EXISTS idempotency_keys (
+    key TEXT PRIMARY KEY,
+    fingerprint TEXT NOT NULL,
+    status INTEGER NOT NULL,
+    body TEXT NOT NULL,
+    stored_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idempotency_keys_stored_at ON idempotency_keys (stored_at);
+";
+
+// Сколько записей сохранять одной транзакцией
+const MAX_BATCH: usize = 256;
+
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Изменение скрипта через API
+#[derive(Debug, Clone, Copy)]
+pub enum ScriptChange {
+    Create,
+    Update,
+    Rollback,
+    Import,
+    Fetch,
+    Sync,
+    Delete,
+    Copy,
+    Rename,
+    Tags,
+    Restore,
+    Disable,
+    Enable,
+}
+
+impl ScriptChange {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScriptChange::Create => "create",
+            ScriptChange::Update => "update",
+            ScriptChange::Rollback => "rollback",
+            ScriptChange::Import => "import",
+            ScriptChange::Fetch => "fetch",
+            ScriptChange::Sync => "sync",
+            ScriptChange::Delete => "delete",
+            ScriptChange::Copy => "copy",
+            ScriptChange::Rename => "rename",
+            ScriptChange::Tags => "tags",
+            ScriptChange::Restore => "restore",
+            ScriptChange::Disable => "disable",
+            ScriptChange::Enable => "enable",
+        }
+    }
+}
+
+enum Command {
+    Run {
+        entry: Box<HistoryEntry>,
+        args: Vec<String>,
+        input: Bytes,
+    },
+    Change {
+        script: String,
+        change: ScriptChange,
+        code_hash: Option<String>,
+        changed_at: DateTime<Utc>,
+    },
+    Purge {
+        before: DateTime<Utc>,
+        // Число удалённых записей после фиксации транзакции
+        reply: oneshot::Sender<usize>,
+    },
+    IdempotencyKey {
+        key: String,
+        fingerprint: String,
+        status: u16,
+        body: String,
+        stored_at: DateTime<Utc>,
+    },
+    ExpireIdempotencyKeys {
+        before: DateTime<Utc>,
+    },
+}
+
+/// Журнал запусков и изменений скриптов в SQLite (`RUNNER_HISTORY_DB`).
+/// Запись идёт в отдельном потоке: медленный диск не задерживает ответы.
+pub struct HistoryDb {
+    tx: mpsc::UnboundedSender<Command>,
+    // Отдельное соединение для чтения; в режиме WAL не ждёт записи
+    reader: Arc<Mutex<Connection>>,
+}
+
+impl HistoryDb {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let writer = open_connection(path)?;
+        writer
+            .pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))
+            .context("Failed to enable WAL")?;
+        writer.execute_batch(SCHEMA).context("Failed to create history tables")?;
+        add_column(&writer, "runs", "invocation", "TEXT").context("Failed to upgrade history tables")?;
+        let reader = open_connection(path)?;
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        std::thread::Builder::new()
+            .name("history-db".to_string())
+            .spawn(move || write_loop(writer, rx))
+            .context("Failed to start history writer")?;
+        info!("Recording history to {}", path.display());
+        Ok(Self {
+            tx,
+            reader: Arc::new(Mutex::new(reader)),
+        })
+    }
+
+    /// Ставит запуск в очередь на запись; хэш входных данных считается при записи
+    pub fn record_run(&self, entry: HistoryEntry, args: Vec<String>, input: Bytes) {
+        self.send(Command::Run {
+            entry: Box::new(entry),
+            args,
+            input,
+        });
+    }
+
+    /// Ставит изменение скрипта в очередь на запись
+    pub fn record_change(&self, script: &str, change: ScriptChange, code_hash: Option<String>) {
+        self.send(Command::Change {
+            script: script.to_string(),
+            change,
+            code_hash,
+            changed_at: Utc::now(),
+        });
+    }
+
+    /// Удаляет записи старше `retention`, возвращает число удалённых
+    pub async fn purge(&self, retention: Duration) -> usize {
+        let before = Utc::now() - retention;
+        let (reply, rx) = oneshot::channel();
+        self.send(Command::Purge { before, reply });
+        // Ответа нет, если запись не удалась
+        rx.await.unwrap_or(0)
+    }
+
+    /// Ставит ответ на запрос с `Idempotency-Key` в очередь на запись
+    pub fn record_idempotency_key(
+        &self,
+        key: &str,
+        fingerprint: &str,
+        response: &StoredResponse,
+        stored_at: DateTime<Utc>,
+    ) {
+        self.send(Command::IdempotencyKey {
+            key: key.to_string(),
+            fingerprint: fingerprint.to_string(),
+            status: response.status,
+            body: response.body.to_string(),
+            stored_at,
+        });
+    }
+
+    /// Ставит в очередь удаление ключей идемпотентности, сохранённых до `before`
+    pub fn expire_idempotency_keys(&self, before: DateTime<Utc>) {
+        self.send(Command::ExpireIdempotencyKeys { before });
+    }
+
+    /// Ключи идемпотентности, сохранённые не раньше `since`; читается при запуске сервера
+    pub fn load_idempotency_keys(&self, since: DateTime<Utc>) -> anyhow::Result<Vec<StoredKey>> {
+        let conn = self.reader.lock().unwrap();
+        let mut statement = conn.prepare(
+            "SELECT key, fingerprint, status, body, stored_at FROM idempotency_keys \
+             WHERE stored_at >= ?1 ORDER BY stored_at",
+        )?;
+        let rows = statement.query_map([since.timestamp_millis()], |row| {
+            let body: String = row.get(3)?;
+            let stored_at: i64 = row.get(4)?;
+            Ok(StoredKey {
+                key: row.get(0)?,
+                fingerprint: row.get(1)?,
+                response: StoredResponse {
+                    status: row.get(2)?,
+                    body: serde_json::from_str(&body).unwrap_or_default(),
+                },
+                stored_at: DateTime::from_timestamp_millis(stored_at).unwrap_or_default(),
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    fn send(&self, command: Command) {
+        if self.tx.send(command).is_err() {
+            warn!("History writer has stopped, record dropped");
+        }
+    }
+
+    /// Запуски по фильтру, от новых к старым
+    pub async fn query(
+        &self,
+        query: &HistoryQuery,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<HistoryEntry>, AppError> {
+        let mut sql = String::from(
+            "SELECT run_id, script, args_hash, started_at, duration_ms, status, exit_code, \
+             timed_out, cached, stderr, error, invocation FROM runs WHERE 1 = 1",
+        );
+        let mut values = Vec::new();
+        if let Some(script) = &query.script {
+            sql.push_str(" AND script = ?");
+            values.push(Value::Text(script.clone()));
+        }
+        if let Some(status) = &query.status {
+            sql.push_str(" AND status = ?");
+            values.push(Value::Text(status.clone()));
+        }
+        if let Some(since) = query.since {
+            sql.push_str(" AND started_at >= ?");
+            values.push(Value::Integer(since.timestamp_millis()));
+        }
+        sql.push_str(" ORDER BY started_at DESC, id DESC LIMIT ? OFFSET ?");
+        values.push(Value::Integer(limit as i64));
+        values.push(Value::Integer(offset as i64));
+
+        let reader = Arc::clone(&self.reader);
+        let entries = tokio::task::spawn_blocking(move || {
+            let conn = reader.lock().unwrap();
+            let mut statement = conn.prepare_cached(&sql)?;
+            let rows = statement.query_map(params_from_iter(values), read_entry)?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()
+        })
+        .await
+        .map_err(|e| AppError::Internal(format!("History query failed: {}", e)))??;
+        Ok(entries)
+    }
+}
+
+fn open_connection(path: &Path) -> anyhow::Result<Connection> {
+    let conn = Connection::open(path)
+        .with_context(|| format!("Failed to open history database {}", path.display()))?;
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    Ok(conn)
+}
+
+// Столбец, появившийся после создания базы, добавляется к существующей таблице
+fn add_column(conn: &Connection, table: &str, column: &str, definition: &str) -> rusqlite::Result<()> {
+    let exists = conn
+        .prepare(&format!("SELECT 1 FROM pragma_table_info('{}') WHERE name = ?1", table))?
+        .exists([column])?;
+    if !exists {
+        conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))?;
+    }
+    Ok(())
+}
+
+fn read_entry(row: &Row) -> rusqlite::Result<HistoryEntry> {
+    let started_at: i64 = row.get(3)?;
+    let duration_ms: i64 = row.get(4)?;
+    let status: String = row.get(5)?;
+    let invocation: Option<String> = row.get(11)?;
+    Ok(HistoryEntry {
+        run_id: row.get(0)?,
+        script: row.get(1)?,
+        args_hash: row.get(2)?,
+        started_at: DateTime::from_timestamp_millis(started_at).unwrap_or_default(),
+        duration_ms: duration_ms as u64,
+        status: RunStatus::parse(&status).unwrap_or(RunStatus::Error),
+        exit_code: row.get(6)?,
+        timed_out: row.get(7)?,
+        cached: row.get(8)?,
+        stderr: row.get(9)?,
+        error: row.get(10)?,
+        invocation: invocation.and_then(|json| serde_json::from_str::<Invocation>(&json).ok()),
+    })
+}
+
+// Накопившиеся записи сохраняются одной транзакцией
+fn write_loop(mut conn: Connection, mut rx: mpsc::UnboundedReceiver<Command>) {
+    while let Some(command) = rx.blocking_recv() {
+        let mut batch = vec![command];
+        while batch.len() < MAX_BATCH {
+            match rx.try_recv() {
+                Ok(command) => batch.push(command),
+                Err(_) => break,
+            }
+        }
+        let count = batch.len();
+        if let Err(e) = write_batch(&mut conn, batch) {
+            warn!("Failed to write {} history records: {}", count, e);
+        }
+    }
+}
+
+fn write_batch(conn: &mut Connection, batch: Vec<Command>) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    let mut replies = Vec::new();
+    for command in batch {
+        match command {
+            Command::Run { entry, args, input } => {
+                tx.prepare_cached(
+                    "INSERT INTO runs (run_id, script, args, args_hash, input_hash, started_at, \
+                     duration_ms, status, exit_code, timed_out, cached, stderr, error, invocation) \
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+                )?
+                .execute(params![
+                    entry.run_id,
+                    entry.script,
+                    serde_json::to_string(&args).unwrap_or_default(),
+                    entry.args_hash,
+                    sha256_hex(&input),
+                    entry.started_at.timestamp_millis(),
+                    entry.duration_ms as i64,
+                    entry.status.as_str(),
+                    entry.exit_code,
+                    entry.timed_out,
+                    entry.cached,
+                    entry.stderr,
+                    entry.error,
+                    entry.invocation.as_ref().and_then(|i| serde_json::to_string(i).ok()),
+                ])?;
+            }
+            Command::Change {
+                script,
+                change,
+                code_hash,
+                changed_at,
+            } => {
+                tx.prepare_cached(
+                    "INSERT INTO script_changes (script, action, code_hash, changed_at) \
+                     VALUES (?1, ?2, ?3, ?4)",
+                )?
+                .execute(params![
+                    script,
+                    change.as_str(),
+                    code_hash,
+                    changed_at.timestamp_millis(),
+                ])?;
+            }
+            Command::Purge { before, reply } => {
+                let before = before.timestamp_millis();
+                let runs = tx.execute("DELETE FROM runs WHERE started_at < ?1", [before])?;
+                let changes =
+                    tx.execute("DELETE FROM script_changes WHERE changed_at < ?1", [before])?;
+                if runs + changes > 0 {
+                    info!("Removed {} runs and {} script changes past retention", runs, changes);
+                }
+                replies.push((reply, runs + changes));
+            }
+            Command::IdempotencyKey {
+                key,
+                fingerprint,
+                status,
+                body,
+                stored_at,
+            } => {
+                tx.prepare_cached(
+                    "INSERT OR REPLACE INTO idempotency_keys (key, fingerprint, status, body, stored_at) \
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                )?
+                .execute(params![key, fingerprint, status, body, stored_at.timestamp_millis()])?;
+            }
+            Command::ExpireIdempotencyKeys { before } => {
+                tx.execute(
+                    "DELETE FROM idempotency_keys WHERE stored_at < ?1",
+                    [before.timestamp_millis()],
+                )?;
+            }
+        }
+    }
+    tx.commit()?;
+    for (reply, removed) in replies {
+        let _ = reply.send(removed);
+    }
+    Ok(())
+}
@@ -0,0 +1,284 @@
+use crate::{
+    app_state::AppState, config::Config, error::AppError, history_db::HistoryDb, quota::Caller,
+    utils::sha256_hex,
+};
+use axum::{
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tracing::{info, warn};
+
+/// Заголовок запроса с ключом идемпотентности
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Заголовок ответа, повторённого по ключу без нового запуска
+pub const REPLAYED_HEADER: &str = "idempotent-replayed";
+
+const MAX_KEY_LEN: usize = 255;
+
+/// Ответ первого выполнения запроса с ключом
+#[derive(Debug, Clone)]
+pub struct StoredResponse {
+    pub status: u16,
+    pub body: serde_json::Value,
+}
+
+impl IntoResponse for StoredResponse {
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK);
+        (
+            status,
+            [(REPLAYED_HEADER, "true"), (header::CACHE_CONTROL.as_str(), "no-store")],
+            Json(self.body),
+        )
+            .into_response()
+    }
+}
+
+/// Сохранённый ключ из базы журнала
+pub struct StoredKey {
+    pub key: String,
+    pub fingerprint: String,
+    pub response: StoredResponse,
+    pub stored_at: DateTime<Utc>,
+}
+
+enum Slot {
+    // Первый запрос ещё выполняется
+    Pending,
+    Done(StoredResponse),
+}
+
+struct Entry {
+    fingerprint: String,
+    slot: Slot,
+    stored: Instant,
+    bytes: usize,
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: HashMap<String, Entry>,
+    // Завершённые ключи в порядке сохранения: первыми вытесняются и устаревают самые старые
+    order: VecDeque<String>,
+    bytes: usize,
+}
+
+impl Inner {
+    fn pop_oldest(&mut self) {
+        let Some(key) = self.order.pop_front() else {
+            return;
+        };
+        if let Some(entry) = self.entries.remove(&key) {
+            self.bytes -= entry.bytes;
+        }
+    }
+
+    fn evict_expired(&mut self, ttl: Duration) {
+        while let Some(key) = self.order.front() {
+            match self.entries.get(key) {
+                Some(entry) if entry.stored.elapsed() < ttl => break,
+                _ => self.pop_oldest(),
+            }
+        }
+    }
+
+    fn insert_done(&mut self, key: String, fingerprint: String, response: StoredResponse, stored: Instant, max_bytes: usize) {
+        let bytes = key.len() + fingerprint.len() + response.body.to_string().len();
+        if bytes > max_bytes {
+            warn!("Response for idempotency key is {} bytes, not stored (max {})", bytes, max_bytes);
+            self.entries.remove(&key);
+            return;
+        }
+        while self.bytes + bytes > max_bytes && !self.order.is_empty() {
+            self.pop_oldest();
+        }
+        self.bytes += bytes;
+        self.order.push_back(key.clone());
+        self.entries.insert(
+            key,
+            Entry {
+                fingerprint,
+                slot: Slot::Done(response),
+                stored,
+                bytes,
+            },
+        );
+    }
+}
+
+/// Ответы на запросы с `Idempotency-Key`: повтор с тем же ключом получает
+/// ответ первого выполнения, а скрипт не запускается заново. Хранятся в памяти
+/// не дольше `RUNNER_IDEMPOTENCY_TTL_SECS` и не больше
+/// `RUNNER_IDEMPOTENCY_STORE_BYTES` байт, с `RUNNER_IDEMPOTENCY_PERSIST` —
+/// ещё и в базе журнала.
+pub struct Idempotency {
+    inner: Mutex<Inner>,
+    max_bytes: usize,
+    ttl: Duration,
+    persist: bool,
+}
+
+impl Idempotency {
+    /// Хранилище с ключами, сохранёнными в базе журнала до перезапуска
+    pub fn new(config: &Config, history_db: Option<&HistoryDb>) -> Self {
+        let store = Self {
+            inner: Mutex::new(Inner::default()),
+            max_bytes: config.idempotency_store_bytes,
+            ttl: config.idempotency_ttl,
+            persist: config.idempotency_persist,
+        };
+        let Some(history_db) = history_db.filter(|_| store.persist && store.max_bytes > 0) else {
+            return store;
+        };
+        match history_db.load_idempotency_keys(Utc::now() - store.ttl) {
+            Ok(keys) => {
+                let count = keys.len();
+                let mut inner = store.inner.lock().unwrap();
+                for key in keys {
+                    let age = (Utc::now() - key.stored_at).to_std().unwrap_or_default();
+                    let stored = Instant::now().checked_sub(age).unwrap_or_else(Instant::now);
+                    inner.insert_done(key.key, key.fingerprint, key.response, stored, store.max_bytes);
+                }
+                drop(inner);
+                info!("Restored {} idempotency keys", count);
+            }
+            Err(e) => warn!("Failed to restore idempotency keys: {}", e),
+        }
+        store
+    }
+
+    /// Удаляет устаревшие ключи из памяти и из базы журнала
+    pub fn prune(&self, history_db: Option<&HistoryDb>) {
+        self.inner.lock().unwrap().evict_expired(self.ttl);
+        if let (true, Some(history_db)) = (self.persist, history_db) {
+            history_db.expire_idempotency_keys(Utc::now() - self.ttl);
+        }
+    }
+}
+
+/// Итог проверки ключа
+pub enum Begin<'a> {
+    /// Запрос с этим ключом ещё не выполнялся: результат нужно сохранить
+    Run(Pending<'a>),
+    /// Ответ первого выполнения
+    Replay(StoredResponse),
+}
+
+/// Ключ, занятый выполняющимся запросом. Если результат не сохранён
+/// (ошибка до запуска, клиент отключился), ключ освобождается при удалении.
+pub struct Pending<'a> {
+    state: &'a AppState,
+    key: String,
+    fingerprint: String,
+}
+
+impl Pending<'_> {
+    /// Сохраняет ответ для повторов с тем же ключом
+    pub fn complete(self, status: StatusCode, body: &impl Serialize) {
+        let body = match serde_json::to_value(body) {
+            Ok(body) => body,
+            Err(e) => {
+                warn!("Failed to store idempotent response: {}", e);
+                return;
+            }
+        };
+        let response = StoredResponse {
+            status: status.as_u16(),
+            body,
+        };
+        let store = &self.state.idempotency;
+        if let (true, Some(history_db)) = (store.persist, &self.state.history_db) {
+            history_db.record_idempotency_key(&self.key, &self.fingerprint, &response, Utc::now());
+        }
+        let mut inner = store.inner.lock().unwrap();
+        inner.evict_expired(store.ttl);
+        inner.insert_done(self.key.clone(), self.fingerprint.clone(), response, Instant::now(), store.max_bytes);
+    }
+}
+
+impl Drop for Pending<'_> {
+    fn drop(&mut self) {
+        let mut inner = self.state.idempotency.inner.lock().unwrap();
+        if matches!(inner.entries.get(&self.key), Some(Entry { slot: Slot::Pending, .. })) {
+            inner.entries.remove(&self.key);
+        }
+    }
+}
+
+/// Ключ из заголовка `Idempotency-Key`; `None`, если заголовка нет или
+/// хранилище выключено (`RUNNER_IDEMPOTENCY_STORE_BYTES=0`)
+pub fn key_from_headers(state: &AppState, headers: &HeaderMap) -> Result<Option<String>, AppError> {
+    let Some(value) = headers.get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(None);
+    };
+    if state.idempotency.max_bytes == 0 {
+        return Ok(None);
+    }
+    let key = value
+        .to_str()
+        .ok()
+        .filter(|key| !key.is_empty() && key.len() <= MAX_KEY_LEN && key.bytes().all(|b| b.is_ascii_graphic()))
+        .ok_or_else(|| {
+            AppError::BadRequest(format!(
+                "Idempotency-Key must be 1 to {} visible ASCII characters",
+                MAX_KEY_LEN
+            ))
+        })?;
+    Ok(Some(key.to_string()))
+}
+
+/// Отпечаток запроса: маршрут, скрипт и SHA-256 тела. Повтор с тем же ключом
+/// и другим отпечатком отклоняется.
+pub fn fingerprint(route: &str, script: &str, payload: &impl Serialize) -> String {
+    let body = serde_json::to_vec(payload).unwrap_or_default();
+    format!("{} {} {}", route, script, sha256_hex(&body))
+}
+
+/// Занимает ключ вызывающего или возвращает ответ первого выполнения.
+/// Ключи разных вызывающих не пересекаются.
+pub fn begin<'a>(
+    state: &'a AppState,
+    caller: &Caller,
+    key: &str,
+    fingerprint: String,
+) -> Result<Begin<'a>, AppError> {
+    let store = &state.idempotency;
+    let scoped = format!("{}\n{}", caller.0.as_deref().unwrap_or(""), key);
+    let mut inner = store.inner.lock().unwrap();
+    inner.evict_expired(store.ttl);
+    match inner.entries.get(&scoped) {
+        Some(entry) if entry.fingerprint != fingerprint => {
+            Err(AppError::IdempotencyKeyReused(key.to_string()))
+        }
+        Some(Entry { slot: Slot::Pending, .. }) => Err(AppError::IdempotencyKeyInProgress(key.to_string())),
+        Some(Entry { slot: Slot::Done(response), .. }) => {
+            info!("Replaying response for idempotency key {}", key);
+            Ok(Begin::Replay(response.clone()))
+        }
+        None => {
+            inner.entries.insert(
+                scoped.clone(),
+                Entry {
+                    fingerprint: fingerprint.clone(),
+                    slot: Slot::Pending,
+                    stored: Instant::now(),
+                    bytes: 0,
+                },
+            );
+            Ok(Begin::Run(Pending {
+                state,
+                key: scoped,
+                fingerprint,
+            }))
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use crate::{error::AppError, models::ScriptResult};
+use std::{collections::HashMap, sync::Mutex};
+use tokio::sync::watch;
+
+/// Итог запуска, который получают дождавшиеся его одинаковые запросы
+#[derive(Clone)]
+pub enum Outcome {
+    Done(Box<ScriptResult>),
+    /// Таймаут; с выводом, полученным до остановки, если он сохранён
+    TimedOut(Option<Box<ScriptResult>>),
+    /// Запуск прерван или завершился ошибкой — ждавшие запускают скрипт сами
+    Failed,
+}
+
+pub type Waiter = watch::Receiver<Option<Outcome>>;
+
+/// Выполняющиеся запуски по ключу кэша (single-flight)
+#[derive(Default)]
+pub struct InFlight {
+    runs: Mutex<HashMap<String, Waiter>>,
+}
+
+pub enum Flight<'a> {
+    /// Запрос выполняет скрипт и сообщает итог через `FlightGuard::complete`
+    Leader(FlightGuard<'a>),
+    /// Такой же запуск уже идёт — нужно дождаться его итога
+    Follower(Waiter),
+}
+
+impl InFlight {
+    pub fn join(&self, key: &str) -> Flight<'_> {
+        let mut runs = self.runs.lock().unwrap();
+        if let Some(waiter) = runs.get(key) {
+            return Flight::Follower(waiter.clone());
+        }
+        let (tx, rx) = watch::channel(None);
+        runs.insert(key.to_string(), rx);
+        Flight::Leader(FlightGuard {
+            inflight: self,
+            key: key.to_string(),
+            tx,
+        })
+    }
+}
+
+/// Снимает запуск с учёта при удалении, в том числе если запрос прерван
+pub struct FlightGuard<'a> {
+    inflight: &'a InFlight,
+    key: String,
+    tx: watch::Sender<Option<Outcome>>,
+}
+
+impl FlightGuard<'_> {
+    pub fn complete(&self, result: &Result<ScriptResult, AppError>) {
+        let outcome = match result {
+            Ok(result) if result.timed_out => Outcome::TimedOut(Some(Box::new(result.clone()))),
+            Ok(result) => Outcome::Done(Box::new(result.clone())),
+            Err(AppError::Timeout) => Outcome::TimedOut(None),
+            Err(_) => Outcome::Failed,
+        };
+        self.tx.send_replace(Some(outcome));
+    }
+}
+
+impl Drop for FlightGuard<'_> {
+    fn drop(&mut self) {
+        self.inflight.runs.lock().unwrap().remove(&self.key);
+    }
+}
+
+/// Ждёт итога запуска; если запуск прерван без итога — `Failed`
+pub async fn wait(mut waiter: Waiter) -> Outcome {
+    match waiter.wait_for(Option::is_some).await {
+        Ok(outcome) => outcome.clone().unwrap_or(Outcome::Failed),
+        Err(_) => Outcome::Failed,
+    }
+}
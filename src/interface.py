@@ -0,0 +1,168 @@
+# Разбор интерфейса скрипта без его выполнения: докстринг модуля и аргументы
+# argparse из вызовов add_argument. Путь к файлу — первый аргумент, результат —
+# одна строка JSON в stdout. Скрипт не импортируется, поэтому его код не
+# запускается; то, что не удалось извлечь, остаётся пустым, а причина пишется
+# в "error".
+import ast
+import json
+import sys
+import tokenize
+
+
+def source_text(node):
+    unparse = getattr(ast, "unparse", None)
+    return unparse(node) if unparse else None
+
+
+def literal(node):
+    try:
+        value = ast.literal_eval(node)
+    except (ValueError, TypeError, SyntaxError, MemoryError, RecursionError):
+        return None
+    return to_json(value)
+
+
+def to_json(value):
+    if isinstance(value, (list, tuple, set, frozenset)):
+        return [to_json(item) for item in value]
+    if isinstance(value, dict):
+        return {str(key): to_json(item) for key, item in value.items()}
+    if value is None or isinstance(value, (bool, int, float, str)):
+        return value
+    return repr(value)
+
+
+def string(node):
+    value = literal(node)
+    return value if isinstance(value, str) else None
+
+
+def imports_argparse(tree):
+    for node in ast.walk(tree):
+        if isinstance(node, ast.Import) and any(a.name == "argparse" for a in node.names):
+            return True
+        if isinstance(node, ast.ImportFrom) and node.module == "argparse":
+            return True
+    return False
+
+
+def method_calls(tree, name):
+    for node in ast.walk(tree):
+        if (
+            isinstance(node, ast.Call)
+            and isinstance(node.func, ast.Attribute)
+            and node.func.attr == name
+        ):
+            yield node
+
+
+def parser_description(tree):
+    for node in ast.walk(tree):
+        if not isinstance(node, ast.Call):
+            continue
+        func = node.func
+        called = func.attr if isinstance(func, ast.Attribute) else getattr(func, "id", None)
+        if called != "ArgumentParser":
+            continue
+        for keyword in node.keywords:
+            if keyword.arg == "description":
+                return string(keyword.value)
+    return None
+
+
+def argument(call):
+    flags = [s for s in (string(arg) for arg in call.args) if s is not None]
+    if not flags:
+        return None
+    keywords = {k.arg: k.value for k in call.keywords if k.arg}
+    positional = not flags[0].startswith("-")
+
+    type_node = keywords.get("type")
+    type_name = None
+    if isinstance(type_node, ast.Name):
+        type_name = type_node.id
+    elif type_node is not None:
+        type_name = source_text(type_node)
+
+    nargs = literal(keywords["nargs"]) if "nargs" in keywords else None
+    if nargs is not None:
+        nargs = str(nargs)
+    required = literal(keywords["required"]) if "required" in keywords else None
+    if not isinstance(required, bool):
+        required = positional and nargs not in ("?", "*")
+
+    dest = string(keywords["dest"]) if "dest" in keywords else None
+    if dest is None:
+        if positional:
+            dest = flags[0]
+        else:
+            long_flags = [f for f in flags if f.startswith("--")]
+            dest = (long_flags or flags)[0].lstrip("-").replace("-", "_")
+
+    choices = literal(keywords["choices"]) if "choices" in keywords else None
+    return {
+        "name": dest,
+        "flags": [] if positional else flags,
+        "positional": positional,
+        "type": type_name,
+        "default": literal(keywords["default"]) if "default" in keywords else None,
+        "help": string(keywords["help"]) if "help" in keywords else None,
+        "required": required,
+        "choices": choices if isinstance(choices, list) else None,
+        "action": string(keywords["action"]) if "action" in keywords else None,
+        "nargs": nargs,
+        "metavar": string(keywords["metavar"]) if "metavar" in keywords else None,
+    }
+
+
+# Докстринг файла с синтаксической ошибкой: первая строковая лексема модуля
+def leading_docstring(path):
+    skipped = (tokenize.COMMENT, tokenize.NL, tokenize.NEWLINE, tokenize.ENCODING)
+    try:
+        with open(path, "rb") as source:
+            for token in tokenize.tokenize(source.readline):
+                if token.type in skipped:
+                    continue
+                if token.type == tokenize.STRING:
+                    value = string(ast.parse(token.string, mode="eval").body)
+                    return value.strip() if value else None
+                return None
+    except (tokenize.TokenError, SyntaxError, OSError, ValueError):
+        return None
+    return None
+
+
+def inspect(path):
+    result = {
+        "docstring": None,
+        "description": None,
+        "argparse": False,
+        "arguments": [],
+        "error": None,
+    }
+    try:
+        with open(path, "rb") as source:
+            tree = ast.parse(source.read(), filename=path)
+    except SyntaxError as e:
+        result["docstring"] = leading_docstring(path)
+        result["error"] = "SyntaxError: {} (line {})".format(e.msg, e.lineno)
+        return result
+    except (OSError, ValueError, RecursionError) as e:
+        result["error"] = "{}: {}".format(type(e).__name__, e)
+        return result
+
+    docstring = ast.get_docstring(tree)
+    result["docstring"] = docstring.strip() if docstring else None
+    if not imports_argparse(tree):
+        return result
+    result["argparse"] = True
+    result["description"] = parser_description(tree)
+    calls = sorted(method_calls(tree, "add_argument"), key=lambda c: (c.lineno, c.col_offset))
+    for call in calls:
+        parsed = argument(call)
+        if parsed is not None and parsed["name"] != "help":
+            result["arguments"].append(parsed)
+    return result
+
+
+sys.stdout.write(json.dumps(inspect(sys.argv[1])) + "\n")
@@ -0,0 +1,85 @@
+use crate::{
+    app_state::AppState,
+    error::AppError,
+    models::{ScriptInterface, ScriptKind},
+    package,
+    script_runner,
+};
+use std::{path::Path, process::Stdio, time::Duration};
+use tokio::{process::Command, time::timeout};
+
+// Разбирает код скрипта модулем ast, не импортируя его
+const HELPER: &str = include_str!("interface.py");
+
+// Разбор занимает миллисекунды; зависший интерпретатор не должен держать запрос
+const INSPECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Интерфейс скрипта: докстринг и аргументы argparse. Результат кэшируется
+/// по SHA-256 содержимого скрипта. Если разобрать скрипт не удалось,
+/// возвращается то, что извлечено, с причиной в `error`.
+pub async fn inspect(state: &AppState, name: &str) -> Result<ScriptInterface, AppError> {
+    let (path, entry) = script_runner::resolve_script(state, name).await?;
+    if let Some((hash, interface)) = state.interfaces.lock().await.get(&path) {
+        if *hash == entry.hash {
+            return Ok(interface.clone());
+        }
+    }
+
+    let file = match entry.kind {
+        ScriptKind::Package => package::entry_file(&path, &entry.config),
+        ScriptKind::File if is_python(&path) => path.clone(),
+        ScriptKind::File => return Ok(partial("Only Python scripts can be introspected".to_string())),
+    };
+    let interpreter = match script_runner::base_interpreter(state, name, &entry) {
+        Ok(interpreter) => interpreter,
+        Err(e) => return Ok(partial(e.to_string())),
+    };
+    let interface = match run_helper(&interpreter, &file).await {
+        Ok(interface) => interface,
+        // Сбой самого разбора не кэшируется: он может не повториться
+        Err(error) => return Ok(partial(error)),
+    };
+    state
+        .interfaces
+        .lock()
+        .await
+        .insert(path, (entry.hash, interface.clone()));
+    Ok(interface)
+}
+
+fn is_python(path: &Path) -> bool {
+    path.extension().and_then(|ext| ext.to_str()) == Some(package::EXTENSION)
+}
+
+fn partial(error: String) -> ScriptInterface {
+    ScriptInterface {
+        error: Some(error),
+        ..Default::default()
+    }
+}
+
+async fn run_helper(interpreter: &str, file: &Path) -> Result<ScriptInterface, String> {
+    let mut parts = interpreter.split_whitespace();
+    let program = parts.next().unwrap_or(interpreter);
+    let output = Command::new(program)
+        .args(parts)
+        .arg("-c")
+        .arg(HELPER)
+        .arg(file)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .output();
+    let output = match timeout(INSPECT_TIMEOUT, output).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => return Err(script_runner::spawn_error(interpreter, e).to_string()),
+        Err(_) => return Err(format!("Introspection timed out after {:?}", INSPECT_TIMEOUT)),
+    };
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr.trim().lines().last().unwrap_or("no output");
+        return Err(format!("Introspection failed: {}", reason));
+    }
+    serde_json::from_slice(&output.stdout).map_err(|e| format!("Introspection returned invalid output: {}", e))
+}
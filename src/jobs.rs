@@ -0,0 +1,443 @@
+use crate::{
+    app_state::{AppState, JobRecord},
+    error::AppError,
+    models::{JobInfo, JobStatus},
+    script_runner::{self, RunOptions},
+    utils::write_atomic,
+};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use bytes::Bytes;
+use chrono::Utc;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{
+    fs,
+    sync::{watch, Notify},
+};
+use tracing::{info, warn, Instrument};
+use uuid::Uuid;
+
+/// Файл задач в директории скриптов: очередь и результаты переживают перезапуск
+pub const JOBS_FILE: &str = ".jobs.json";
+
+/// Параметры запуска незавершённой задачи, по которым её можно поставить
+/// в очередь заново после перезапуска сервера
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobRequest {
+    /// stdin в base64
+    input: String,
+    args: Vec<String>,
+    env: BTreeMap<String, String>,
+    timeout_secs: Option<u64>,
+    #[serde(default)]
+    queue_timeout_secs: Option<u64>,
+    no_cache: bool,
+    keep_workdir: bool,
+    callback_url: Option<String>,
+    partial_on_timeout: bool,
+    interpreter: Option<String>,
+    parse_output: Option<bool>,
+    omit_stdout: bool,
+    #[serde(default)]
+    parse_traceback: Option<bool>,
+    #[serde(default)]
+    network: Option<bool>,
+    /// Только имена: значения берутся из хранилища при запуске
+    #[serde(default)]
+    secrets: Vec<String>,
+    /// Кому засчитывается запуск в квотах
+    #[serde(default)]
+    caller: Option<String>,
+    retry_on_restart: bool,
+}
+
+impl JobRequest {
+    fn new(input_bytes: &Bytes, options: &RunOptions, retry_on_restart: bool) -> Self {
+        Self {
+            input: STANDARD.encode(input_bytes),
+            args: options.args.clone(),
+            env: options.env.clone(),
+            timeout_secs: options.timeout_secs,
+            queue_timeout_secs: options.queue_timeout_secs,
+            no_cache: options.no_cache,
+            keep_workdir: options.keep_workdir,
+            callback_url: options.callback_url.as_ref().map(Url::to_string),
+            partial_on_timeout: options.partial_on_timeout,
+            interpreter: options.interpreter.clone(),
+            parse_output: options.parse_output,
+            omit_stdout: options.omit_stdout,
+            parse_traceback: options.parse_traceback,
+            network: options.network,
+            secrets: options.secrets.clone(),
+            caller: options.caller.clone(),
+            retry_on_restart,
+        }
+    }
+
+    fn to_run(&self) -> Result<(Bytes, RunOptions), String> {
+        let input = STANDARD
+            .decode(&self.input)
+            .map_err(|e| format!("Invalid saved input: {}", e))?;
+        let callback_url = self
+            .callback_url
+            .as_deref()
+            .map(Url::parse)
+            .transpose()
+            .map_err(|e| format!("Invalid saved callback_url: {}", e))?;
+        let options = RunOptions {
+            args: self.args.clone(),
+            env: self.env.clone(),
+            timeout_secs: self.timeout_secs,
+            queue_timeout_secs: self.queue_timeout_secs,
+            no_cache: self.no_cache,
+            keep_workdir: self.keep_workdir,
+            callback_url,
+            partial_on_timeout: self.partial_on_timeout,
+            interpreter: self.interpreter.clone(),
+            parse_output: self.parse_output,
+            omit_stdout: self.omit_stdout,
+            parse_traceback: self.parse_traceback,
+            network: self.network,
+            secrets: self.secrets.clone(),
+            caller: self.caller.clone(),
+            ..Default::default()
+        };
+        Ok((Bytes::from(input), options))
+    }
+}
+
+// Запись файла задач; параметры запуска есть только у незавершённых
+#[derive(Serialize, Deserialize)]
+struct StoredJob<'a> {
+    info: Cow<'a, JobInfo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    request: Option<Cow<'a, JobRequest>>,
+}
+
+/// Ставит запуск скрипта в очередь и сразу возвращает созданную задачу.
+/// Выполнение идёт в фоне под тем же семафором, что и синхронные запуски.
+pub async fn submit_job(
+    state: Arc<AppState>,
+    script_name: String,
+    input_bytes: Bytes,
+    options: RunOptions,
+    retry_on_restart: bool,
+) -> Result<JobInfo, AppError> {
+    script_runner::resolve_script(&state, &script_name).await?;
+
+    let job_id = Uuid::new_v4();
+    let info = JobInfo {
+        job_id: job_id.to_string(),
+        script: script_name.clone(),
+        status: JobStatus::Queued,
+        created_at: Utc::now(),
+        started_at: None,
+        finished_at: None,
+        result: None,
+        error: None,
+    };
+    let request = JobRequest::new(&input_bytes, &options, retry_on_restart);
+
+    let mut jobs = state.jobs.lock().await;
+    let options = enqueue(&mut jobs, job_id, info.clone(), request, options);
+    persist(&state, &jobs).await;
+    drop(jobs);
+
+    spawn_run(state, job_id, script_name, input_bytes, options);
+    info!("Job {} queued for script {}", job_id, info.script);
+    Ok(info)
+}
+
+// Добавляет задачу в очередь; возвращает параметры запуска с её id и сигналом отмены
+fn enqueue(
+    jobs: &mut HashMap<Uuid, JobRecord>,
+    job_id: Uuid,
+    info: JobInfo,
+    request: JobRequest,
+    mut options: RunOptions,
+) -> RunOptions {
+    let cancel = Arc::new(Notify::new());
+    options.job_id = Some(job_id);
+    options.cancel = Some(Arc::clone(&cancel));
+    jobs.insert(
+        job_id,
+        JobRecord {
+            info,
+            finished: None,
+            cancel,
+            request: Some(request),
+            done: watch::channel(false).0,
+        },
+    );
+    options
+}
+
+// Выполняет задачу в фоне и записывает её итог
+fn spawn_run(
+    state: Arc<AppState>,
+    job_id: Uuid,
+    script_name: String,
+    input_bytes: Bytes,
+    options: RunOptions,
+) {
+    tokio::spawn(async move {
+        let result =
+            script_runner::run_script(Arc::clone(&state), &script_name, input_bytes, options).await;
+
+        // Задача, прерванная остановкой сервера, остаётся в файле незавершённой
+        if matches!(result, Err(AppError::ShuttingDown)) {
+            return;
+        }
+
+        let mut jobs = state.jobs.lock().await;
+        let Some(job) = jobs.get_mut(&job_id) else {
+            return;
+        };
+        let info = &mut job.info;
+        job.request = None;
+
+        // Статус и время отмены уже проставлены в cancel_job — сохраняем только вывод
+        if info.status == JobStatus::Cancelled {
+            info.result = match result {
+                Ok(result) => Some(result),
+                Err(AppError::Cancelled(partial)) => partial.map(|r| *r),
+                Err(_) => None,
+            };
+        } else {
+            match result {
+                // С `partial_on_timeout` задача хранит вывод до остановки
+                Ok(result) if result.timed_out => {
+                    info.status = JobStatus::TimedOut;
+                    info.error = Some(AppError::Timeout.to_string());
+                    info.result = Some(result);
+                }
+                Ok(result) => {
+                    info.status = JobStatus::Finished;
+                    info.result = Some(result);
+                }
+                Err(AppError::Timeout) => {
+                    info.status = JobStatus::TimedOut;
+                    info.error = Some(AppError::Timeout.to_string());
+                }
+                Err(e) => {
+                    info.status = JobStatus::Failed;
+                    info.error = Some(e.to_string());
+                }
+            }
+            info.finished_at = Some(Utc::now());
+            job.finished = Some(Instant::now());
+        }
+        job.done.send_replace(true);
+        persist(&state, &jobs).await;
+    }
+    // Фоновый запуск остаётся в span запроса, который его создал
+    .in_current_span());
+}
+
+/// Загружает задачи, сохранённые до перезапуска. Задачи из очереди ставятся
+/// в неё снова; выполнявшиеся отмечаются `interrupted` или, если они созданы
+/// с `retry_on_restart`, тоже запускаются заново. Завершённые задачи старше
+/// срока хранения отбрасываются. Повреждённый файл переименовывается.
+pub async fn restore(state: &Arc<AppState>) {
+    let path = state.scripts_dir.join(JOBS_FILE);
+    let content = match fs::read(&path).await {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return,
+        Err(e) => {
+            warn!("Failed to read {}: {}", path.display(), e);
+            return;
+        }
+    };
+    let saved: Vec<StoredJob> = match serde_json::from_slice(&content) {
+        Ok(saved) => saved,
+        Err(e) => {
+            let backup = path.with_extension("json.invalid");
+            warn!("Failed to parse {}: {}; moving it to {}", path.display(), e, backup.display());
+            let _ = fs::rename(&path, &backup).await;
+            return;
+        }
+    };
+
+    let now = Utc::now();
+    let mut runs = Vec::new();
+    let mut jobs = state.jobs.lock().await;
+    for saved in saved {
+        let mut info = saved.info.into_owned();
+        let request = saved.request.map(Cow::into_owned);
+        let Ok(job_id) = Uuid::parse_str(&info.job_id) else {
+            warn!("Skipping saved job with invalid id '{}'", info.job_id);
+            continue;
+        };
+
+        let rerun = match info.status {
+            JobStatus::Queued => true,
+            JobStatus::Running => request.as_ref().is_some_and(|r| r.retry_on_restart),
+            _ => false,
+        };
+        if rerun {
+            let run = request
+                .ok_or_else(|| "Saved job has no run parameters".to_string())
+                .and_then(|request| request.to_run().map(|run| (request, run)));
+            match run {
+                Ok((request, (input_bytes, options))) => {
+                    info.status = JobStatus::Queued;
+                    info.started_at = None;
+                    let script_name = info.script.clone();
+                    let options = enqueue(&mut jobs, job_id, info, request, options);
+                    runs.push((job_id, script_name, input_bytes, options));
+                    continue;
+                }
+                Err(e) => {
+                    warn!("Failed to restore job {}: {}", job_id, e);
+                    info.status = JobStatus::Failed;
+                    info.error = Some(e);
+                    info.finished_at = Some(now);
+                }
+            }
+        } else if info.status == JobStatus::Running {
+            info.status = JobStatus::Interrupted;
+            info.error = Some("Server restarted while the job was running".to_string());
+            info.finished_at = Some(now);
+        }
+
+        // Срок хранения отсчитывается от завершения, в том числе до перезапуска
+        let age = info
+            .finished_at
+            .and_then(|finished_at| (now - finished_at).to_std().ok())
+            .unwrap_or_default();
+        if age >= state.job_retention {
+            continue;
+        }
+        jobs.insert(
+            job_id,
+            JobRecord {
+                info,
+                finished: Some(Instant::now().checked_sub(age).unwrap_or_else(Instant::now)),
+                cancel: Arc::new(Notify::new()),
+                request: None,
+                done: watch::channel(true).0,
+            },
+        );
+    }
+    persist(state, &jobs).await;
+    info!("Restored {} jobs, {} of them queued again", jobs.len(), runs.len());
+    drop(jobs);
+
+    for (job_id, script_name, input_bytes, options) in runs {
+        spawn_run(Arc::clone(state), job_id, script_name, input_bytes, options);
+    }
+}
+
+// Записывает все задачи в файл. Вызывается под блокировкой списка,
+// чтобы более старый снимок не перезаписал более новый.
+async fn persist(state: &AppState, jobs: &HashMap<Uuid, JobRecord>) {
+    let mut saved: Vec<StoredJob> = jobs
+        .values()
+        .map(|job| StoredJob {
+            info: Cow::Borrowed(&job.info),
+            request: job.request.as_ref().map(Cow::Borrowed),
+        })
+        .collect();
+    saved.sort_by_key(|job| job.info.created_at);
+    let path = state.scripts_dir.join(JOBS_FILE);
+    let result = match serde_json::to_vec(&saved) {
+        Ok(content) => write_atomic(&path, &content, true).await,
+        Err(e) => Err(std::io::Error::other(e)),
+    };
+    if let Err(e) = result {
+        warn!("Failed to save jobs to {}: {}", path.display(), e);
+    }
+}
+
+/// Отмечает задачу как выполняющуюся (вызывается после получения слота семафора)
+pub async fn mark_running(state: &AppState, job_id: Uuid) {
+    let mut jobs = state.jobs.lock().await;
+    if let Some(job) = jobs.get_mut(&job_id).filter(|job| job.info.status == JobStatus::Queued) {
+        job.info.status = JobStatus::Running;
+        job.info.started_at = Some(Utc::now());
+        persist(state, &jobs).await;
+    }
+}
+
+pub async fn get_job(state: &AppState, job_id: &str) -> Result<JobInfo, AppError> {
+    let id = Uuid::parse_str(job_id).map_err(|_| AppError::JobNotFound(job_id.to_string()))?;
+    let jobs = state.jobs.lock().await;
+    jobs.get(&id)
+        .map(|job| job.info.clone())
+        .ok_or_else(|| AppError::JobNotFound(job_id.to_string()))
+}
+
+/// Статус задачи. Незавершённая задача с ненулевым `wait` ждёт завершения
+/// не дольше `wait` (и `RUNNER_MAX_JOB_WAIT_SECS`); по истечении возвращается
+/// текущий статус. Остановка сервера прерывает ожидание.
+pub async fn wait_job(state: &AppState, job_id: &str, wait: Duration) -> Result<JobInfo, AppError> {
+    let id = Uuid::parse_str(job_id).map_err(|_| AppError::JobNotFound(job_id.to_string()))?;
+    // Подписка под блокировкой: завершение между проверкой и ожиданием не теряется
+    let mut done = {
+        let jobs = state.jobs.lock().await;
+        let job = jobs.get(&id).ok_or_else(|| AppError::JobNotFound(job_id.to_string()))?;
+        if wait.is_zero() || *job.done.borrow() {
+            return Ok(job.info.clone());
+        }
+        job.done.subscribe()
+    };
+    let wait = wait.min(state.max_job_wait);
+    tokio::select! {
+        // Ошибка — задача удалена; её отсутствие сообщит get_job
+        _ = tokio::time::timeout(wait, done.wait_for(|done| *done)) => {}
+        _ = state.shutdown.draining() => {}
+    }
+    get_job(state, job_id).await
+}
+
+/// Отменяет задачу: из очереди она снимается до получения слота,
+/// у выполняющейся завершается процесс. Завершённые задачи не отменяются.
+pub async fn cancel_job(state: &AppState, job_id: &str) -> Result<JobInfo, AppError> {
+    let id = Uuid::parse_str(job_id).map_err(|_| AppError::JobNotFound(job_id.to_string()))?;
+    let mut jobs = state.jobs.lock().await;
+    let job = jobs
+        .get_mut(&id)
+        .ok_or_else(|| AppError::JobNotFound(job_id.to_string()))?;
+
+    match job.info.status {
+        JobStatus::Queued | JobStatus::Running => {}
+        status => {
+            return Err(AppError::JobNotCancellable(job_id.to_string(), status.as_str()));
+        }
+    }
+
+    info!("Cancelling job {} ({})", job_id, job.info.status.as_str());
+    job.info.status = JobStatus::Cancelled;
+    job.info.finished_at = Some(Utc::now());
+    job.finished = Some(Instant::now());
+    job.request = None;
+    job.cancel.notify_one();
+    job.done.send_replace(true);
+
+    let info = job.info.clone();
+    persist(state, &jobs).await;
+    Ok(info)
+}
+
+/// Удаляет завершённые задачи старше срока хранения, возвращает их число
+pub async fn prune_jobs(state: &AppState) -> usize {
+    let mut jobs = state.jobs.lock().await;
+    let before = jobs.len();
+    jobs.retain(|_, job| {
+        job.finished
+            .map(|finished| finished.elapsed() < state.job_retention)
+            .unwrap_or(true)
+    });
+    let removed = before - jobs.len();
+    if removed > 0 {
+        persist(state, &jobs).await;
+        info!("Pruned {} finished jobs", removed);
+    }
+    removed
+}
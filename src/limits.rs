@@ -0,0 +1,199 @@
+use crate::{error::AppError, models::RunLimits};
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use bytes::BytesMut;
+use futures::StreamExt;
+
+// Запас на остальные поля JSON-запроса (env, timeout_secs, ...)
+const JSON_BODY_OVERHEAD: usize = 64 * 1024;
+
+/// Ограничение запроса
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Args,
+    ArgBytes,
+    DataBytes,
+    BatchScripts,
+    ScriptBytes,
+    RunBodyBytes,
+    RawBodyBytes,
+}
+
+impl Limit {
+    /// Имя ограничения в ответе об ошибке и в `GET /limits`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Limit::Args => "max_args",
+            Limit::ArgBytes => "max_arg_bytes",
+            Limit::DataBytes => "max_data_bytes",
+            Limit::BatchScripts => "max_batch_scripts",
+            Limit::ScriptBytes => "max_script_bytes",
+            Limit::RunBodyBytes => "max_run_body_bytes",
+            Limit::RawBodyBytes => "max_raw_body_bytes",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Limit::Args | Limit::ArgBytes | Limit::BatchScripts => StatusCode::BAD_REQUEST,
+            _ => StatusCode::PAYLOAD_TOO_LARGE,
+        }
+    }
+}
+
+/// Проверяет аргументы запуска: их число, длину каждого и отсутствие NUL,
+/// который нельзя передать в аргументах процесса
+pub fn check_args(limits: &RunLimits, args: &[String]) -> Result<(), AppError> {
+    if args.len() > limits.max_args {
+        return Err(AppError::LimitExceeded(
+            Limit::Args,
+            format!("Too many arguments: {} (max {})", args.len(), limits.max_args),
+        ));
+    }
+    for (index, arg) in args.iter().enumerate() {
+        if arg.len() > limits.max_arg_bytes {
+            return Err(AppError::LimitExceeded(
+                Limit::ArgBytes,
+                format!(
+                    "Argument {} is {} bytes long (max {})",
+                    index,
+                    arg.len(),
+                    limits.max_arg_bytes
+                ),
+            ));
+        }
+        if arg.contains('\0') {
+            return Err(AppError::BadRequest(format!(
+                "Argument {} contains a NUL byte",
+                index
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Проверяет размер сериализованного `data`, который передаётся в stdin
+pub fn check_data(limits: &RunLimits, input: &[u8]) -> Result<(), AppError> {
+    if input.len() > limits.max_data_bytes {
+        return Err(AppError::LimitExceeded(
+            Limit::DataBytes,
+            format!(
+                "Serialized data is {} bytes (max {})",
+                input.len(),
+                limits.max_data_bytes
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Проверяет число скриптов, которые запускает один пакетный запрос
+pub fn check_batch(limits: &RunLimits, count: usize) -> Result<(), AppError> {
+    if count > limits.max_batch_scripts {
+        return Err(AppError::LimitExceeded(
+            Limit::BatchScripts,
+            format!(
+                "Batch targets {} scripts (max {})",
+                count, limits.max_batch_scripts
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Наибольшее тело JSON-запроса на запуск: данные, аргументы и остальные поля
+pub fn max_json_body_bytes(limits: &RunLimits) -> usize {
+    limits
+        .max_data_bytes
+        .saturating_add(limits.max_args.saturating_mul(limits.max_arg_bytes))
+        .saturating_add(JSON_BODY_OVERHEAD)
+}
+
+/// Наибольшее тело запроса на создание или изменение скрипта: код и остальные поля
+pub fn max_script_body_bytes(max_script_bytes: usize) -> usize {
+    max_script_bytes.saturating_add(JSON_BODY_OVERHEAD)
+}
+
+/// Проверяет размер кода скрипта до его проверки и записи на диск
+pub fn check_script(max_script_bytes: usize, code: &[u8]) -> Result<(), AppError> {
+    if code.len() > max_script_bytes {
+        return Err(AppError::PayloadTooLarge {
+            limit: Limit::ScriptBytes,
+            size: Some(code.len()),
+            max: max_script_bytes,
+        });
+    }
+    Ok(())
+}
+
+/// Ограничение размера тела запроса для маршрута (`limit_body`)
+#[derive(Debug, Clone, Copy)]
+pub struct BodyLimit {
+    pub limit: Limit,
+    pub max: usize,
+}
+
+/// Отклоняет тело больше `max` ошибкой 413 в формате API до того, как его
+/// разберёт обработчик: по `Content-Length` сразу, без него — при чтении
+pub async fn limit_body(
+    State(BodyLimit { limit, max }): State<BodyLimit>,
+    req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let too_large = |size| AppError::PayloadTooLarge { limit, size, max };
+    let declared = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<usize>().ok());
+    if let Some(size) = declared.filter(|&size| size > max) {
+        return Err(too_large(Some(size)));
+    }
+
+    let (parts, body) = req.into_parts();
+    let mut stream = body.into_data_stream();
+    let mut bytes = BytesMut::new();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk
+            .map_err(|e| AppError::BadRequest(format!("Failed to read request body: {}", e)))?;
+        if bytes.len() + chunk.len() > max {
+            return Err(too_large(None));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(next.run(Request::from_parts(parts, Body::from(bytes.freeze()))).await)
+}
+
+/// Тело запроса — `multipart/form-data`
+pub fn is_multipart(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.to_ascii_lowercase().starts_with("multipart/form-data"))
+}
+
+/// Ограничения тела `POST /scripts`: JSON с одним скриптом или multipart с файлами
+#[derive(Debug, Clone, Copy)]
+pub struct UploadLimits {
+    pub json: BodyLimit,
+    pub multipart: BodyLimit,
+}
+
+/// `limit_body` с ограничением по типу тела запроса
+pub async fn limit_upload_body(
+    State(limits): State<UploadLimits>,
+    req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let limit = if is_multipart(req.headers()) {
+        limits.multipart
+    } else {
+        limits.json
+    };
+    limit_body(State(limit), req, next).await
+}
@@ -0,0 +1,78 @@
+use anyhow::{anyhow, Context};
+use axum_server::tls_rustls::RustlsConfig;
+use std::{fmt, net::SocketAddr, path::PathBuf, str::FromStr};
+
+// Префикс `RUNNER_BIND` для Unix-сокета
+const UNIX_PREFIX: &str = "unix:";
+
+/// Где принимать соединения: TCP-адрес или файл Unix-сокета
+#[derive(Debug, Clone)]
+pub enum Bind {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl FromStr for Bind {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> anyhow::Result<Self> {
+        match value.strip_prefix(UNIX_PREFIX) {
+            Some("") => Err(anyhow!("Unix socket path must not be empty")),
+            Some(_) if !cfg!(unix) => Err(anyhow!("Unix sockets are not supported on this platform")),
+            Some(path) => Ok(Bind::Unix(PathBuf::from(path))),
+            None => Ok(Bind::Tcp(value.parse()?)),
+        }
+    }
+}
+
+impl fmt::Display for Bind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Bind::Tcp(addr) => write!(f, "{}", addr),
+            Bind::Unix(path) => write!(f, "{}{}", UNIX_PREFIX, path.display()),
+        }
+    }
+}
+
+/// Сертификат и ключ в PEM (`RUNNER_TLS_CERT`, `RUNNER_TLS_KEY`)
+#[derive(Debug, Clone)]
+pub struct TlsFiles {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+/// Читает сертификат и ключ; ошибка называет файл, который не удалось загрузить
+pub async fn load_tls(files: &TlsFiles) -> anyhow::Result<RustlsConfig> {
+    let cert = tokio::fs::read(&files.cert)
+        .await
+        .with_context(|| format!("Failed to read TLS certificate {}", files.cert.display()))?;
+    let key = tokio::fs::read(&files.key)
+        .await
+        .with_context(|| format!("Failed to read TLS key {}", files.key.display()))?;
+    RustlsConfig::from_pem(cert, key).await.with_context(|| {
+        format!(
+            "Invalid TLS certificate {} or key {}",
+            files.cert.display(),
+            files.key.display()
+        )
+    })
+}
+
+/// Создаёт Unix-сокет с правами `mode`. Оставшийся от прошлого запуска сокет
+/// удаляется; любой другой файл по этому пути считается ошибкой.
+#[cfg(unix)]
+pub fn bind_unix(path: &std::path::Path, mode: u32) -> anyhow::Result<tokio::net::UnixListener> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => std::fs::remove_file(path)
+            .with_context(|| format!("Failed to remove stale socket {}", path.display()))?,
+        Ok(_) => return Err(anyhow!("{} exists and is not a socket", path.display())),
+        Err(_) => {}
+    }
+    let listener = tokio::net::UnixListener::bind(path)
+        .with_context(|| format!("Failed to bind Unix socket {}", path.display()))?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+        .with_context(|| format!("Failed to set permissions {:o} on {}", mode, path.display()))?;
+    Ok(listener)
+}
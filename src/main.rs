@@ -1,24 +1,85 @@
+mod admin;
 mod app_state;
+mod arg_template;
+mod archive;
+mod artifacts;
+mod audit;
+mod cache;
+mod cache_db;
+mod cli;
+mod config;
+mod cron;
+mod environment;
 mod error;
+mod events;
+mod exec_user;
+mod fetch;
+mod gc;
 mod models;
+mod namespace;
+mod network;
+mod outputs;
+mod package;
+mod pipeline;
+mod quota;
+mod rate_limit;
+mod request_id;
+mod resources;
+mod run_log;
+mod sandbox;
+mod schedules;
 mod db;
+mod deps;
 mod handlers;
+mod health;
+mod history;
+mod history_db;
+mod idempotency;
+mod inflight;
+mod interface;
+mod jobs;
+mod limits;
+mod listener;
+mod script_config;
+mod script_path;
 mod script_runner;
+mod secrets;
+mod shutdown;
+mod stats;
+#[cfg(test)]
+mod test_support;
+mod traceback;
+mod trash;
 mod utils;
+mod validation;
+mod versions;
+mod watcher;
+mod webhook;
+mod workdir;
+mod worker_pool;
 pub mod migrations;
 pub mod jwt;
-pub mod auth_middleware;
+mod auth_middleware;
 
 use utoipa::{OpenApi, Modify};
 use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
 use utoipa_axum::router::OpenApiRouter;
 use utoipa_swagger_ui::SwaggerUi;
-use std::{net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
-use axum::{Router, routing::{get, post}, middleware};
-use tower_http::cors::{CorsLayer, AllowOrigin};
-use tracing::{info, error};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+use axum::{Router, extract::DefaultBodyLimit, response::Redirect, routing::{delete, get, post, put}, middleware};
+use tower_http::{
+    compression::{
+        predicate::{NotForContentType, Predicate, SizeAbove},
+        CompressionLayer,
+    },
+    cors::{CorsLayer, AllowOrigin},
+};
+use clap::Parser;
+use tracing::{info, error, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 use crate::db::ensure_superadmin;
+use crate::limits::{BodyLimit, Limit, UploadLimits};
+use crate::listener::Bind;
 use crate::models::*;
 
 #[derive(OpenApi)]
@@ -31,8 +92,63 @@ use crate::models::*;
         handlers::get_script,
         handlers::update_script,
         handlers::delete_script,
+        handlers::list_trash,
+        handlers::restore_script,
         handlers::run_scripts,
+        handlers::run_batch_entries,
+        handlers::run_pipeline,
+        handlers::eval_code,
         handlers::run_single_script,
+        handlers::run_single_script_get,
+        handlers::run_single_script_raw,
+        handlers::run_single_script_stream,
+        handlers::run_single_script_async,
+        handlers::get_run_artifact,
+        handlers::get_run_log,
+        handlers::get_events,
+        handlers::get_job,
+        handlers::cancel_job,
+        handlers::get_history,
+        handlers::get_audit,
+        handlers::list_secrets,
+        handlers::put_secret,
+        handlers::delete_secret,
+        handlers::get_admin_config,
+        handlers::patch_admin_config,
+        handlers::list_namespaces,
+        handlers::create_namespace,
+        handlers::delete_namespace,
+        handlers::run_gc,
+        handlers::clear_history,
+        handlers::get_stats,
+        handlers::get_limits,
+        handlers::export_scripts,
+        handlers::import_scripts,
+        handlers::fetch_script,
+        handlers::sync_scripts,
+        handlers::list_script_versions,
+        handlers::get_script_version,
+        handlers::rollback_script,
+        handlers::copy_script,
+        handlers::rename_script,
+        handlers::update_script_tags,
+        handlers::disable_script,
+        handlers::enable_script,
+        handlers::get_script_stats,
+        handlers::reset_script_stats,
+        handlers::get_script_deps,
+        handlers::get_script_environment,
+        handlers::get_environment,
+        handlers::install_script_deps,
+        handlers::get_script_interface,
+        handlers::cache_stats,
+        handlers::clear_cache,
+        handlers::clear_script_cache,
+        handlers::create_schedule,
+        handlers::list_schedules,
+        handlers::delete_schedule,
+        handlers::healthz,
+        handlers::readyz,
     ),
     components(
         schemas(
@@ -40,12 +156,110 @@ use crate::models::*;
             LoginRequest,
             LoginResponse,
             ScriptMetadata,
+            ScriptKind,
+            ScriptFileDetails,
+            ScriptConfig,
+            ScriptTags,
+            ScriptAvailability,
+            TagMatch,
+            ScriptVersion,
+            TrashItem,
+            ImportMode,
+            ImportStatus,
+            ImportFileResult,
+            ImportResponse,
+            UploadStatus,
+            UploadFileResult,
+            UploadResponse,
+            ScriptOrigin,
+            FetchScriptRequest,
+            SyncScriptsRequest,
+            SyncStatus,
+            SyncFileResult,
+            SyncResponse,
+            NamespaceInfo,
+            CreateNamespaceRequest,
+            SaveScriptQuery,
+            DeleteScriptQuery,
+            ScriptTargetRequest,
+            GetScriptQuery,
             CreateScriptRequest,
             UpdateScriptRequest,
             RunRequest,
             RunQuery,
+            BatchQuery,
+            BatchEntry,
+            BatchRunRequest,
+            PipelineStep,
+            PipelineRequest,
+            PipelineStepResult,
+            PipelineResponse,
+            EvalRequest,
+            WebhookPayload,
+            ErrorResponse,
+            ErrorBody,
+            OutputEncoding,
             ScriptResult,
+            LogStream,
+            LogLine,
+            Artifact,
+            Termination,
+            TimeoutBudget,
+            KilledReason,
+            PythonError,
+            InputFrom,
+            Invocation,
+            TracebackFrame,
+            BatchItem,
+            BatchSummary,
+            BatchResult,
+            SkippedResult,
             RunResponse,
+            LegacyRunResponse,
+            JobStatus,
+            JobInfo,
+            RunStatus,
+            HistoryEntry,
+            HistoryQuery,
+            JobQuery,
+            RunLogQuery,
+            AuditEntry,
+            AuditQuery,
+            SecretInfo,
+            SecretValueRequest,
+            AdminConfig,
+            AdminConfigPatch,
+            ServerEvent,
+            EventData,
+            EventsQuery,
+            RemovedResponse,
+            ScriptStats,
+            DepsState,
+            DepsStatus,
+            EnvironmentReport,
+            InstalledPackage,
+            EnvironmentQuery,
+            InstallDepsQuery,
+            ScriptInterface,
+            ScriptArgument,
+            StatsSummary,
+            GcReport,
+            GcRemoved,
+            LoadStats,
+            ScriptLoad,
+            PoolStats,
+            WebhookStats,
+            QuotaScope,
+            QuotaUsage,
+            RunLimits,
+            LimitsResponse,
+            CacheStats,
+            OverlapPolicy,
+            CreateScheduleRequest,
+            Schedule,
+            ScheduleRun,
+            HealthCheck,
+            ReadinessReport,
             SearchQuery,
         )
     ),
@@ -53,6 +267,10 @@ use crate::models::*;
         (name = "auth", description = "Authentication endpoints"),
         (name = "scripts", description = "Script management"),
         (name = "execution", description = "Script execution"),
+        (name = "schedules", description = "Scheduled script execution"),
+        (name = "secrets", description = "Secrets passed to scripts as environment variables"),
+        (name = "admin", description = "Runtime server configuration"),
+        (name = "health", description = "Liveness and readiness probes"),
     ),
     modifiers(&SecurityAddon),
 )]
@@ -78,6 +296,10 @@ impl Modify for SecurityAddon {
 #[tokio::main]
 async fn main() {
     dotenv::dotenv().ok();
+    // `client ...` обращается к работающему серверу; без подкоманды запускается сервер
+    if let Some(cli::Command::Client(args)) = cli::Cli::parse().command {
+        std::process::exit(cli::run(args).await);
+    }
     tracing_subscriber::registry()
         .with(tracing_subscriber::EnvFilter::new(
             std::env::var("RUST_LOG").unwrap_or_else(|_| "info".into()),
@@ -85,6 +307,15 @@ async fn main() {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
+    // Конфигурация
+    let config = match config::Config::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Invalid configuration: {:#}", e);
+            std::process::exit(1);
+        }
+    };
+
     // MongoDB
     let mongo_uri = std::env::var("MONGO_URI").unwrap_or_else(|_| "mongodb://localhost:27017".into());
     let db_name = std::env::var("MONGO_DB_NAME").unwrap_or_else(|_| "script_manager".into());
@@ -105,31 +336,224 @@ async fn main() {
     }
 
     // Директория скриптов
-    let scripts_dir = PathBuf::from("./scripts");
-    if !scripts_dir.exists() {
-        tokio::fs::create_dir_all(&scripts_dir).await.expect("Failed to create scripts directory");
+    if !config.scripts_dir.exists() {
+        if let Err(e) = tokio::fs::create_dir_all(&config.scripts_dir).await {
+            error!("Failed to create scripts directory {}: {}", config.scripts_dir.display(), e);
+            std::process::exit(1);
+        }
     }
 
-    let state = Arc::new(app_state::AppState::new(
-        scripts_dir,
-        db,
-        4,
-        Duration::from_secs(30),
-    ));
+    // Журнал запусков в SQLite
+    let history_db = match config.history_db.as_deref().map(history_db::HistoryDb::open).transpose() {
+        Ok(history_db) => history_db,
+        Err(e) => {
+            error!("{:#}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // Журнал аудита; записи из RUNNER_AUDIT_LOG восстанавливаются
+    let audit = match audit::AuditLog::open(config.audit_size, config.audit_log.clone()) {
+        Ok(audit) => audit,
+        Err(e) => {
+            error!("{:#}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // Секреты расшифровываются при запуске: неверный ключ должен быть заметен сразу
+    let secrets = match secrets::SecretStore::open(config.secrets_key.as_deref(), config.secrets_file.clone()) {
+        Ok(secrets) => secrets,
+        Err(e) => {
+            error!("{:#}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let state = Arc::new(app_state::AppState::new(&config, db, history_db, audit, secrets));
+
+    // Результаты, закэшированные до перезапуска
+    if let Some(path) = &config.cache_db {
+        if let Some((store, entries)) = cache_db::CacheDb::open(path, state.cache_ttl()) {
+            state.cache.lock().await.attach_store(store, entries);
+        }
+    }
+
+    // Недоступный интерпретатор должен быть заметен до первого запроса
+    for check in health::check_interpreters(&state).await {
+        if let Some(err) = check.error {
+            warn!("!!! {} is unavailable: {}. Scripts using it will fail with 503", check.name, err);
+        }
+    }
+    if let Some(reason) = &state.network_unavailable {
+        let fallback = match state.network_fallback {
+            network::IsolationFallback::Fail => "fail with 503",
+            network::IsolationFallback::Warn => "run with network access",
+        };
+        warn!("Network isolation is unavailable: {}. Runs with network=false will {}", reason, fallback);
+    }
+    if let Some(Err(reason)) = state.sandbox.as_ref().map(|sandbox| sandbox.probe()) {
+        warn!("!!! Sandbox is unavailable: {}. Script runs will fail with 503 and /readyz reports not ready", reason);
+    }
 
     // Первичная синхронизация
     script_runner::scan_scripts(state.clone()).await;
 
-    // Фоновое сканирование
+    // Отслеживание изменений в директории скриптов; без него — частое сканирование
+    let (_watcher, scan_interval) = match watcher::spawn_watcher(state.clone()) {
+        Ok(watcher) => (Some(watcher), config.scan_interval),
+        Err(e) => {
+            warn!("Failed to watch scripts directory, falling back to polling: {}", e);
+            (None, Duration::from_secs(5))
+        }
+    };
+
+    // Фоновое сканирование (резервное при работающем наблюдателе)
     let scanner_state = state.clone();
     tokio::spawn(async move {
-        let mut interval = tokio::time::interval(Duration::from_secs(5));
+        let mut interval = tokio::time::interval(scan_interval);
         loop {
             interval.tick().await;
             script_runner::scan_scripts(scanner_state.clone()).await;
         }
     });
 
+    // Асинхронные задачи, поставленные до перезапуска
+    jobs::restore(&state).await;
+
+    // Запуск скриптов по расписанию
+    schedules::load(&state).await;
+    tokio::spawn(schedules::run_scheduler(state.clone()));
+
+    // Удаление устаревших записей кэша; TTL меняется через PATCH /admin/config,
+    // поэтому период пересчитывается на каждом шаге
+    let cache_state = state.clone();
+    tokio::spawn(async move {
+        loop {
+            let period = cache_state.cache_ttl().clamp(Duration::from_secs(1), Duration::from_secs(60));
+            tokio::time::sleep(period).await;
+            let ttl = cache_state.cache_ttl();
+            let evicted = cache_state.cache.lock().await.evict_expired(ttl);
+            if evicted > 0 {
+                info!("Evicted {} expired cache entries", evicted);
+            }
+        }
+    });
+
+    // Удаление задач, артефактов, записей журнала, корзины и версий старше
+    // их сроков хранения
+    gc::spawn(state.clone(), config.gc_interval);
+
+    // Очистка состояния ограничителя частоты для неактивных клиентов
+    let limiter_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            limiter_state.rate_limiter.prune().await;
+        }
+    });
+
+    // Удаление окон квот без запусков за последний час
+    let quota_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(300));
+        loop {
+            interval.tick().await;
+            quota_state.quotas.prune().await;
+        }
+    });
+
+    let app = build_app(&config, state.clone());
+
+    // Ошибки сертификата и сокета обнаруживаются до начала приёма соединений
+    let tls = match &config.tls {
+        Some(files) => match listener::load_tls(files).await {
+            Ok(tls) => Some(tls),
+            Err(e) => {
+                error!("{:#}", e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    // Остановка по SIGTERM/SIGINT: приём соединений прекращается,
+    // текущие запуски получают RUNNER_SHUTDOWN_TIMEOUT_SECS на завершение
+    let drain_state = state.clone();
+    let shutdown_timeout = config.shutdown_timeout;
+    let drain = tokio::spawn(async move {
+        shutdown_signal().await;
+        info!("Shutdown signal received, no longer accepting runs");
+        drain_state.shutdown.drain(shutdown_timeout).await
+    });
+
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    let served = match (&config.bind, tls) {
+        (Bind::Tcp(addr), None) => {
+            let listener = match tokio::net::TcpListener::bind(addr).await {
+                Ok(listener) => listener,
+                Err(e) => {
+                    error!("Failed to bind {}: {}", addr, e);
+                    std::process::exit(1);
+                }
+            };
+            log_listening(&config, scheme);
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .with_graceful_shutdown(state.shutdown.draining())
+            .await
+        }
+        (Bind::Tcp(addr), Some(tls)) => {
+            let handle = axum_server::Handle::new();
+            let shutdown_handle = handle.clone();
+            let draining = state.shutdown.draining();
+            tokio::spawn(async move {
+                draining.await;
+                shutdown_handle.graceful_shutdown(None);
+            });
+            log_listening(&config, scheme);
+            axum_server::bind_rustls(*addr, tls)
+                .handle(handle)
+                .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+                .await
+        }
+        #[cfg(unix)]
+        (Bind::Unix(path), _) => {
+            let listener = match listener::bind_unix(path, config.socket_mode) {
+                Ok(listener) => listener,
+                Err(e) => {
+                    error!("{:#}", e);
+                    std::process::exit(1);
+                }
+            };
+            log_listening(&config, scheme);
+            let served = axum::serve(listener, app.into_make_service())
+                .with_graceful_shutdown(state.shutdown.draining())
+                .await;
+            let _ = std::fs::remove_file(path);
+            served
+        }
+        #[cfg(not(unix))]
+        (Bind::Unix(_), _) => unreachable!("Unix sockets are rejected by Config::from_env"),
+    };
+    if let Err(e) = served {
+        error!("Server error: {}", e);
+    }
+
+    match drain.await {
+        Ok(report) => info!(
+            "Shutdown complete: {} runs finished, {} killed",
+            report.completed, report.killed
+        ),
+        Err(e) => error!("Shutdown drain task failed: {}", e),
+    }
+}
+
+/// Роутер сервера со всеми маршрутами, ограничениями и спецификацией OpenAPI
+fn build_app(config: &config::Config, state: Arc<app_state::AppState>) -> Router {
     // CORS
     let origins = std::env::var("ALLOWED_ORIGINS").ok();
     let (allow_origin, is_any) = if let Some(origins_str) = origins {
@@ -152,6 +576,7 @@ async fn main() {
             axum::http::Method::GET,
             axum::http::Method::POST,
             axum::http::Method::PUT,
+            axum::http::Method::PATCH,
             axum::http::Method::DELETE,
         ])
         .allow_headers([
@@ -159,22 +584,146 @@ async fn main() {
             axum::http::header::ACCESS_CONTROL_ALLOW_ORIGIN,
             axum::http::header::ACCEPT,
             axum::http::header::AUTHORIZATION,
+            axum::http::header::IF_MATCH,
+            axum::http::header::IF_NONE_MATCH,
+            request_id::REQUEST_ID_HEADER,
+            axum::http::HeaderName::from_static(idempotency::IDEMPOTENCY_KEY_HEADER),
+        ])
+        .expose_headers([
+            axum::http::header::ETAG,
+            request_id::REQUEST_ID_HEADER,
+            quota::QUOTA_REMAINING_HEADER,
+            axum::http::HeaderName::from_static(idempotency::REPLAYED_HEADER),
         ]);
 
     if !is_any && std::env::var("CORS_ALLOW_CREDENTIALS").as_deref() == Ok("true") {
         cors = cors.allow_credentials(true);
     }
 
-    let protected_routes = Router::new()
-        .route("/scripts", get(handlers::list_scripts).post(handlers::create_script))
-        .route("/scripts/{name}", get(handlers::get_script).put(handlers::update_script).delete(handlers::delete_script))
+    // Слишком большое тело отклоняется до разбора ответом 413 в формате ошибок API
+    let body_limit = |limit: Limit, max: usize| {
+        (
+            middleware::from_fn_with_state(BodyLimit { limit, max }, limits::limit_body),
+            DefaultBodyLimit::max(max),
+        )
+    };
+    let raw_body_limit = body_limit(Limit::RawBodyBytes, config.max_raw_body_bytes);
+    let script_body_limit = body_limit(
+        Limit::ScriptBytes,
+        limits::max_script_body_bytes(config.max_script_bytes),
+    );
+    // POST /scripts принимает и JSON, и multipart с несколькими файлами
+    let upload_limits = UploadLimits {
+        json: BodyLimit {
+            limit: Limit::ScriptBytes,
+            max: limits::max_script_body_bytes(config.max_script_bytes),
+        },
+        multipart: BodyLimit {
+            limit: Limit::RawBodyBytes,
+            max: config.max_raw_body_bytes,
+        },
+    };
+    let upload_body_limit = (
+        middleware::from_fn_with_state(upload_limits, limits::limit_upload_body),
+        DefaultBodyLimit::max(upload_limits.json.max.max(upload_limits.multipart.max)),
+    );
+
+    // gzip или br по Accept-Encoding; потоки SSE не сжимаются, чтобы события
+    // доходили сразу, а не копились в буфере компрессора
+    let compression = CompressionLayer::new()
+        .gzip(config.compression)
+        .br(config.compression)
+        .compress_when(
+            SizeAbove::new(config.compression_min_bytes)
+                .and(NotForContentType::GRPC)
+                .and(NotForContentType::SSE)
+                .and(NotForContentType::IMAGES),
+        );
+
+    // Маршруты запуска скриптов — под ограничением частоты по IP клиента
+    let run_routes = Router::new()
         .route("/run", post(handlers::run_scripts))
-        .route("/run/{name}", post(handlers::run_single_script))
-        .layer(middleware::from_fn(auth_middleware::auth_middleware));
+        .route("/run/batch", post(handlers::run_batch_entries))
+        .route("/pipeline", post(handlers::run_pipeline))
+        .route("/eval", post(handlers::eval_code))
+        .route("/run/{name}", get(handlers::run_single_script_get).post(handlers::run_single_script))
+        .route("/run/{name}/stream", post(handlers::run_single_script_stream))
+        .route("/run/{name}/async", post(handlers::run_single_script_async))
+        .layer(body_limit(Limit::RunBodyBytes, config.max_run_body_bytes))
+        // Добавлен после общего ограничения JSON-запросов: у него своё
+        .route(
+            "/run/{name}/raw",
+            post(handlers::run_single_script_raw).layer(raw_body_limit.clone()),
+        )
+        .layer(compression.clone())
+        .route_layer(middleware::from_fn_with_state(state.clone(), rate_limit::rate_limit));
+
+    let protected_routes = Router::new()
+        .route(
+            "/scripts",
+            get(handlers::list_scripts)
+                .merge(post(handlers::create_script).layer(upload_body_limit)),
+        )
+        .route("/scripts/export", get(handlers::export_scripts))
+        .route("/scripts/import", post(handlers::import_scripts).layer(raw_body_limit))
+        .route("/scripts/fetch", post(handlers::fetch_script))
+        .route("/scripts/sync", post(handlers::sync_scripts))
+        .route("/scripts/trash", get(handlers::list_trash))
+        .route("/scripts/trash/{id}/restore", post(handlers::restore_script))
+        .route(
+            "/scripts/{name}",
+            get(handlers::get_script)
+                .delete(handlers::delete_script)
+                .merge(put(handlers::update_script).layer(script_body_limit)),
+        )
+        .route("/scripts/{name}/versions", get(handlers::list_script_versions))
+        .route("/scripts/{name}/versions/{id}", get(handlers::get_script_version))
+        .route("/scripts/{name}/rollback/{id}", post(handlers::rollback_script))
+        .route("/scripts/{name}/copy", post(handlers::copy_script))
+        .route("/scripts/{name}/rename", post(handlers::rename_script))
+        .route("/scripts/{name}/tags", put(handlers::update_script_tags))
+        .route("/scripts/{name}/disable", post(handlers::disable_script))
+        .route("/scripts/{name}/enable", post(handlers::enable_script))
+        .route("/scripts/{name}/stats", get(handlers::get_script_stats).delete(handlers::reset_script_stats))
+        .route("/scripts/{name}/deps", get(handlers::get_script_deps))
+        .route("/scripts/{name}/deps/install", post(handlers::install_script_deps))
+        .route("/scripts/{name}/environment", get(handlers::get_script_environment))
+        .route("/scripts/{name}/interface", get(handlers::get_script_interface))
+        .route("/stats", get(handlers::get_stats))
+        .route("/limits", get(handlers::get_limits))
+        .route("/environment", get(handlers::get_environment))
+        .merge(run_routes)
+        .route("/runs/{run_id}/artifacts/{*filename}", get(handlers::get_run_artifact))
+        .route("/runs/{run_id}/log", get(handlers::get_run_log))
+        .route(
+            "/jobs/{id}",
+            get(handlers::get_job).layer(compression.clone()).delete(handlers::cancel_job),
+        )
+        .route(
+            "/history",
+            get(handlers::get_history).layer(compression).delete(handlers::clear_history),
+        )
+        .route("/audit", get(handlers::get_audit))
+        .route("/secrets", get(handlers::list_secrets))
+        .route("/secrets/{key}", put(handlers::put_secret).delete(handlers::delete_secret))
+        .route("/admin/config", get(handlers::get_admin_config).patch(handlers::patch_admin_config))
+        .route("/admin/namespaces", get(handlers::list_namespaces).post(handlers::create_namespace))
+        .route("/admin/namespaces/{name}", delete(handlers::delete_namespace))
+        .route("/admin/gc", post(handlers::run_gc))
+        .route("/events", get(handlers::get_events))
+        .route("/cache", delete(handlers::clear_cache))
+        .route("/cache/stats", get(handlers::cache_stats))
+        .route("/cache/{script}", delete(handlers::clear_script_cache))
+        .route("/schedules", get(handlers::list_schedules).post(handlers::create_schedule))
+        .route("/schedules/{id}", delete(handlers::delete_schedule))
+        .layer(middleware::from_fn_with_state(state.clone(), namespace::namespace_access))
+        .layer(middleware::from_fn_with_state(state.clone(), auth_middleware::auth_middleware));
 
     let public_routes = Router::new()
         .route("/register", post(handlers::register))
-        .route("/login", post(handlers::login));
+        .route("/login", post(handlers::login))
+        .route("/healthz", get(handlers::healthz))
+        .route("/readyz", get(handlers::readyz));
 
     // Создаём OpenApiRouter из обычного роутера (через .into())
     let (openapi_router, api) = OpenApiRouter::with_openapi(ApiDoc::openapi())
@@ -182,13 +731,246 @@ async fn main() {
         .merge(protected_routes.into())
         .split_for_parts();
 
+    // Спецификация доступна без авторизации: по адресу `/openapi.json` и для Swagger UI
+    let spec = api.clone();
     let app = openapi_router
+        .route("/openapi.json", get(move || async move { axum::Json(spec) }))
+        .route("/docs", get(|| async { Redirect::permanent("/swagger-ui/") }))
         .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", api))
         .layer(cors)
-        .with_state(state);
+        .layer(middleware::from_fn(request_id::request_id))
+        .with_state(state.clone());
+    // Вложенные имена (`/run/etl/extract.py`, `/run/etl/loader`) кодируются
+    // до маршрутизации
+    script_path::with_nested_names(app, state)
+}
+
+fn log_listening(config: &config::Config, scheme: &str) {
+    let address = match &config.bind {
+        Bind::Tcp(addr) => format!("{}://{}", scheme, addr),
+        bind => bind.to_string(),
+    };
+    info!(
+        "Server listening on {} (scripts dir: {}, max concurrent: {})",
+        address,
+        config.scripts_dir.display(),
+        config.max_concurrent
+    );
+}
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("Failed to listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestServer;
+    use axum::{
+        body::{to_bytes, Body},
+        http::{header, Request, StatusCode},
+    };
+    use auth_middleware::{ApiToken, TokenAccess};
+    use axum::response::Response;
+    use models::CreateNamespaceRequest;
+    use tower::ServiceExt;
+
+    // Токен с правом записи; `namespaces` — привязка к пространствам имён
+    fn api_token(token: &str, namespaces: Option<&[&str]>) -> ApiToken {
+        ApiToken {
+            token: token.to_string(),
+            access: TokenAccess::ReadWrite,
+            namespaces: namespaces.map(|names| names.iter().map(|name| name.to_string()).collect()),
+        }
+    }
+
+    async fn send(app: &Router, method: &str, uri: &str, token: &str) -> Response {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .body(Body::empty())
+            .unwrap();
+        app.clone().oneshot(request).await.unwrap()
+    }
 
-    let addr: SocketAddr = "0.0.0.0:3000".parse().unwrap();
-    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    info!("Server listening on http://{}", addr);
-    axum::serve(listener, app).await.unwrap();
-}
\ No newline at end of file
+    async fn create_namespace(server: &TestServer, name: &str) {
+        let request = CreateNamespaceRequest {
+            name: name.to_string(),
+            max_concurrent: None,
+            runs_per_hour: None,
+            exec_secs_per_hour: None,
+        };
+        let state = &server.state;
+        state.namespaces.create(&state.scripts_dir, request).await.expect("create namespace");
+    }
+
+    // Путь спецификации с подставленными значениями параметров: `{name}` → `x`
+    fn sample_path(path: &str) -> String {
+        path.split('/')
+            .map(|segment| if segment.starts_with('{') { "x" } else { segment })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    // Каждый путь и метод из /openapi.json должен найти маршрут. Запросы идут
+    // с токеном, иначе ответ 401 скрыл бы 405 неверного метода; обработчики
+    // работают с временной директорией сервера. Несуществующий маршрут и
+    // неверный метод дают ответ роутера с пустым телом, ошибки обработчиков — JSON.
+    #[tokio::test]
+    async fn openapi_lists_every_route() {
+        let server = TestServer::new(|config| config.api_tokens = vec![api_token("test-token", None)]).await;
+        let config = config::Config::from_env().expect("default config");
+        let app = build_app(&config, server.state.clone());
+
+        let response = app
+            .clone()
+            .oneshot(Request::get("/openapi.json").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let spec: serde_json::Value = serde_json::from_slice(&body).expect("valid JSON");
+        assert!(spec["openapi"].as_str().is_some_and(|v| v.starts_with("3.")), "{}", spec["openapi"]);
+        let paths = spec["paths"].as_object().expect("paths");
+        assert_eq!(paths.len(), ApiDoc::openapi().paths.paths.len());
+
+        let mut unrouted = Vec::new();
+        for (path, item) in paths {
+            for method in item.as_object().expect("path item").keys() {
+                let response = send(&app, &method.to_uppercase(), &sample_path(path), "test-token").await;
+                // Тело читается только у ошибок маршрутизации: у `/events` оно бесконечно
+                if !matches!(response.status(), StatusCode::NOT_FOUND | StatusCode::METHOD_NOT_ALLOWED) {
+                    continue;
+                }
+                let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                if body.is_empty() {
+                    unrouted.push(format!("{} {}", method, path));
+                }
+            }
+        }
+        assert!(unrouted.is_empty(), "documented but not routed: {:?}", unrouted);
+    }
+
+    #[tokio::test]
+    async fn large_cached_result_is_gzipped() {
+        let server = TestServer::new(|config| {
+            config.api_tokens = vec![ApiToken {
+                token: "test-token".to_string(),
+                access: TokenAccess::ReadWrite,
+                namespaces: None,
+            }];
+        })
+        .await;
+        server.add_script("big.py", "print('x' * 20000)\n").await;
+        let config = config::Config::from_env().expect("default config");
+        let app = build_app(&config, server.state.clone());
+        let run = |accept_encoding: Option<&str>| {
+            let mut request = Request::get("/run/big.py")
+                .header(header::AUTHORIZATION, "Bearer test-token");
+            if let Some(encoding) = accept_encoding {
+                request = request.header(header::ACCEPT_ENCODING, encoding);
+            }
+            app.clone().oneshot(request.body(Body::empty()).unwrap())
+        };
+
+        // Первый запуск кладёт результат в кэш
+        let plain = run(None).await.unwrap();
+        assert_eq!(plain.status(), StatusCode::OK);
+        assert!(plain.headers().get(header::CONTENT_ENCODING).is_none());
+        let body = to_bytes(plain.into_body(), usize::MAX).await.unwrap();
+        assert!(body.len() > 20000);
+
+        let gzipped = run(Some("gzip")).await.unwrap();
+        assert_eq!(gzipped.status(), StatusCode::OK);
+        assert!(gzipped.headers().contains_key(header::ETAG), "result is not from cache");
+        assert_eq!(gzipped.headers()[header::CONTENT_ENCODING], "gzip");
+        let body = to_bytes(gzipped.into_body(), usize::MAX).await.unwrap();
+        assert!(body.len() < 20000, "body is not compressed: {} bytes", body.len());
+    }
+
+    #[tokio::test]
+    async fn bound_token_cannot_reach_other_namespace_job() {
+        let server = TestServer::new(|config| {
+            config.api_tokens = vec![
+                api_token("admin-token", None),
+                api_token("team-a-token", Some(&["team-a"])),
+                api_token("team-b-token", Some(&["team-b"])),
+            ];
+        })
+        .await;
+        create_namespace(&server, "team-a").await;
+        create_namespace(&server, "team-b").await;
+        server.add_script("team-b/slow.py", "import time\ntime.sleep(30)\n").await;
+        let config = config::Config::from_env().expect("default config");
+        let app = build_app(&config, server.state.clone());
+
+        let response = send(&app, "POST", "/run/team-b/slow.py/async", "admin-token").await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let job: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let uri = format!("/jobs/{}", job["job_id"].as_str().expect("job_id"));
+
+        assert_eq!(send(&app, "GET", &uri, "team-a-token").await.status(), StatusCode::FORBIDDEN);
+        assert_eq!(send(&app, "DELETE", &uri, "team-a-token").await.status(), StatusCode::FORBIDDEN);
+        assert_eq!(send(&app, "GET", &uri, "team-b-token").await.status(), StatusCode::OK);
+        assert_eq!(send(&app, "DELETE", &uri, "team-b-token").await.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn bound_token_reads_only_own_namespace_logs() {
+        let server = TestServer::new(|config| {
+            config.api_tokens = vec![
+                api_token("admin-token", None),
+                api_token("team-a-token", Some(&["team-a"])),
+            ];
+        })
+        .await;
+        create_namespace(&server, "team-a").await;
+        create_namespace(&server, "team-b").await;
+        server.add_script("team-a/hello.py", "print('a')\n").await;
+        server.add_script("team-b/hello.py", "print('b')\n").await;
+        let config = config::Config::from_env().expect("default config");
+        let app = build_app(&config, server.state.clone());
+
+        let mut logs = Vec::new();
+        for script in ["team-a/hello.py", "team-b/hello.py"] {
+            let response = send(&app, "GET", &format!("/run/{}", script), "admin-token").await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let result: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            logs.push(format!("/runs/{}/log", result["run_id"].as_str().expect("run_id")));
+        }
+
+        let own = send(&app, "GET", &logs[0], "team-a-token").await;
+        assert_eq!(own.status(), StatusCode::OK);
+        let body = to_bytes(own.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).ends_with(" stdout a\n"), "{:?}", body);
+        assert_eq!(send(&app, "GET", &logs[1], "team-a-token").await.status(), StatusCode::FORBIDDEN);
+    }
+}
@@ -1,6 +1,10 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use indexmap::IndexMap;
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Duration,
+};
 use mongodb::bson::oid::ObjectId;
 use mongodb::bson::DateTime as BsonDateTime;
 use utoipa::{ToSchema, IntoParams};
@@ -9,6 +13,9 @@ use utoipa::{ToSchema, IntoParams};
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct ScriptMetadata {
     pub name: String,
+    /// `package` — директория пакета Python, запускаемая через `python -m`
+    #[serde(default)]
+    pub kind: ScriptKind,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub code: Option<String>,
     pub description: Option<String>,
@@ -16,15 +23,141 @@ pub struct ScriptMetadata {
     pub size: u64,
     pub created: DateTime<Utc>,
     pub modified: DateTime<Utc>,
+    /// Сведения о файле на диске (только при `detail=true`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<ScriptFileDetails>,
+    /// Откуда загружен скрипт (`/scripts/fetch`, `/scripts/sync`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub origin: Option<ScriptOrigin>,
+    /// Запуски отключены через `POST /scripts/{name}/disable`
+    #[serde(default)]
+    pub disabled: bool,
+}
+
+// Источник скрипта, загруженного по URL или из Git-репозитория.
+// Сбрасывается, когда код меняется через API.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ScriptOrigin {
+    /// URL файла или репозитория
+    pub url: String,
+    /// Ветка, тег или коммит, запрошенные при синхронизации
+    #[serde(rename = "ref", skip_serializing_if = "Option::is_none")]
+    pub git_ref: Option<String>,
+    /// Хеш коммита, из которого взят файл
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// Путь файла в репозитории
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    pub fetched: DateTime<Utc>,
+}
+
+// Вид скрипта: отдельный файл или пакет с `__main__.py`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ScriptKind {
+    #[default]
+    File,
+    Package,
+}
+
+// Сведения о файле скрипта, собранные с диска
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ScriptFileDetails {
+    pub size_bytes: u64,
+    pub modified: DateTime<Utc>,
+    pub line_count: usize,
+    /// Первая строка docstring или комментария в начале файла
+    pub description: Option<String>,
+    /// Настройки запуска из комментария `runner:` или файла `<имя>.toml`
+    pub config: ScriptConfig,
+    /// ETag содержимого для заголовка `If-Match`
+    pub etag: String,
+}
+
+// Настройки запуска конкретного скрипта; незаданные берутся из конфигурации сервера
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ScriptConfig {
+    /// Таймаут по умолчанию для этого скрипта (секунды)
+    pub timeout_secs: Option<u64>,
+    /// `false` — результаты скрипта никогда не кэшируются
+    pub cache: Option<bool>,
+    /// Команда интерпретатора вместо назначенной расширению
+    pub interpreter: Option<String>,
+    /// Сколько запусков скрипта выполняется одновременно; `0` — без ограничения
+    pub max_concurrent: Option<usize>,
+    /// Разбирать stdout как JSON в поле `output` результата
+    pub parse_output: Option<bool>,
+    /// Разбирать traceback Python из stderr в поле `error`
+    pub parse_traceback: Option<bool>,
+    /// Предел адресного пространства процесса (МиБ); `0` — без ограничения
+    pub memory_mb: Option<u64>,
+    /// Предел процессорного времени (секунды); `0` — без ограничения
+    pub cpu_secs: Option<u64>,
+    /// `false` — запускать процесс без доступа к сети
+    pub network: Option<bool>,
+    /// Файл точки входа пакета относительно его директории (только `<имя>.toml`)
+    pub entrypoint: Option<String>,
+    /// Теги для выбора скриптов в `/run?tags=` и `GET /scripts?tag=`
+    pub tags: Vec<String>,
+    /// Запуски скрипта отключены (`disabled = true` в `<имя>.toml`)
+    #[serde(default)]
+    pub disabled: bool,
+}
+
+// Ответ POST /scripts/{name}/disable и /enable
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ScriptAvailability {
+    pub name: String,
+    pub disabled: bool,
+}
+
+// Теги скрипта: тело и ответ PUT /scripts/{name}/tags
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ScriptTags {
+    pub tags: Vec<String>,
+}
+
+// Параметры получения одного скрипта
+#[derive(Debug, Deserialize, ToSchema, IntoParams)]
+pub struct GetScriptQuery {
+    /// `json` — вернуть данные скрипта в JSON вместо исходного кода
+    pub format: Option<String>,
+}
+
+// Параметры сохранения скрипта
+#[derive(Debug, Deserialize, ToSchema, IntoParams)]
+pub struct SaveScriptQuery {
+    /// `false` — не проверять синтаксис перед сохранением
+    pub validate: Option<bool>,
+}
+
+// Параметры удаления скрипта
+#[derive(Debug, Deserialize, ToSchema, IntoParams)]
+pub struct DeleteScriptQuery {
+    /// `true` — удалить поддиректории, оставшиеся пустыми после удаления скрипта
+    pub remove_empty_dirs: Option<bool>,
+    /// `true` — удалить файл сразу, минуя корзину
+    pub permanent: Option<bool>,
 }
 
 // Запрос на создание скрипта
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct CreateScriptRequest {
+    /// Имя файла, может включать поддиректории: `etl/extract.py`
     pub name: String,
     pub code: String,
     pub description: Option<String>,
     pub result: Option<String>,
+    /// `true` — заменить существующий скрипт вместо ошибки 409
+    pub overwrite: Option<bool>,
+}
+
+// Новое имя для копии или переименования скрипта
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ScriptTargetRequest {
+    /// Имя файла, может включать поддиректории: `etl/report_v2.py`
+    pub new_name: String,
 }
 
 // Запрос на обновление скрипта
@@ -36,28 +169,1315 @@ pub struct UpdateScriptRequest {
 }
 
 // Запрос на выполнение
-#[derive(Debug, Deserialize, ToSchema)]
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
 pub struct RunRequest {
-    pub data: serde_json::Value,
+    /// Данные для stdin в виде JSON; без них (или с `null`) stdin пуст
+    pub data: Option<serde_json::Value>,
+    /// Вместо `data`: stdin — stdout прошлого запуска
+    pub input_from: Option<InputFrom>,
     pub args: Option<Vec<String>>,
+    /// Таймаут выполнения в секундах (ограничен серверным максимумом)
+    pub timeout_secs: Option<u64>,
+    /// Сколько ждать свободного слота в секундах, не больше `RUNNER_MAX_QUEUE_WAIT_SECS`;
+    /// время в очереди не входит в `timeout_secs`
+    pub queue_timeout_secs: Option<u64>,
+    /// `false` — выполнить заново, не читая и не записывая кэш
+    pub cache: Option<bool>,
+    /// Переменные окружения, добавляемые к окружению сервера
+    pub env: Option<HashMap<String, String>>,
+    /// `true` — не удалять рабочую директорию после запуска и вернуть её путь
+    pub keep_workdir: Option<bool>,
+    /// Пакетный запуск: выполнять скрипты по одному в порядке `names`
+    pub sequential: Option<bool>,
+    /// Пакетный запуск: не запускать следующие скрипты после первой ошибки
+    pub fail_fast: Option<bool>,
+    /// Адрес, на который после завершения запуска отправляется POST с результатом
+    pub callback_url: Option<String>,
+    /// `true` — по таймауту вернуть 200 с `timed_out` и выводом до остановки вместо 504
+    pub partial_on_timeout: Option<bool>,
+    /// Интерпретатор из `RUNNER_ALLOWED_INTERPRETERS` вместо назначенного скрипту
+    pub interpreter: Option<String>,
+    /// `true` — разобрать stdout как JSON в поле `output` (по умолчанию — по настройкам скрипта)
+    pub parse_output: Option<bool>,
+    /// `true` — не возвращать stdout, если он разобран в `output`
+    pub omit_stdout: Option<bool>,
+    /// `true` — разобрать traceback Python из stderr в поле `error`
+    /// (по умолчанию — по настройкам скрипта)
+    pub parse_traceback: Option<bool>,
+    /// `false` — запустить процесс без доступа к сети (по умолчанию — по настройкам скрипта)
+    pub network: Option<bool>,
+    /// Секреты из `/secrets`, передаваемые скрипту как переменные окружения
+    /// с теми же именами; их значения в выводе заменяются на `***`
+    pub secrets: Option<Vec<String>>,
+    /// Только `/run/{name}/async`: `true` — запустить задачу заново, если сервер
+    /// перезапустился во время её выполнения
+    pub retry_on_restart: Option<bool>,
+    /// Только `POST /run/{name}`: SHA-256 скрипта из прошлого результата
+    /// (`script_hash`); если скрипт с тех пор не менялся — 304 без запуска
+    pub if_script_changed_since: Option<String>,
+}
+
+/// Запуск, stdout которого передаётся в stdin: `{"run_id": "..."}` или
+/// `{"script": "extract.py", "latest": true}` — последний успешный запуск скрипта
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum InputFrom {
+    Run { run_id: String },
+    Latest { script: String, latest: bool },
+}
+
+// Запись пакетного запуска: свои аргументы и данные для каждого скрипта
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BatchEntry {
+    /// Ключ результата в ответе; по умолчанию — индекс записи
+    pub id: Option<String>,
+    pub name: String,
+    pub data: Option<serde_json::Value>,
+    pub args: Option<Vec<String>>,
+    pub env: Option<HashMap<String, String>>,
+    pub timeout_secs: Option<u64>,
+    pub interpreter: Option<String>,
+}
+
+// Запрос на пакетный запуск
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BatchRunRequest {
+    pub entries: Vec<BatchEntry>,
+    /// Таймаут по умолчанию для записей без собственного
+    pub timeout_secs: Option<u64>,
+    pub cache: Option<bool>,
+    pub sequential: Option<bool>,
+    pub fail_fast: Option<bool>,
+    pub partial_on_timeout: Option<bool>,
+}
+
+// Шаг пайплайна: скрипт и его аргументы
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PipelineStep {
+    pub name: String,
+    pub args: Option<Vec<String>>,
+    pub env: Option<HashMap<String, String>>,
+    /// Таймаут шага; ограничен остатком общего таймаута пайплайна
+    pub timeout_secs: Option<u64>,
+}
+
+// Запрос на запуск пайплайна
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PipelineRequest {
+    pub steps: Vec<PipelineStep>,
+    /// stdin первого шага
+    pub data: Option<serde_json::Value>,
+    /// Общий таймаут на все шаги
+    pub timeout_secs: Option<u64>,
+    pub cache: Option<bool>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PipelineStepResult {
+    pub script: String,
+    pub result: ScriptResult,
+}
+
+// Результаты выполненных шагов; шаги после неудачного не запускаются
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PipelineResponse {
+    pub steps: Vec<PipelineStepResult>,
+    /// Индекс шага, на котором пайплайн остановился
+    pub failed_step: Option<usize>,
+    /// Пайплайн остановлен по общему таймауту
+    pub timed_out: bool,
+}
+
+// Запрос на выполнение кода без сохранения скрипта
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct EvalRequest {
+    pub code: String,
+    /// Расширение, по которому выбирается интерпретатор; по умолчанию `py`
+    pub language: Option<String>,
+    pub args: Option<Vec<String>>,
+    pub data: Option<serde_json::Value>,
+    pub env: Option<HashMap<String, String>>,
+    pub timeout_secs: Option<u64>,
+    pub partial_on_timeout: Option<bool>,
+    /// `false` — выполнить код без доступа к сети
+    pub network: Option<bool>,
+}
+
+// Сохранённая предыдущая версия скрипта
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ScriptVersion {
+    pub id: u64,
+    pub size_bytes: u64,
+    /// Время, когда содержимое этой версии было записано
+    pub modified: DateTime<Utc>,
+}
+
+// Удалённый скрипт в корзине
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TrashItem {
+    /// Идентификатор для восстановления
+    pub id: String,
+    /// Имя, под которым скрипт будет восстановлен
+    pub name: String,
+    pub deleted_at: DateTime<Utc>,
+    pub size_bytes: u64,
+}
+
+// Секрет без значения
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SecretInfo {
+    pub name: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+// Значение секрета
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SecretValueRequest {
+    pub value: String,
+}
+
+// Что делать при импорте файла, который уже существует
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportMode {
+    /// Оставить существующий скрипт
+    #[default]
+    Skip,
+    /// Заменить (прежнее содержимое сохраняется как версия)
+    Overwrite,
+    /// Отклонить весь импорт, ничего не записывая
+    Fail,
+}
+
+// Параметры импорта скриптов из архива
+#[derive(Debug, Deserialize, ToSchema, IntoParams)]
+pub struct ImportQuery {
+    /// `skip` (по умолчанию), `overwrite` или `fail`
+    pub mode: Option<ImportMode>,
+}
+
+// Что произошло с файлом при импорте
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportStatus {
+    Created,
+    Overwritten,
+    Skipped,
+    Invalid,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImportFileResult {
+    pub name: String,
+    pub status: ImportStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImportResponse {
+    pub files: Vec<ImportFileResult>,
+}
+
+// Итог загрузки одного файла через multipart `POST /scripts`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UploadStatus {
+    Created,
+    Overwritten,
+    /// Скрипт уже существует, а `overwrite` не передан
+    Skipped,
+    Error,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UploadFileResult {
+    pub name: String,
+    pub status: UploadStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorBody>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UploadResponse {
+    pub files: Vec<UploadFileResult>,
+}
+
+// Загрузка скрипта по URL
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct FetchScriptRequest {
+    /// Имя файла, может включать поддиректории: `etl/extract.py`
+    pub name: String,
+    /// HTTPS-адрес файла; хост должен входить в `RUNNER_FETCH_HOSTS`
+    pub url: String,
+    pub description: Option<String>,
+    /// `true` — заменить существующий скрипт вместо ошибки 409
+    pub overwrite: Option<bool>,
+}
+
+// Синхронизация скриптов из Git-репозитория
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SyncScriptsRequest {
+    /// HTTPS-адрес репозитория; хост должен входить в `RUNNER_FETCH_HOSTS`
+    pub git_url: String,
+    /// Ветка, тег или коммит; по умолчанию — ветка по умолчанию
+    #[serde(rename = "ref")]
+    pub git_ref: Option<String>,
+    /// Поддиректория репозитория со скриптами; по умолчанию — корень
+    pub subdir: Option<String>,
+}
+
+// Что произошло с файлом при синхронизации
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncStatus {
+    Created,
+    Updated,
+    Unchanged,
+    Invalid,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SyncFileResult {
+    pub name: String,
+    pub status: SyncStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SyncResponse {
+    /// Хеш коммита, из которого взяты файлы
+    pub commit: String,
+    pub files: Vec<SyncFileResult>,
 }
 
 #[derive(Debug, Deserialize, ToSchema, IntoParams)]
 pub struct RunQuery {
+    /// Имена или glob-шаблоны через запятую: `report_*.py,etl/*.py`
     pub names: Option<String>,
+    /// Имена или glob-шаблоны через запятую; подходящие скрипты не запускаются
+    pub exclude: Option<String>,
+    /// Теги через запятую; скрипты с ними добавляются к `names`
+    pub tags: Option<String>,
+    /// `any` — скрипт с любым из тегов (по умолчанию), `all` — со всеми
+    pub tag_match: Option<TagMatch>,
+    /// `true` — прежний формат `results` (ошибки в виде `ScriptResult`)
+    pub legacy: Option<bool>,
+    /// `true` — запустить все скрипты; без `names`, `tags` и этого флага запрос отклоняется
+    pub all: Option<bool>,
 }
 
-#[derive(Debug, Serialize, Clone, ToSchema)]
+// Параметры пакетного запуска `/run/batch`
+#[derive(Debug, Deserialize, ToSchema, IntoParams)]
+pub struct BatchQuery {
+    /// `true` — прежний формат `results` (ошибки в виде `ScriptResult`)
+    pub legacy: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TagMatch {
+    #[default]
+    Any,
+    All,
+}
+
+// Тело ответа об ошибке
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ErrorResponse {
+    pub error: ErrorBody,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ErrorBody {
+    /// Машиночитаемый код: `script_not_found`, `timeout`, ...
+    pub code: String,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub script: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<String>,
+    /// Превышенное ограничение: `max_args`, `max_arg_bytes`, `max_data_bytes`, ...
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<String>,
+    /// Размер отклонённого тела запроса или кода скрипта (байты)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<usize>,
+    /// Исчерпанный бюджет времени: `queue` — ожидание слота, `exec` — выполнение
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub budget: Option<TimeoutBudget>,
+    /// Вывод, полученный до отмены выполнения
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<ScriptResult>,
+    /// Исчерпанная квота: расход и ограничения
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quota: Option<QuotaUsage>,
+}
+
+// Кодировка stdout/stderr в ответе: base64, если вывод не является корректным UTF-8
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputEncoding {
+    #[default]
+    Utf8,
+    Base64,
+}
+
+/// Файл-результат запуска, сохранённый на `RUNNER_ARTIFACT_RETENTION_SECS`
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Artifact {
+    /// Путь относительно `./artifacts` (или прежней `./output`)
+    pub name: String,
+    /// Размер в байтах
+    pub size: u64,
+    /// Адрес для скачивания; у результата из кэша — адрес исходного запуска
+    pub url: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, ToSchema)]
 pub struct ScriptResult {
     pub stdout: String,
     pub stderr: String,
     pub exit_code: i32,
     pub timed_out: bool,
+    /// Как завершился процесс: сам, по сигналу или по таймауту
+    pub termination: Termination,
+    /// Номер сигнала, завершившего процесс (только Unix)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signal: Option<i32>,
+    pub stdout_encoding: OutputEncoding,
+    pub stderr_encoding: OutputEncoding,
+    /// Вывод обрезан по `RUNNER_MAX_OUTPUT_BYTES`, процесс остановлен
+    pub stdout_truncated: bool,
+    pub stderr_truncated: bool,
+    /// Файлы, оставленные скриптом в `./artifacts` (или `./output`) рабочей директории
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub artifacts: Vec<Artifact>,
+    /// Путь сохранённой рабочей директории (только при `keep_workdir`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workdir: Option<String>,
+    /// Время выполнения процесса; для ответа из кэша — исходного запуска
+    pub duration_ms: u64,
+    /// Сколько запуск ждал свободного слота; для ответа из кэша — 0
+    #[serde(default)]
+    pub queued_ms: u64,
+    /// Исчерпанный бюджет времени (`exec` при таймауте выполнения)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_budget: Option<TimeoutBudget>,
+    /// Начало и конец выполнения (RFC 3339); нет, если скрипт не запускался
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    /// Результат взят из кэша
+    pub cached: bool,
+    /// Идентификатор запуска, по которому находятся строки лога
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<String>,
+    /// SHA-256 выполненной версии скрипта (совпадает с его ETag без кавычек)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub script_hash: Option<String>,
+    /// stdout, разобранный как JSON (при `parse_output`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<serde_json::Value>,
+    /// Почему stdout не удалось разобрать как JSON
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_parse_error: Option<String>,
+    /// Последний traceback Python из stderr (при `parse_traceback`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<PythonError>,
+    /// Только при `network: false`: `true` — процесс выполнялся без доступа к сети,
+    /// `false` — изоляция недоступна (`RUNNER_NETWORK_ISOLATION_FALLBACK=warn`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network_isolated: Option<bool>,
+    /// Процесс выполнялся в песочнице `RUNNER_SANDBOX`
+    #[serde(default)]
+    pub sandboxed: bool,
+    /// Чем и с какими аргументами выполнен скрипт; у ответа из кэша и
+    /// ожидавшего одинаковый запуск — по параметрам этого запроса
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invocation: Option<Invocation>,
+    /// Процесс остановлен ограничением памяти или процессорного времени
+    /// либо, по всей видимости, нехваткой памяти в системе
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub killed_reason: Option<KilledReason>,
+    /// Для ответа из кэша: заголовки ETag и Cache-Control
+    #[serde(skip)]
+    pub freshness: Option<Freshness>,
+    /// Остаток квот после запуска: заголовок X-Quota-Remaining
+    #[serde(skip)]
+    pub quota_remaining: Option<QuotaRemaining>,
+    /// Объединённый лог stdout и stderr доступен в `GET /runs/{run_id}/log`
+    #[serde(default)]
+    pub log_available: bool,
+}
+
+/// Поток, в который скрипт записал строку
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+}
+
+impl LogStream {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogStream::Stdout => "stdout",
+            LogStream::Stderr => "stderr",
+        }
+    }
+}
+
+/// Строка объединённого лога запуска
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct LogLine {
+    /// Когда сервер прочитал строку
+    pub ts: DateTime<Utc>,
+    pub stream: LogStream,
+    pub line: String,
+}
+
+// Параметры GET /runs/{run_id}/log
+#[derive(Debug, Deserialize, ToSchema, IntoParams)]
+pub struct RunLogQuery {
+    /// `text` (по умолчанию) — строки с временем и потоком, `jsonl` — объект `LogLine` в строке
+    pub format: Option<String>,
+}
+
+/// Команда, которой выполнен скрипт, — чтобы повторить запуск вручную.
+/// Переменные окружения и секреты в неё не входят.
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct Invocation {
+    /// Команда интерпретатора с путём, найденным в `PATH` (`/usr/bin/python3 -u`)
+    pub interpreter: String,
+    /// Скрипт или директория пакета относительно `RUNNER_SCRIPTS_DIR`
+    pub script: String,
+    /// Аргументы после подстановок
+    pub args: Vec<String>,
+    /// Таймаут выполнения
+    pub timeout_secs: u64,
+    /// Запуск в песочнице `RUNNER_SANDBOX`
+    pub sandbox: bool,
+    /// Интерпретатор из виртуального окружения скрипта с requirements
+    pub venv: bool,
+    /// Скрипт выполнил воркер пула `RUNNER_POOL_SIZE`
+    pub pooled: bool,
+}
+
+/// Исключение Python, завершившее скрипт
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct PythonError {
+    /// Класс исключения, например `ValueError` или `json.decoder.JSONDecodeError`
+    #[serde(rename = "type")]
+    pub error_type: String,
+    pub message: String,
+    /// Файл и строка, где возникло исключение (последний кадр)
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    /// Кадры стека от внешнего вызова к месту исключения
+    pub frames: Vec<TracebackFrame>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct TracebackFrame {
+    pub file: String,
+    pub line: u32,
+    /// Нет у кадров SyntaxError
+    pub function: Option<String>,
+    /// Строка исходного кода, если Python её вывел
+    pub code: Option<String>,
+}
+
+/// Срок годности результата из кэша
+#[derive(Debug, Clone)]
+pub struct Freshness {
+    /// Не меняется, пока запись кэша действительна
+    pub etag: String,
+    /// Сколько ещё запись проживёт в кэше (`RUNNER_CACHE_TTL_SECS`)
+    pub max_age: Duration,
+}
+
+/// Остаток квот запуска в текущем окне; `None` — ограничения нет
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QuotaRemaining {
+    pub runs: Option<u64>,
+    pub exec_secs: Option<u64>,
+}
+
+// Чей расход ограничивает квота
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaScope {
+    Script,
+    /// API-токен или пользователь JWT
+    Caller,
+    /// Пространство имён скрипта
+    Namespace,
+}
+
+// Расход квоты скрипта или вызывающего за последний час
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct QuotaUsage {
+    pub scope: QuotaScope,
+    /// Имя скрипта, пространства имён или вызывающего: `user:<имя>`,
+    /// `token:<начало SHA-256 токена>`
+    pub subject: String,
+    pub runs: u64,
+    pub exec_secs: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_runs_per_hour: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_exec_secs_per_hour: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remaining_runs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remaining_exec_secs: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Termination {
+    #[default]
+    Exited,
+    Signaled,
+    TimedOut,
+}
+
+// Бюджет времени запуска: ожидание слота (`queue_timeout_secs`)
+// или выполнение процесса (`timeout_secs`)
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeoutBudget {
+    Queue,
+    Exec,
+}
+
+// Данные события `exit` потока `/run/{name}/stream`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StreamExit {
+    pub exit_code: i32,
+    pub timed_out: bool,
+    pub signal: Option<i32>,
+    pub killed_reason: Option<KilledReason>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum KilledReason {
+    MemoryLimit,
+    CpuLimit,
+    OutOfMemory,
+}
+
+// Результат скрипта в пакетном запуске: `status` различает завершившийся
+// процесс (с любым кодом возврата), ошибку запуска, таймаут и пропуск
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum BatchItem {
+    Ok {
+        result: Box<ScriptResult>,
+    },
+    Error {
+        error: ErrorBody,
+    },
+    /// Таймаут выполнения или ожидания слота; `result` — вывод до остановки
+    /// при `partial_on_timeout`
+    Timeout {
+        error: ErrorBody,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        result: Option<Box<ScriptResult>>,
+    },
+    /// Не запускался: fail_fast после ошибки предыдущего или скрипт отключён
+    Skipped {
+        reason: String,
+    },
+}
+
+// Число результатов пакета по статусам
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct BatchSummary {
+    pub ok: usize,
+    pub error: usize,
+    pub timeout: usize,
+    pub skipped: usize,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
 pub struct RunResponse {
-    pub results: HashMap<String, ScriptResult>,
+    /// Порядок ключей совпадает с порядком скриптов в запросе
+    #[schema(value_type = HashMap<String, BatchItem>)]
+    pub results: IndexMap<String, BatchItem>,
+    pub summary: BatchSummary,
+    /// Скрипты, подошедшие под каждый glob-шаблон из `names` (до `exclude`)
+    #[serde(skip_serializing_if = "IndexMap::is_empty")]
+    #[schema(value_type = HashMap<String, Vec<String>>)]
+    pub expanded: IndexMap<String, Vec<String>>,
+}
+
+// Результат скрипта в прежнем формате (`?legacy=true`)
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum BatchResult {
+    Completed(Box<ScriptResult>),
+    Skipped(SkippedResult),
+}
+
+// Скрипт не запускался: fail_fast после ошибки предыдущего или скрипт отключён
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SkippedResult {
+    pub skipped: bool,
+    pub reason: String,
+}
+
+// Ответ пакетного запуска в прежнем формате (`?legacy=true`)
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LegacyRunResponse {
+    #[schema(value_type = HashMap<String, BatchResult>)]
+    pub results: IndexMap<String, BatchResult>,
+    #[serde(skip_serializing_if = "IndexMap::is_empty")]
+    #[schema(value_type = HashMap<String, Vec<String>>)]
+    pub expanded: IndexMap<String, Vec<String>>,
+}
+
+// Статус асинхронной задачи
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Finished,
+    Failed,
+    TimedOut,
+    Cancelled,
+    /// Сервер перезапустился во время выполнения
+    Interrupted,
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Finished => "finished",
+            JobStatus::Failed => "failed",
+            JobStatus::TimedOut => "timed_out",
+            JobStatus::Cancelled => "cancelled",
+            JobStatus::Interrupted => "interrupted",
+        }
+    }
+}
+
+// Параметры GET /jobs/{id}
+#[derive(Debug, Deserialize, ToSchema, IntoParams)]
+pub struct JobQuery {
+    /// Сколько секунд ждать завершения задачи (не больше `RUNNER_MAX_JOB_WAIT_SECS`)
+    pub wait: Option<u64>,
+}
+
+// Асинхронная задача выполнения скрипта
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct JobInfo {
+    pub job_id: String,
+    pub script: String,
+    pub status: JobStatus,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub result: Option<ScriptResult>,
+    pub error: Option<String>,
+}
+
+// Состояние виртуального окружения скрипта с requirements.txt
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DepsState {
+    /// У скрипта нет файла зависимостей
+    None,
+    /// Окружение ещё не собиралось
+    Missing,
+    Installing,
+    Ready,
+    /// Окружение собрано для прежней версии requirements
+    Outdated,
+    Failed,
+}
+
+// Зависимости скрипта и журнал последней установки
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct DepsStatus {
+    /// Файл зависимостей относительно директории скриптов
+    pub requirements: Option<String>,
+    pub state: DepsState,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub error: Option<String>,
+    /// Последние строки вывода `venv` и `pip`
+    pub log: Vec<String>,
+}
+
+// Окружение, в котором выполняются Python-скрипты (GET /environment)
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct EnvironmentReport {
+    /// Команда интерпретатора, которой выполнена проверка (`python3 -u`)
+    pub interpreter: String,
+    /// Путь к исполняемому файлу (`sys.executable`)
+    pub executable: Option<String>,
+    /// Версия Python
+    pub version: Option<String>,
+    /// Платформа (`platform.platform()`)
+    pub platform: Option<String>,
+    /// Виртуальное окружение скрипта относительно директории скриптов
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub venv: Option<String>,
+    /// Установленные пакеты, по имени без учёта регистра
+    pub packages: Vec<InstalledPackage>,
+    /// Переменные окружения интерпретатора без похожих на секреты
+    pub env: BTreeMap<String, String>,
+    pub probed_at: DateTime<Utc>,
+    /// Отчёт взят из кэша (обновляется `?refresh=true`)
+    pub cached: bool,
+    /// Что не удалось узнать; остальные поля заполнены насколько возможно
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct InstalledPackage {
+    pub name: String,
+    pub version: String,
+}
+
+// Параметры GET /environment
+#[derive(Debug, Deserialize, ToSchema, IntoParams)]
+pub struct EnvironmentQuery {
+    /// `true` — выполнить проверку заново, не используя кэш
+    pub refresh: Option<bool>,
+}
+
+// Интерфейс Python-скрипта, извлечённый разбором кода без выполнения
+#[derive(Debug, Serialize, Deserialize, Clone, Default, ToSchema)]
+pub struct ScriptInterface {
+    /// Докстринг модуля (у пакета — файла точки входа)
+    pub docstring: Option<String>,
+    /// `description` из `argparse.ArgumentParser`
+    pub description: Option<String>,
+    /// Импортирует ли скрипт `argparse`
+    pub argparse: bool,
+    /// Аргументы из вызовов `add_argument` в порядке объявления
+    pub arguments: Vec<ScriptArgument>,
+    /// Почему интерфейс извлечён не полностью (синтаксическая ошибка, тайм-аут разбора)
+    pub error: Option<String>,
+}
+
+// Аргумент командной строки, объявленный через add_argument
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct ScriptArgument {
+    /// Имя атрибута (`dest`) или позиционного аргумента
+    pub name: String,
+    /// Флаги опции (`-n`, `--limit`); у позиционного аргумента пусто
+    pub flags: Vec<String>,
+    pub positional: bool,
+    /// `type` как в коде: `int`, `pathlib.Path`
+    #[serde(rename = "type")]
+    pub type_name: Option<String>,
+    /// Значение по умолчанию, если оно задано литералом
+    pub default: Option<serde_json::Value>,
+    pub help: Option<String>,
+    pub required: bool,
+    pub choices: Option<Vec<serde_json::Value>>,
+    pub action: Option<String>,
+    pub nargs: Option<String>,
+    pub metavar: Option<String>,
+}
+
+// Параметры установки зависимостей
+#[derive(Debug, Deserialize, ToSchema, IntoParams)]
+pub struct InstallDepsQuery {
+    /// `true` — пересобрать окружение, даже если оно актуально
+    pub force: Option<bool>,
+}
+
+// Что делать, если время расписания наступило, а предыдущий запуск ещё идёт
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum OverlapPolicy {
+    /// Пропустить запуск
+    #[default]
+    Skip,
+    /// Запустить после завершения предыдущего
+    Queue,
+}
+
+// Запрос на создание расписания
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateScheduleRequest {
+    pub script: String,
+    /// Cron-выражение из пяти полей (UTC): `*/5 * * * *`
+    pub cron: String,
+    pub args: Option<Vec<String>>,
+    pub data: Option<serde_json::Value>,
+    pub env: Option<HashMap<String, String>>,
+    pub timeout_secs: Option<u64>,
+    pub overlap: Option<OverlapPolicy>,
+}
+
+// Расписание запуска скрипта; сохраняется в `.schedules.json`
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct Schedule {
+    pub id: String,
+    pub script: String,
+    pub cron: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    pub data: Option<serde_json::Value>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    pub timeout_secs: Option<u64>,
+    #[serde(default)]
+    pub overlap: OverlapPolicy,
+    pub created_at: DateTime<Utc>,
+    /// Следующий запуск по расписанию
+    #[serde(default)]
+    pub next_run: Option<DateTime<Utc>>,
+    /// Итог последнего завершённого запуска
+    #[serde(default)]
+    pub last_run: Option<ScheduleRun>,
+    /// Запуски, пропущенные из-за ещё идущего предыдущего (`overlap: skip`)
+    #[serde(default)]
+    pub skipped: u64,
+}
+
+// Итог запуска по расписанию
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct ScheduleRun {
+    pub run_id: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub status: RunStatus,
+    pub exit_code: Option<i32>,
+    pub error: Option<String>,
+}
+
+// Ограничения запроса на запуск
+#[derive(Debug, Serialize, Clone, Copy, ToSchema)]
+pub struct RunLimits {
+    /// Наибольшее число аргументов
+    pub max_args: usize,
+    /// Наибольшая длина одного аргумента (байты)
+    pub max_arg_bytes: usize,
+    /// Наибольший размер сериализованного `data` (байты)
+    pub max_data_bytes: usize,
+    /// Наибольшее число скриптов в одном пакетном запуске
+    pub max_batch_scripts: usize,
+}
+
+// Ограничения сервера для клиентов (GET /limits)
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LimitsResponse {
+    pub max_args: usize,
+    pub max_arg_bytes: usize,
+    pub max_data_bytes: usize,
+    /// Наибольшее число скриптов в `/run` и записей в `/run/batch`
+    pub max_batch_scripts: usize,
+    /// Наибольшее тело запроса для `/run/{name}/raw` (байты)
+    pub max_raw_body_bytes: usize,
+    /// Наибольший код скрипта в `POST /scripts` и `PUT /scripts/{name}` (байты)
+    pub max_script_bytes: usize,
+    /// Наибольшее тело JSON-запроса на запуск (байты)
+    pub max_run_body_bytes: usize,
+    /// Наибольший объём stdout и stderr запуска (байты)
+    pub max_output_bytes: usize,
+    pub default_timeout_secs: u64,
+    pub max_timeout_secs: u64,
+    /// Скрипты нельзя создавать, изменять и удалять через API (`RUNNER_READ_ONLY`)
+    pub read_only: bool,
+}
+
+// Настройки, которые меняются без перезапуска (GET и PATCH /admin/config)
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AdminConfig {
+    /// Срок жизни результатов в кэше (`RUNNER_CACHE_TTL_SECS`)
+    pub cache_ttl_secs: u64,
+    /// Таймаут запуска по умолчанию (`RUNNER_SCRIPT_TIMEOUT_SECS`)
+    pub default_timeout_secs: u64,
+    /// Лимит каждого из потоков stdout и stderr (`RUNNER_MAX_OUTPUT_BYTES`)
+    pub max_output_bytes: usize,
+    /// Запусков в минуту с одного IP (`RUNNER_RATE_LIMIT`); `0` — без ограничения
+    pub rate_limit: u32,
+    pub rate_burst: u32,
+    /// Слотов одновременных запусков (`RUNNER_MAX_CONCURRENT`)
+    pub max_concurrent: usize,
+}
+
+// Изменение настроек; отсутствующие поля не меняются
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AdminConfigPatch {
+    pub cache_ttl_secs: Option<u64>,
+    /// От 1 до `RUNNER_MAX_SCRIPT_TIMEOUT_SECS`
+    pub default_timeout_secs: Option<u64>,
+    /// Больше нуля
+    pub max_output_bytes: Option<usize>,
+    pub rate_limit: Option<u32>,
+    pub rate_burst: Option<u32>,
+    /// Больше нуля; при уменьшении лишние слоты освобождаются по мере завершения запусков
+    pub max_concurrent: Option<usize>,
+}
+
+// Пространство имён и его ограничения (GET и POST /admin/namespaces)
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NamespaceInfo {
+    pub name: String,
+    /// Слотов одновременных запусков; нет — без ограничения
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_concurrent: Option<usize>,
+    /// Выполняющиеся сейчас запуски (только при `max_concurrent`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub running: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub runs_per_hour: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exec_secs_per_hour: Option<u64>,
+}
+
+// Создание пространства имён; отсутствующие ограничения берутся из `RUNNER_NAMESPACE_*`
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct CreateNamespaceRequest {
+    /// Латинские буквы, цифры, `-` и `_`; это же имя у директории пространства
+    pub name: String,
+    /// `0` — без ограничения
+    pub max_concurrent: Option<usize>,
+    /// `0` — без ограничения
+    pub runs_per_hour: Option<u64>,
+    /// `0` — без ограничения
+    pub exec_secs_per_hour: Option<u64>,
+}
+
+// Результат одной проверки готовности
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct HealthCheck {
+    pub name: String,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// Ответ /readyz
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReadinessReport {
+    pub status: String,
+    pub checks: Vec<HealthCheck>,
+}
+
+// Итог запуска в истории выполнения
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Success,
+    Failed,
+    TimedOut,
+    Cancelled,
+    Error,
+}
+
+impl RunStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RunStatus::Success => "success",
+            RunStatus::Failed => "failed",
+            RunStatus::TimedOut => "timed_out",
+            RunStatus::Cancelled => "cancelled",
+            RunStatus::Error => "error",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "success" => Some(RunStatus::Success),
+            "failed" => Some(RunStatus::Failed),
+            "timed_out" => Some(RunStatus::TimedOut),
+            "cancelled" => Some(RunStatus::Cancelled),
+            "error" => Some(RunStatus::Error),
+            _ => None,
+        }
+    }
+}
+
+// Запись истории выполнения
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct HistoryEntry {
+    pub run_id: String,
+    pub script: String,
+    pub args_hash: String,
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    pub status: RunStatus,
+    pub exit_code: Option<i32>,
+    pub timed_out: bool,
+    pub cached: bool,
+    // Начало stderr (обрезается)
+    pub stderr: String,
+    pub error: Option<String>,
+    /// Команда запуска; нет у ответа из кэша и у запуска, не дошедшего до процесса
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invocation: Option<Invocation>,
+}
+
+// Событие сервера в потоке `GET /events`
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ServerEvent {
+    pub time: DateTime<Utc>,
+    #[serde(flatten)]
+    pub data: EventData,
+}
+
+// Содержимое события; `type` совпадает с именем события SSE
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventData {
+    RunStarted {
+        script: String,
+        run_id: String,
+        /// Число аргументов запуска
+        args: usize,
+    },
+    RunFinished {
+        script: String,
+        run_id: String,
+        /// Нет, если скрипт не удалось запустить
+        exit_code: Option<i32>,
+        duration_ms: u64,
+        cached: bool,
+        error: Option<String>,
+    },
+    RunTimedOut {
+        script: String,
+        run_id: String,
+        duration_ms: u64,
+    },
+    /// Изменение скрипта через API: create, update, rollback, import, delete, copy, rename, tags, disable, enable
+    ScriptChanged {
+        script: String,
+        action: String,
+    },
+    ScanCompleted {
+        scripts: usize,
+        duration_ms: u64,
+    },
+}
+
+impl EventData {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EventData::RunStarted { .. } => "run_started",
+            EventData::RunFinished { .. } => "run_finished",
+            EventData::RunTimedOut { .. } => "run_timed_out",
+            EventData::ScriptChanged { .. } => "script_changed",
+            EventData::ScanCompleted { .. } => "scan_completed",
+        }
+    }
+}
+
+// Параметры подписки на события
+#[derive(Debug, Deserialize, ToSchema, IntoParams)]
+pub struct EventsQuery {
+    /// Группы событий через запятую: `run`, `crud`, `scan`; по умолчанию — все
+    pub filter: Option<String>,
+}
+
+// Параметры выборки истории
+#[derive(Debug, Deserialize, ToSchema, IntoParams)]
+pub struct HistoryQuery {
+    pub script: Option<String>,
+    /// success, failed, timed_out, cancelled, error
+    pub status: Option<String>,
+    pub limit: Option<usize>,
+    /// Сколько записей пропустить (постраничный вывод)
+    pub offset: Option<usize>,
+    /// Только запуски, начатые после этого момента (RFC3339)
+    pub since: Option<DateTime<Utc>>,
+}
+
+/// Запись журнала аудита: изменение скрипта через API
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AuditEntry {
+    pub timestamp: DateTime<Utc>,
+    pub script: String,
+    /// create, update, rollback, import, delete, copy, rename, tags, disable, enable
+    pub action: String,
+    /// Пользователь из JWT или `api_token` для статического токена
+    pub principal: Option<String>,
+    /// Нет у запросов через Unix-сокет
+    pub client_ip: Option<String>,
+    /// SHA-256 и размер кода до и после изменения; у удаления остаётся последний хэш
+    pub hash_before: Option<String>,
+    pub hash_after: Option<String>,
+    pub size_before: Option<u64>,
+    pub size_after: Option<u64>,
+    /// Изменение размера в байтах
+    pub size_delta: i64,
+}
+
+// Параметры выборки журнала аудита
+#[derive(Debug, Deserialize, ToSchema, IntoParams)]
+pub struct AuditQuery {
+    pub script: Option<String>,
+    /// Записи не раньше этого момента (RFC3339)
+    pub since: Option<DateTime<Utc>>,
+    /// Записи раньше этого момента (RFC3339)
+    pub until: Option<DateTime<Utc>>,
+    /// По умолчанию 100
+    pub limit: Option<usize>,
+}
+
+// Счётчики запусков одного скрипта
+#[derive(Debug, Serialize, Clone, Default, ToSchema)]
+pub struct ScriptStats {
+    pub script: String,
+    pub runs: u64,
+    /// Ненулевой код возврата или ошибка запуска
+    pub failures: u64,
+    pub timeouts: u64,
+    pub cache_hits: u64,
+    /// Длительность реальных запусков (без ответов из кэша)
+    pub min_duration_ms: Option<u64>,
+    pub avg_duration_ms: Option<u64>,
+    pub max_duration_ms: Option<u64>,
+    pub last_run_at: Option<DateTime<Utc>>,
+    #[serde(skip)]
+    pub executed: u64,
+    #[serde(skip)]
+    pub total_duration_ms: u64,
+}
+
+// Сводная статистика по всем скриптам
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StatsSummary {
+    pub runs: u64,
+    pub failures: u64,
+    pub timeouts: u64,
+    pub cache_hits: u64,
+    pub load: LoadStats,
+    pub webhooks: WebhookStats,
+    /// Расход квот за последний час по скриптам и вызывающим
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub quotas: Vec<QuotaUsage>,
+    /// Последняя очистка устаревших данных; нет, пока она не выполнялась
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gc: Option<GcReport>,
+    pub scripts: Vec<ScriptStats>,
+}
+
+// Итог очистки устаревших задач, артефактов, журнала, корзины и версий
+#[derive(Debug, Serialize, Clone, ToSchema)]
+pub struct GcReport {
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    /// Удалено по категориям
+    pub removed: GcRemoved,
+    /// Освобождено на диске файлами артефактов, корзины и версий
+    pub reclaimed_bytes: u64,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, Default, ToSchema)]
+pub struct GcRemoved {
+    /// Завершённые асинхронные задачи
+    pub jobs: usize,
+    /// Директории артефактов запусков
+    pub artifacts: usize,
+    /// Записи журнала SQLite: запуски и изменения скриптов
+    pub history: usize,
+    /// Скрипты в корзине
+    pub trash: usize,
+    /// Предыдущие версии скриптов
+    pub versions: usize,
+}
+
+// Текущая загрузка слотов выполнения
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LoadStats {
+    /// Выполняющиеся сейчас скрипты
+    pub running: usize,
+    /// Запуски, ожидающие свободного слота
+    pub queued: usize,
+    pub max_concurrent: usize,
+    /// Запуски, отклонённые с `busy` с момента старта сервера
+    pub busy_rejections: u64,
+    /// Пул воркеров Python; нет, если пул выключен
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pool: Option<PoolStats>,
+    /// Скрипты с ограничением `max_concurrent`, запускавшиеся с момента старта
+    pub scripts: BTreeMap<String, ScriptLoad>,
+}
+
+// Загрузка слотов одного скрипта
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ScriptLoad {
+    pub running: usize,
+    /// Запуски, ожидающие слота скрипта
+    pub queued: usize,
+    pub max_concurrent: usize,
+    /// Запуски, отклонённые с `script_busy` при текущем ограничении
+    pub rejections: u64,
+}
+
+// Использование пула воркеров
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PoolStats {
+    /// Настроенный размер пула
+    pub size: usize,
+    /// Запущенные воркеры (меньше `size`, если воркер не удалось запустить)
+    pub alive: usize,
+    /// Воркеры, выполняющие задачу
+    pub busy: usize,
+    /// Задачи, выполненные пулом
+    pub tasks: u64,
+    /// Запуски обычным способом, потому что свободных воркеров не было
+    pub fallbacks: u64,
+    /// Воркеры, заменённые после падения или превышения порога памяти
+    pub recycled: u64,
+}
+
+// Доставка уведомлений о завершении запусков (`callback_url`)
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WebhookStats {
+    /// Все попытки отправки, включая повторные
+    pub attempts: u64,
+    pub delivered: u64,
+    /// Уведомления, не доставленные после всех попыток
+    pub failed: u64,
+}
+
+// Тело уведомления, которое отправляется на `callback_url`
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WebhookPayload {
+    pub script: String,
+    pub run_id: String,
+    /// Задача асинхронного API, если запуск был поставлен в очередь
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<String>,
+    /// Время от получения запроса до завершения запуска
+    pub duration_ms: u64,
+    /// Результат; нет, если скрипт завершился ошибкой (см. `error`)
+    pub result: Option<ScriptResult>,
+    pub error: Option<String>,
+}
+
+// Статистика кэша результатов
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub max_entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+    // Примерный объём закэшированных stdout/stderr в байтах
+    pub memory_bytes: usize,
+}
+
+// Ответ на операции очистки: сколько записей удалено
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RemovedResponse {
+    pub removed: usize,
 }
 
 #[derive(Debug, Deserialize, ToSchema, IntoParams)]
@@ -65,6 +1485,10 @@ pub struct SearchQuery {
     pub query: Option<String>,
     pub sort_by: Option<String>,
     pub sort_order: Option<String>,
+    /// `true` — добавить сведения о файле (размер, строки, описание из комментария)
+    pub detail: Option<bool>,
+    /// Только скрипты с этим тегом
+    pub tag: Option<String>,
 }
 
 // Модель пользователя (хранится в БД)
@@ -0,0 +1,384 @@
+use crate::{
+    app_state::AppState,
+    auth_middleware::ApiToken,
+    error::AppError,
+    models::{CreateNamespaceRequest, NamespaceInfo},
+    quota::QuotaLimits,
+    utils::{files_under, is_valid_dir_name, write_atomic},
+};
+use axum::{
+    extract::{FromRequestParts, MatchedPath, RawPathParams, Request, State},
+    http::request::Parts,
+    middleware::Next,
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    convert::Infallible,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+use tokio::{fs, sync::Semaphore};
+use tracing::{info, warn};
+
+/// Пространство имён маршрутов без префикса `/ns/{namespace}`: корень
+/// директории скриптов без директорий других пространств
+pub const DEFAULT_NAMESPACE: &str = "default";
+
+/// Пространство имён запроса; добавляется при разборе `/ns/{namespace}/...`
+#[derive(Debug, Clone)]
+pub struct Namespace(pub String);
+
+impl Default for Namespace {
+    fn default() -> Self {
+        Self(DEFAULT_NAMESPACE.to_string())
+    }
+}
+
+impl Namespace {
+    pub fn is_default(&self) -> bool {
+        self.0 == DEFAULT_NAMESPACE
+    }
+
+    /// Полное имя скрипта этого пространства: `team-a/report.py`
+    pub fn qualify(&self, name: &str) -> String {
+        if self.is_default() {
+            name.to_string()
+        } else {
+            format!("{}/{}", self.0, name)
+        }
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Namespace {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Infallible> {
+        Ok(parts.extensions.get::<Namespace>().cloned().unwrap_or_default())
+    }
+}
+
+/// Пространства, доступные вызывающему; `None` — все. Добавляется
+/// `auth_middleware`: ограничены только API-токены с привязкой.
+#[derive(Debug, Clone, Default)]
+pub struct NamespaceAccess(pub Option<Arc<HashSet<String>>>);
+
+impl NamespaceAccess {
+    pub fn token(token: &ApiToken) -> Self {
+        Self(token.namespaces.clone().map(Arc::new))
+    }
+
+    fn allows(&self, namespace: &str) -> bool {
+        self.0.as_ref().is_none_or(|allowed| allowed.contains(namespace))
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for NamespaceAccess {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Infallible> {
+        Ok(parts.extensions.get::<NamespaceAccess>().cloned().unwrap_or_default())
+    }
+}
+
+// Ограничения пространства, заданные при создании; остальные берутся из конфигурации
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Overrides {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_concurrent: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    runs_per_hour: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    exec_secs_per_hour: Option<u64>,
+}
+
+struct Entry {
+    max_concurrent: usize,
+    quota: QuotaLimits,
+    // Слоты одновременных запусков; `None` — без ограничения
+    semaphore: Option<Arc<Semaphore>>,
+    overrides: Overrides,
+}
+
+/// Ограничения пространства по умолчанию (`RUNNER_NAMESPACE_*`)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NamespaceDefaults {
+    pub max_concurrent: usize,
+    pub quota: QuotaLimits,
+}
+
+/// Пространства имён: директории верхнего уровня `RUNNER_SCRIPTS_DIR` со
+/// своими ограничениями одновременных запусков и квотами. Список хранится
+/// в `RUNNER_SCRIPTS_DIR/.namespaces.json`.
+pub struct Namespaces {
+    file: PathBuf,
+    defaults: NamespaceDefaults,
+    entries: RwLock<BTreeMap<String, Entry>>,
+}
+
+impl Namespaces {
+    pub fn load(scripts_dir: &Path, defaults: NamespaceDefaults) -> Self {
+        let file = scripts_dir.join(".namespaces.json");
+        let stored: BTreeMap<String, Overrides> = match std::fs::read(&file) {
+            Ok(content) => serde_json::from_slice(&content).unwrap_or_else(|e| {
+                warn!("Ignoring invalid {}: {}", file.display(), e);
+                BTreeMap::new()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => {
+                warn!("Failed to read {}: {}", file.display(), e);
+                BTreeMap::new()
+            }
+        };
+        let mut entries: BTreeMap<String, Entry> = stored
+            .into_iter()
+            .filter(|(name, _)| is_valid_namespace(name))
+            .map(|(name, overrides)| (name, Entry::new(&defaults, overrides)))
+            .collect();
+        entries.insert(DEFAULT_NAMESPACE.to_string(), Entry::new(&defaults, Overrides::default()));
+        Self {
+            file,
+            defaults,
+            entries: RwLock::new(entries),
+        }
+    }
+
+    pub fn exists(&self, namespace: &str) -> bool {
+        self.entries.read().unwrap().contains_key(namespace)
+    }
+
+    /// Пространство скрипта: первый сегмент имени, если это директория
+    /// пространства, иначе `default`
+    pub fn of(&self, script_name: &str) -> String {
+        script_name
+            .split_once('/')
+            .map(|(first, _)| first)
+            .filter(|first| *first != DEFAULT_NAMESPACE && self.exists(first))
+            .unwrap_or(DEFAULT_NAMESPACE)
+            .to_string()
+    }
+
+    /// Принадлежит ли скрипт пространству `namespace`
+    pub fn contains(&self, namespace: &str, script_name: &str) -> bool {
+        self.of(script_name) == namespace
+    }
+
+    /// Слоты одновременных запусков пространства скрипта
+    pub fn semaphore(&self, script_name: &str) -> Option<Arc<Semaphore>> {
+        let namespace = self.of(script_name);
+        self.entries.read().unwrap().get(&namespace)?.semaphore.clone()
+    }
+
+    /// Квоты пространства скрипта: имя пространства и ограничения
+    pub fn quota(&self, script_name: &str) -> (String, QuotaLimits) {
+        let namespace = self.of(script_name);
+        let quota = self
+            .entries
+            .read()
+            .unwrap()
+            .get(&namespace)
+            .map(|entry| entry.quota)
+            .unwrap_or_default();
+        (namespace, quota)
+    }
+
+    /// Отклоняет обращение к скрипту пространства, недоступного вызывающему
+    pub fn check_access(&self, access: &NamespaceAccess, script_name: &str) -> Result<(), AppError> {
+        let namespace = self.of(script_name);
+        if access.allows(&namespace) {
+            Ok(())
+        } else {
+            Err(AppError::Forbidden(format!("Token has no access to namespace '{}'", namespace)))
+        }
+    }
+
+    pub fn list(&self) -> Vec<NamespaceInfo> {
+        self.entries
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(name, entry)| entry.info(name))
+            .collect()
+    }
+
+    /// Регистрирует пространство и создаёт его директорию; существующая
+    /// директория с тем же именем становится директорией пространства
+    pub async fn create(&self, scripts_dir: &Path, request: CreateNamespaceRequest) -> Result<NamespaceInfo, AppError> {
+        if !is_valid_namespace(&request.name) {
+            return Err(AppError::BadRequest(format!(
+                "Invalid namespace name '{}': use latin letters, digits, '-' and '_'",
+                request.name
+            )));
+        }
+        if self.exists(&request.name) {
+            return Err(AppError::NamespaceAlreadyExists(request.name));
+        }
+        fs::create_dir_all(scripts_dir.join(&request.name)).await?;
+        let overrides = Overrides {
+            max_concurrent: request.max_concurrent,
+            runs_per_hour: request.runs_per_hour,
+            exec_secs_per_hour: request.exec_secs_per_hour,
+        };
+        let info = {
+            let mut entries = self.entries.write().unwrap();
+            let entry = entries
+                .entry(request.name.clone())
+                .or_insert_with(|| Entry::new(&self.defaults, overrides));
+            entry.info(&request.name)
+        };
+        self.save().await?;
+        info!("Created namespace {}", request.name);
+        Ok(info)
+    }
+
+    /// Снимает пространство с учёта и удаляет его директорию. Пространство
+    /// с файлами не удаляется (409): скрипты удаляются по одному, через
+    /// корзину и с записью в журнал изменений.
+    pub async fn remove(&self, scripts_dir: &Path, name: &str) -> Result<(), AppError> {
+        if name == DEFAULT_NAMESPACE {
+            return Err(AppError::BadRequest("The default namespace cannot be deleted".to_string()));
+        }
+        // Пока идёт проверка, запросы `/ns/{name}/...` уже получают 404
+        // и не добавляют скрипты в проверенную директорию
+        let Some(entry) = self.entries.write().unwrap().remove(name) else {
+            return Err(AppError::NamespaceNotFound(name.to_string()));
+        };
+        let files = files_under(&scripts_dir.join(name)).await.len();
+        if files > 0 {
+            self.entries.write().unwrap().insert(name.to_string(), entry);
+            return Err(AppError::NamespaceNotEmpty(name.to_string(), files));
+        }
+        self.save().await?;
+        match fs::remove_dir_all(scripts_dir.join(name)).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        info!("Deleted namespace {}", name);
+        Ok(())
+    }
+
+    // Записывает список пространств; `default` не хранится
+    async fn save(&self) -> Result<(), AppError> {
+        let stored: BTreeMap<String, Overrides> = self
+            .entries
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(name, _)| name.as_str() != DEFAULT_NAMESPACE)
+            .map(|(name, entry)| (name.clone(), entry.overrides.clone()))
+            .collect();
+        let content = serde_json::to_vec_pretty(&stored)?;
+        write_atomic(&self.file, &content, true).await?;
+        Ok(())
+    }
+}
+
+impl Entry {
+    fn new(defaults: &NamespaceDefaults, overrides: Overrides) -> Self {
+        let max_concurrent = overrides.max_concurrent.unwrap_or(defaults.max_concurrent);
+        // 0 в запросе на создание, как и в конфигурации, снимает ограничение
+        let limit = |value: Option<u64>, default: Option<u64>| match value {
+            Some(0) => None,
+            Some(value) => Some(value),
+            None => default,
+        };
+        Self {
+            max_concurrent,
+            quota: QuotaLimits {
+                runs_per_hour: limit(overrides.runs_per_hour, defaults.quota.runs_per_hour),
+                exec_secs_per_hour: limit(overrides.exec_secs_per_hour, defaults.quota.exec_secs_per_hour),
+            },
+            semaphore: (max_concurrent > 0).then(|| Arc::new(Semaphore::new(max_concurrent))),
+            overrides,
+        }
+    }
+
+    fn info(&self, name: &str) -> NamespaceInfo {
+        NamespaceInfo {
+            name: name.to_string(),
+            max_concurrent: (self.max_concurrent > 0).then_some(self.max_concurrent),
+            running: self
+                .semaphore
+                .as_ref()
+                .map(|semaphore| self.max_concurrent - semaphore.available_permits()),
+            runs_per_hour: self.quota.runs_per_hour,
+            exec_secs_per_hour: self.quota.exec_secs_per_hour,
+        }
+    }
+}
+
+// Имя пространства — имя директории; `default` зарезервировано
+fn is_valid_namespace(name: &str) -> bool {
+    is_valid_dir_name(name) && name != DEFAULT_NAMESPACE
+}
+
+// Маршруты, доступные токенам с привязкой к пространствам: скрипты, их
+// запуск и кэш, а также задачи, артефакты и логи по идентификатору
+fn is_namespaced_route(route: &str) -> bool {
+    route == "/scripts"
+        || ["/scripts/{name}", "/run/{name}"]
+            .iter()
+            .any(|prefix| route == *prefix || route.starts_with(&format!("{}/", prefix)))
+        || matches!(
+            route,
+            "/cache/{script}"
+                | "/jobs/{id}"
+                | "/runs/{run_id}/artifacts/{*filename}"
+                | "/runs/{run_id}/log"
+                | "/limits"
+        )
+}
+
+/// Проверяет пространство запроса: `/ns/{namespace}/...` неизвестного
+/// пространства — 404. Токенам с привязкой доступны только скрипты своих
+/// пространств; маршруты, охватывающие весь сервер, для них закрыты.
+pub async fn namespace_access(
+    State(state): State<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let namespace = req.extensions().get::<Namespace>().cloned();
+    if let Some(namespace) = &namespace {
+        if !state.namespaces.exists(&namespace.0) {
+            return Err(AppError::NamespaceNotFound(namespace.0.clone()));
+        }
+    }
+
+    let access = req.extensions().get::<NamespaceAccess>().cloned().unwrap_or_default();
+    if access.0.is_none() {
+        return Ok(next.run(req).await);
+    }
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_default();
+    if !is_namespaced_route(&route) {
+        return Err(AppError::Forbidden("Token bound to namespaces cannot use this endpoint".to_string()));
+    }
+
+    let (mut parts, body) = req.into_parts();
+    let params = RawPathParams::from_request_parts(&mut parts, &state)
+        .await
+        .map_err(|e| AppError::BadRequest(e.body_text()))?;
+    let mut checked = false;
+    for (key, value) in &params {
+        if matches!(key, "name" | "script") {
+            state.namespaces.check_access(&access, value)?;
+            checked = true;
+        }
+    }
+    // Список и создание скриптов относятся к пространству из пути
+    if !checked && route == "/scripts" {
+        let namespace = namespace.unwrap_or_default();
+        if !access.allows(&namespace.0) {
+            return Err(AppError::Forbidden(format!(
+                "Token has no access to namespace '{}'",
+                namespace.0
+            )));
+        }
+    }
+    Ok(next.run(Request::from_parts(parts, body)).await)
+}
@@ -0,0 +1,116 @@
+use crate::{app_state::AppState, error::AppError, exec_user::ExecUser};
+use tokio::process::Command;
+use tracing::warn;
+
+/// Что делать с запуском `network: false`, если изолировать процесс от сети
+/// нельзя (`RUNNER_NETWORK_ISOLATION_FALLBACK`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IsolationFallback {
+    /// Отклонить запуск с `503 network_isolation_unavailable`
+    #[default]
+    Fail,
+    /// Запустить с доступом к сети, записав предупреждение в лог
+    Warn,
+}
+
+/// Изоляция для запуска с настройкой `network`: `None` — не запрошена,
+/// `Some(true)` — процесс запускается без сети, `Some(false)` — изоляция
+/// недоступна, и запуск идёт с сетью по `IsolationFallback::Warn`
+pub fn isolation(state: &AppState, script_name: &str, network: Option<bool>) -> Result<Option<bool>, AppError> {
+    if network != Some(false) {
+        return Ok(None);
+    }
+    let Some(reason) = &state.network_unavailable else {
+        return Ok(Some(true));
+    };
+    match state.network_fallback {
+        IsolationFallback::Fail => Err(AppError::NetworkIsolationUnavailable(reason.clone())),
+        IsolationFallback::Warn => {
+            warn!(
+                "Network isolation is unavailable ({}), running {} with network access",
+                reason, script_name
+            );
+            Ok(Some(false))
+        }
+    }
+}
+
+/// Проверяет при старте, что процесс скрипта (от имени `user`, если он задан)
+/// сможет создать собственное сетевое пространство имён; `Err` — причина
+/// недоступности изоляции
+#[cfg(target_os = "linux")]
+pub fn probe(user: Option<ExecUser>) -> Result<(), String> {
+    // SAFETY: дочерний процесс вызывает только async-signal-safe функции
+    // и сразу завершается с errno в коде возврата
+    unsafe {
+        match libc::fork() {
+            -1 => Err(std::io::Error::last_os_error().to_string()),
+            0 => {
+                let dropped = user.is_none_or(|user| libc::setgid(user.gid) == 0 && libc::setuid(user.uid) == 0);
+                let code = if dropped && unshare_net() == 0 {
+                    0
+                } else {
+                    std::io::Error::last_os_error().raw_os_error().unwrap_or(libc::EPERM)
+                };
+                libc::_exit(code)
+            }
+            pid => {
+                let mut status = 0;
+                while libc::waitpid(pid, &mut status, 0) == -1 {
+                    let e = std::io::Error::last_os_error();
+                    if e.kind() != std::io::ErrorKind::Interrupted {
+                        return Err(e.to_string());
+                    }
+                }
+                match libc::WIFEXITED(status).then(|| libc::WEXITSTATUS(status)) {
+                    Some(0) => Ok(()),
+                    Some(code) => Err(format!(
+                        "cannot create network namespace: {}",
+                        std::io::Error::from_raw_os_error(code)
+                    )),
+                    None => Err("network namespace probe was killed".to_string()),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn probe(_user: Option<ExecUser>) -> Result<(), String> {
+    Err("network isolation is supported only on Linux".to_string())
+}
+
+/// Запускает процесс в новом сетевом пространстве имён: в нём нет
+/// интерфейсов, кроме выключенного loopback, и любые соединения не удаются
+#[cfg(target_os = "linux")]
+pub fn apply(command: &mut Command, isolate: bool) {
+    if !isolate {
+        return;
+    }
+    // SAFETY: между fork и exec вызывается только unshare, он async-signal-safe
+    unsafe {
+        command.pre_exec(|| {
+            if unshare_net() == 0 {
+                Ok(())
+            } else {
+                Err(std::io::Error::last_os_error())
+            }
+        });
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn apply(_command: &mut Command, _isolate: bool) {}
+
+// Без CAP_SYS_ADMIN новое сетевое пространство создаётся внутри
+// непривилегированного пространства имён пользователя
+#[cfg(target_os = "linux")]
+fn unshare_net() -> libc::c_int {
+    // SAFETY: unshare меняет только пространства имён вызывающего процесса
+    unsafe {
+        if libc::unshare(libc::CLONE_NEWNET) == 0 {
+            return 0;
+        }
+        libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNET)
+    }
+}
@@ -0,0 +1,118 @@
+use crate::{
+    error::AppError,
+    models::{InputFrom, ScriptResult},
+    utils::decode_output,
+};
+use bytes::Bytes;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+struct StoredOutput {
+    script: String,
+    stdout: Bytes,
+    stored: Instant,
+}
+
+#[derive(Default)]
+struct Inner {
+    runs: HashMap<String, StoredOutput>,
+    // run_id в порядке сохранения: первыми вытесняются и устаревают самые старые
+    order: VecDeque<String>,
+    // Последний успешный запуск каждого скрипта
+    latest: HashMap<String, String>,
+    bytes: usize,
+}
+
+impl Inner {
+    fn pop_oldest(&mut self) {
+        let Some(run_id) = self.order.pop_front() else {
+            return;
+        };
+        if let Some(output) = self.runs.remove(&run_id) {
+            self.bytes -= output.stdout.len();
+            if self.latest.get(&output.script) == Some(&run_id) {
+                self.latest.remove(&output.script);
+            }
+        }
+    }
+
+    fn evict_expired(&mut self, retention: Duration) {
+        while let Some(run_id) = self.order.front() {
+            match self.runs.get(run_id) {
+                Some(output) if output.stored.elapsed() < retention => break,
+                _ => self.pop_oldest(),
+            }
+        }
+    }
+}
+
+/// stdout недавних запусков для `input_from`: по run_id и последний успешный
+/// запуск каждого скрипта. Хранится в памяти не дольше
+/// `RUNNER_OUTPUT_RETENTION_SECS` и не больше `RUNNER_OUTPUT_STORE_BYTES` байт.
+pub struct RunOutputs {
+    inner: Mutex<Inner>,
+    max_bytes: usize,
+    retention: Duration,
+}
+
+impl RunOutputs {
+    pub fn new(max_bytes: usize, retention: Duration) -> Self {
+        Self {
+            inner: Mutex::new(Inner::default()),
+            max_bytes,
+            retention,
+        }
+    }
+
+    /// Сохраняет stdout запуска. Обрезанный вывод и вывод запуска,
+    /// остановленного по таймауту, не сохраняются: продолжение на неполных
+    /// данных дало бы неверный результат.
+    pub fn record(&self, script: &str, run_id: &str, result: &ScriptResult) {
+        if self.max_bytes == 0 || result.timed_out || result.stdout_truncated {
+            return;
+        }
+        let stdout = Bytes::from(decode_output(&result.stdout, result.stdout_encoding));
+        if stdout.len() > self.max_bytes {
+            return;
+        }
+
+        let mut inner = self.inner.lock().unwrap();
+        inner.evict_expired(self.retention);
+        while inner.bytes + stdout.len() > self.max_bytes {
+            inner.pop_oldest();
+        }
+        inner.bytes += stdout.len();
+        if result.exit_code == 0 {
+            inner.latest.insert(script.to_string(), run_id.to_string());
+        }
+        inner.order.push_back(run_id.to_string());
+        inner.runs.insert(
+            run_id.to_string(),
+            StoredOutput {
+                script: script.to_string(),
+                stdout,
+                stored: Instant::now(),
+            },
+        );
+    }
+
+    /// Имя скрипта и stdout запуска-источника
+    pub fn lookup(&self, source: &InputFrom) -> Result<(String, Bytes), AppError> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.evict_expired(self.retention);
+        let (run_id, missing) = match source {
+            InputFrom::Run { run_id } => (Some(run_id), format!("run '{}'", run_id)),
+            InputFrom::Latest { script, .. } => (
+                inner.latest.get(script),
+                format!("the latest successful run of '{}'", script),
+            ),
+        };
+        run_id
+            .and_then(|run_id| inner.runs.get(run_id))
+            .map(|output| (output.script.clone(), output.stdout.clone()))
+            .ok_or(AppError::SourceOutputNotFound(missing))
+    }
+}
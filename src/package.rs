@@ -0,0 +1,176 @@
+use crate::{models::ScriptConfig, script_config, utils::MAX_SCRIPT_DEPTH};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+use tokio::{fs, process::Command};
+
+/// Файл, по которому директория считается пакетом
+pub const MAIN_FILE: &str = "__main__.py";
+
+/// Расширение, интерпретатор которого (`RUNNER_INTERPRETERS`) запускает пакеты
+pub const EXTENSION: &str = "py";
+
+// Байт-код, который Python пишет рядом с модулями, не является частью пакета
+const BYTECODE_DIR: &str = "__pycache__";
+
+/// Файлы пакета, отсортированные по пути, их общий размер и самый поздний
+/// mtime среди файлов и директорий пакета (удаление файла меняет mtime
+/// директории, в которой он лежал)
+pub struct Snapshot {
+    pub files: Vec<PathBuf>,
+    pub size: u64,
+    pub newest: Option<SystemTime>,
+}
+
+/// Является ли директория пакетом: в ней есть `__main__.py` или точка входа,
+/// заданная `entrypoint` в `<имя>.toml` рядом с директорией
+pub async fn is_package(dir: &Path) -> bool {
+    let config = script_config::load(dir).await;
+    fs::metadata(entry_file(dir, &config))
+        .await
+        .is_ok_and(|meta| meta.is_file())
+}
+
+/// Файл точки входа пакета: `entrypoint` из настроек или `__main__.py`
+pub fn entry_file(dir: &Path, config: &ScriptConfig) -> PathBuf {
+    dir.join(config.entrypoint.as_deref().unwrap_or(MAIN_FILE))
+}
+
+/// Лежит ли файл в `__pycache__`: его изменение не меняет пакет
+pub fn is_bytecode(path: &Path) -> bool {
+    path.components().any(|c| c.as_os_str() == BYTECODE_DIR)
+}
+
+/// Обходит пакет; скрытые файлы и директории, `__pycache__` и символические
+/// ссылки на директории пропускаются
+pub async fn snapshot(dir: &Path) -> std::io::Result<Snapshot> {
+    let mut files = BTreeMap::new();
+    let mut newest = fs::metadata(dir).await?.modified().ok();
+    let mut dirs = vec![(dir.to_path_buf(), 0)];
+    while let Some((current, depth)) = dirs.pop() {
+        let mut entries = fs::read_dir(&current).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let skipped = entry
+                .file_name()
+                .to_str()
+                .is_none_or(|name| name.starts_with('.') || name == BYTECODE_DIR);
+            if skipped {
+                continue;
+            }
+            let path = entry.path();
+            if entry.file_type().await?.is_dir() {
+                newest = newest.max(entry.metadata().await?.modified().ok());
+                if depth < MAX_SCRIPT_DEPTH {
+                    dirs.push((path, depth + 1));
+                }
+                continue;
+            }
+            // Символическая ссылка на файл учитывается по самому файлу
+            let Ok(meta) = fs::metadata(&path).await else {
+                continue;
+            };
+            if meta.is_file() {
+                newest = newest.max(meta.modified().ok());
+                files.insert(path, meta.len());
+            }
+        }
+    }
+    Ok(Snapshot {
+        size: files.values().sum(),
+        files: files.into_keys().collect(),
+        newest,
+    })
+}
+
+/// SHA-256 пакета: относительные пути и содержимое всех его файлов
+pub async fn hash(dir: &Path, snapshot: &Snapshot) -> String {
+    let mut hasher = Sha256::new();
+    for path in &snapshot.files {
+        let Ok(content) = fs::read(path).await else {
+            continue;
+        };
+        let relative = path.strip_prefix(dir).unwrap_or(path);
+        hasher.update(relative.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update((content.len() as u64).to_le_bytes());
+        hasher.update(&content);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Что получает интерпретатор: файл скрипта или пакет
+#[derive(Debug, Clone)]
+pub enum Launch {
+    File(PathBuf),
+    /// Пакет запускается с директорией скриптов в начале `PYTHONPATH`
+    Package {
+        dir: PathBuf,
+        args: Vec<OsString>,
+        python_path: PathBuf,
+    },
+}
+
+impl Launch {
+    /// Пакет без `entrypoint`, имя которого — путь модуля Python, запускается
+    /// как `python -m etl.mypkg`; иначе выполняется файл точки входа
+    pub fn package(scripts_dir: &Path, name: &str, dir: &Path, config: &ScriptConfig) -> std::io::Result<Self> {
+        let module = name.split('/').all(is_identifier).then(|| name.replace('/', "."));
+        let args = match (&config.entrypoint, module) {
+            (None, Some(module)) => vec!["-m".into(), module.into()],
+            _ => vec![std::path::absolute(entry_file(dir, config))?.into_os_string()],
+        };
+        Ok(Self::Package {
+            dir: dir.to_path_buf(),
+            args,
+            python_path: std::path::absolute(scripts_dir)?,
+        })
+    }
+
+    /// Файл скрипта или директория пакета
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::File(path) => path,
+            Self::Package { dir, .. } => dir,
+        }
+    }
+
+    /// Добавляет аргументы запуска. Скрипт выполняется в рабочей директории
+    /// запуска, поэтому путь к файлу передаётся абсолютным.
+    pub fn add_args(&self, command: &mut Command) -> std::io::Result<()> {
+        match self {
+            Self::File(path) => {
+                command.arg(std::path::absolute(path)?);
+            }
+            Self::Package { args, .. } => {
+                command.args(args);
+            }
+        }
+        Ok(())
+    }
+
+    /// Ставит директорию скриптов перед `PYTHONPATH` сервера (если его
+    /// окружение наследуется). `PYTHONPATH` нельзя задать в `env` запроса,
+    /// а вызов после `Command::envs` всё равно его перекрывает.
+    pub fn set_python_path(&self, command: &mut Command, inherit: bool) {
+        let Self::Package { python_path, .. } = self else {
+            return;
+        };
+        let inherited = inherit.then(|| std::env::var_os("PYTHONPATH")).flatten();
+        let mut paths = vec![python_path.clone()];
+        paths.extend(inherited.iter().flat_map(std::env::split_paths));
+        if let Ok(value) = std::env::join_paths(paths) {
+            command.env("PYTHONPATH", value);
+        }
+    }
+}
+
+// Сегмент пути модуля Python: буквы, цифры и `_`, не с цифры
+fn is_identifier(segment: &str) -> bool {
+    !segment.is_empty()
+        && !segment.starts_with(|c: char| c.is_ascii_digit())
+        && segment.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')
+}
@@ -0,0 +1,93 @@
+use crate::{
+    app_state::AppState,
+    arg_template,
+    error::AppError,
+    models::{PipelineRequest, PipelineResponse, PipelineStepResult, ScriptResult},
+    quota::Caller,
+    script_runner::{self, RunOptions},
+    limits,
+    utils::{decode_output, stdin_bytes, validate_env},
+};
+use bytes::Bytes;
+use std::sync::Arc;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+/// Выполняет шаги по очереди: stdout шага становится stdin следующего,
+/// первый шаг получает `data`. Шаги последовательны, поэтому пайплайн
+/// занимает не больше одного слота семафора. Остановка — на первом
+/// ненулевом коде возврата, таймауте или ошибке запуска.
+pub async fn run_pipeline(
+    state: Arc<AppState>,
+    caller: Caller,
+    payload: PipelineRequest,
+) -> Result<PipelineResponse, AppError> {
+    if payload.steps.is_empty() {
+        return Err(AppError::BadRequest("Pipeline has no steps".to_string()));
+    }
+    // Ошибки в запросе отклоняются до запуска первого шага
+    let mut steps = Vec::with_capacity(payload.steps.len());
+    for step in payload.steps {
+        script_runner::resolve_script(&state, &step.name).await?;
+        let args = step.args.unwrap_or_default();
+        limits::check_args(&state.run_limits, &args)?;
+        arg_template::validate(&args)?;
+        let env = step.env.unwrap_or_default().into_iter().collect();
+        validate_env(&env)?;
+        let options = RunOptions {
+            args,
+            env,
+            no_cache: payload.cache == Some(false),
+            caller: caller.0.clone(),
+            ..Default::default()
+        };
+        steps.push((step.name, step.timeout_secs, options));
+    }
+
+    // Общий таймаут на весь пайплайн; каждый шаг получает не больше остатка
+    let pipeline_timeout = state.effective_timeout(payload.timeout_secs);
+    let deadline = Instant::now() + pipeline_timeout;
+    let mut input = stdin_bytes(payload.data.as_ref())?;
+    limits::check_data(&state.run_limits, &input)?;
+    let mut results = Vec::with_capacity(steps.len());
+    let mut failed_step = None;
+    let mut timed_out = false;
+
+    for (index, (name, step_timeout_secs, mut options)) in steps.into_iter().enumerate() {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            warn!("Pipeline timed out after {:?} before step {}", pipeline_timeout, index);
+            failed_step = Some(index);
+            timed_out = true;
+            break;
+        }
+        let step_timeout = state.effective_timeout(step_timeout_secs).min(remaining);
+        options.timeout_secs = Some(step_timeout.as_secs_f64().ceil() as u64);
+
+        info!("Pipeline step {}: {}", index, name);
+        let result = match script_runner::run_script(Arc::clone(&state), &name, input, options).await {
+            Ok(r) => r,
+            Err(e) => ScriptResult::from_error(&e),
+        };
+
+        let failed = result.exit_code != 0 || result.timed_out;
+        input = Bytes::from(decode_output(&result.stdout, result.stdout_encoding));
+        if failed {
+            failed_step = Some(index);
+            timed_out = result.timed_out && Instant::now() >= deadline;
+        }
+        results.push(PipelineStepResult {
+            script: name,
+            result,
+        });
+        if failed {
+            break;
+        }
+    }
+
+    Ok(PipelineResponse {
+        steps: results,
+        failed_step,
+        timed_out,
+    })
+}
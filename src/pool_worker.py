@@ -0,0 +1,118 @@
+# Воркер пула: читает задачи построчно в JSON из stdin и выполняет каждую
+# в дочернем процессе (fork), поэтому интерпретатор запускается один раз.
+# На задачу отвечает двумя строками: {"pid": ...} после запуска и итогом
+# после завершения.
+import json
+import os
+import resource
+import runpy
+import signal
+import sys
+import time
+import traceback
+
+KILL_GRACE = 2.0
+POLL_INTERVAL = 0.002
+
+
+def reply(message):
+    sys.stdout.write(json.dumps(message) + "\n")
+    sys.stdout.flush()
+
+
+def redirect(fd, path, flags):
+    target = os.open(path, flags, 0o600)
+    os.dup2(target, fd)
+    os.close(target)
+
+
+def run_child(task):
+    os.setsid()
+    os.chdir(task["cwd"])
+    os.environ.update(task["env"])
+    redirect(0, task["stdin"], os.O_RDONLY)
+    redirect(1, task["stdout"], os.O_WRONLY | os.O_CREAT | os.O_TRUNC)
+    redirect(2, task["stderr"], os.O_WRONLY | os.O_CREAT | os.O_TRUNC)
+    # Новый объект stdin без буфера протокола родителя
+    sys.stdin = open(0, closefd=False)
+    # Запись сверх лимита вывода завершает процесс по SIGXFSZ
+    limit = task["max_output_bytes"] + 1
+    resource.setrlimit(resource.RLIMIT_FSIZE, (limit, limit))
+    signal.signal(signal.SIGXFSZ, signal.SIG_DFL)
+
+    sys.argv = [task["script"]] + task["args"]
+    sys.path[0] = os.path.dirname(task["script"])
+    code = 0
+    try:
+        runpy.run_path(task["script"], run_name="__main__")
+    except SystemExit as e:
+        if e.code is None:
+            code = 0
+        elif isinstance(e.code, int):
+            code = e.code
+        else:
+            print(e.code, file=sys.stderr)
+            code = 1
+    except BaseException:
+        traceback.print_exc()
+        code = 1
+    try:
+        sys.stdout.flush()
+        sys.stderr.flush()
+    finally:
+        os._exit(code)
+
+
+def wait_child(pid, timeout):
+    deadline = time.monotonic() + timeout
+    while True:
+        wpid, status, usage = os.wait4(pid, os.WNOHANG)
+        if wpid:
+            return status, usage, False
+        if time.monotonic() >= deadline:
+            break
+        time.sleep(POLL_INTERVAL)
+
+    # Таймаут: SIGTERM группе процессов задачи, затем SIGKILL
+    try:
+        os.killpg(pid, signal.SIGTERM)
+    except ProcessLookupError:
+        pass
+    grace = time.monotonic() + KILL_GRACE
+    while time.monotonic() < grace:
+        wpid, status, usage = os.wait4(pid, os.WNOHANG)
+        if wpid:
+            return status, usage, True
+        time.sleep(POLL_INTERVAL)
+    try:
+        os.killpg(pid, signal.SIGKILL)
+    except ProcessLookupError:
+        pass
+    _, status, usage = os.wait4(pid, 0)
+    return status, usage, True
+
+
+def main():
+    for line in sys.stdin:
+        task = json.loads(line)
+        pid = os.fork()
+        if pid == 0:
+            try:
+                run_child(task)
+            finally:
+                os._exit(1)
+        reply({"pid": pid})
+
+        status, usage, timed_out = wait_child(pid, task["timeout_secs"])
+        exit_code = os.WEXITSTATUS(status) if os.WIFEXITED(status) else -1
+        reply({
+            "exit_code": exit_code,
+            "signal": os.WTERMSIG(status) if os.WIFSIGNALED(status) else None,
+            "timed_out": timed_out,
+            "task_rss_kb": usage.ru_maxrss,
+            "worker_rss_kb": resource.getrusage(resource.RUSAGE_SELF).ru_maxrss,
+        })
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,270 @@
+use crate::{
+    error::AppError,
+    models::{QuotaRemaining, QuotaScope, QuotaUsage},
+    utils::sha256_hex,
+};
+use axum::{
+    extract::FromRequestParts,
+    http::{request::Parts, HeaderName, HeaderValue},
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::Infallible,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+/// Заголовок ответа на запуск с остатком квот
+pub const QUOTA_REMAINING_HEADER: HeaderName = HeaderName::from_static("x-quota-remaining");
+
+/// Скользящее окно, в котором считаются запуски и время выполнения
+pub const QUOTA_WINDOW: Duration = Duration::from_secs(3600);
+
+// Длина префикса SHA-256, по которому различаются API-токены
+const TOKEN_ID_LEN: usize = 12;
+
+/// Ограничения за час; `None` — без ограничения
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QuotaLimits {
+    pub runs_per_hour: Option<u64>,
+    pub exec_secs_per_hour: Option<u64>,
+}
+
+impl QuotaLimits {
+    fn is_set(&self) -> bool {
+        self.runs_per_hour.is_some() || self.exec_secs_per_hour.is_some()
+    }
+}
+
+/// Вызывающий, которому засчитываются запуски: пользователь JWT или
+/// API-токен. Добавляется в запрос `auth_middleware`.
+#[derive(Debug, Clone, Default)]
+pub struct Caller(pub Option<String>);
+
+impl Caller {
+    pub fn user(name: &str) -> Self {
+        Self(Some(format!("user:{}", name)))
+    }
+
+    // Сам токен в статистику не попадает
+    pub fn token(token: &str) -> Self {
+        Self(Some(format!("token:{}", &sha256_hex(token.as_bytes())[..TOKEN_ID_LEN])))
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Caller {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Infallible> {
+        Ok(parts.extensions.get::<Caller>().cloned().unwrap_or_default())
+    }
+}
+
+// Один запуск в окне; время выполнения известно после завершения
+struct Usage {
+    id: u64,
+    at: Instant,
+    exec: Duration,
+}
+
+/// Запуск, засчитанный в квоты до завершения; см. `Quotas::finish`
+pub struct Reservation {
+    id: u64,
+    subjects: Vec<(QuotaScope, String)>,
+}
+
+// Запуски одного скрипта, вызывающего или пространства имён в окне и
+// ограничения, действовавшие при последнем запуске
+#[derive(Default)]
+struct Window {
+    limits: QuotaLimits,
+    usages: VecDeque<Usage>,
+}
+
+/// Квоты запусков по скриптам, вызывающим и пространствам имён: число
+/// запусков и суммарное время выполнения за последний час
+pub struct Quotas {
+    script: QuotaLimits,
+    caller: QuotaLimits,
+    /// Засчитывать ли ответы из кэша
+    pub count_cache_hits: bool,
+    next_id: AtomicU64,
+    windows: Mutex<HashMap<(QuotaScope, String), Window>>,
+}
+
+impl Quotas {
+    pub fn new(script: QuotaLimits, caller: QuotaLimits, count_cache_hits: bool) -> Self {
+        Self {
+            script,
+            caller,
+            count_cache_hits,
+            next_id: AtomicU64::new(0),
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Засчитывает запуск скрипта, если квоты скрипта, вызывающего и
+    /// пространства имён (имя и его ограничения) не исчерпаны; иначе —
+    /// `QuotaExceeded` с расходом исчерпанной квоты
+    pub async fn reserve(
+        &self,
+        script: &str,
+        caller: Option<&str>,
+        (namespace, namespace_limits): (String, QuotaLimits),
+    ) -> Result<Reservation, AppError> {
+        let mut subjects = Vec::new();
+        if self.script.is_set() {
+            subjects.push(((QuotaScope::Script, script.to_string()), self.script));
+        }
+        if let Some(caller) = caller.filter(|_| self.caller.is_set()) {
+            subjects.push(((QuotaScope::Caller, caller.to_string()), self.caller));
+        }
+        if namespace_limits.is_set() {
+            subjects.push(((QuotaScope::Namespace, namespace), namespace_limits));
+        }
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        if subjects.is_empty() {
+            return Ok(Reservation { id, subjects: Vec::new() });
+        }
+
+        let now = Instant::now();
+        let mut windows = self.windows.lock().await;
+        for (subject, limits) in &subjects {
+            let window = windows.entry(subject.clone()).or_default();
+            window.limits = *limits;
+            expire(window, now);
+            let usage = usage(subject, window);
+            let runs_exhausted = usage.remaining_runs == Some(0);
+            if runs_exhausted || usage.remaining_exec_secs == Some(0) {
+                // Место в окне освободится, когда из него выйдет самый старый запуск;
+                // время выполнения может освободиться и позже
+                let retry_after = window
+                    .usages
+                    .front()
+                    .map(|oldest| (oldest.at + QUOTA_WINDOW).saturating_duration_since(now))
+                    .unwrap_or(QUOTA_WINDOW);
+                warn!("Quota of {:?} '{}' exhausted", usage.scope, usage.subject);
+                return Err(AppError::QuotaExceeded {
+                    usage: Box::new(usage),
+                    retry_after: retry_after.as_secs().max(1),
+                });
+            }
+        }
+        let subjects: Vec<_> = subjects.into_iter().map(|(subject, _)| subject).collect();
+        for subject in &subjects {
+            if let Some(window) = windows.get_mut(subject) {
+                window.usages.push_back(Usage { id, at: now, exec: Duration::ZERO });
+            }
+        }
+        Ok(Reservation { id, subjects })
+    }
+
+    /// Записывает время выполнения запуска; `None` снимает запуск с учёта
+    /// (ответ из кэша или запуск, который не состоялся). Возвращает
+    /// наименьший остаток квот после запуска.
+    pub async fn finish(&self, reservation: Reservation, exec: Option<Duration>) -> Option<QuotaRemaining> {
+        if reservation.subjects.is_empty() {
+            return None;
+        }
+        let now = Instant::now();
+        let mut windows = self.windows.lock().await;
+        let mut remaining = QuotaRemaining::default();
+        for subject in &reservation.subjects {
+            let Some(window) = windows.get_mut(subject) else {
+                continue;
+            };
+            match exec {
+                Some(exec) => {
+                    if let Some(usage) = window.usages.iter_mut().find(|usage| usage.id == reservation.id) {
+                        usage.exec = exec;
+                    }
+                }
+                None => window.usages.retain(|usage| usage.id != reservation.id),
+            }
+            expire(window, now);
+            let usage = usage(subject, window);
+            remaining.runs = min_remaining(remaining.runs, usage.remaining_runs);
+            remaining.exec_secs = min_remaining(remaining.exec_secs, usage.remaining_exec_secs);
+        }
+        Some(remaining)
+    }
+
+    /// Расход квот за последний час: по скриптам, вызывающим, затем по пространствам имён
+    pub async fn summary(&self) -> Vec<QuotaUsage> {
+        let now = Instant::now();
+        let mut windows = self.windows.lock().await;
+        let mut usages: Vec<QuotaUsage> = windows
+            .iter_mut()
+            .filter_map(|(subject, window)| {
+                expire(window, now);
+                (!window.usages.is_empty()).then(|| usage(subject, window))
+            })
+            .collect();
+        usages.sort_by(|a, b| (a.scope, &a.subject).cmp(&(b.scope, &b.subject)));
+        usages
+    }
+
+    /// Удаляет окна без запусков за последний час
+    pub async fn prune(&self) {
+        let now = Instant::now();
+        let mut windows = self.windows.lock().await;
+        let before = windows.len();
+        windows.retain(|_, window| {
+            expire(window, now);
+            !window.usages.is_empty()
+        });
+        let removed = before - windows.len();
+        if removed > 0 {
+            info!("Pruned {} idle quota windows", removed);
+        }
+    }
+}
+
+// Расход и остаток квоты по запускам в окне
+fn usage((scope, subject): &(QuotaScope, String), window: &Window) -> QuotaUsage {
+    let limits = window.limits;
+    let runs = window.usages.len() as u64;
+    let exec_secs = window.usages.iter().map(|usage| usage.exec).sum::<Duration>().as_secs();
+    QuotaUsage {
+        scope: *scope,
+        subject: subject.clone(),
+        runs,
+        exec_secs,
+        max_runs_per_hour: limits.runs_per_hour,
+        max_exec_secs_per_hour: limits.exec_secs_per_hour,
+        remaining_runs: limits.runs_per_hour.map(|max| max.saturating_sub(runs)),
+        remaining_exec_secs: limits.exec_secs_per_hour.map(|max| max.saturating_sub(exec_secs)),
+    }
+}
+
+// Убирает запуски старше окна; запуски добавляются по времени начала
+fn expire(window: &mut Window, now: Instant) {
+    while window
+        .usages
+        .front()
+        .is_some_and(|usage| now.saturating_duration_since(usage.at) >= QUOTA_WINDOW)
+    {
+        window.usages.pop_front();
+    }
+}
+
+fn min_remaining(current: Option<u64>, other: Option<u64>) -> Option<u64> {
+    match (current, other) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Значение заголовка `X-Quota-Remaining`: `runs=12, exec_secs=3400`
+pub fn header_value(remaining: QuotaRemaining) -> Option<HeaderValue> {
+    let parts: Vec<String> = [("runs", remaining.runs), ("exec_secs", remaining.exec_secs)]
+        .into_iter()
+        .filter_map(|(name, value)| value.map(|value| format!("{}={}", name, value)))
+        .collect();
+    if parts.is_empty() {
+        return None;
+    }
+    HeaderValue::from_str(&parts.join(", ")).ok()
+}
@@ -0,0 +1,115 @@
+use crate::{app_state::AppState, error::AppError};
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    net::{IpAddr, SocketAddr},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Ограничение частоты запусков по IP клиента (token bucket):
+/// `burst` запросов сразу, затем `per_minute` запросов в минуту.
+pub struct RateLimiter {
+    per_minute: AtomicU32,
+    burst: AtomicU32,
+    exempt: HashSet<IpAddr>,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(per_minute: u32, burst: u32, exempt: HashSet<IpAddr>) -> Self {
+        Self {
+            per_minute: AtomicU32::new(per_minute),
+            burst: AtomicU32::new(burst.max(1)),
+            exempt,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Запросов в минуту и размер всплеска
+    pub fn limits(&self) -> (u32, u32) {
+        (self.per_minute.load(Ordering::Relaxed), self.burst.load(Ordering::Relaxed))
+    }
+
+    /// Новые значения действуют со следующего запроса; накопленные
+    /// клиентами токены ограничиваются новым `burst` при пополнении
+    pub fn set_limits(&self, per_minute: u32, burst: u32) {
+        self.per_minute.store(per_minute, Ordering::Relaxed);
+        self.burst.store(burst.max(1), Ordering::Relaxed);
+    }
+
+    /// Забирает токен клиента; если токенов нет — время до появления следующего
+    pub async fn check(&self, ip: IpAddr) -> Result<(), Duration> {
+        let (per_minute, burst) = self.limits();
+        if per_minute == 0 || self.exempt.contains(&ip) {
+            return Ok(());
+        }
+
+        let rate = per_minute as f64 / 60.0;
+        let burst = burst as f64;
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().await;
+        let bucket = buckets.entry(ip).or_insert(Bucket {
+            tokens: burst,
+            updated: now,
+        });
+
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(burst);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / rate))
+        }
+    }
+
+    /// Удаляет корзины клиентов, которые успели полностью восстановиться
+    pub async fn prune(&self) {
+        let (per_minute, burst) = self.limits();
+        if per_minute == 0 {
+            return;
+        }
+        let full_after = Duration::from_secs_f64(burst as f64 * 60.0 / per_minute as f64);
+        let mut buckets = self.buckets.lock().await;
+        let before = buckets.len();
+        buckets.retain(|_, bucket| bucket.updated.elapsed() < full_after);
+        let removed = before - buckets.len();
+        if removed > 0 {
+            info!("Pruned {} idle rate limit buckets", removed);
+        }
+    }
+}
+
+/// Middleware для маршрутов запуска: превышение лимита — 429 с Retry-After.
+/// У соединений через Unix-сокет нет адреса клиента, они не ограничиваются.
+pub async fn rate_limit(
+    State(state): State<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let Some(ConnectInfo(addr)) = req.extensions().get::<ConnectInfo<SocketAddr>>().copied() else {
+        return Ok(next.run(req).await);
+    };
+    if let Err(retry_after) = state.rate_limiter.check(addr.ip()).await {
+        warn!("Rate limit exceeded for {}", addr.ip());
+        return Err(AppError::RateLimited(retry_after.as_secs_f64().ceil().max(1.0) as u64));
+    }
+    Ok(next.run(req).await)
+}
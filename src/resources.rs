@@ -0,0 +1,121 @@
+use crate::{
+    app_state::AppState,
+    models::{KilledReason, ScriptConfig},
+};
+use tokio::process::Command;
+
+// Сообщения интерпретаторов о нехватке памяти: при RLIMIT_AS или исчерпании
+// памяти системы процесс видит ошибку выделения памяти и завершается сам
+const OUT_OF_MEMORY_MARKERS: &[&str] = &[
+    "MemoryError",
+    "Cannot allocate memory",
+    "out of memory",
+    "std::bad_alloc",
+    "memory allocation of",
+];
+
+// Сколько последних байт stderr просматривается в поисках этих сообщений
+const STDERR_TAIL_BYTES: usize = 4096;
+
+/// Ограничения ресурсов процесса скрипта; `None` — без ограничения
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceLimits {
+    pub memory_mb: Option<u64>,
+    pub cpu_secs: Option<u64>,
+}
+
+impl ResourceLimits {
+    /// Ограничения из настроек скрипта или `RUNNER_MAX_MEMORY_MB` и
+    /// `RUNNER_MAX_CPU_SECS`; `0` в настройках снимает ограничение
+    pub fn for_script(state: &AppState, config: &ScriptConfig) -> Self {
+        Self {
+            memory_mb: config.memory_mb.or(state.memory_limit_mb).filter(|&mb| mb > 0),
+            cpu_secs: config.cpu_secs.or(state.cpu_limit_secs).filter(|&secs| secs > 0),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.memory_mb.is_none() && self.cpu_secs.is_none()
+    }
+}
+
+/// Устанавливает ограничения дочернему процессу перед запуском интерпретатора
+#[cfg(target_os = "linux")]
+pub fn apply(command: &mut Command, limits: ResourceLimits) {
+    if limits.is_empty() {
+        return;
+    }
+    // SAFETY: между fork и exec вызывается только setrlimit, он async-signal-safe
+    unsafe {
+        command.pre_exec(move || {
+            if let Some(mb) = limits.memory_mb {
+                check(libc::setrlimit(libc::RLIMIT_AS, &rlimit(mb.saturating_mul(1024 * 1024), 0)))?;
+            }
+            // По мягкому пределу процесс получает SIGXCPU, через секунду — SIGKILL
+            if let Some(secs) = limits.cpu_secs {
+                check(libc::setrlimit(libc::RLIMIT_CPU, &rlimit(secs, 1)))?;
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn rlimit(soft: u64, grace: u64) -> libc::rlimit {
+    libc::rlimit {
+        rlim_cur: soft as libc::rlim_t,
+        rlim_max: soft.saturating_add(grace) as libc::rlim_t,
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn check(ret: libc::c_int) -> std::io::Result<()> {
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn apply(_command: &mut Command, limits: ResourceLimits) {
+    use std::sync::Once;
+    static WARNED: Once = Once::new();
+    if !limits.is_empty() {
+        WARNED.call_once(|| {
+            tracing::warn!("Memory and CPU limits are supported only on Linux, running scripts without them")
+        });
+    }
+}
+
+/// Причина, по которой процесс, завершившийся сам или по чужому сигналу,
+/// остановлен ограничением ресурсов или нехваткой памяти
+pub fn killed_reason(
+    exit_code: i32,
+    signal: Option<i32>,
+    limits: ResourceLimits,
+    stderr: &[u8],
+) -> Option<KilledReason> {
+    if exit_code == 0 && signal.is_none() {
+        return None;
+    }
+    #[cfg(unix)]
+    if limits.cpu_secs.is_some() && matches!(signal, Some(libc::SIGXCPU) | Some(libc::SIGKILL)) {
+        return Some(KilledReason::CpuLimit);
+    }
+    let tail = &stderr[stderr.len().saturating_sub(STDERR_TAIL_BYTES)..];
+    let tail = String::from_utf8_lossy(tail);
+    if OUT_OF_MEMORY_MARKERS.iter().any(|marker| tail.contains(marker)) {
+        return Some(if limits.memory_mb.is_some() {
+            KilledReason::MemoryLimit
+        } else {
+            KilledReason::OutOfMemory
+        });
+    }
+    // Сервер сам SIGKILL здесь не посылает; обычно это OOM killer ядра
+    #[cfg(unix)]
+    if signal == Some(libc::SIGKILL) {
+        return Some(KilledReason::OutOfMemory);
+    }
+    None
+}
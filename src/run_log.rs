@@ -0,0 +1,175 @@
+use crate::{
+    models::{LogLine, LogStream},
+    secrets::RunSecrets,
+};
+use chrono::{DateTime, Utc};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+// Строка в том виде, в каком её прочитал сервер
+struct RawLine {
+    ts: DateTime<Utc>,
+    stream: LogStream,
+    bytes: Vec<u8>,
+}
+
+/// Строки stdout и stderr одного запуска в порядке, в котором их прочитал
+/// сервер. В лог попадает только вывод в пределах `RUNNER_MAX_OUTPUT_BYTES`.
+#[derive(Default)]
+pub struct LogCapture {
+    lines: Mutex<Vec<RawLine>>,
+}
+
+impl LogCapture {
+    /// Построчная запись одного потока в лог
+    pub fn writer(self: &Arc<Self>, stream: LogStream) -> LineWriter {
+        LineWriter {
+            capture: Arc::clone(self),
+            stream,
+            partial: Vec::new(),
+        }
+    }
+
+    /// Строки лога со скрытыми значениями секретов
+    pub fn take_lines(&self, secrets: &RunSecrets) -> Vec<LogLine> {
+        let lines = std::mem::take(&mut *self.lines.lock().unwrap());
+        lines
+            .into_iter()
+            .map(|RawLine { ts, stream, bytes }| {
+                let line = String::from_utf8_lossy(&bytes)
+                    .trim_end_matches('\n')
+                    .replace('\r', "");
+                LogLine {
+                    ts,
+                    stream,
+                    line: secrets.mask_text(line),
+                }
+            })
+            .collect()
+    }
+
+    fn push(&self, ts: DateTime<Utc>, stream: LogStream, bytes: Vec<u8>) {
+        self.lines.lock().unwrap().push(RawLine { ts, stream, bytes });
+    }
+}
+
+/// Делит прочитанные порции вывода на строки; незавершённая последняя
+/// строка попадает в лог, когда поток закрыт
+pub struct LineWriter {
+    capture: Arc<LogCapture>,
+    stream: LogStream,
+    partial: Vec<u8>,
+}
+
+impl LineWriter {
+    pub fn write(&mut self, bytes: &[u8]) {
+        let ts = Utc::now();
+        for piece in bytes.split_inclusive(|&b| b == b'\n') {
+            self.partial.extend_from_slice(piece);
+            if piece.ends_with(b"\n") {
+                let line = std::mem::take(&mut self.partial);
+                self.capture.push(ts, self.stream, line);
+            }
+        }
+    }
+}
+
+impl Drop for LineWriter {
+    fn drop(&mut self) {
+        if !self.partial.is_empty() {
+            let line = std::mem::take(&mut self.partial);
+            self.capture.push(Utc::now(), self.stream, line);
+        }
+    }
+}
+
+struct StoredLog {
+    script: String,
+    lines: Arc<Vec<LogLine>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    logs: HashMap<String, StoredLog>,
+    // run_id последних запусков каждого скрипта, от старых к новым
+    by_script: HashMap<String, VecDeque<String>>,
+}
+
+/// Логи последних `RUNNER_RUN_LOGS_PER_SCRIPT` запусков каждого скрипта в памяти
+pub struct RunLogs {
+    inner: Mutex<Inner>,
+    per_script: usize,
+}
+
+impl RunLogs {
+    pub fn new(per_script: usize) -> Self {
+        Self {
+            inner: Mutex::new(Inner::default()),
+            per_script,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.per_script > 0
+    }
+
+    /// Сохраняет лог запуска, вытесняя самый старый лог скрипта
+    pub fn record(&self, script: &str, run_id: &str, lines: Vec<LogLine>) {
+        if !self.enabled() {
+            return;
+        }
+        let mut inner = self.inner.lock().unwrap();
+        let runs = inner.by_script.entry(script.to_string()).or_default();
+        runs.push_back(run_id.to_string());
+        let evicted = if runs.len() > self.per_script {
+            runs.pop_front()
+        } else {
+            None
+        };
+        if let Some(evicted) = evicted {
+            inner.logs.remove(&evicted);
+        }
+        inner.logs.insert(
+            run_id.to_string(),
+            StoredLog {
+                script: script.to_string(),
+                lines: Arc::new(lines),
+            },
+        );
+    }
+
+    /// Имя скрипта и строки лога запуска
+    pub fn get(&self, run_id: &str) -> Option<(String, Arc<Vec<LogLine>>)> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .logs
+            .get(run_id)
+            .map(|log| (log.script.clone(), Arc::clone(&log.lines)))
+    }
+}
+
+/// Лог текстом: время, поток и строка через пробел
+pub fn render_text(lines: &[LogLine]) -> String {
+    let mut text = String::new();
+    for line in lines {
+        text.push_str(&format!(
+            "{} {} {}\n",
+            line.ts.format("%Y-%m-%dT%H:%M:%S%.3fZ"),
+            line.stream.as_str(),
+            line.line
+        ));
+    }
+    text
+}
+
+/// Лог в JSON Lines: объект `LogLine` в каждой строке
+pub fn render_jsonl(lines: &[LogLine]) -> String {
+    let mut text = String::new();
+    for line in lines {
+        text.push_str(&serde_json::to_string(line).unwrap_or_default());
+        text.push('\n');
+    }
+    text
+}
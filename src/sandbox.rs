@@ -0,0 +1,119 @@
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+/// Значение `RUNNER_SANDBOX`, выбирающее встроенный шаблон bubblewrap
+pub const BWRAP_PRESET: &str = "bwrap";
+
+// Только системные директории для чтения, интерпретатор и скрипт, рабочая
+// директория запуска для записи и пустой `/tmp`. Привязки идут после
+// `--tmpfs /tmp`: рабочая директория и код `/eval` лежат во временной директории.
+const BWRAP_TEMPLATE: &str = "bwrap --die-with-parent --unshare-pid --unshare-ipc --unshare-uts \
+    --ro-bind /usr /usr --ro-bind-try /bin /bin --ro-bind-try /lib /lib --ro-bind-try /lib64 /lib64 \
+    --ro-bind-try /etc/alternatives /etc/alternatives --ro-bind-try /etc/ld.so.cache /etc/ld.so.cache \
+    --proc /proc --dev /dev --tmpfs /tmp \
+    --ro-bind {interpreter} {interpreter} --ro-bind {script} {script} --bind {workdir} {workdir} \
+    --chdir {workdir} --";
+
+const PLACEHOLDERS: &[&str] = &["{interpreter}", "{script}", "{scripts_dir}", "{workdir}"];
+
+// Подставляется один раз при разборе шаблона
+const SCRIPTS_DIR: &str = "{scripts_dir}";
+
+/// Команда песочницы (`RUNNER_SANDBOX`), в которую оборачивается вызов
+/// интерпретатора; в её аргументах подставляются пути запуска
+#[derive(Debug, Clone)]
+pub struct Sandbox {
+    template: Vec<String>,
+}
+
+/// Пути одного запуска для подстановки в шаблон
+pub struct Vars<'a> {
+    pub interpreter: &'a str,
+    pub script: &'a Path,
+    pub workdir: &'a Path,
+}
+
+impl Sandbox {
+    /// `bwrap` — встроенный шаблон, иначе команда с подстановками
+    /// `{interpreter}`, `{script}`, `{scripts_dir}` и `{workdir}`
+    pub fn parse(value: &str, scripts_dir: &Path) -> Result<Self, String> {
+        let scripts_dir = std::path::absolute(scripts_dir).map_err(|e| e.to_string())?;
+        let value = match value.trim() {
+            BWRAP_PRESET => BWRAP_TEMPLATE,
+            value => value,
+        };
+        let template: Vec<String> = value
+            .split_whitespace()
+            .map(|token| token.replace(SCRIPTS_DIR, &scripts_dir.to_string_lossy()))
+            .collect();
+        if template.is_empty() {
+            return Err("sandbox command must not be empty".to_string());
+        }
+        for token in &template {
+            let rest = PLACEHOLDERS.iter().fold(token.clone(), |rest, p| rest.replace(p, ""));
+            if rest.contains('{') || rest.contains('}') {
+                return Err(format!("unknown placeholder in '{}'", token));
+            }
+        }
+        Ok(Self { template })
+    }
+
+    pub fn program(&self) -> &str {
+        &self.template[0]
+    }
+
+    /// Проверяет при старте, что программа песочницы установлена
+    pub fn probe(&self) -> Result<(), String> {
+        match find_program(self.program()) {
+            Some(_) => Ok(()),
+            None => Err(format!("'{}' is not installed", self.program())),
+        }
+    }
+
+    /// Аргументы программы песочницы до вызова интерпретатора. Пути
+    /// подставляются абсолютными, интерпретатор — найденным в `PATH`.
+    pub fn args(&self, vars: &Vars) -> std::io::Result<Vec<OsString>> {
+        let program = vars.interpreter.split_whitespace().next().unwrap_or(vars.interpreter);
+        let interpreter = find_program(program).unwrap_or_else(|| PathBuf::from(program));
+        let values = [
+            ("{interpreter}", interpreter),
+            ("{script}", std::path::absolute(vars.script)?),
+            ("{workdir}", std::path::absolute(vars.workdir)?),
+        ];
+        let args = self.template[1..]
+            .iter()
+            .map(|token| {
+                let arg = values.iter().fold(token.clone(), |arg, (placeholder, value)| {
+                    arg.replace(placeholder, &value.to_string_lossy())
+                });
+                OsString::from(arg)
+            })
+            .collect();
+        Ok(args)
+    }
+}
+
+// Путь к исполняемому файлу: как есть, если в имени есть `/`, иначе поиск в `PATH`
+pub fn find_program(program: &str) -> Option<PathBuf> {
+    if program.contains('/') {
+        let path = PathBuf::from(program);
+        return is_executable(&path).then_some(path);
+    }
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths)
+        .map(|dir| dir.join(program))
+        .find(|path| is_executable(path))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path).is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
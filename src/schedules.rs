@@ -0,0 +1,235 @@
+use crate::{
+    app_state::{AppState, ScheduleRecord},
+    arg_template,
+    cron::CronExpr,
+    error::AppError,
+    history,
+    limits,
+    models::{CreateScheduleRequest, OverlapPolicy, Schedule, ScheduleRun},
+    script_runner::{self, RunOptions},
+    utils::{stdin_bytes, validate_env, validate_script_name, write_atomic},
+};
+use chrono::{DateTime, TimeDelta, Timelike, Utc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+use tokio::{fs, sync::Mutex};
+use tracing::{info, info_span, warn, Instrument};
+use uuid::Uuid;
+
+/// Файл расписаний в директории скриптов
+pub const SCHEDULES_FILE: &str = ".schedules.json";
+
+fn parse_cron(cron: &str) -> Result<CronExpr, AppError> {
+    cron.parse()
+        .map_err(|e| AppError::BadRequest(format!("Invalid cron expression '{}': {}", cron, e)))
+}
+
+/// Загружает расписания, сохранённые до перезапуска. Повреждённый файл
+/// переименовывается, чтобы его не перезаписало первое же изменение.
+pub async fn load(state: &AppState) {
+    let path = state.scripts_dir.join(SCHEDULES_FILE);
+    let content = match fs::read(&path).await {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return,
+        Err(e) => {
+            warn!("Failed to read {}: {}", path.display(), e);
+            return;
+        }
+    };
+    let saved: Vec<Schedule> = match serde_json::from_slice(&content) {
+        Ok(saved) => saved,
+        Err(e) => {
+            let backup = path.with_extension("json.invalid");
+            warn!("Failed to parse {}: {}; moving it to {}", path.display(), e, backup.display());
+            let _ = fs::rename(&path, &backup).await;
+            return;
+        }
+    };
+
+    let now = Utc::now();
+    let mut schedules = state.schedules.lock().await;
+    for mut info in saved {
+        let cron = match info.cron.parse::<CronExpr>() {
+            Ok(cron) => cron,
+            Err(e) => {
+                warn!("Skipping schedule {} with invalid cron '{}': {}", info.id, info.cron, e);
+                continue;
+            }
+        };
+        // Запуски, пропущенные пока сервер был остановлен, не догоняются
+        info.next_run = cron.next_after(now);
+        let record = ScheduleRecord {
+            info,
+            cron,
+            running: Arc::new(Mutex::new(())),
+        };
+        schedules.insert(record.info.id.clone(), record);
+    }
+    info!("Loaded {} schedules", schedules.len());
+}
+
+// Записывает все расписания в файл. Вызывается под блокировкой списка,
+// чтобы более старый снимок не перезаписал более новый.
+async fn persist(state: &AppState, schedules: &HashMap<String, ScheduleRecord>) {
+    let mut saved: Vec<&Schedule> = schedules.values().map(|record| &record.info).collect();
+    saved.sort_by_key(|schedule| schedule.created_at);
+    let path = state.scripts_dir.join(SCHEDULES_FILE);
+    let result = match serde_json::to_vec_pretty(&saved) {
+        Ok(content) => write_atomic(&path, &content, true).await,
+        Err(e) => Err(std::io::Error::other(e)),
+    };
+    if let Err(e) = result {
+        warn!("Failed to save schedules to {}: {}", path.display(), e);
+    }
+}
+
+/// Создаёт расписание для существующего скрипта
+pub async fn create(state: &AppState, payload: CreateScheduleRequest) -> Result<Schedule, AppError> {
+    validate_script_name(&payload.script)?;
+    script_runner::resolve_script(state, &payload.script).await?;
+    let cron = parse_cron(&payload.cron)?;
+    let args = payload.args.unwrap_or_default();
+    limits::check_args(&state.run_limits, &args)?;
+    arg_template::validate(&args)?;
+    limits::check_data(&state.run_limits, &stdin_bytes(payload.data.as_ref())?)?;
+    let env = payload.env.unwrap_or_default();
+    let run_env: BTreeMap<String, String> = env.clone().into_iter().collect();
+    validate_env(&run_env)?;
+
+    let now = Utc::now();
+    let next_run = cron
+        .next_after(now)
+        .ok_or_else(|| AppError::BadRequest(format!("Cron expression '{}' never fires", payload.cron)))?;
+    let info = Schedule {
+        id: Uuid::new_v4().to_string(),
+        script: payload.script,
+        cron: payload.cron,
+        args,
+        data: payload.data,
+        env,
+        timeout_secs: payload.timeout_secs,
+        overlap: payload.overlap.unwrap_or_default(),
+        created_at: now,
+        next_run: Some(next_run),
+        last_run: None,
+        skipped: 0,
+    };
+
+    let mut schedules = state.schedules.lock().await;
+    schedules.insert(
+        info.id.clone(),
+        ScheduleRecord {
+            info: info.clone(),
+            cron,
+            running: Arc::new(Mutex::new(())),
+        },
+    );
+    persist(state, &schedules).await;
+    info!("Created schedule {} for {} ({})", info.id, info.script, info.cron);
+    Ok(info)
+}
+
+/// Все расписания в порядке создания
+pub async fn list(state: &AppState) -> Vec<Schedule> {
+    let schedules = state.schedules.lock().await;
+    let mut list: Vec<Schedule> = schedules.values().map(|record| record.info.clone()).collect();
+    list.sort_by_key(|schedule| schedule.created_at);
+    list
+}
+
+/// Удаляет расписание; уже идущий запуск доработает до конца
+pub async fn delete(state: &AppState, id: &str) -> Result<(), AppError> {
+    let mut schedules = state.schedules.lock().await;
+    if schedules.remove(id).is_none() {
+        return Err(AppError::ScheduleNotFound(id.to_string()));
+    }
+    persist(state, &schedules).await;
+    info!("Deleted schedule {}", id);
+    Ok(())
+}
+
+/// Фоновый цикл: в начале каждой минуты запускает наступившие расписания
+pub async fn run_scheduler(state: Arc<AppState>) {
+    loop {
+        let now = Utc::now();
+        let next_minute = now.with_second(0).and_then(|t| t.with_nanosecond(0)).unwrap_or(now)
+            + TimeDelta::minutes(1);
+        tokio::time::sleep((next_minute - now).to_std().unwrap_or_default()).await;
+
+        if state.shutdown.is_draining() {
+            return;
+        }
+        for id in take_due(&state, Utc::now()).await {
+            let span = info_span!("schedule", schedule_id = %id);
+            tokio::spawn(fire(Arc::clone(&state), id).instrument(span));
+        }
+    }
+}
+
+// Расписания, время которых наступило; их следующий запуск сдвигается
+async fn take_due(state: &AppState, now: DateTime<Utc>) -> Vec<String> {
+    let mut schedules = state.schedules.lock().await;
+    let mut due = Vec::new();
+    for (id, record) in schedules.iter_mut() {
+        if record.info.next_run.is_some_and(|next_run| next_run <= now) {
+            record.info.next_run = record.cron.next_after(now);
+            due.push(id.clone());
+        }
+    }
+    due
+}
+
+async fn fire(state: Arc<AppState>, id: String) {
+    let (info, running) = match state.schedules.lock().await.get(&id) {
+        Some(record) => (record.info.clone(), Arc::clone(&record.running)),
+        None => return,
+    };
+
+    let _running = match info.overlap {
+        OverlapPolicy::Skip => match running.try_lock_owned() {
+            Ok(guard) => guard,
+            Err(_) => {
+                info!("Skipping run of {}: previous run is still in progress", info.script);
+                if let Some(record) = state.schedules.lock().await.get_mut(&id) {
+                    record.info.skipped += 1;
+                }
+                return;
+            }
+        },
+        OverlapPolicy::Queue => running.lock_owned().await,
+    };
+    // Расписание могли удалить, пока запуск ждал в очереди
+    if !state.schedules.lock().await.contains_key(&id) {
+        return;
+    }
+
+    info!("Running scheduled script {}", info.script);
+    let input_bytes = stdin_bytes(info.data.as_ref()).unwrap_or_default();
+    let env: BTreeMap<String, String> = info.env.into_iter().collect();
+    // Периодический запуск всегда выполняет скрипт, а не отдаёт кэш
+    let options = RunOptions {
+        args: info.args,
+        env,
+        timeout_secs: info.timeout_secs,
+        no_cache: true,
+        ..Default::default()
+    };
+    let started_at = Utc::now();
+    let result = script_runner::run_script(Arc::clone(&state), &info.script, input_bytes, options).await;
+
+    let last_run = ScheduleRun {
+        run_id: result.as_ref().ok().and_then(|r| r.run_id.clone()),
+        started_at,
+        finished_at: Utc::now(),
+        status: history::run_status(result.as_ref()),
+        exit_code: result.as_ref().ok().map(|r| r.exit_code),
+        error: result.as_ref().err().map(|e| e.to_string()),
+    };
+    let mut schedules = state.schedules.lock().await;
+    if let Some(record) = schedules.get_mut(&id) {
+        record.info.last_run = Some(last_run);
+        persist(&state, &schedules).await;
+    }
+}
@@ -0,0 +1,272 @@
+use crate::{error::AppError, models::ScriptConfig, utils::write_atomic};
+use serde::Deserialize;
+use std::path::{Component, Path, PathBuf};
+use tokio::fs;
+use tracing::warn;
+
+/// Суффикс файла настроек рядом со скриптом: `report.py` -> `report.py.toml`
+pub const SIDECAR_SUFFIX: &str = ".toml";
+
+// Метка строки настроек в комментарии: `# runner: timeout=120 cache=false`
+const HEADER_MARKER: &str = "runner:";
+
+// Метка строки тегов в комментарии: `# tags: nightly, reports`
+const TAGS_MARKER: &str = "tags:";
+
+// Сколько строк в начале файла просматривается в поисках настроек
+const HEADER_MAX_LINES: usize = 20;
+
+const MAX_TAG_LEN: usize = 64;
+
+// Содержимое файла `<имя>.toml`; ключи совпадают с ключами комментария
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Sidecar {
+    timeout: Option<u64>,
+    cache: Option<bool>,
+    interpreter: Option<String>,
+    max_concurrent: Option<usize>,
+    parse_output: Option<bool>,
+    parse_traceback: Option<bool>,
+    memory_mb: Option<u64>,
+    cpu_secs: Option<u64>,
+    network: Option<bool>,
+    entrypoint: Option<String>,
+    tags: Option<Vec<String>>,
+    disabled: Option<bool>,
+}
+
+/// Путь к файлу настроек скрипта
+pub fn sidecar_path(script_path: &Path) -> PathBuf {
+    let mut path = script_path.as_os_str().to_owned();
+    path.push(SIDECAR_SUFFIX);
+    PathBuf::from(path)
+}
+
+/// Путь к скрипту, если `path` — файл его настроек
+pub fn script_for_sidecar(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?.to_str()?;
+    let script = name.strip_suffix(SIDECAR_SUFFIX)?;
+    Some(path.with_file_name(script))
+}
+
+/// Настройки скрипта из комментария `runner:` в начале файла и из `<имя>.toml`;
+/// значения из файла настроек важнее. Источник с ошибкой пишется в лог
+/// и игнорируется, чтобы скрипт оставался запускаемым.
+pub async fn load(script_path: &Path) -> ScriptConfig {
+    let name = script_path.display();
+    let mut config = match fs::read_to_string(script_path).await {
+        Ok(code) => parse_header(&code).unwrap_or_else(|e| {
+            warn!("Ignoring malformed runner header in {}: {}", name, e);
+            ScriptConfig::default()
+        }),
+        Err(_) => ScriptConfig::default(),
+    };
+
+    let sidecar_path = sidecar_path(script_path);
+    let text = match fs::read_to_string(&sidecar_path).await {
+        Ok(text) => text,
+        Err(_) => return config,
+    };
+    match parse_sidecar(&text) {
+        Ok(sidecar) => {
+            config.timeout_secs = sidecar.timeout.or(config.timeout_secs);
+            config.cache = sidecar.cache.or(config.cache);
+            config.interpreter = sidecar.interpreter.or(config.interpreter);
+            config.max_concurrent = sidecar.max_concurrent.or(config.max_concurrent);
+            config.parse_output = sidecar.parse_output.or(config.parse_output);
+            config.parse_traceback = sidecar.parse_traceback.or(config.parse_traceback);
+            config.memory_mb = sidecar.memory_mb.or(config.memory_mb);
+            config.cpu_secs = sidecar.cpu_secs.or(config.cpu_secs);
+            config.network = sidecar.network.or(config.network);
+            config.entrypoint = sidecar.entrypoint;
+            if let Some(tags) = sidecar.tags {
+                config.tags = tags;
+            }
+            config.disabled = sidecar.disabled.unwrap_or(false);
+        }
+        Err(e) => warn!("Ignoring malformed {}: {}", sidecar_path.display(), e),
+    }
+    config
+}
+
+// Строки `# runner: ...` и `# tags: ...` (или `// ...`) в ведущем блоке комментариев
+fn parse_header(code: &str) -> Result<ScriptConfig, String> {
+    let mut config = ScriptConfig::default();
+    let mut tags = Vec::new();
+    for line in code.lines().take(HEADER_MAX_LINES) {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("#!") {
+            continue;
+        }
+        let Some(comment) = line.strip_prefix('#').or_else(|| line.strip_prefix("//")) else {
+            break;
+        };
+        let comment = comment.trim();
+        if let Some(list) = comment.strip_prefix(TAGS_MARKER) {
+            tags.extend(list.split(','));
+            continue;
+        }
+        let Some(settings) = comment.strip_prefix(HEADER_MARKER) else {
+            continue;
+        };
+        for token in settings.split_whitespace() {
+            let (key, value) = token
+                .split_once('=')
+                .ok_or_else(|| format!("expected key=value, got '{}'", token))?;
+            match key {
+                "timeout" => config.timeout_secs = Some(parse_timeout(value)?),
+                "cache" => {
+                    let cache = value
+                        .parse()
+                        .map_err(|_| format!("invalid cache value '{}'", value))?;
+                    config.cache = Some(cache);
+                }
+                "interpreter" => config.interpreter = Some(parse_interpreter(value)?),
+                "max_concurrent" => {
+                    let max_concurrent = value
+                        .parse()
+                        .map_err(|_| format!("invalid max_concurrent value '{}'", value))?;
+                    config.max_concurrent = Some(max_concurrent);
+                }
+                "parse_output" => {
+                    let parse_output = value
+                        .parse()
+                        .map_err(|_| format!("invalid parse_output value '{}'", value))?;
+                    config.parse_output = Some(parse_output);
+                }
+                "parse_traceback" => {
+                    let parse_traceback = value
+                        .parse()
+                        .map_err(|_| format!("invalid parse_traceback value '{}'", value))?;
+                    config.parse_traceback = Some(parse_traceback);
+                }
+                "memory_mb" => {
+                    let memory_mb = value
+                        .parse()
+                        .map_err(|_| format!("invalid memory_mb value '{}'", value))?;
+                    config.memory_mb = Some(memory_mb);
+                }
+                "cpu_secs" => {
+                    let cpu_secs = value
+                        .parse()
+                        .map_err(|_| format!("invalid cpu_secs value '{}'", value))?;
+                    config.cpu_secs = Some(cpu_secs);
+                }
+                "network" => {
+                    let network = value
+                        .parse()
+                        .map_err(|_| format!("invalid network value '{}'", value))?;
+                    config.network = Some(network);
+                }
+                _ => return Err(format!("unknown key '{}'", key)),
+            }
+        }
+    }
+    config.tags = normalize_tags(tags)?;
+    Ok(config)
+}
+
+fn parse_sidecar(text: &str) -> Result<Sidecar, String> {
+    let mut sidecar: Sidecar = toml::from_str(text).map_err(|e| e.message().to_string())?;
+    if sidecar.timeout == Some(0) {
+        return Err("timeout must be greater than 0".to_string());
+    }
+    if let Some(interpreter) = &sidecar.interpreter {
+        parse_interpreter(interpreter)?;
+    }
+    if let Some(entrypoint) = &sidecar.entrypoint {
+        parse_entrypoint(entrypoint)?;
+    }
+    sidecar.tags = sidecar.tags.map(normalize_tags).transpose()?;
+    Ok(sidecar)
+}
+
+/// Теги в нижнем регистре без повторов, в исходном порядке; пустые значения
+/// пропускаются. Допустимы латинские буквы, цифры, `-` и `_`.
+pub fn normalize_tags<I, S>(tags: I) -> Result<Vec<String>, String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.as_ref().trim().to_ascii_lowercase();
+        if tag.is_empty() {
+            continue;
+        }
+        let valid = tag.len() <= MAX_TAG_LEN
+            && tag.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(format!("invalid tag '{}'", tag));
+        }
+        if !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    Ok(normalized)
+}
+
+/// Записывает теги в `<имя>.toml`, сохраняя остальные настройки файла.
+/// Теги из файла настроек заменяют теги из комментария скрипта.
+pub async fn save_tags(script_path: &Path, tags: &[String]) -> Result<(), AppError> {
+    let tags = tags.iter().cloned().map(toml::Value::String).collect();
+    update_sidecar(script_path, |table| {
+        table.insert("tags".to_string(), toml::Value::Array(tags));
+    })
+    .await
+}
+
+/// Записывает в `<имя>.toml` отметку `disabled = true` или снимает её
+pub async fn save_disabled(script_path: &Path, disabled: bool) -> Result<(), AppError> {
+    update_sidecar(script_path, |table| {
+        if disabled {
+            table.insert("disabled".to_string(), toml::Value::Boolean(true));
+        } else {
+            table.remove("disabled");
+        }
+    })
+    .await
+}
+
+// Изменяет один ключ файла настроек, сохраняя остальные
+async fn update_sidecar(script_path: &Path, update: impl FnOnce(&mut toml::Table)) -> Result<(), AppError> {
+    let sidecar_path = sidecar_path(script_path);
+    let mut table = match fs::read_to_string(&sidecar_path).await {
+        Ok(text) => text.parse::<toml::Table>().map_err(|e| {
+            AppError::BadRequest(format!("Cannot update malformed settings file: {}", e.message()))
+        })?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => toml::Table::new(),
+        Err(e) => return Err(AppError::Io(e)),
+    };
+    update(&mut table);
+    let text = toml::to_string(&table)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize settings: {}", e)))?;
+    write_atomic(&sidecar_path, text.as_bytes(), true).await?;
+    Ok(())
+}
+
+fn parse_timeout(value: &str) -> Result<u64, String> {
+    match value.parse::<u64>() {
+        Ok(secs) if secs > 0 => Ok(secs),
+        _ => Err(format!("invalid timeout '{}'", value)),
+    }
+}
+
+// Точка входа — файл внутри директории пакета
+fn parse_entrypoint(value: &str) -> Result<(), String> {
+    let inside = Path::new(value)
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)));
+    if value.is_empty() || !inside {
+        return Err(format!("entrypoint must be a relative path inside the package, got '{}'", value));
+    }
+    Ok(())
+}
+
+fn parse_interpreter(value: &str) -> Result<String, String> {
+    if value.trim().is_empty() {
+        return Err("interpreter must not be empty".to_string());
+    }
+    Ok(value.trim().to_string())
+}
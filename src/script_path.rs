@@ -0,0 +1,201 @@
+use axum::{
+    extract::{Request, State},
+    http::Uri,
+    middleware, Router,
+};
+use std::{collections::HashSet, sync::Arc};
+
+use crate::{
+    app_state::AppState,
+    namespace::{Namespace, DEFAULT_NAMESPACE},
+    script_runner,
+};
+
+// Маршруты, в которых после префикса идёт имя скрипта
+const SCRIPT_ROUTE_PREFIXES: &[&str] = &["/scripts/", "/run/", "/cache/"];
+
+// Маршруты, доступные внутри пространства имён: `/ns/{namespace}/scripts/...`
+const NAMESPACED_ROUTES: &[&str] = &["scripts", "run", "cache"];
+
+/// Кодирует `/` внутри имени вложенного скрипта (`/run/etl/extract.py/raw` ->
+/// `/run/etl%2Fextract.py/raw`), чтобы имя целиком попало в параметр `{name}`.
+/// Подключается вокруг всего роутера: переписывать URI нужно до маршрутизации.
+/// Маршруты пространства имён переписываются в обычные с именем из
+/// директории пространства: `/ns/team-a/run/report.py` -> `/run/team-a%2Freport.py`.
+pub async fn encode_nested_names(State(state): State<Arc<AppState>>, mut req: Request) -> Request {
+    let (namespace, path) = match strip_namespace(req.uri().path()) {
+        Some((namespace, path)) => (Some(namespace), path),
+        None => (None, req.uri().path().to_string()),
+    };
+    // Пакеты известны только сканеру, поэтому список нужен лишь для путей,
+    // в которых имя может оказаться вложенным пакетом
+    let packages = if may_name_package(&path) {
+        script_runner::package_names(&state).await
+    } else {
+        HashSet::new()
+    };
+    let path = match encode_path(&path, &packages) {
+        Some(encoded) => encoded,
+        None if namespace.is_some() => path,
+        None => return req,
+    };
+    if let Some(namespace) = namespace {
+        req.extensions_mut().insert(Namespace(namespace));
+    }
+    let path_and_query = match req.uri().query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path,
+    };
+    let mut parts = req.uri().clone().into_parts();
+    if let Ok(path_and_query) = path_and_query.parse() {
+        parts.path_and_query = Some(path_and_query);
+        if let Ok(uri) = Uri::from_parts(parts) {
+            *req.uri_mut() = uri;
+        }
+    }
+    req
+}
+
+/// Роутер за `encode_nested_names`. Внешний роутер принимает тело запроса
+/// любого типа (axum-server передаёт тело hyper) и переписывает URI до
+/// маршрутизации во внутреннем.
+pub fn with_nested_names(app: Router, state: Arc<AppState>) -> Router {
+    Router::new()
+        .fallback_service(app)
+        .layer(middleware::map_request_with_state(state, encode_nested_names))
+}
+
+// Пространство имён и путь без `/ns/{namespace}`; имя скрипта получает
+// префикс директории пространства (закодированный, чтобы не разделить имя)
+fn strip_namespace(path: &str) -> Option<(String, String)> {
+    let rest = path.strip_prefix("/ns/")?;
+    let (namespace, rest) = rest.split_once('/')?;
+    let (route, name) = match rest.split_once('/') {
+        Some((route, name)) => (route, Some(name)),
+        None => (rest, None),
+    };
+    if !NAMESPACED_ROUTES.contains(&route) || namespace.is_empty() {
+        return None;
+    }
+    let path = match name {
+        Some(name) if namespace != DEFAULT_NAMESPACE => format!("/{}/{}%2F{}", route, namespace, name),
+        Some(name) => format!("/{}/{}", route, name),
+        // Без имени доступен только список скриптов: `/run` и `/cache` охватывают все пространства
+        None if route == "scripts" => format!("/{}", route),
+        None => return None,
+    };
+    Some((namespace.to_string(), path))
+}
+
+// Имя скрипта заканчивается на первом сегменте с точкой: в именах
+// поддиректорий точки запрещены, а у файла скрипта всегда есть расширение.
+// Имя пакета точек не содержит, поэтому он ищется среди известных пакетов
+// (`/run/etl/loader/raw` -> `/run/etl%2Floader/raw`).
+fn encode_path(path: &str, packages: &HashSet<String>) -> Option<String> {
+    let (prefix, rest) = split_route(path)?;
+    let segments: Vec<&str> = rest.split('/').collect();
+    let file = segments.iter().position(|segment| segment.contains('.'));
+    let last = file.unwrap_or(segments.len() - 1);
+    let name_end = (1..=last)
+        .find(|&end| packages.contains(&package_name(&segments[..=end])))
+        .or(file)?;
+    if name_end == 0 {
+        return None;
+    }
+    let (name, tail) = segments.split_at(name_end + 1);
+    let mut encoded = format!("{}{}", prefix, name.join("%2F"));
+    for segment in tail {
+        encoded.push('/');
+        encoded.push_str(segment);
+    }
+    Some(encoded)
+}
+
+// Префикс маршрута скрипта и остаток пути после него
+fn split_route(path: &str) -> Option<(&'static str, &str)> {
+    SCRIPT_ROUTE_PREFIXES
+        .iter()
+        .find_map(|prefix| path.strip_prefix(prefix).map(|rest| (*prefix, rest)))
+}
+
+// Путь может начинаться с вложенного пакета: до первого сегмента с точкой
+// есть хотя бы два сегмента
+fn may_name_package(path: &str) -> bool {
+    split_route(path).is_some_and(|(_, rest)| {
+        rest.split('/').take_while(|segment| !segment.contains('.')).nth(1).is_some()
+    })
+}
+
+// Имя пакета из сегментов пути; пространство имён уже закодировано в первом
+fn package_name(segments: &[&str]) -> String {
+    segments.join("/").replace("%2F", "/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestServer;
+    use axum::{body::Body, extract::Path, routing::get};
+    use tower::ServiceExt;
+
+    // Маршруты с именем скрипта, отвечающие именем, которое получил обработчик
+    fn echo_router() -> Router {
+        async fn name(Path(name): Path<String>) -> String {
+            name
+        }
+        async fn raw(Path(name): Path<String>) -> String {
+            format!("raw {}", name)
+        }
+        Router::new()
+            .route("/run/{name}", get(name))
+            .route("/run/{name}/raw", get(raw))
+            .route("/scripts/{name}/versions", get(name))
+    }
+
+    async fn route(server: &TestServer, uri: &str) -> String {
+        let app = with_nested_names(echo_router(), Arc::clone(&server.state));
+        let response = app
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert!(response.status().is_success(), "{} -> {}", uri, response.status());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn routes_nested_scripts_and_packages() {
+        let server = TestServer::new(|_| {}).await;
+        server.add_script("etl/extract.py", "print('extract')\n").await;
+        server.add_script("team/pkg/__main__.py", "print('pkg')\n").await;
+        script_runner::register_script(&server.state, server.state.script_path("team/pkg")).await;
+        assert!(script_runner::package_names(&server.state).await.contains("team/pkg"));
+
+        let cases = [
+            ("/run/hello.py", "hello.py"),
+            ("/run/etl/extract.py", "etl/extract.py"),
+            ("/run/etl/extract.py/raw", "raw etl/extract.py"),
+            ("/run/etl%2Fextract.py", "etl/extract.py"),
+            ("/run/team/pkg", "team/pkg"),
+            ("/run/team/pkg/raw", "raw team/pkg"),
+            ("/run/team%2Fpkg/raw", "raw team/pkg"),
+            ("/scripts/team/pkg/versions", "team/pkg"),
+            ("/run/toplevel", "toplevel"),
+        ];
+        for (uri, expected) in cases {
+            assert_eq!(route(&server, uri).await, expected, "{}", uri);
+        }
+    }
+
+    #[test]
+    fn unknown_nested_directory_is_not_encoded() {
+        let packages = HashSet::from(["team/pkg".to_string()]);
+        assert_eq!(encode_path("/run/team/other", &packages), None);
+        assert_eq!(encode_path("/run/team/pkg/raw", &packages).as_deref(), Some("/run/team%2Fpkg/raw"));
+        // Путь после `strip_namespace`: пространство имён уже закодировано
+        let namespaced = HashSet::from(["ns/team/pkg".to_string()]);
+        assert_eq!(encode_path("/run/ns%2Fteam/pkg", &namespaced).as_deref(), Some("/run/ns%2Fteam%2Fpkg"));
+        assert!(!may_name_package("/run/etl/extract.py"));
+        assert!(may_name_package("/run/team/pkg"));
+    }
+}
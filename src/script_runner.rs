@@ -1,179 +1,1754 @@
 use crate::{
-    app_state::{AppState, CachedResult},
+    app_state::{AppState, CachedResult, ScriptEntry, ScriptLimiter},
+    arg_template,
+    artifacts,
     db,
+    deps,
     error::AppError,
-    models::ScriptResult,
+    exec_user::{self, ExecUser},
+    network,
+    package::{self, Launch},
+    history,
+    inflight::{self, Flight, Outcome},
+    jobs,
+    stats,
+    models::{
+        EventData, Freshness, HistoryEntry, Invocation, KilledReason, LogStream, OutputEncoding, RunStatus, ScriptConfig,
+        ScriptKind, ScriptResult, TagMatch, Termination, TimeoutBudget, WebhookPayload,
+    },
+    resources::{self, ResourceLimits},
+    run_log::{LineWriter, LogCapture},
+    sandbox::{self, Sandbox},
+    secrets::RunSecrets,
+    script_config,
+    traceback,
+    utils::{encode_output, is_valid_dir_name, relative_name, sha256_hex, validate_script_name, MAX_SCRIPT_DEPTH},
+    workdir::RunDir,
+    worker_pool::PoolTask,
 };
 use bytes::Bytes;
+use reqwest::Url;
+use sha2::{Digest, Sha256};
 use chrono::{DateTime, Utc};
+use globset::GlobMatcher;
 use mongodb::bson::{doc};
 use std::{
-    collections::hash_map::DefaultHasher,
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap, HashSet},
+    future::Future,
     hash::{Hash, Hasher},
-    sync::Arc,
+    path::{Path, PathBuf},
+    process::ExitStatus,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
     time::{Duration, Instant, SystemTime},
 };
 use tokio::{
     fs,
-    io::AsyncWriteExt,
-    process::Command,
-    time::timeout,
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader},
+    process::{Child, Command},
+    sync::{mpsc, Notify, OwnedSemaphorePermit, SemaphorePermit},
+    task::JoinHandle,
+    time::{timeout, timeout_at},
 };
-use tracing::{info, warn};
+use tracing::{info, info_span, warn, Instrument};
+use uuid::Uuid;
 
-async fn get_mtime(path: &std::path::Path) -> Option<SystemTime> {
-    fs::metadata(path).await.ok().and_then(|m| m.modified().ok())
+// Сколько строк вывода буферизуется для потокового клиента
+const STREAM_CHANNEL_CAPACITY: usize = 256;
+
+// Через сколько секунд предлагать повторить запрос, когда все слоты заняты
+const BUSY_RETRY_AFTER_SECS: u64 = 5;
+
+// Сколько ждать завершения скрипта после SIGTERM перед SIGKILL
+const KILL_GRACE_PERIOD: Duration = Duration::from_secs(2);
+
+/// Вывод процесса в один поток, не больше `RUNNER_MAX_OUTPUT_BYTES`
+#[derive(Debug, Clone, Default)]
+pub struct PipeOutput {
+    pub bytes: Vec<u8>,
+    pub truncated: bool,
+}
+
+type PipeTask = JoinHandle<std::io::Result<PipeOutput>>;
+
+// Читает поток порциями до лимита; при превышении будит `overflow`
+// и перестаёт читать — процесс будет остановлен. Прочитанное в пределах
+// лимита построчно пишется в лог запуска, если он ведётся.
+fn read_pipe<R>(pipe: Option<R>, limit: usize, overflow: Arc<Notify>, mut log: Option<LineWriter>) -> PipeTask
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut output = PipeOutput::default();
+        let Some(mut pipe) = pipe else {
+            return Ok(output);
+        };
+        let mut chunk = [0u8; 8192];
+        loop {
+            let n = pipe.read(&mut chunk).await?;
+            if n == 0 {
+                break;
+            }
+            let room = limit - output.bytes.len();
+            let kept = &chunk[..n.min(room)];
+            output.bytes.extend_from_slice(kept);
+            if let Some(log) = &mut log {
+                log.write(kept);
+            }
+            if n > room {
+                output.truncated = true;
+                overflow.notify_one();
+                break;
+            }
+        }
+        Ok(output)
+    })
+}
+
+async fn join_pipe(task: PipeTask) -> Result<PipeOutput, AppError> {
+    task.await
+        .map_err(|e| AppError::Internal(format!("Output reader failed: {}", e)))?
+        .map_err(AppError::Io)
+}
+
+// Дочитывает то, что процесс успел вывести до остановки
+async fn collect_partial(task: PipeTask) -> PipeOutput {
+    match timeout(KILL_GRACE_PERIOD, task).await {
+        Ok(Ok(Ok(output))) => output,
+        _ => PipeOutput::default(),
+    }
+}
+
+// Процесс скрипта — лидер собственной группы (см. `spawn_script`), поэтому
+// сигнал получают и все порождённые им процессы, в том числе внутри песочницы
+#[cfg(unix)]
+fn signal_group(child: &Child, signal: libc::c_int) -> bool {
+    match child.id() {
+        // SAFETY: pid принадлежит нашему ещё не собранному дочернему процессу
+        Some(pid) => unsafe { libc::kill(-(pid as libc::pid_t), signal) == 0 },
+        None => false,
+    }
+}
+
+#[cfg(unix)]
+fn send_sigterm(child: &Child) -> bool {
+    signal_group(child, libc::SIGTERM)
+}
+
+#[cfg(not(unix))]
+fn send_sigterm(_child: &Child) -> bool {
+    false
+}
+
+/// Группа процессов запуска. После выхода скрипта `Child::id` уже пуст,
+/// поэтому номер группы запоминается сразу после запуска. При удалении
+/// оставшиеся в группе процессы получают SIGKILL — в том числе когда future
+/// запуска сброшен, не дойдя до `terminate_child`. После сборки лидера
+/// номер группы может достаться другому процессу, поэтому тогда сигнал
+/// больше не отправляется.
+struct ProcessGroup(Option<u32>);
+
+impl ProcessGroup {
+    fn of(child: &Child) -> Self {
+        Self(child.id())
+    }
+
+    /// Завершает процессы, которые скрипт оставил после себя; без этого они
+    /// продолжают работать и держат открытыми stdout и stderr запуска
+    fn kill(&self) {
+        #[cfg(unix)]
+        if let Some(pid) = self.0 {
+            // SAFETY: лидер группы ещё не собран, поэтому номер принадлежит
+            // группе запуска; если в ней никого не осталось, kill вернёт ESRCH
+            unsafe {
+                libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+            }
+        }
+    }
+
+    /// Завершает оставшиеся в группе процессы и собирает вышедшего лидера
+    /// (см. `wait_exited`)
+    async fn reap(&mut self, child: &mut Child) -> std::io::Result<ExitStatus> {
+        self.kill();
+        let status = child.wait().await;
+        self.0 = None;
+        status
+    }
+}
+
+impl Drop for ProcessGroup {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+/// Ждёт выхода процесса, не собирая его: пока лидер остаётся зомби, номер
+/// его группы не может достаться другому процессу. Собирает процесс
+/// `ProcessGroup::reap`.
+#[cfg(unix)]
+async fn wait_exited(child: &mut Child) -> std::io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let Some(pid) = child.id() else {
+        return Ok(());
+    };
+    // Подписка до проверки: SIGCHLD между проверкой и ожиданием не теряется
+    let mut sigchld = signal(SignalKind::child())?;
+    while !has_exited(pid)? {
+        sigchld.recv().await;
+    }
+    Ok(())
+}
+
+// Проверка без ожидания. `siginfo_t` не держится через `await`: в новых
+// версиях libc он не `Send`.
+#[cfg(unix)]
+fn has_exited(pid: u32) -> std::io::Result<bool> {
+    // SAFETY: нули — допустимое значение siginfo_t
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    // SAFETY: pid — наш дочерний процесс; WNOWAIT оставляет его несобранным
+    let rc = unsafe {
+        libc::waitid(
+            libc::P_PID,
+            pid as libc::id_t,
+            &mut info,
+            libc::WEXITED | libc::WNOHANG | libc::WNOWAIT,
+        )
+    };
+    if rc != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // Пока процесс работает, waitid с WNOHANG оставляет si_signo нулевым
+    Ok(info.si_signo == libc::SIGCHLD)
+}
+
+#[cfg(not(unix))]
+async fn wait_exited(child: &mut Child) -> std::io::Result<()> {
+    child.wait().await.map(|_| ())
+}
+
+/// Останавливает группу процессов скрипта: SIGTERM, короткое ожидание,
+/// затем SIGKILL. На Windows сигналов нет, и процесс сразу завершается
+/// `TerminateProcess` (`Child::kill`). В любом случае процесс собирается
+/// через `wait`, чтобы не оставлять зомби.
+async fn terminate_child(child: &mut Child, group: &mut ProcessGroup) -> Option<ExitStatus> {
+    let exited = send_sigterm(child)
+        && matches!(timeout(KILL_GRACE_PERIOD, wait_exited(child)).await, Ok(Ok(())));
+    if !exited {
+        if let Err(e) = child.start_kill() {
+            warn!("Failed to kill script process: {}", e);
+            return None;
+        }
+    }
+    group.reap(child).await.ok()
+}
+
+// Сигнал, завершивший процесс; `None`, если процесс завершился сам
+#[cfg(unix)]
+fn exit_signal(status: &ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal()
+}
+
+#[cfg(not(unix))]
+fn exit_signal(_status: &ExitStatus) -> Option<i32> {
+    None
+}
+
+// Ограничения процесса скрипта, применяемые между fork и exec,
+// и песочница, в которой запускается интерпретатор
+#[derive(Debug, Clone)]
+struct Confinement {
+    resources: ResourceLimits,
+    user: Option<ExecUser>,
+    isolate_network: bool,
+    sandbox: Option<Arc<Sandbox>>,
+}
+
+// Команда интерпретатора может содержать собственные аргументы ("python3 -u").
+// Переменные из `env` добавляются к окружению сервера (или к минимальному
+// окружению при `RUNNER_EXEC_UID`). Скрипт выполняется в `workdir`.
+// С песочницей запускается её программа, а интерпретатор — внутри неё.
+fn spawn_script(
+    interpreter: &str,
+    launch: &Launch,
+    args: &[String],
+    env: &BTreeMap<String, String>,
+    workdir: &Path,
+    confinement: Confinement,
+) -> Result<Child, AppError> {
+    let mut parts = interpreter.split_whitespace();
+    let program = parts.next().unwrap_or(interpreter);
+    let Confinement { resources, user, isolate_network, sandbox } = confinement;
+    let mut command = match &sandbox {
+        Some(sandbox) => {
+            let vars = sandbox::Vars {
+                interpreter: program,
+                script: launch.path(),
+                workdir,
+            };
+            let mut command = Command::new(sandbox.program());
+            command.args(sandbox.args(&vars)?).arg(program);
+            command
+        }
+        None => Command::new(program),
+    };
+    exec_user::apply(&mut command, user, workdir);
+    command.args(parts);
+    launch.add_args(&mut command)?;
+    command.args(args).envs(env);
+    launch.set_python_path(&mut command, user.is_none());
+    command
+        .current_dir(workdir)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .kill_on_drop(true);
+    // Своя группа процессов: остановка скрипта завершает и порождённые им процессы
+    #[cfg(unix)]
+    command.process_group(0);
+    resources::apply(&mut command, resources);
+    network::apply(&mut command, isolate_network);
+    command.spawn().map_err(|e| match (user, &sandbox) {
+        (Some(user), _) if exec_user::is_refused(&e) => exec_user::refused(user, e),
+        (_, Some(sandbox)) if e.kind() == std::io::ErrorKind::NotFound => {
+            AppError::SandboxUnavailable(format!("'{}' is not installed", sandbox.program()))
+        }
+        _ => spawn_error(interpreter, e),
+    })
+}
+
+// Отсутствующий интерпретатор — отдельная ошибка, а не общий IO error
+pub fn spawn_error(interpreter: &str, e: std::io::Error) -> AppError {
+    if e.kind() == std::io::ErrorKind::NotFound {
+        let program = interpreter.split_whitespace().next().unwrap_or(interpreter);
+        AppError::InterpreterNotFound(program.to_string())
+    } else {
+        AppError::Io(e)
+    }
+}
+
+impl ScriptResult {
+    pub fn from_output(
+        stdout: &PipeOutput,
+        stderr: &PipeOutput,
+        exit_code: i32,
+        timed_out: bool,
+    ) -> Self {
+        let (stdout_text, stdout_encoding) = encode_output(&stdout.bytes);
+        let (stderr_text, stderr_encoding) = encode_output(&stderr.bytes);
+        Self {
+            stdout: stdout_text,
+            stderr: stderr_text,
+            exit_code,
+            timed_out,
+            stdout_encoding,
+            stderr_encoding,
+            stdout_truncated: stdout.truncated,
+            stderr_truncated: stderr.truncated,
+            termination: if timed_out { Termination::TimedOut } else { Termination::Exited },
+            timeout_budget: timed_out.then_some(TimeoutBudget::Exec),
+            ..Default::default()
+        }
+    }
+
+    /// Результат запуска, завершившегося ошибкой, в том же виде, что и обычный
+    /// (для пакетного запуска и цепочек)
+    pub fn from_error(e: &AppError) -> Self {
+        let timed_out = matches!(e, AppError::Timeout);
+        Self {
+            stderr: format!("Error: {}", e),
+            exit_code: -1,
+            timed_out,
+            termination: if timed_out { Termination::TimedOut } else { Termination::Exited },
+            timeout_budget: e.timeout_budget(),
+            ..Default::default()
+        }
+    }
+
+    fn set_signal(&mut self, signal: Option<i32>) {
+        self.signal = signal;
+        if signal.is_some() && !self.timed_out {
+            self.termination = Termination::Signaled;
+        }
+    }
+}
+
+// Причина, по которой выполнение прервано до завершения процесса
+enum Interrupted {
+    Cancelled,
+    Shutdown,
+    OutputLimit,
+}
+
+async fn wait_cancel(cancel: &Option<Arc<Notify>>) {
+    match cancel {
+        Some(cancel) => cancel.notified().await,
+        None => std::future::pending().await,
+    }
+}
+
+// Запуск, ожидающий слота семафора; снимается с учёта при удалении
+struct QueueSlot<'a>(&'a AtomicUsize);
+
+impl Drop for QueueSlot<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// Занятые запуском слоты: скрипта и пространства имён (если для них есть
+// ограничение) и общий
+struct RunPermit<'a> {
+    _script: Option<OwnedSemaphorePermit>,
+    _namespace: Option<OwnedSemaphorePermit>,
+    _global: SemaphorePermit<'a>,
+}
+
+/// Ждёт свободного слота не дольше `wait_limit`; прерывается отменой
+/// запроса или принудительной остановкой сервера. Сначала занимаются слоты
+/// скрипта и пространства имён, чтобы ожидающие их запуски не держали общих слотов.
+async fn acquire_permit<'a>(
+    state: &'a AppState,
+    script_name: &str,
+    limiter: Option<Arc<ScriptLimiter>>,
+    wait_limit: Option<Duration>,
+    cancelled: impl Future<Output = ()>,
+) -> Result<RunPermit<'a>, AppError> {
+    state.queued.fetch_add(1, Ordering::Relaxed);
+    let _queued = QueueSlot(&state.queued);
+
+    let deadline = wait_limit.map(|limit| tokio::time::Instant::now() + limit);
+    let acquire = async {
+        let script = match limiter {
+            Some(limiter) => Some(acquire_script_slot(state, script_name, limiter, deadline).await?),
+            None => None,
+        };
+        let busy = || {
+            state.busy_rejections.fetch_add(1, Ordering::Relaxed);
+            AppError::Busy(BUSY_RETRY_AFTER_SECS)
+        };
+        let namespace = match state.namespaces.semaphore(script_name) {
+            Some(semaphore) => match until(deadline, semaphore.acquire_owned()).await {
+                Some(permit) => Some(permit.unwrap()),
+                None => return Err(busy()),
+            },
+            None => None,
+        };
+        match until(deadline, state.semaphore.acquire()).await {
+            Some(permit) => Ok(RunPermit {
+                _script: script,
+                _namespace: namespace,
+                _global: permit.unwrap(),
+            }),
+            None => Err(busy()),
+        }
+    };
+    tokio::select! {
+        permit = acquire => permit,
+        _ = cancelled => Err(AppError::Cancelled(None)),
+        _ = state.shutdown.killed() => Err(AppError::ShuttingDown),
+    }
 }
 
+async fn until<F: Future>(deadline: Option<tokio::time::Instant>, future: F) -> Option<F::Output> {
+    match deadline {
+        Some(deadline) => timeout_at(deadline, future).await.ok(),
+        None => Some(future.await),
+    }
+}
+
+// Слот скрипта; если очередь скрипта заполнена (`RUNNER_SCRIPT_MAX_QUEUED`)
+// или слот не освободился до `deadline`, запуск отклоняется с `script_busy`
+async fn acquire_script_slot(
+    state: &AppState,
+    script_name: &str,
+    limiter: Arc<ScriptLimiter>,
+    deadline: Option<tokio::time::Instant>,
+) -> Result<OwnedSemaphorePermit, AppError> {
+    if let Ok(permit) = Arc::clone(&limiter.semaphore).try_acquire_owned() {
+        return Ok(permit);
+    }
+    let busy = |queue_timeout| {
+        limiter.rejections.fetch_add(1, Ordering::Relaxed);
+        AppError::ScriptBusy {
+            script: script_name.to_string(),
+            retry_after: BUSY_RETRY_AFTER_SECS,
+            queue_timeout,
+        }
+    };
+    let waiting = limiter.queued.fetch_add(1, Ordering::Relaxed);
+    let _queued = QueueSlot(&limiter.queued);
+    if waiting >= state.script_max_queued {
+        return Err(busy(false));
+    }
+    match until(deadline, Arc::clone(&limiter.semaphore).acquire_owned()).await {
+        Some(permit) => Ok(permit.unwrap()),
+        None => Err(busy(true)),
+    }
+}
+
+/// Ограничитель одновременных запусков скрипта; `None` — без ограничения.
+/// Изменённое ограничение действует на новые запуски, уже идущие
+/// дорабатывают со слотами прежнего.
+async fn script_limiter(
+    state: &AppState,
+    script_name: &str,
+    max_concurrent: Option<usize>,
+) -> Option<Arc<ScriptLimiter>> {
+    let limit = max_concurrent.filter(|&limit| limit > 0)?;
+    let mut limiters = state.script_limiters.lock().await;
+    let limiter = limiters
+        .entry(script_name.to_string())
+        .or_insert_with(|| Arc::new(ScriptLimiter::new(limit)));
+    if limiter.limit != limit {
+        *limiter = Arc::new(ScriptLimiter::new(limit));
+    }
+    Some(Arc::clone(limiter))
+}
+
+/// Параметры одного запуска скрипта
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    pub args: Vec<String>,
+    /// Дополнительные переменные окружения (проверяются `utils::validate_env`)
+    pub env: BTreeMap<String, String>,
+    pub timeout_secs: Option<u64>,
+    /// Задача асинхронного API, статус которой обновляется по ходу запуска
+    pub job_id: Option<Uuid>,
+    /// Сигнал отмены: снимает запуск из очереди или завершает процесс
+    pub cancel: Option<Arc<Notify>>,
+    /// Не читать и не записывать кэш результатов
+    pub no_cache: bool,
+    /// Не удалять рабочую директорию запуска (для отладки); кэш не используется
+    pub keep_workdir: bool,
+    /// Куда отправить уведомление о завершении (проверен `Webhooks::validate`)
+    pub callback_url: Option<Url>,
+    /// По таймауту вернуть результат с `timed_out` и выводом до остановки вместо ошибки
+    pub partial_on_timeout: bool,
+    /// Интерпретатор из запроса (проверен `AppState::check_interpreter`)
+    pub interpreter: Option<String>,
+    /// Ограничение одновременных запусков скрипта из его настроек
+    /// или `RUNNER_SCRIPT_MAX_CONCURRENT`
+    pub max_concurrent: Option<usize>,
+    /// Разобрать stdout как JSON в поле `output`; без значения — по настройкам скрипта
+    pub parse_output: Option<bool>,
+    /// Не возвращать stdout, если он разобран в `output`
+    pub omit_stdout: bool,
+    /// Разобрать traceback Python из stderr в поле `error`; без значения — по настройкам скрипта
+    pub parse_traceback: Option<bool>,
+    /// Ограничения памяти и процессорного времени процесса
+    pub resources: ResourceLimits,
+    /// Идентификатор запуска, под которым сохраняются артефакты
+    pub run_id: Option<String>,
+    /// Имена секретов; значения подставляются только при запуске процесса
+    pub secrets: Vec<String>,
+    /// Сколько ждать свободного слота (секунды); ограничено `RUNNER_MAX_QUEUE_WAIT_SECS`
+    pub queue_timeout_secs: Option<u64>,
+    /// `false` — запустить без доступа к сети; без значения — по настройкам скрипта
+    pub network: Option<bool>,
+    /// Изоляция от сети для этого запуска (см. `network::isolation`)
+    pub network_isolated: Option<bool>,
+    /// Вызывающий, которому засчитывается запуск (см. `quota::Caller`)
+    pub caller: Option<String>,
+}
+
+/// Путь к скрипту, его настройки и хэш, если скрипт известен сканеру
+pub async fn resolve_script(
+    state: &AppState,
+    script_name: &str,
+) -> Result<(PathBuf, ScriptEntry), AppError> {
+    validate_script_name(script_name)?;
+    let script_path = state.script_path(script_name);
+    let scripts = state.scripts.lock().await;
+    match scripts.get(&script_path) {
+        Some(entry) => Ok((script_path, entry.clone())),
+        None => Err(AppError::ScriptNotFound(script_name.to_string())),
+    }
+}
+
+/// Имена известных сканеру скриптов с тегами `tags` (любым или всеми),
+/// по алфавиту
+pub async fn scripts_with_tags(state: &AppState, tags: &[String], tag_match: TagMatch) -> Vec<String> {
+    if tags.is_empty() {
+        return Vec::new();
+    }
+    let scripts = state.scripts.lock().await;
+    let mut names: Vec<String> = scripts
+        .iter()
+        .filter(|(_, entry)| match tag_match {
+            TagMatch::Any => tags.iter().any(|tag| entry.config.tags.contains(tag)),
+            TagMatch::All => tags.iter().all(|tag| entry.config.tags.contains(tag)),
+        })
+        .filter_map(|(path, _)| state.script_name(path))
+        .collect();
+    names.sort();
+    names
+}
+
+/// Имена известных сканеру скриптов, подходящих под шаблон, по алфавиту
+pub async fn scripts_matching(state: &AppState, glob: &GlobMatcher) -> Vec<String> {
+    let scripts = state.scripts.lock().await;
+    let mut names: Vec<String> = scripts
+        .keys()
+        .filter_map(|path| state.script_name(path))
+        .filter(|name| glob.is_match(name))
+        .collect();
+    names.sort();
+    names
+}
+
+/// Имена известных сканеру пакетов
+pub async fn package_names(state: &AppState) -> HashSet<String> {
+    let scripts = state.scripts.lock().await;
+    scripts
+        .iter()
+        .filter(|(_, entry)| entry.kind == ScriptKind::Package)
+        .filter_map(|(path, _)| state.script_name(path))
+        .collect()
+}
+
+/// Имена скриптов с отключёнными запусками
+pub async fn disabled_names(state: &AppState) -> HashSet<String> {
+    let scripts = state.scripts.lock().await;
+    scripts
+        .iter()
+        .filter(|(_, entry)| entry.config.disabled)
+        .filter_map(|(path, _)| state.script_name(path))
+        .collect()
+}
+
+/// Запуски скрипта отключены; неизвестный скрипт не считается отключённым
+pub async fn is_disabled(state: &AppState, script_name: &str) -> bool {
+    let scripts = state.scripts.lock().await;
+    scripts
+        .get(&state.script_path(script_name))
+        .is_some_and(|entry| entry.config.disabled)
+}
+
+/// Настройки скрипта и хэш его текущего содержимого
+pub async fn load_entry(path: &Path) -> ScriptEntry {
+    reload_entry(path, None).await
+}
+
+// Как `load_entry`, но хэш `previous` переиспользуется, если mtime файла
+// не изменился: сканирование не перечитывает неизменённые скрипты.
+// Настройки читаются всегда — sidecar-файл мог измениться отдельно.
+// Пакет перечитывается целиком, если изменился любой его файл.
+async fn reload_entry(path: &Path, previous: Option<&ScriptEntry>) -> ScriptEntry {
+    let meta = fs::metadata(path).await.ok();
+    let config = script_config::load(path).await;
+    let is_package = meta.as_ref().is_some_and(|m| m.is_dir());
+    let snapshot = if is_package { package::snapshot(path).await.ok() } else { None };
+    let (kind, mtime) = if is_package {
+        (ScriptKind::Package, snapshot.as_ref().and_then(|s| s.newest))
+    } else {
+        (ScriptKind::File, meta.and_then(|m| m.modified().ok()))
+    };
+    let hash = match (previous, &snapshot) {
+        (Some(previous), _) if mtime.is_some() && previous.mtime == mtime => previous.hash.clone(),
+        (_, Some(snapshot)) => package::hash(path, snapshot).await,
+        _ => fs::read(path).await.map(|c| sha256_hex(&c)).unwrap_or_default(),
+    };
+    ScriptEntry { config, kind, hash, mtime }
+}
+
+// Хэш скрипта по последнему сканированию или сохранению
+async fn known_hash(state: &AppState, path: &Path) -> Option<String> {
+    state.scripts.lock().await.get(path).map(|entry| entry.hash.clone())
+}
+
+/// Добавляет скрипт в список или перечитывает его настройки после изменения файла
+pub async fn register_script(state: &AppState, path: PathBuf) {
+    let entry = load_entry(&path).await;
+    state.scripts.lock().await.insert(path, entry);
+}
+
+// Интерпретатор из запроса, из настроек скрипта или назначенный его расширению.
+// Скрипт с requirements выполняется python-ом своего виртуального окружения.
+async fn script_interpreter(
+    state: &Arc<AppState>,
+    script_name: &str,
+    entry: &ScriptEntry,
+    options: &RunOptions,
+) -> Result<String, AppError> {
+    if let Some(interpreter) = &options.interpreter {
+        return Ok(interpreter.clone());
+    }
+    let interpreter = base_interpreter(state, script_name, entry)?;
+    Ok(deps::ensure(state, script_name, &interpreter).await?.unwrap_or(interpreter))
+}
+
+/// Интерпретатор из настроек скрипта или назначенный его расширению
+/// (пакету — назначенный `.py`); им же создаётся виртуальное окружение скрипта
+pub fn base_interpreter(
+    state: &AppState,
+    script_name: &str,
+    entry: &ScriptEntry,
+) -> Result<String, AppError> {
+    match (&entry.config.interpreter, entry.kind) {
+        (Some(interpreter), _) => Ok(interpreter.clone()),
+        (None, ScriptKind::File) => Ok(state.interpreter_for(script_name)?.to_string()),
+        (None, ScriptKind::Package) => state
+            .interpreters
+            .get(package::EXTENSION)
+            .cloned()
+            .ok_or_else(|| AppError::UnsupportedScriptType(script_name.to_string())),
+    }
+}
+
+// Файл скрипта или пакет, который передаётся интерпретатору
+fn launch_for(state: &AppState, script_name: &str, path: &Path, entry: &ScriptEntry) -> Result<Launch, AppError> {
+    match entry.kind {
+        ScriptKind::File => Ok(Launch::File(path.to_path_buf())),
+        ScriptKind::Package => Ok(Launch::package(&state.scripts_dir, script_name, path, &entry.config)?),
+    }
+}
+
+/// Запускает скрипт (или отдаёт результат из кэша) и записывает запуск в историю
 pub async fn run_script(
     state: Arc<AppState>,
     script_name: &str,
-    args: Vec<String>,
     input_bytes: Bytes,
+    mut options: RunOptions,
 ) -> Result<ScriptResult, AppError> {
-    let script_path = state.scripts_dir.join(script_name);
+    // Отключённый скрипт отклоняется до квот, событий и записи в историю
+    if is_disabled(&state, script_name).await {
+        return Err(AppError::ScriptDisabled(script_name.to_string()));
+    }
+    let started_at = Utc::now();
+    let started = Instant::now();
+    // Все строки лога запуска, включая попадание в кэш, несут run_id
+    let run_id = Uuid::new_v4().to_string();
+    // Подстановки раскрываются до ключа кэша и записи в историю
+    let vars = arg_template::Vars {
+        script: script_name,
+        run_id: &run_id,
+        now: started_at,
+    };
+    options.args = arg_template::expand(&options.args, &vars)?;
+    let args_hash = hash_args(&options.args);
+    let callback_url = options.callback_url.clone();
+    let job_id = options.job_id;
 
-    {
-        let scripts = state.scripts.lock().await;
-        if !scripts.contains(&script_path) {
-            return Err(AppError::ScriptNotFound(script_name.to_string()));
+    let span = info_span!("run", run_id = %run_id, script = %script_name);
+    options.run_id = Some(run_id.clone());
+    // Артефакты запуска не удаляются очисткой, пока он не завершён
+    let _artifacts = state.gc.track_run(&run_id);
+    let namespace = state.namespaces.quota(script_name);
+    let reservation = state.quotas.reserve(script_name, options.caller.as_deref(), namespace).await?;
+    let (args, input) = (options.args.clone(), input_bytes.clone());
+    state.events.publish(EventData::RunStarted {
+        script: script_name.to_string(),
+        run_id: run_id.clone(),
+        args: args.len(),
+    });
+    let mut result = execute_script(&state, script_name, input_bytes, &mut options)
+        .instrument(span)
+        .await;
+    set_run_id(&mut result, &run_id);
+    // Сохраняется до разбора: с `omit_stdout` stdout из ответа убирается
+    if let Ok(result) = &result {
+        state.outputs.record(script_name, &run_id, result);
+    }
+    let quota_remaining = state.quotas.finish(reservation, quota_exec(&state, &result, started)).await;
+    if let Ok(result) = &mut result {
+        result.quota_remaining = quota_remaining;
+    }
+    // Разбор после кэша и объединения запросов: у каждого запроса свои параметры
+    if let (Ok(result), Some(true)) = (&mut result, options.parse_output) {
+        parse_output(result, options.omit_stdout);
+    }
+    if let (Ok(result), Some(true)) = (&mut result, options.parse_traceback) {
+        if result.stderr_encoding == OutputEncoding::Utf8 {
+            result.error = traceback::parse(&result.stderr);
         }
     }
 
-    let current_mtime = get_mtime(&script_path).await;
+    let entry = HistoryEntry::new(
+        run_id.clone(),
+        script_name,
+        args_hash,
+        started_at,
+        started.elapsed(),
+        result.as_ref().map(|r| (r, r.cached)),
+    );
+    // Запросы к несуществующим скриптам не заводят счётчиков
+    if !matches!(result, Err(AppError::ScriptNotFound(_))) {
+        stats::record(&state, &entry).await;
+    }
+    state.events.publish(finished_event(&entry));
+    history::record(&state, entry, args, input).await;
+
+    // Уведомление отправляется в фоне и не задерживает ответ
+    if let Some(url) = callback_url {
+        let payload = WebhookPayload {
+            script: script_name.to_string(),
+            run_id,
+            job_id: job_id.map(|id| id.to_string()),
+            duration_ms: started.elapsed().as_millis() as u64,
+            result: result.as_ref().ok().cloned(),
+            error: result.as_ref().err().map(|e| e.to_string()),
+        };
+        let state = Arc::clone(&state);
+        tokio::spawn(async move { state.webhooks.deliver(url, &payload).await }.in_current_span());
+    }
+
+    result
+}
+
+// Время выполнения, засчитываемое в квоты. Запуски, которые не состоялись,
+// и ответы из кэша (без RUNNER_QUOTA_COUNT_CACHE_HITS) не засчитываются.
+fn quota_exec(state: &AppState, result: &Result<ScriptResult, AppError>, started: Instant) -> Option<Duration> {
+    match result {
+        Ok(result) if result.cached => state.quotas.count_cache_hits.then_some(Duration::ZERO),
+        Ok(result) => Some(Duration::from_millis(result.duration_ms)),
+        Err(
+            AppError::ScriptNotFound(_)
+            | AppError::ShuttingDown
+            | AppError::Busy(_)
+            | AppError::ScriptBusy { .. }
+            | AppError::Cancelled(None),
+        ) => None,
+        Err(_) => Some(started.elapsed()),
+    }
+}
+
+fn finished_event(entry: &HistoryEntry) -> EventData {
+    if entry.status == RunStatus::TimedOut {
+        return EventData::RunTimedOut {
+            script: entry.script.clone(),
+            run_id: entry.run_id.clone(),
+            duration_ms: entry.duration_ms,
+        };
+    }
+    EventData::RunFinished {
+        script: entry.script.clone(),
+        run_id: entry.run_id.clone(),
+        exit_code: entry.exit_code,
+        duration_ms: entry.duration_ms,
+        cached: entry.cached,
+        error: entry.error.clone(),
+    }
+}
+
+// Идентификатор запуска в ответе, в том числе в частичном результате отменённого запуска
+fn set_run_id(result: &mut Result<ScriptResult, AppError>, run_id: &str) {
+    match result {
+        Ok(result) => result.run_id = Some(run_id.to_string()),
+        Err(AppError::Cancelled(Some(partial))) => partial.run_id = Some(run_id.to_string()),
+        Err(_) => {}
+    }
+}
 
-    // Ключ кэша
+fn hash_args(args: &[String]) -> String {
     let mut hasher = DefaultHasher::new();
     args.hash(&mut hasher);
+    format!("{:x}", hasher.finish())
+}
+
+/// Выполняет переданный код как скрипт с расширением `extension`.
+/// Код пишется во временный файл вне директории скриптов и удаляется после
+/// запуска; список скриптов, кэш, история и статистика не затрагиваются.
+pub async fn run_code(
+    state: &AppState,
+    extension: &str,
+    code: &str,
+    input_bytes: Bytes,
+    mut options: RunOptions,
+) -> Result<ScriptResult, AppError> {
+    let _run = state.shutdown.track_run()?;
+    let script_name = format!("eval.{}", extension);
+    let interpreter = state.interpreter_for(&script_name)?.to_string();
+    options.resources = ResourceLimits::for_script(state, &ScriptConfig::default());
+    options.network_isolated = network::isolation(state, &script_name, options.network)?;
+
+    let run_id = Uuid::new_v4().to_string();
+    let span = info_span!("eval", run_id = %run_id, language = %extension);
+    options.run_id = Some(run_id.clone());
+    let _artifacts = state.gc.track_run(&run_id);
+    let tmp_path = std::env::temp_dir().join(format!("runner-eval-{}.{}", run_id, extension));
+    fs::write(&tmp_path, code).await?;
+
+    let result = async {
+        info!("Evaluating {} bytes of .{} code", code.len(), extension);
+        execute_process(
+            state,
+            &script_name,
+            &Launch::File(tmp_path.clone()),
+            &interpreter,
+            input_bytes,
+            &options,
+            None,
+        )
+        .await
+    }
+    .instrument(span)
+    .await;
+    let mut result = timeout_result(result, options.partial_on_timeout);
+    let _ = fs::remove_file(&tmp_path).await;
+    set_run_id(&mut result, &run_id);
+    result
+}
+
+async fn execute_script(
+    state: &Arc<AppState>,
+    script_name: &str,
+    input_bytes: Bytes,
+    options: &mut RunOptions,
+) -> Result<ScriptResult, AppError> {
+    let _run = state.shutdown.track_run()?;
+    let (script_path, entry) = resolve_script(state, script_name).await?;
+    let interpreter = script_interpreter(state, script_name, &entry, options).await?;
+    let launch = launch_for(state, script_name, &script_path, &entry)?;
+    let ScriptEntry { config, hash, .. } = entry;
+
+    // Таймаут и разбор вывода из запроса важнее настроек скрипта
+    options.timeout_secs = options.timeout_secs.or(config.timeout_secs);
+    options.parse_output = options.parse_output.or(config.parse_output);
+    options.parse_traceback = options.parse_traceback.or(config.parse_traceback);
+    options.max_concurrent = config.max_concurrent.or(state.script_max_concurrent);
+    options.resources = ResourceLimits::for_script(state, &config);
+    options.network_isolated = network::isolation(state, script_name, options.network.or(config.network))?;
+    let no_cache = options.no_cache || options.keep_workdir || config.cache == Some(false);
+
+    // Ключ кэша; результаты разных интерпретаторов (версий Python) и разных
+    // версий скрипта не смешиваются
+    let mut hasher = DefaultHasher::new();
+    hash.hash(&mut hasher);
+    interpreter.hash(&mut hasher);
+    options.args.hash(&mut hasher);
+    options.env.hash(&mut hasher);
+    // Только имена: значения секретов в ключ не попадают
+    options.secrets.hash(&mut hasher);
     input_bytes.hash(&mut hasher);
+    // Результаты без доступа к сети и в песочнице кэшируются отдельно
+    if options.network_isolated.is_some() {
+        options.network_isolated.hash(&mut hasher);
+    }
+    if state.sandbox.is_some() {
+        "sandbox".hash(&mut hasher);
+    }
     let cache_key = format!("{}:{:x}", script_name, hasher.finish());
 
-    // Проверка кэша
-    {
+    // Проверка кэша: под блокировкой берётся только Arc на запись
+    let cached = if no_cache {
+        None
+    } else {
         let mut cache = state.cache.lock().await;
-        if let Some(cached) = cache.get(&cache_key) {
-            if cached.timestamp.elapsed() < state.cache_ttl
-                && current_mtime
-                .map(|m| m == cached.script_mtime)
-                .unwrap_or(false)
+        match cache.get(&cache_key) {
+            Some(cached)
+                if cached.timestamp.elapsed() < state.cache_ttl()
+                    && cached.script_hash == hash =>
             {
-                info!("Cache hit for {}", script_name);
-                return Ok(ScriptResult {
-                    stdout: cached.stdout.clone(),
-                    stderr: cached.stderr.clone(),
-                    exit_code: cached.exit_code,
-                    timed_out: false,
-                });
-            } else {
+                cache.record_hit();
+                Some(cached)
+            }
+            Some(_) => {
                 cache.remove(&cache_key);
+                cache.record_miss();
+                None
+            }
+            None => {
+                cache.record_miss();
+                None
+            }
+        }
+    };
+
+    if let Some(cached) = cached {
+        info!("Cache hit for {}", script_name);
+        let mut result =
+            ScriptResult::from_output(&cached.stdout, &cached.stderr, cached.exit_code, false);
+        result.artifacts = cached.artifacts.clone();
+        // Время и длительность исходного запуска
+        result.duration_ms = cached.duration_ms;
+        result.started_at = Some(cached.started_at);
+        result.finished_at = Some(cached.finished_at);
+        result.cached = true;
+        result.network_isolated = options.network_isolated;
+        result.sandboxed = state.sandbox.is_some();
+        result.script_hash = Some(hash);
+        result.invocation = request_invocation(state, &interpreter, &launch, options).await;
+        result.freshness = Some(Freshness {
+            etag: cached_etag(&cache_key, &cached, options),
+            max_age: state.cache_ttl().saturating_sub(cached.timestamp.elapsed()),
+        });
+        return Ok(result);
+    }
+
+    // Одинаковые запросы, пришедшие во время выполнения, ждут его результат
+    // вместо запуска ещё одного процесса
+    let flight = if no_cache {
+        None
+    } else {
+        loop {
+            let waiter = match state.inflight.join(&cache_key) {
+                Flight::Leader(guard) => break Some(guard),
+                Flight::Follower(waiter) => waiter,
+            };
+            info!("Joining in-flight run of {}", script_name);
+            if let Some(job_id) = options.job_id {
+                jobs::mark_running(state, job_id).await;
+            }
+            let exec_timeout = state.effective_timeout(options.timeout_secs);
+            let outcome = tokio::select! {
+                res = timeout(exec_timeout, inflight::wait(waiter)) => {
+                    res.map_err(|_| AppError::Timeout)?
+                }
+                _ = wait_cancel(&options.cancel) => return Err(AppError::Cancelled(None)),
+                _ = state.shutdown.killed() => return Err(AppError::ShuttingDown),
+            };
+            match outcome {
+                // Лог есть только у первого запуска, под его run_id
+                Outcome::Done(mut result) => {
+                    result.cached = true;
+                    result.log_available = false;
+                    let own = request_invocation(state, &interpreter, &launch, options).await;
+                    adopt_invocation(&mut result, own);
+                    return Ok(*result);
+                }
+                Outcome::TimedOut(Some(mut partial)) if options.partial_on_timeout => {
+                    partial.cached = true;
+                    partial.log_available = false;
+                    let own = request_invocation(state, &interpreter, &launch, options).await;
+                    adopt_invocation(&mut partial, own);
+                    return Ok(*partial);
+                }
+                Outcome::TimedOut(_) => return Err(AppError::Timeout),
+                // Первый запуск прерван или завершился ошибкой — пробуем сами
+                Outcome::Failed => continue,
             }
         }
+    };
+
+    let cache_entry = (!no_cache).then(|| (cache_key, hash.clone()));
+    let mut result = execute_process(
+        state,
+        script_name,
+        &launch,
+        &interpreter,
+        input_bytes,
+        options,
+        cache_entry,
+    )
+    .await;
+    if let Ok(result) = &mut result {
+        result.script_hash = Some(hash);
     }
 
-    let _permit = state.semaphore.acquire().await.unwrap();
+    if let Some(flight) = &flight {
+        flight.complete(&result);
+    }
+    timeout_result(result, options.partial_on_timeout)
+}
 
-    let run_fut = async {
-        let mut child = Command::new("python3")
-            .arg("-u")
-            .arg(&script_path)
-            .args(&args)
-            .stdin(std::process::Stdio::piped())
-            .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped())
-            .spawn()?;
+// ETag результата из кэша: ключ и содержимое записи, а также параметры,
+// меняющие тело ответа, но не входящие в ключ кэша
+fn cached_etag(cache_key: &str, cached: &CachedResult, options: &RunOptions) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(cache_key.as_bytes());
+    hasher.update(cached.exit_code.to_le_bytes());
+    hasher.update(cached.started_at.timestamp_micros().to_le_bytes());
+    for output in [&cached.stdout, &cached.stderr] {
+        hasher.update((output.bytes.len() as u64).to_le_bytes());
+        hasher.update(&output.bytes);
+    }
+    hasher.update([
+        options.parse_output.unwrap_or(false) as u8,
+        options.omit_stdout as u8,
+        options.parse_traceback.unwrap_or(false) as u8,
+    ]);
+    format!("\"{:x}\"", hasher.finalize())
+}
 
-        if let Some(mut stdin) = child.stdin.take() {
-            stdin.write_all(&input_bytes).await?;
-            stdin.flush().await?;
+// JSON-документ из stdout в поле `output`; ошибка разбора не прерывает запуск
+fn parse_output(result: &mut ScriptResult, omit_stdout: bool) {
+    if result.stdout_encoding != OutputEncoding::Utf8 {
+        result.output_parse_error = Some("stdout is not valid UTF-8".to_string());
+        return;
+    }
+    match serde_json::from_str(result.stdout.trim()) {
+        Ok(output) => {
+            result.output = Some(output);
+            if omit_stdout {
+                result.stdout.clear();
+            }
         }
-        drop(child.stdin.take());
+        Err(e) => result.output_parse_error = Some(e.to_string()),
+    }
+}
 
-        let output = child.wait_with_output().await?;
-        Ok::<_, std::io::Error>(output)
-    };
+// Результат, прерванный таймаутом, становится ошибкой `Timeout`, если
+// клиент не просил вернуть частичный вывод
+fn timeout_result(
+    result: Result<ScriptResult, AppError>,
+    partial_on_timeout: bool,
+) -> Result<ScriptResult, AppError> {
+    match result {
+        Ok(result) if result.timed_out && !partial_on_timeout => Err(AppError::Timeout),
+        result => result,
+    }
+}
+
+// Запуск процесса скрипта под семафором с таймаутом, отменой и лимитом вывода.
+// С `cache_entry` (ключ и хэш скрипта) результат сохраняется в кэш. По таймауту
+// возвращается результат с `timed_out`; см. `timeout_result`.
+async fn execute_process(
+    state: &AppState,
+    script_name: &str,
+    launch: &Launch,
+    interpreter: &str,
+    input_bytes: Bytes,
+    options: &RunOptions,
+    cache_entry: Option<(String, String)>,
+) -> Result<ScriptResult, AppError> {
+    let RunOptions {
+        args,
+        env,
+        timeout_secs,
+        job_id,
+        cancel,
+        keep_workdir,
+        ..
+    } = options;
+    let secrets = state.secrets.resolve(&options.secrets).await?;
+    let env = &secrets.with_env(env);
+
+    // Отменённый до получения слота запуск так и не занимает семафор.
+    // Асинхронные задачи ждут слота без ограничения по времени.
+    let wait_limit = state.queue_wait(options.queue_timeout_secs, job_id.is_some());
+    let limiter = script_limiter(state, script_name, options.max_concurrent).await;
+    let queue_started = Instant::now();
+    let _permit = acquire_permit(state, script_name, limiter, wait_limit, wait_cancel(cancel)).await?;
+    let queued_ms = queue_started.elapsed().as_millis() as u64;
 
-    let result = timeout(Duration::from_secs(30), run_fut).await;
+    if let Some(job_id) = *job_id {
+        jobs::mark_running(state, job_id).await;
+    }
 
-    let (stdout, stderr, exit_code, timed_out) = match result {
-        Ok(Ok(output)) => (
-            String::from_utf8(output.stdout)?,
-            String::from_utf8(output.stderr)?,
-            output.status.code().unwrap_or(-1),
-            false,
-        ),
-        Ok(Err(e)) => return Err(AppError::Io(e)),
-        Err(_) => {
-            warn!("Script {} timed out", script_name);
-            return Err(AppError::Timeout);
+    // Удаляется при выходе из функции, в том числе по таймауту и ошибке
+    let run_dir = RunDir::create(*keep_workdir).await?;
+    exec_user::prepare_dir(&run_dir, state.exec_user)?;
+    // Длительность считается от запуска процесса, без ожидания слота
+    let started_at = Utc::now();
+    let started = Instant::now();
+    let exec_timeout = state.effective_timeout(*timeout_secs);
+    let isolate_network = options.network_isolated == Some(true);
+    // Python-скрипты выполняет свободный воркер пула, если пул включён;
+    // запуски с ограничениями ресурсов или без сети идут отдельным процессом
+    let pooled = match (&state.pool, launch) {
+        (Some(pool), Launch::File(script_path))
+            if pool.accepts(script_name, interpreter)
+                && options.resources.is_empty()
+                && !isolate_network =>
+        {
+            let task = PoolTask {
+                script_path,
+                args,
+                env,
+                workdir: run_dir.path(),
+                input: &input_bytes,
+                timeout: exec_timeout,
+                max_output_bytes: state.max_output_bytes(),
+            };
+            let interrupt = async {
+                tokio::select! {
+                    _ = wait_cancel(cancel) => Interrupted::Cancelled,
+                    _ = state.shutdown.killed() => Interrupted::Shutdown,
+                }
+            };
+            pool.try_run(task, interrupt).await
         }
+        _ => None,
     };
 
-    if let Some(mtime) = current_mtime {
-        let mut cache = state.cache.lock().await;
-        cache.insert(
-            cache_key,
-            CachedResult {
-                stdout: stdout.clone(),
-                stderr: stderr.clone(),
-                exit_code,
-                timestamp: Instant::now(),
-                script_mtime: mtime,
-            },
+    let mut killed_reason = None;
+    let signal;
+    let sandboxed = pooled.is_none() && state.sandbox.is_some();
+    // Воркер пула отдаёт stdout и stderr целиком, поэтому порядок строк
+    // известен только для отдельного процесса
+    let log = (pooled.is_none() && options.run_id.is_some() && state.run_logs.enabled())
+        .then(|| Arc::new(LogCapture::default()));
+    // Описание собирается из тех же значений, с которыми стартует процесс
+    let invocation = Invocation {
+        sandbox: sandboxed,
+        pooled: pooled.is_some(),
+        ..describe_invocation(state, interpreter, launch, args, exec_timeout, &secrets)
+    };
+    let (stdout, stderr, exit_code, timed_out) = if let Some(run) = pooled {
+        let run = run?;
+        match run.interrupted {
+            Some(Interrupted::Cancelled) => {
+                info!("Pooled script {} cancelled", script_name);
+                let partial = ScriptResult::from_output(
+                    &secrets.mask(run.stdout),
+                    &secrets.mask(run.stderr),
+                    -1,
+                    false,
+                );
+                return Err(AppError::Cancelled(Some(Box::new(partial))));
+            }
+            Some(_) => {
+                warn!("Killing pooled script {} on shutdown", script_name);
+                return Err(AppError::ShuttingDown);
+            }
+            None if run.timed_out => {
+                warn!("Pooled script {} timed out after {:?}", script_name, exec_timeout);
+                signal = run.signal;
+                (run.stdout, run.stderr, -1, true)
+            }
+            None => {
+                signal = run.signal;
+                killed_reason =
+                    resources::killed_reason(run.exit_code, signal, options.resources, &run.stderr.bytes);
+                (run.stdout, run.stderr, run.exit_code, false)
+            }
+        }
+    } else {
+        let confinement = Confinement {
+            resources: options.resources,
+            user: state.exec_user,
+            isolate_network,
+            sandbox: state.sandbox.clone(),
+        };
+        let mut child = spawn_script(interpreter, launch, args, env, run_dir.path(), confinement)?;
+        let mut group = ProcessGroup::of(&child);
+
+        // Читаем вывод в отдельных задачах, чтобы handle процесса пережил таймаут
+        let overflow = Arc::new(Notify::new());
+        let max_output_bytes = state.max_output_bytes();
+        let stdout_task = read_pipe(
+            child.stdout.take(),
+            max_output_bytes,
+            Arc::clone(&overflow),
+            log.as_ref().map(|log| log.writer(LogStream::Stdout)),
+        );
+        let stderr_task = read_pipe(
+            child.stderr.take(),
+            max_output_bytes,
+            Arc::clone(&overflow),
+            log.as_ref().map(|log| log.writer(LogStream::Stderr)),
         );
+
+        let stdin = child.stdin.take();
+        let run_fut = async {
+            if let Some(mut stdin) = stdin {
+                stdin.write_all(&input_bytes).await?;
+                stdin.flush().await?;
+            }
+            wait_exited(&mut child).await?;
+            group.reap(&mut child).await
+        };
+
+        let result = tokio::select! {
+            res = timeout(exec_timeout, run_fut) => Ok(res),
+            _ = wait_cancel(cancel) => Err(Interrupted::Cancelled),
+            _ = state.shutdown.killed() => Err(Interrupted::Shutdown),
+            _ = overflow.notified() => Err(Interrupted::OutputLimit),
+        };
+
+        match result {
+            Ok(Ok(Ok(status))) => {
+                let stdout = join_pipe(stdout_task).await?;
+                let stderr = join_pipe(stderr_task).await?;
+                let exit_code = status.code().unwrap_or(-1);
+                signal = exit_signal(&status);
+                killed_reason = resources::killed_reason(exit_code, signal, options.resources, &stderr.bytes);
+                (stdout, stderr, exit_code, false)
+            }
+            Ok(Ok(Err(e))) => {
+                terminate_child(&mut child, &mut group).await;
+                return Err(AppError::Io(e));
+            }
+            // Вывод до остановки сохраняется; в ошибку его превращает вызывающий
+            Ok(Err(_)) => {
+                warn!("Script {} timed out after {:?}, terminating", script_name, exec_timeout);
+                signal = terminate_child(&mut child, &mut group).await.as_ref().and_then(exit_signal);
+                (
+                    collect_partial(stdout_task).await,
+                    collect_partial(stderr_task).await,
+                    -1,
+                    true,
+                )
+            }
+            // Вывод обрезан по лимиту — процесс останавливается, результат сохраняется
+            Err(Interrupted::OutputLimit) => {
+                warn!(
+                    "Script {} exceeded output limit of {} bytes, terminating",
+                    script_name, max_output_bytes
+                );
+                signal = terminate_child(&mut child, &mut group).await.as_ref().and_then(exit_signal);
+                (
+                    collect_partial(stdout_task).await,
+                    collect_partial(stderr_task).await,
+                    -1,
+                    false,
+                )
+            }
+            Err(Interrupted::Shutdown) => {
+                warn!("Killing script {} on shutdown", script_name);
+                terminate_child(&mut child, &mut group).await;
+                stdout_task.abort();
+                stderr_task.abort();
+                return Err(AppError::ShuttingDown);
+            }
+            Err(Interrupted::Cancelled) => {
+                info!("Script {} cancelled, terminating", script_name);
+                terminate_child(&mut child, &mut group).await;
+                let partial = ScriptResult::from_output(
+                    &secrets.mask(collect_partial(stdout_task).await),
+                    &secrets.mask(collect_partial(stderr_task).await),
+                    -1,
+                    false,
+                );
+                return Err(AppError::Cancelled(Some(Box::new(partial))));
+            }
+        }
+    };
+
+    // Значения секретов не попадают ни в ответ, ни в кэш
+    let (stdout, stderr) = (secrets.mask(stdout), secrets.mask(stderr));
+    let finished_at = Utc::now();
+    let duration_ms = started.elapsed().as_millis() as u64;
+    let artifacts = match &options.run_id {
+        Some(run_id) => artifacts::store(state, run_id, script_name, &run_dir).await,
+        None => Vec::new(),
+    };
+
+    if let Some(reason) = killed_reason {
+        warn!("Script {} was killed: {:?}", script_name, reason);
+    }
+
+    // Результат, прерванный таймаутом, сигналом или ограничением ресурсов,
+    // не кэшируется. Не кэшируется и результат скрипта, который за время
+    // запуска переименовали, удалили или изменили.
+    let cacheable = !timed_out && signal.is_none() && killed_reason.is_none();
+    let cache_entry = match cache_entry.filter(|_| cacheable) {
+        Some((key, hash)) if known_hash(state, launch.path()).await.as_ref() == Some(&hash) => {
+            Some((key, hash))
+        }
+        _ => None,
+    };
+    if let Some((cache_key, script_hash)) = cache_entry {
+        let entry = CachedResult {
+            stdout: stdout.clone(),
+            stderr: stderr.clone(),
+            artifacts: artifacts.clone(),
+            exit_code,
+            started_at,
+            finished_at,
+            duration_ms,
+            timestamp: Instant::now(),
+            script_hash,
+        };
+        state.cache.lock().await.insert(cache_key, entry);
     }
 
-    Ok(ScriptResult {
-        stdout,
-        stderr,
-        exit_code,
-        timed_out,
+    let mut result = ScriptResult::from_output(&stdout, &stderr, exit_code, timed_out);
+    if let (Some(log), Some(run_id)) = (log, &options.run_id) {
+        state.run_logs.record(script_name, run_id, log.take_lines(&secrets));
+        result.log_available = true;
+    }
+    result.artifacts = artifacts;
+    result.set_signal(signal);
+    result.killed_reason = killed_reason;
+    result.workdir = run_dir.kept_path();
+    result.duration_ms = duration_ms;
+    result.queued_ms = queued_ms;
+    result.network_isolated = options.network_isolated;
+    result.sandboxed = sandboxed;
+    result.invocation = Some(invocation);
+    result.started_at = Some(started_at);
+    result.finished_at = Some(finished_at);
+    Ok(result)
+}
+
+// Команда запуска для ответа и истории: программа интерпретатора — найденной
+// в `PATH`, скрипт — относительно директории скриптов. Значения секретов,
+// попавшие в аргументы, маскируются.
+fn describe_invocation(
+    state: &AppState,
+    interpreter: &str,
+    launch: &Launch,
+    args: &[String],
+    timeout: Duration,
+    secrets: &RunSecrets,
+) -> Invocation {
+    let mut parts = interpreter.split_whitespace();
+    let program = parts.next().unwrap_or(interpreter);
+    let venv = Path::new(program).starts_with(state.scripts_dir.join(deps::VENVS_DIR));
+    let resolved = sandbox::find_program(program)
+        .map_or_else(|| program.to_string(), |path| path.display().to_string());
+    let script = relative_name(&state.scripts_dir, launch.path())
+        .unwrap_or_else(|| launch.path().display().to_string());
+    Invocation {
+        interpreter: std::iter::once(resolved.as_str()).chain(parts).collect::<Vec<_>>().join(" "),
+        script,
+        args: args.iter().map(|arg| secrets.mask_text(arg.clone())).collect(),
+        timeout_secs: timeout.as_secs(),
+        sandbox: false,
+        venv,
+        pooled: false,
+    }
+}
+
+// Описание запуска для ответа, который не запускал процесс (из кэша или
+// ожидавшего одинаковый запуск): по параметрам этого запроса. `None`, если
+// секреты запроса больше не читаются.
+async fn request_invocation(
+    state: &AppState,
+    interpreter: &str,
+    launch: &Launch,
+    options: &RunOptions,
+) -> Option<Invocation> {
+    let secrets = state.secrets.resolve(&options.secrets).await.ok()?;
+    let timeout = state.effective_timeout(options.timeout_secs);
+    Some(Invocation {
+        sandbox: state.sandbox.is_some(),
+        ..describe_invocation(state, interpreter, launch, &options.args, timeout, &secrets)
     })
 }
 
-// Фоновое сканирование
-pub async fn scan_scripts(state: Arc<AppState>) {
-    let mut current_files = Vec::new();
-    if let Ok(mut entries) = fs::read_dir(&state.scripts_dir).await {
-        while let Ok(Some(entry)) = entries.next_entry().await {
-            let path = entry.path();
-            if path.extension().and_then(|ext| ext.to_str()) == Some("py") {
-                current_files.push(path);
+// Ответ общего запуска получает описание этого запроса; то, как выполнялся
+// процесс (песочница, пул), остаётся от общего запуска
+fn adopt_invocation(result: &mut ScriptResult, own: Option<Invocation>) {
+    let shared = result.invocation.take();
+    result.invocation = match (own, shared) {
+        (Some(own), Some(shared)) => Some(Invocation {
+            sandbox: shared.sandbox,
+            pooled: shared.pooled,
+            ..own
+        }),
+        (own, shared) => own.or(shared),
+    };
+}
+
+/// Событие потокового выполнения скрипта
+pub enum StreamEvent {
+    Stdout(String),
+    Stderr(String),
+    Exit {
+        exit_code: i32,
+        timed_out: bool,
+        signal: Option<i32>,
+        killed_reason: Option<KilledReason>,
+    },
+    Error(String),
+}
+
+// Построчно пересылает вывод процесса в канал событий; после `limit` байт
+// будит `overflow` и перестаёт читать
+fn forward_lines<R>(
+    pipe: Option<R>,
+    tx: mpsc::Sender<StreamEvent>,
+    wrap: fn(String) -> StreamEvent,
+    limit: usize,
+    overflow: Arc<Notify>,
+    secrets: Arc<RunSecrets>,
+) -> JoinHandle<()>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        let Some(pipe) = pipe else { return };
+        let mut reader = BufReader::new(pipe);
+        let mut line = Vec::new();
+        let mut total = 0;
+        loop {
+            line.clear();
+            match reader.read_until(b'\n', &mut line).await {
+                Ok(0) | Err(_) => break,
+                Ok(n) if total + n > limit => {
+                    overflow.notify_one();
+                    break;
+                }
+                Ok(n) => {
+                    total += n;
+                    let text = String::from_utf8_lossy(&line)
+                        .trim_end_matches('\n')
+                        .replace('\r', "");
+                    if tx.send(wrap(secrets.mask_text(text))).await.is_err() {
+                        break;
+                    }
+                }
             }
         }
+    })
+}
+
+/// Запускает скрипт так же, как `run_script`, но отдаёт stdout/stderr построчно
+/// по мере появления. Кэш не используется. Если получатель закрыт
+/// (клиент отключился), процесс завершается.
+pub async fn run_script_stream(
+    state: Arc<AppState>,
+    script_name: &str,
+    input_bytes: Bytes,
+    options: RunOptions,
+) -> Result<mpsc::Receiver<StreamEvent>, AppError> {
+    if state.shutdown.is_draining() {
+        return Err(AppError::ShuttingDown);
+    }
+    let (script_path, entry) = resolve_script(&state, script_name).await?;
+    if entry.config.disabled {
+        return Err(AppError::ScriptDisabled(script_name.to_string()));
+    }
+    let interpreter = script_interpreter(&state, script_name, &entry, &options).await?;
+    let launch = launch_for(&state, script_name, &script_path, &entry)?;
+    let config = entry.config;
+    let caller = options.caller.clone();
+    let RunOptions {
+        args,
+        env,
+        timeout_secs,
+        secrets,
+        queue_timeout_secs,
+        network,
+        ..
+    } = options;
+    let secrets = Arc::new(state.secrets.resolve(&secrets).await?);
+    let env = secrets.with_env(&env);
+
+    let exec_timeout = state.effective_timeout(timeout_secs.or(config.timeout_secs));
+    let queue_wait = state.queue_wait(queue_timeout_secs, false);
+    let max_concurrent = config.max_concurrent.or(state.script_max_concurrent);
+    let confinement = Confinement {
+        resources: ResourceLimits::for_script(&state, &config),
+        user: state.exec_user,
+        isolate_network: network::isolation(&state, script_name, network.or(config.network))? == Some(true),
+        sandbox: state.sandbox.clone(),
+    };
+    let script_name = script_name.to_string();
+    let (tx, rx) = mpsc::channel(STREAM_CHANNEL_CAPACITY);
+    let span = info_span!("run", run_id = %Uuid::new_v4(), script = %script_name);
+    let namespace = state.namespaces.quota(&script_name);
+    let reservation = state.quotas.reserve(&script_name, caller.as_deref(), namespace).await?;
+
+    tokio::spawn(async move {
+        // Время выполнения засчитывается в квоты, только если процесс запущен
+        let spawned = async {
+            let Ok(_run) = state.shutdown.track_run() else {
+                let _ = tx.send(StreamEvent::Error(AppError::ShuttingDown.to_string())).await;
+                return None;
+            };
+            let limiter = script_limiter(&state, &script_name, max_concurrent).await;
+            let permit = acquire_permit(&state, &script_name, limiter, queue_wait, tx.closed());
+            let _permit = match permit.await {
+                Ok(permit) => permit,
+                Err(AppError::Cancelled(_)) => return None,
+                Err(e) => {
+                    let _ = tx.send(StreamEvent::Error(e.to_string())).await;
+                    return None;
+                }
+            };
+
+            let run_dir = match RunDir::create(false).await {
+                Ok(run_dir) => run_dir,
+                Err(e) => {
+                    let _ = tx.send(StreamEvent::Error(format!("IO error: {}", e.kind()))).await;
+                    return None;
+                }
+            };
+            let spawned = exec_user::prepare_dir(&run_dir, state.exec_user).and_then(|()| {
+                spawn_script(&interpreter, &launch, &args, &env, run_dir.path(), confinement.clone())
+            });
+            let mut child = match spawned {
+                Ok(child) => child,
+                Err(error) => {
+                    let _ = tx.send(StreamEvent::Error(error.to_string())).await;
+                    return None;
+                }
+            };
+            let spawned = Instant::now();
+            let mut group = ProcessGroup::of(&child);
+
+            let limit = state.max_output_bytes();
+            let overflow = Arc::new(Notify::new());
+            let stdout_task = forward_lines(
+                child.stdout.take(),
+                tx.clone(),
+                StreamEvent::Stdout,
+                limit,
+                Arc::clone(&overflow),
+                Arc::clone(&secrets),
+            );
+            let stderr_task = forward_lines(
+                child.stderr.take(),
+                tx.clone(),
+                StreamEvent::Stderr,
+                limit,
+                Arc::clone(&overflow),
+                secrets,
+            );
+
+            let stdin = child.stdin.take();
+            let run_fut = async {
+                if let Some(mut stdin) = stdin {
+                    stdin.write_all(&input_bytes).await?;
+                    stdin.flush().await?;
+                }
+                wait_exited(&mut child).await?;
+                group.reap(&mut child).await
+            };
+
+            let result = tokio::select! {
+                res = timeout(exec_timeout, run_fut) => Ok(res),
+                _ = tx.closed() => Err(Interrupted::Cancelled),
+                _ = state.shutdown.killed() => Err(Interrupted::Shutdown),
+                _ = overflow.notified() => Err(Interrupted::OutputLimit),
+            };
+
+            let event = match result {
+                Ok(Ok(Ok(status))) => {
+                    let _ = stdout_task.await;
+                    let _ = stderr_task.await;
+                    let exit_code = status.code().unwrap_or(-1);
+                    let signal = exit_signal(&status);
+                    // stderr уже передан клиенту, поэтому распознаётся только остановка сигналом
+                    StreamEvent::Exit {
+                        exit_code,
+                        timed_out: false,
+                        signal,
+                        killed_reason: resources::killed_reason(exit_code, signal, confinement.resources, &[]),
+                    }
+                }
+                Ok(Ok(Err(e))) => {
+                    terminate_child(&mut child, &mut group).await;
+                    StreamEvent::Error(format!("IO error: {}", e))
+                }
+                Ok(Err(_)) => {
+                    warn!("Streamed script {} timed out after {:?}, terminating", script_name, exec_timeout);
+                    let signal = terminate_child(&mut child, &mut group).await.as_ref().and_then(exit_signal);
+                    stdout_task.abort();
+                    stderr_task.abort();
+                    StreamEvent::Exit {
+                        exit_code: -1,
+                        timed_out: true,
+                        signal,
+                        killed_reason: None,
+                    }
+                }
+                Err(Interrupted::OutputLimit) => {
+                    warn!("Streamed script {} exceeded output limit, terminating", script_name);
+                    terminate_child(&mut child, &mut group).await;
+                    stdout_task.abort();
+                    stderr_task.abort();
+                    StreamEvent::Error(format!("Output limit of {} bytes exceeded", limit))
+                }
+                Err(Interrupted::Shutdown) => {
+                    warn!("Killing streamed script {} on shutdown", script_name);
+                    terminate_child(&mut child, &mut group).await;
+                    stdout_task.abort();
+                    stderr_task.abort();
+                    StreamEvent::Error(AppError::ShuttingDown.to_string())
+                }
+                Err(Interrupted::Cancelled) => {
+                    info!("Client disconnected, terminating streamed script {}", script_name);
+                    terminate_child(&mut child, &mut group).await;
+                    stdout_task.abort();
+                    stderr_task.abort();
+                    return Some(spawned);
+                }
+            };
+
+            let _ = tx.send(event).await;
+            Some(spawned)
+        }
+        .await;
+        let exec = spawned.map(|spawned| spawned.elapsed());
+        state.quotas.finish(reservation, exec).await;
+    }
+    .instrument(span));
+
+    Ok(rx)
+}
+
+/// Очищает кэш целиком, возвращает число удалённых записей
+pub async fn clear_cache(state: &AppState) -> usize {
+    state.cache.lock().await.clear()
+}
+
+/// Удаляет из кэша все результаты скрипта, возвращает их число
+pub async fn invalidate_script_cache(state: &AppState, script_name: &str) -> usize {
+    let prefix = format!("{}:", script_name);
+    state
+        .cache
+        .lock()
+        .await
+        .retain(|key, _| !key.starts_with(&prefix))
+}
+
+// Удаляет результаты скриптов, которых нет среди `names` (в том числе
+// восстановленные из RUNNER_CACHE_DB для удалённых при остановке сервера)
+async fn prune_cache(state: &AppState, names: &HashSet<String>) -> usize {
+    state
+        .cache
+        .lock()
+        .await
+        .retain(|key, _| key.rsplit_once(':').is_some_and(|(name, _)| names.contains(name)))
+}
+
+// Фоновое сканирование. Обход директории и синхронизация с БД идут без
+// блокировки `scripts`; новый список подменяется одной операцией в конце.
+pub async fn scan_scripts(state: Arc<AppState>) {
+    let started = Instant::now();
+    let known_before: HashSet<PathBuf> = state.scripts.lock().await.keys().cloned().collect();
+    let current_files = collect_scripts(&state).await;
+    if !sync_db(&state, &current_files).await {
+        return;
     }
+    let scripts = refresh_scripts(&state, &known_before, current_files).await;
+    state.events.publish(EventData::ScanCompleted {
+        scripts,
+        duration_ms: started.elapsed().as_millis() as u64,
+    });
+}
 
+// Приводит записи скриптов в БД к файлам на диске; `false`, если список
+// из БД не прочитан
+async fn sync_db(state: &AppState, current_files: &[(PathBuf, String)]) -> bool {
     let db_docs = match db::get_all_scripts(&state.db).await {
         Ok(docs) => docs,
         Err(e) => {
             warn!("Failed to get scripts from DB during scan: {}", e);
-            return;
+            return false;
         }
     };
 
-    for path in &current_files {
-        let file_name = match path.file_name().and_then(|n| n.to_str()) {
-            Some(name) => name.to_string(),
-            None => continue,
-        };
+    let docs_by_name: HashMap<&str, &db::ScriptDoc> =
+        db_docs.iter().map(|doc| (doc.name.as_str(), doc)).collect();
+    let current_names: HashSet<&str> = current_files.iter().map(|(_, name)| name.as_str()).collect();
+
+    for (path, file_name) in current_files {
+        let file_name = file_name.clone();
         let meta = match fs::metadata(path).await {
             Ok(m) => m,
             Err(_) => continue,
         };
-        let modified: DateTime<Utc> = meta
-            .modified()
-            .unwrap_or_else(|_| SystemTime::now())
-            .into();
+        // Пакет хранится в БД кодом точки входа, общим размером и mtime самого нового файла
+        let (code_path, size, modified) = if meta.is_dir() {
+            let Ok(snapshot) = package::snapshot(path).await else {
+                continue;
+            };
+            let config = script_config::load(path).await;
+            (package::entry_file(path, &config), snapshot.size, snapshot.newest)
+        } else {
+            (path.clone(), meta.len(), meta.modified().ok())
+        };
+        let modified: DateTime<Utc> = modified.unwrap_or_else(SystemTime::now).into();
 
-        if let Some(doc) = db_docs.iter().find(|d| d.name == file_name) {
+        if let Some(doc) = docs_by_name.get(file_name.as_str()) {
             // Сравниваем по миллисекундам
             if doc.modified.timestamp_millis() < modified.timestamp_millis() {
-                let code = match fs::read_to_string(path).await {
+                let code = match fs::read_to_string(&code_path).await {
                     Ok(c) => c,
                     Err(_) => continue,
                 };
                 let update = doc! {
                     "code": code,
-                    "size": meta.len() as i64,
+                    "size": size as i64,
                     "modified": mongodb::bson::DateTime::from_millis(modified.timestamp_millis()),
                 };
                 if let Err(e) = db::update_script(&state.db, &file_name, update).await {
@@ -189,15 +1764,16 @@ pub async fn scan_scripts(state: Arc<AppState>) {
             let doc = db::ScriptDoc {
                 id: None,
                 name: file_name,
-                code: match fs::read_to_string(path).await {
+                code: match fs::read_to_string(&code_path).await {
                     Ok(c) => c,
                     Err(_) => continue,
                 },
                 description: None,
                 result: None,
-                size: meta.len(),
+                size,
                 created: mongodb::bson::DateTime::from_millis(created.timestamp_millis()),
                 modified: mongodb::bson::DateTime::from_millis(modified.timestamp_millis()),
+                origin: None,
             };
             if let Err(e) = db::insert_script(&state.db, doc).await {
                 warn!("Failed to insert new script into DB: {}", e);
@@ -206,18 +1782,322 @@ pub async fn scan_scripts(state: Arc<AppState>) {
     }
 
     // Удаляем из БД записи, для которых нет файлов
-    for doc in db_docs {
-        if !current_files
-            .iter()
-            .any(|p| p.file_name().and_then(|n| n.to_str()) == Some(&doc.name))
-        {
+    for doc in &db_docs {
+        if !current_names.contains(doc.name.as_str()) {
             if let Err(e) = db::delete_script(&state.db, &doc.name).await {
                 warn!("Failed to delete script from DB: {}", e);
             }
         }
     }
+    true
+}
+
+// Обновляет список в памяти вместе с настройками и хэшами скриптов;
+// `known_before` — список на начало сканирования. Возвращает число скриптов.
+async fn refresh_scripts(
+    state: &AppState,
+    known_before: &HashSet<PathBuf>,
+    current_files: Vec<(PathBuf, String)>,
+) -> usize {
+    let mut entries = HashMap::with_capacity(current_files.len());
+    for (path, _) in current_files {
+        let previous = state.scripts.lock().await.get(&path).cloned();
+        let entry = reload_entry(&path, previous.as_ref()).await;
+        entries.insert(path, entry);
+    }
+    let scripts = entries.len();
+    // Скрипты, изменённые на диске с прошлого сканирования
+    let (changed, names): (Vec<String>, HashSet<String>) = {
+        let mut known = state.scripts.lock().await;
+        let changed = entries
+            .iter()
+            .filter(|(path, entry)| known.get(*path).is_some_and(|old| old.hash != entry.hash))
+            .filter_map(|(path, _)| state.script_name(path))
+            .collect();
+        merge_scanned(&mut known, known_before, entries);
+        let names = known.keys().filter_map(|path| state.script_name(path)).collect();
+        (changed, names)
+    };
+
+    let mut evicted = prune_cache(state, &names).await;
+    for name in &changed {
+        evicted += invalidate_script_cache(state, name).await;
+    }
+    if evicted > 0 {
+        info!("Evicted {} cached results of changed or removed scripts", evicted);
+    }
+    scripts
+}
+
+// Переносит результат сканирования в список в памяти. Скрипты, которые
+// API или наблюдатель добавили во время сканирования, остаются, а удалённые
+// за это время не возвращаются; `before` — список на начало сканирования.
+fn merge_scanned(
+    known: &mut HashMap<PathBuf, ScriptEntry>,
+    before: &HashSet<PathBuf>,
+    scanned: HashMap<PathBuf, ScriptEntry>,
+) {
+    known.retain(|path, _| !before.contains(path) || scanned.contains_key(path));
+    for (path, entry) in scanned {
+        let registered_during_scan = !before.contains(&path) && known.contains_key(&path);
+        let removed_during_scan = before.contains(&path) && !known.contains_key(&path);
+        if !registered_during_scan && !removed_during_scan {
+            known.insert(path, entry);
+        }
+    }
+}
+
+// Скрипты в директории и её поддиректориях (не глубже MAX_SCRIPT_DEPTH) с их
+// именами. Скрытые файлы и директории, в том числе предыдущие версии в
+// `.versions`, пропускаются; символические ссылки на директории не обходятся.
+// Пакет — один скрипт с именем директории, его файлы отдельно не учитываются.
+async fn collect_scripts(state: &AppState) -> Vec<(PathBuf, String)> {
+    let mut scripts = Vec::new();
+    let mut dirs = vec![(state.scripts_dir.clone(), 0)];
+    while let Some((dir, depth)) = dirs.pop() {
+        let Ok(mut entries) = fs::read_dir(&dir).await else {
+            continue;
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            let is_dir = entry.file_type().await.is_ok_and(|t| t.is_dir());
+            if is_dir {
+                let valid = entry.file_name().to_str().is_some_and(is_valid_dir_name);
+                if !valid {
+                    continue;
+                }
+                if package::is_package(&path).await {
+                    if let Some(name) = state.script_name(&path) {
+                        scripts.push((path, name));
+                    }
+                } else if depth < MAX_SCRIPT_DEPTH {
+                    dirs.push((path, depth + 1));
+                }
+            } else if state.is_supported_script(&path) {
+                if let Some(name) = state.script_name(&path) {
+                    scripts.push((path, name));
+                }
+            }
+        }
+    }
+    scripts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestServer;
+
+    fn entry(hash: &str) -> ScriptEntry {
+        ScriptEntry {
+            config: ScriptConfig::default(),
+            kind: ScriptKind::File,
+            hash: hash.to_string(),
+            mtime: None,
+        }
+    }
+
+    #[test]
+    fn scan_keeps_scripts_changed_while_it_ran() {
+        let path = |name: &str| PathBuf::from("/scripts").join(name);
+        let before: HashSet<PathBuf> = ["kept.py", "gone.py", "deleted.py"].map(path).into();
+        // Во время сканирования через API создан new.py и удалён deleted.py
+        let mut known: HashMap<PathBuf, ScriptEntry> = [
+            (path("kept.py"), entry("old")),
+            (path("gone.py"), entry("gone")),
+            (path("new.py"), entry("api")),
+        ]
+        .into();
+        let scanned: HashMap<PathBuf, ScriptEntry> = [
+            (path("kept.py"), entry("scanned")),
+            (path("deleted.py"), entry("deleted")),
+            (path("new.py"), entry("scanned")),
+        ]
+        .into();
+
+        merge_scanned(&mut known, &before, scanned);
+
+        let mut hashes: Vec<(String, String)> = known
+            .iter()
+            .map(|(path, entry)| (path.file_name().unwrap().to_string_lossy().into_owned(), entry.hash.clone()))
+            .collect();
+        hashes.sort();
+        assert_eq!(
+            hashes,
+            [("kept.py".to_string(), "scanned".to_string()), ("new.py".to_string(), "api".to_string())]
+        );
+    }
+
+    // Шаги сканирования по порядку: снимок списка и обход директории, затем
+    // (пока идёт синхронизация с БД) регистрация и удаление скриптов через
+    // API, затем обновление списка в памяти
+    #[tokio::test]
+    async fn scan_keeps_scripts_registered_during_it() {
+        let server = TestServer::new(|_| {}).await;
+        let state = &server.state;
+        let kept = server.add_script("kept.py", "print('kept')\n").await;
+        let deleted = server.add_script("deleted.py", "print('deleted')\n").await;
+
+        let before: HashSet<PathBuf> = state.scripts.lock().await.keys().cloned().collect();
+        let files = collect_scripts(state).await;
+
+        let added = server.add_script("added.py", "print('added')\n").await;
+        std::fs::remove_file(&deleted).unwrap();
+        state.scripts.lock().await.remove(&deleted);
+
+        let scripts = refresh_scripts(state, &before, files).await;
+        assert_eq!(scripts, 2);
+        let known = state.scripts.lock().await;
+        let mut paths: Vec<&PathBuf> = known.keys().collect();
+        paths.sort();
+        assert_eq!(paths, [&added, &kept]);
+    }
+
+    // Скрипт оставляет внука, который игнорирует SIGTERM и переживает родителя
+    #[cfg(target_os = "linux")]
+    const FORKING_SCRIPT: &str = r#"import os, signal, time
+if os.fork() == 0:
+    if os.fork() == 0:
+        signal.signal(signal.SIGTERM, signal.SIG_IGN)
+        time.sleep(1000)
+    os._exit(0)
+print(os.getpgid(0), flush=True)
+time.sleep(1000)
+"#;
+
+    fn timeout_options() -> RunOptions {
+        RunOptions {
+            timeout_secs: Some(1),
+            partial_on_timeout: true,
+            no_cache: true,
+            ..Default::default()
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn timeout_leaves_no_process_behind() {
+        let server = TestServer::new(|_| {}).await;
+        server
+            .add_script("sleeper.py", "import os, time\nprint(os.getpid(), flush=True)\ntime.sleep(1000)\n")
+            .await;
+
+        let result = run_script(Arc::clone(&server.state), "sleeper.py", Bytes::new(), timeout_options())
+            .await
+            .expect("partial result");
+        assert!(result.timed_out);
+        let pid: libc::pid_t = result.stdout.trim().parse().expect("script pid");
+        // SAFETY: сигнал 0 только проверяет, существует ли процесс
+        let alive = unsafe { libc::kill(pid, 0) } == 0;
+        assert!(!alive, "script process {} is still running", pid);
+    }
 
-    // Обновляем список в памяти
-    let mut scripts = state.scripts.lock().await;
-    *scripts = current_files;
-}
\ No newline at end of file
+    #[tokio::test]
+    async fn invalid_utf8_output_is_returned_as_base64() {
+        let server = TestServer::new(|_| {}).await;
+        server
+            .add_script("binary.py", "import sys\nsys.stdout.buffer.write(b'\\xff\\xfe')\n")
+            .await;
+
+        // Второй запуск отдаётся из кэша и должен совпадать с первым
+        for cached in [false, true] {
+            let result = run_script(Arc::clone(&server.state), "binary.py", Bytes::new(), RunOptions::default())
+                .await
+                .expect("result");
+            assert_eq!(result.cached, cached);
+            assert_eq!(result.stdout_encoding, OutputEncoding::Base64);
+            assert_eq!(result.stdout, "//4=");
+            let json = serde_json::to_value(&result).expect("serialize result");
+            assert_eq!(json["stdout"], "//4=");
+            assert_eq!(json["stdout_encoding"], "base64");
+            assert_eq!(json["stderr_encoding"], "utf8");
+        }
+    }
+
+    #[tokio::test]
+    async fn artifacts_are_collected_from_artifacts_and_output() {
+        let server = TestServer::new(|_| {}).await;
+        let code = "\
+for path, text in [('artifacts/report.txt', 'new'), ('artifacts/same.txt', 'artifacts'),
+                   ('output/legacy.txt', 'old'), ('output/same.txt', 'output')]:
+    open(path, 'w').write(text)
+";
+        server.add_script("files.py", code).await;
+        let result = run_script(Arc::clone(&server.state), "files.py", Bytes::new(), RunOptions::default())
+            .await
+            .expect("result");
+
+        let names: Vec<&str> = result.artifacts.iter().map(|artifact| artifact.name.as_str()).collect();
+        assert_eq!(names, ["legacy.txt", "report.txt", "same.txt"]);
+        let run_id = result.run_id.as_deref().expect("run_id");
+        let same = artifacts::artifact_path(&server.state, run_id, "same.txt").expect("artifact path");
+        assert_eq!(std::fs::read_to_string(same).unwrap(), "artifacts");
+    }
+
+    #[tokio::test]
+    async fn cached_result_describes_its_invocation() {
+        let server = TestServer::new(|_| {}).await;
+        server.add_script("echo.py", "import sys\nprint(sys.argv[1:])\n").await;
+
+        let mut invocations = Vec::new();
+        for (cached, timeout_secs) in [(false, 5), (true, 7)] {
+            let options = RunOptions {
+                args: vec!["a".to_string(), "b".to_string()],
+                timeout_secs: Some(timeout_secs),
+                ..RunOptions::default()
+            };
+            let result = run_script(Arc::clone(&server.state), "echo.py", Bytes::new(), options)
+                .await
+                .expect("result");
+            assert_eq!(result.cached, cached);
+            let invocation = result.invocation.expect("invocation");
+            assert_eq!(invocation.script, "echo.py");
+            assert_eq!(invocation.args, ["a", "b"]);
+            assert_eq!(invocation.timeout_secs, timeout_secs);
+            invocations.push(invocation.interpreter);
+        }
+        assert_eq!(invocations[0], invocations[1]);
+    }
+
+    #[tokio::test]
+    async fn endless_output_is_truncated_and_stopped() {
+        let limit = 64 * 1024;
+        let server = TestServer::new(|config| config.max_output_bytes = limit).await;
+        server.add_script("noisy.py", "while True:\n    print('x' * 1000)\n").await;
+        let options = RunOptions {
+            timeout_secs: Some(60),
+            no_cache: true,
+            ..Default::default()
+        };
+
+        let started = Instant::now();
+        let result = run_script(Arc::clone(&server.state), "noisy.py", Bytes::new(), options)
+            .await
+            .expect("truncated result");
+        assert!(result.stdout_truncated);
+        assert!(result.stdout.len() <= limit, "stdout has {} bytes", result.stdout.len());
+        assert!(!result.timed_out);
+        // Процесс остановлен по лимиту, а не дождался таймаута
+        assert!(started.elapsed() < Duration::from_secs(30));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn timeout_kills_whole_process_group() {
+        let server = TestServer::new(|_| {}).await;
+        server.add_script("forker.py", FORKING_SCRIPT).await;
+
+        let result = run_script(Arc::clone(&server.state), "forker.py", Bytes::new(), timeout_options())
+            .await
+            .expect("partial result");
+        assert!(result.timed_out);
+        let pgid: u32 = result.stdout.trim().parse().expect("process group id");
+
+        // SIGKILL доставляется асинхронно
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !crate::test_support::running_in_group(pgid).is_empty() {
+            assert!(Instant::now() < deadline, "processes left in group {}", pgid);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use crate::{
+    error::AppError,
+    models::SecretInfo,
+    script_runner::PipeOutput,
+    utils::{validate_env, write_atomic},
+};
+use anyhow::{anyhow, Context};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    ChaCha20Poly1305, Nonce,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+// Чем заменяются значения секретов в выводе скрипта
+const MASK: &str = "***";
+
+const MAX_SECRET_NAME_LEN: usize = 128;
+
+// Запись файла секретов; имя секрета — дополнительные данные шифра,
+// поэтому значение нельзя переставить под другое имя
+#[derive(Serialize, Deserialize)]
+struct StoredSecret {
+    nonce: String,
+    value: String,
+    updated_at: DateTime<Utc>,
+}
+
+struct Secret {
+    value: String,
+    updated_at: DateTime<Utc>,
+}
+
+/// Секреты для переменных окружения запусков. Значения хранятся в файле
+/// `RUNNER_SECRETS_FILE`, зашифрованные ChaCha20-Poly1305 ключом из
+/// `RUNNER_SECRETS_KEY`; без ключа хранилище выключено.
+pub struct SecretStore {
+    cipher: Option<ChaCha20Poly1305>,
+    path: PathBuf,
+    secrets: Mutex<BTreeMap<String, Secret>>,
+}
+
+impl SecretStore {
+    /// Расшифровывает сохранённые секреты. Неверный ключ или повреждённый
+    /// файл — ошибка запуска сервера: иначе первое изменение затёрло бы секреты.
+    pub fn open(key: Option<&str>, path: PathBuf) -> anyhow::Result<Self> {
+        let content = match std::fs::read(&path) {
+            Ok(content) => Some(content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e).with_context(|| format!("Failed to read secrets file {}", path.display())),
+        };
+        let Some(key) = key else {
+            if content.is_some() {
+                warn!("RUNNER_SECRETS_KEY is not set; secrets in {} are unavailable", path.display());
+            }
+            return Ok(Self {
+                cipher: None,
+                path,
+                secrets: Mutex::new(BTreeMap::new()),
+            });
+        };
+
+        let key = STANDARD
+            .decode(key.trim())
+            .ok()
+            .filter(|key| key.len() == 32)
+            .ok_or_else(|| anyhow!("RUNNER_SECRETS_KEY must be 32 bytes encoded in base64"))?;
+        let cipher = ChaCha20Poly1305::new_from_slice(&key).map_err(|e| anyhow!("Invalid secrets key: {}", e))?;
+
+        let mut secrets = BTreeMap::new();
+        if let Some(content) = content {
+            let stored: BTreeMap<String, StoredSecret> = serde_json::from_slice(&content)
+                .with_context(|| format!("Failed to parse secrets file {}", path.display()))?;
+            for (name, secret) in stored {
+                let value = decrypt(&cipher, &name, &secret).ok_or_else(|| {
+                    anyhow!(
+                        "Failed to decrypt secret {} from {}: wrong RUNNER_SECRETS_KEY or corrupted file",
+                        name,
+                        path.display()
+                    )
+                })?;
+                secrets.insert(
+                    name,
+                    Secret {
+                        value,
+                        updated_at: secret.updated_at,
+                    },
+                );
+            }
+            info!("Loaded {} secrets from {}", secrets.len(), path.display());
+        }
+        Ok(Self {
+            cipher: Some(cipher),
+            path,
+            secrets: Mutex::new(secrets),
+        })
+    }
+
+    fn cipher(&self) -> Result<&ChaCha20Poly1305, AppError> {
+        self.cipher
+            .as_ref()
+            .ok_or_else(|| AppError::BadRequest("Secrets store is disabled: RUNNER_SECRETS_KEY is not set".to_string()))
+    }
+
+    /// Имена секретов без значений, по алфавиту
+    pub async fn list(&self) -> Vec<SecretInfo> {
+        let secrets = self.secrets.lock().await;
+        secrets
+            .iter()
+            .map(|(name, secret)| SecretInfo {
+                name: name.clone(),
+                updated_at: secret.updated_at,
+            })
+            .collect()
+    }
+
+    /// Создаёт или заменяет секрет
+    pub async fn set(&self, name: &str, value: String) -> Result<(), AppError> {
+        validate_secret_name(name)?;
+        if value.is_empty() || value.contains('\0') {
+            return Err(AppError::BadRequest(format!("Invalid value for secret '{}'", name)));
+        }
+        let cipher = self.cipher()?;
+        let mut secrets = self.secrets.lock().await;
+        secrets.insert(
+            name.to_string(),
+            Secret {
+                value,
+                updated_at: Utc::now(),
+            },
+        );
+        self.persist(cipher, &secrets).await
+    }
+
+    pub async fn delete(&self, name: &str) -> Result<(), AppError> {
+        let cipher = self.cipher()?;
+        let mut secrets = self.secrets.lock().await;
+        if secrets.remove(name).is_none() {
+            return Err(AppError::SecretNotFound(name.to_string()));
+        }
+        self.persist(cipher, &secrets).await
+    }
+
+    /// Значения секретов запуска
+    pub async fn resolve(&self, names: &[String]) -> Result<RunSecrets, AppError> {
+        if names.is_empty() {
+            return Ok(RunSecrets::default());
+        }
+        self.cipher()?;
+        let secrets = self.secrets.lock().await;
+        let mut env = BTreeMap::new();
+        for name in names {
+            let secret = secrets
+                .get(name)
+                .ok_or_else(|| AppError::SecretNotFound(name.clone()))?;
+            env.insert(name.clone(), secret.value.clone());
+        }
+        Ok(RunSecrets { env })
+    }
+
+    // Шифрует все секреты заново и записывает файл под блокировкой списка
+    async fn persist(&self, cipher: &ChaCha20Poly1305, secrets: &BTreeMap<String, Secret>) -> Result<(), AppError> {
+        let mut stored = BTreeMap::new();
+        for (name, secret) in secrets {
+            let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+            let payload = Payload {
+                msg: secret.value.as_bytes(),
+                aad: name.as_bytes(),
+            };
+            let value = cipher
+                .encrypt(&nonce, payload)
+                .map_err(|_| AppError::Internal(format!("Failed to encrypt secret {}", name)))?;
+            stored.insert(
+                name,
+                StoredSecret {
+                    nonce: STANDARD.encode(nonce),
+                    value: STANDARD.encode(value),
+                    updated_at: secret.updated_at,
+                },
+            );
+        }
+        let content = serde_json::to_vec_pretty(&stored)
+            .map_err(|e| AppError::Internal(format!("Failed to serialize secrets: {}", e)))?;
+        write_atomic(&self.path, &content, true).await?;
+        restrict_permissions(&self.path);
+        Ok(())
+    }
+}
+
+fn decrypt(cipher: &ChaCha20Poly1305, name: &str, secret: &StoredSecret) -> Option<String> {
+    let nonce = STANDARD.decode(&secret.nonce).ok().filter(|nonce| nonce.len() == 12)?;
+    let value = STANDARD.decode(&secret.value).ok()?;
+    let payload = Payload {
+        msg: &value,
+        aad: name.as_bytes(),
+    };
+    let plain = cipher.decrypt(Nonce::from_slice(&nonce), payload).ok()?;
+    String::from_utf8(plain).ok()
+}
+
+#[cfg(unix)]
+fn restrict_permissions(path: &Path) {
+    use std::os::unix::fs::PermissionsExt;
+    if let Err(e) = std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600)) {
+        warn!("Failed to restrict permissions of {}: {}", path.display(), e);
+    }
+}
+
+#[cfg(not(unix))]
+fn restrict_permissions(_path: &Path) {}
+
+/// Имя секрета — имя переменной окружения: латинские буквы, цифры и `_`,
+/// не с цифры; переменные, влияющие на загрузку программ, запрещены
+pub fn validate_secret_name(name: &str) -> Result<(), AppError> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_SECRET_NAME_LEN
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(AppError::BadRequest(format!("Invalid secret name '{}'", name)));
+    }
+    validate_env(&BTreeMap::from([(name.to_string(), String::new())]))
+}
+
+/// Секреты одного запуска: переменные окружения процесса и значения,
+/// которые маскируются в его выводе
+#[derive(Default)]
+pub struct RunSecrets {
+    env: BTreeMap<String, String>,
+}
+
+impl RunSecrets {
+    /// Окружение запуска вместе с секретами
+    pub fn with_env(&self, env: &BTreeMap<String, String>) -> BTreeMap<String, String> {
+        let mut merged = env.clone();
+        merged.extend(self.env.iter().map(|(k, v)| (k.clone(), v.clone())));
+        merged
+    }
+
+    pub fn mask(&self, mut output: PipeOutput) -> PipeOutput {
+        for value in self.env.values() {
+            output.bytes = replace_all(&output.bytes, value.as_bytes());
+        }
+        output
+    }
+
+    pub fn mask_text(&self, mut text: String) -> String {
+        for value in self.env.values().filter(|value| !value.is_empty()) {
+            if text.contains(value.as_str()) {
+                text = text.replace(value.as_str(), MASK);
+            }
+        }
+        text
+    }
+}
+
+fn replace_all(haystack: &[u8], needle: &[u8]) -> Vec<u8> {
+    if needle.is_empty() {
+        return haystack.to_vec();
+    }
+    let mut result = Vec::with_capacity(haystack.len());
+    let mut rest = haystack;
+    while !rest.is_empty() {
+        if rest.starts_with(needle) {
+            result.extend_from_slice(MASK.as_bytes());
+            rest = &rest[needle.len()..];
+        } else {
+            result.push(rest[0]);
+            rest = &rest[1..];
+        }
+    }
+    result
+}
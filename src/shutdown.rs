@@ -0,0 +1,105 @@
+use crate::error::AppError;
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+use tokio::{sync::Notify, time::timeout};
+use tokio_util::sync::{CancellationToken, WaitForCancellationFutureOwned};
+use tracing::{info, warn};
+
+// Сколько ждать сборки процессов после принудительной остановки
+const KILL_WAIT: Duration = Duration::from_secs(5);
+
+/// Учёт выполняющихся запусков и корректное завершение сервера:
+/// после начала остановки новые запуски отклоняются, текущие получают
+/// время на завершение, оставшиеся процессы останавливаются принудительно.
+#[derive(Default)]
+pub struct Shutdown {
+    draining: CancellationToken,
+    kill: CancellationToken,
+    active: AtomicUsize,
+    idle: Notify,
+}
+
+/// Итог остановки
+#[derive(Debug, Clone, Copy)]
+pub struct DrainReport {
+    pub completed: usize,
+    pub killed: usize,
+}
+
+/// Запуск, учтённый в счётчике; снимается с учёта при удалении
+pub struct RunGuard<'a> {
+    shutdown: &'a Shutdown,
+}
+
+impl Drop for RunGuard<'_> {
+    fn drop(&mut self) {
+        if self.shutdown.active.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.shutdown.idle.notify_waiters();
+        }
+    }
+}
+
+impl Shutdown {
+    pub fn is_draining(&self) -> bool {
+        self.draining.is_cancelled()
+    }
+
+    /// Регистрирует новый запуск; во время остановки возвращает `ShuttingDown`
+    pub fn track_run(&self) -> Result<RunGuard<'_>, AppError> {
+        if self.is_draining() {
+            return Err(AppError::ShuttingDown);
+        }
+        self.active.fetch_add(1, Ordering::AcqRel);
+        Ok(RunGuard { shutdown: self })
+    }
+
+    /// Завершается, когда началась остановка (для `with_graceful_shutdown`)
+    pub fn draining(&self) -> WaitForCancellationFutureOwned {
+        self.draining.clone().cancelled_owned()
+    }
+
+    /// Завершается, когда время на остановку вышло и процессы нужно убить
+    pub async fn killed(&self) {
+        self.kill.cancelled().await
+    }
+
+    /// Останавливает приём запусков и ждёт текущие не дольше `drain_timeout`,
+    /// затем принудительно завершает оставшиеся.
+    pub async fn drain(&self, drain_timeout: Duration) -> DrainReport {
+        self.draining.cancel();
+        let in_flight = self.active.load(Ordering::Acquire);
+        info!("Waiting up to {:?} for {} in-flight runs", drain_timeout, in_flight);
+
+        if timeout(drain_timeout, self.wait_idle()).await.is_ok() {
+            return DrainReport {
+                completed: in_flight,
+                killed: 0,
+            };
+        }
+
+        let killed = self.active.load(Ordering::Acquire);
+        warn!("Drain timeout expired, killing {} remaining runs", killed);
+        self.kill.cancel();
+        if timeout(KILL_WAIT, self.wait_idle()).await.is_err() {
+            warn!("Some runs did not stop after kill");
+        }
+
+        DrainReport {
+            completed: in_flight.saturating_sub(killed),
+            killed,
+        }
+    }
+
+    async fn wait_idle(&self) {
+        loop {
+            // Future создаётся до проверки счётчика, чтобы не пропустить notify_waiters
+            let notified = self.idle.notified();
+            if self.active.load(Ordering::Acquire) == 0 {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use crate::{
+    app_state::AppState,
+    models::{HistoryEntry, LoadStats, RunStatus, ScriptLoad, ScriptStats, StatsSummary},
+};
+use std::{collections::BTreeMap, sync::atomic::Ordering};
+
+/// Учитывает запуск в счётчиках скрипта. Длительность считается
+/// только по реальным запускам, без ответов из кэша.
+pub async fn record(state: &AppState, entry: &HistoryEntry) {
+    let mut stats = state.stats.lock().await;
+    let s = stats
+        .entry(entry.script.clone())
+        .or_insert_with(|| ScriptStats {
+            script: entry.script.clone(),
+            ..Default::default()
+        });
+
+    s.runs += 1;
+    match entry.status {
+        RunStatus::Failed | RunStatus::Error => s.failures += 1,
+        RunStatus::TimedOut => s.timeouts += 1,
+        RunStatus::Success | RunStatus::Cancelled => {}
+    }
+    s.last_run_at = Some(entry.started_at);
+
+    if entry.cached {
+        s.cache_hits += 1;
+        return;
+    }
+    s.executed += 1;
+    s.total_duration_ms += entry.duration_ms;
+    s.min_duration_ms = Some(s.min_duration_ms.map_or(entry.duration_ms, |m| m.min(entry.duration_ms)));
+    s.max_duration_ms = Some(s.max_duration_ms.map_or(entry.duration_ms, |m| m.max(entry.duration_ms)));
+    s.avg_duration_ms = Some(s.total_duration_ms / s.executed);
+}
+
+/// Счётчики скрипта; для скрипта без запусков — нулевые
+pub async fn get(state: &AppState, script: &str) -> ScriptStats {
+    state
+        .stats
+        .lock()
+        .await
+        .get(script)
+        .cloned()
+        .unwrap_or_else(|| ScriptStats {
+            script: script.to_string(),
+            ..Default::default()
+        })
+}
+
+/// Сводка по всем скриптам, отсортированным по имени
+pub async fn summary(state: &AppState) -> StatsSummary {
+    let mut scripts: Vec<ScriptStats> = state.stats.lock().await.values().cloned().collect();
+    scripts.sort_by(|a, b| a.script.cmp(&b.script));
+    let script_load: BTreeMap<String, ScriptLoad> = state
+        .script_limiters
+        .lock()
+        .await
+        .iter()
+        .map(|(name, limiter)| {
+            let load = ScriptLoad {
+                running: limiter.running(),
+                queued: limiter.queued.load(Ordering::Relaxed),
+                max_concurrent: limiter.limit,
+                rejections: limiter.rejections.load(Ordering::Relaxed),
+            };
+            (name.clone(), load)
+        })
+        .collect();
+
+    StatsSummary {
+        runs: scripts.iter().map(|s| s.runs).sum(),
+        failures: scripts.iter().map(|s| s.failures).sum(),
+        timeouts: scripts.iter().map(|s| s.timeouts).sum(),
+        cache_hits: scripts.iter().map(|s| s.cache_hits).sum(),
+        load: LoadStats {
+            running: state.running(),
+            queued: state.queued.load(Ordering::Relaxed),
+            max_concurrent: state.max_concurrent(),
+            busy_rejections: state.busy_rejections.load(Ordering::Relaxed),
+            pool: state.pool.as_ref().map(|pool| pool.stats()),
+            scripts: script_load,
+        },
+        webhooks: state.webhooks.stats(),
+        quotas: state.quotas.summary().await,
+        gc: state.gc.last(),
+        scripts,
+    }
+}
+
+/// Сбрасывает счётчики скрипта, возвращает `true`, если они были
+pub async fn reset(state: &AppState, script: &str) -> bool {
+    state.stats.lock().await.remove(script).is_some()
+}
@@ -0,0 +1,96 @@
+//! Окружение модульных тестов: состояние сервера над временной директорией
+//! скриптов. MongoDB не нужна: клиент подключается лениво, а запуск скриптов
+//! к базе не обращается; запросы к базе завершаются ошибкой.
+use crate::{
+    app_state::AppState, audit::AuditLog, config::Config, db, script_runner, secrets::SecretStore,
+};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use uuid::Uuid;
+
+// Адрес, на котором MongoDB заведомо нет
+const MONGO_URI: &str = "mongodb://127.0.0.1:9/?serverSelectionTimeoutMS=2000";
+
+/// Временная директория, удаляемая вместе со значением
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new() -> Self {
+        let path = std::env::temp_dir().join(format!("script-runner-test-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&path).expect("create temp dir");
+        Self(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Состояние сервера и его временная директория
+pub struct TestServer {
+    pub state: Arc<AppState>,
+    // Удаляется вместе с сервером
+    _dir: TempDir,
+}
+
+impl TestServer {
+    /// Настройки по умолчанию, изменённые `configure`. Все файлы сервера —
+    /// во временной директории.
+    pub async fn new(configure: impl FnOnce(&mut Config)) -> Self {
+        let dir = TempDir::new();
+        let mut config = Config::from_env().expect("default config");
+        config.scripts_dir = dir.path().join("scripts");
+        config.artifacts_dir = dir.path().join("artifacts");
+        config.secrets_file = dir.path().join("secrets.json");
+        config.cache_db = None;
+        config.history_db = None;
+        config.audit_log = None;
+        configure(&mut config);
+        std::fs::create_dir_all(&config.scripts_dir).expect("create scripts dir");
+
+        let db = db::init_db(MONGO_URI, "script_runner_test").await.expect("lazy MongoDB client");
+        let audit = AuditLog::open(config.audit_size, None).expect("audit log");
+        let secrets = SecretStore::open(None, config.secrets_file.clone()).expect("secret store");
+        let state = Arc::new(AppState::new(&config, db, None, audit, secrets));
+        Self { state, _dir: dir }
+    }
+
+    /// Записывает скрипт и регистрирует его, как это делает наблюдатель
+    pub async fn add_script(&self, name: &str, code: &str) -> PathBuf {
+        let path = self.state.script_path(name);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).expect("create script dir");
+        }
+        std::fs::write(&path, code).expect("write script");
+        script_runner::register_script(&self.state, path.clone()).await;
+        path
+    }
+}
+
+/// Процессы группы `pgid`, которые ещё работают (зомби не считаются)
+#[cfg(target_os = "linux")]
+pub fn running_in_group(pgid: u32) -> Vec<u32> {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let pid: u32 = entry.file_name().to_str()?.parse().ok()?;
+            let stat = std::fs::read_to_string(entry.path().join("stat")).ok()?;
+            // После `(comm)`: состояние, ppid, pgrp
+            let mut fields = stat.rsplit_once(')')?.1.split_whitespace();
+            let state = fields.next()?;
+            let group: u32 = fields.nth(1)?.parse().ok()?;
+            (group == pgid && state != "Z" && state != "X").then_some(pid)
+        })
+        .collect()
+}
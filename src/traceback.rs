@@ -0,0 +1,76 @@
+use crate::models::{PythonError, TracebackFrame};
+
+const TRACEBACK_HEADER: &str = "Traceback (most recent call last):";
+
+/// Последний traceback Python в stderr. Вложенные traceback'и (например,
+/// `RemoteTraceback` из multiprocessing) и цепочки исключений дают последний,
+/// то есть исключение, завершившее процесс. Всё, что не удалось разобрать, — `None`.
+pub fn parse(stderr: &str) -> Option<PythonError> {
+    let lines: Vec<&str> = stderr.lines().collect();
+    let start = lines.iter().rposition(|line| line.trim() == TRACEBACK_HEADER)?;
+    let indent = indent_of(lines[start]);
+
+    let mut frames: Vec<TracebackFrame> = Vec::new();
+    let mut rest = lines[start + 1..].iter();
+    for line in rest.by_ref() {
+        let line = line.get(indent..).unwrap_or_default();
+        let body = line.trim_start();
+        if body.is_empty() || body.starts_with("[Previous line repeated") {
+            continue;
+        }
+        if indent_of(line) == 0 {
+            let (error_type, message) = exception_line(body)?;
+            let last = frames.last();
+            return Some(PythonError {
+                error_type,
+                message,
+                file: last.map(|frame| frame.file.clone()),
+                line: last.map(|frame| frame.line),
+                frames,
+            });
+        }
+        if let Some(frame) = frame_line(body) {
+            frames.push(frame);
+            continue;
+        }
+        // Строка кода под кадром; маркеры `^^^` и `~~~` Python 3.11+ пропускаются
+        let frame = frames.last_mut()?;
+        if frame.code.is_none() && !body.chars().all(|c| matches!(c, '^' | '~' | ' ')) {
+            frame.code = Some(body.to_string());
+        }
+    }
+    None
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+// `File "path", line 12, in func`; у SyntaxError функции нет
+fn frame_line(body: &str) -> Option<TracebackFrame> {
+    let rest = body.strip_prefix("File \"")?;
+    let (file, rest) = rest.rsplit_once("\", line ")?;
+    let (line, function) = match rest.split_once(", in ") {
+        Some((line, function)) => (line, Some(function.to_string())),
+        None => (rest, None),
+    };
+    Some(TracebackFrame {
+        file: file.to_string(),
+        line: line.trim().parse().ok()?,
+        function,
+        code: None,
+    })
+}
+
+// `module.ErrorType: message` или только `ErrorType`
+fn exception_line(body: &str) -> Option<(String, String)> {
+    let (error_type, message) = match body.split_once(':') {
+        Some((error_type, message)) => (error_type, message.trim()),
+        None => (body.trim_end(), ""),
+    };
+    let valid = error_type.split('.').all(|part| {
+        part.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_')
+            && part.chars().all(|c| c.is_alphanumeric() || c == '_')
+    });
+    valid.then(|| (error_type.to_string(), message.to_string()))
+}
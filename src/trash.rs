@@ -0,0 +1,135 @@
+use crate::{
+    app_state::AppState,
+    error::AppError,
+    gc::Swept,
+    models::TrashItem,
+    utils::{files_under, join_name, relative_name, remove_file_pruning_dirs, validate_script_name},
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{DateTime, Utc};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tracing::{info, warn};
+
+/// Корзина удалённых скриптов внутри директории скриптов; сканер её пропускает
+pub const TRASH_DIR: &str = ".trash";
+
+fn trash_dir(state: &AppState) -> PathBuf {
+    state.scripts_dir.join(TRASH_DIR)
+}
+
+// Удалённый скрипт хранится как `<имя>.<время удаления в мс>`; идентификатор —
+// этот путь в base64url, чтобы в URL не было `/` и точек
+fn item_id(name: &str, deleted_ms: i64) -> String {
+    URL_SAFE_NO_PAD.encode(format!("{}.{}", name, deleted_ms))
+}
+
+// Имя скрипта и время удаления по относительному пути в корзине
+fn parse_entry(relative: &str) -> Option<(String, i64)> {
+    let (name, millis) = relative.rsplit_once('.')?;
+    let millis = millis.parse().ok()?;
+    validate_script_name(name).ok()?;
+    Some((name.to_string(), millis))
+}
+
+/// Переносит скрипт в корзину; возвращает идентификатор для восстановления
+pub async fn move_to_trash(state: &AppState, name: &str) -> Result<String, AppError> {
+    let deleted_ms = Utc::now().timestamp_millis();
+    let target = join_name(&trash_dir(state), &format!("{}.{}", name, deleted_ms));
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    fs::rename(state.script_path(name), &target).await?;
+    info!("Moved script {} to trash", name);
+    Ok(item_id(name, deleted_ms))
+}
+
+/// Скрипты в корзине, от недавно удалённых к давним
+pub async fn list(state: &AppState) -> Vec<TrashItem> {
+    let root = trash_dir(state);
+    let mut items = Vec::new();
+    for path in files_under(&root).await {
+        let Some(relative) = relative_name(&root, &path) else {
+            continue;
+        };
+        let Some((name, deleted_ms)) = parse_entry(&relative) else {
+            continue;
+        };
+        let Ok(meta) = fs::metadata(&path).await else {
+            continue;
+        };
+        items.push(TrashItem {
+            id: item_id(&name, deleted_ms),
+            name,
+            deleted_at: DateTime::from_timestamp_millis(deleted_ms).unwrap_or_default(),
+            size_bytes: meta.len(),
+        });
+    }
+    items.sort_by_key(|item| std::cmp::Reverse(item.deleted_at));
+    items
+}
+
+/// Возвращает скрипт из корзины под прежним именем. Существующий скрипт
+/// с тем же именем не заменяется (409). Возвращает имя скрипта.
+pub async fn restore(state: &AppState, id: &str) -> Result<String, AppError> {
+    let not_found = || AppError::TrashItemNotFound(id.to_string());
+    let relative = URL_SAFE_NO_PAD
+        .decode(id)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .ok_or_else(not_found)?;
+    let (name, _) = parse_entry(&relative).ok_or_else(not_found)?;
+    let source = join_name(&trash_dir(state), &relative);
+    if !fs::try_exists(&source).await? {
+        return Err(not_found());
+    }
+
+    let path = state.script_path(&name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    // Жёсткая ссылка не заменяет скрипт, созданный после удаления
+    fs::hard_link(&source, &path).await.map_err(|e| match e.kind() {
+        std::io::ErrorKind::AlreadyExists => AppError::ScriptAlreadyExists(name.clone()),
+        _ => AppError::Io(e),
+    })?;
+    remove_item(state, &source).await;
+    info!("Restored script {} from trash", name);
+    Ok(name)
+}
+
+/// Окончательно удаляет скрипты, пролежавшие в корзине дольше `RUNNER_TRASH_RETENTION_DAYS`
+pub async fn purge(state: &AppState, retention: std::time::Duration) -> Swept {
+    let root = trash_dir(state);
+    let cutoff = Utc::now() - retention;
+    let mut swept = Swept::default();
+    for path in files_under(&root).await {
+        let expired = relative_name(&root, &path)
+            .as_deref()
+            .and_then(parse_entry)
+            .and_then(|(_, millis)| DateTime::from_timestamp_millis(millis))
+            .is_some_and(|deleted_at| deleted_at < cutoff);
+        if !expired {
+            continue;
+        }
+        let size = fs::metadata(&path).await.map_or(0, |meta| meta.len());
+        if remove_item(state, &path).await {
+            swept.add(size);
+        }
+    }
+    if swept.items > 0 {
+        info!("Purged {} scripts from trash", swept.items);
+    }
+    swept
+}
+
+// Удаляет файл корзины и оставшиеся пустыми поддиректории
+async fn remove_item(state: &AppState, path: &Path) -> bool {
+    match remove_file_pruning_dirs(&trash_dir(state), path).await {
+        Ok(()) => true,
+        Err(e) => {
+            warn!("Failed to remove {} from trash: {}", path.display(), e);
+            false
+        }
+    }
+}
@@ -0,0 +1,433 @@
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use bytes::Bytes;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    path::{Component, Path, PathBuf},
+};
+use tokio::{fs, io::AsyncWriteExt};
+use unicode_normalization::is_nfkc;
+use uuid::Uuid;
+use crate::{error::AppError, models::OutputEncoding};
+
+// Переменные, влияющие на поиск и загрузку программ, интерпретаторов
+// и их модулей
+const BLOCKED_ENV_VARS: &[&str] = &[
+    "PATH",
+    "LD_PRELOAD",
+    "LD_LIBRARY_PATH",
+    "LD_AUDIT",
+    "DYLD_INSERT_LIBRARIES",
+    "DYLD_LIBRARY_PATH",
+    "GCONV_PATH",
+    "BASH_ENV",
+    "ENV",
+    "IFS",
+    "PYTHONSTARTUP",
+    "PYTHONPATH",
+    "PYTHONHOME",
+    "NODE_PATH",
+    "NODE_OPTIONS",
+    "RUBYLIB",
+    "RUBYOPT",
+    "PERL5LIB",
+    "PERL5OPT",
+];
+
+// Символы и имена устройств, недопустимые в именах файлов Windows
+const WINDOWS_RESERVED_CHARS: &[char] = &['<', '>', ':', '"', '|', '?', '*'];
+const WINDOWS_RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Наибольшая вложенность скрипта в поддиректории (`a/b/script.py` — 2)
+pub const MAX_SCRIPT_DEPTH: usize = 5;
+
+// Предел длины имени файла в большинстве файловых систем (байты)
+const MAX_SEGMENT_LEN: usize = 255;
+
+/// Проверяет имя скрипта — путь относительно директории скриптов через `/`
+/// (`etl/extract.py`). Пути с `..`, абсолютные, скрытые файлы и директории
+/// отклоняются. Поддерживаемость расширения проверяется через
+/// `AppState::interpreter_for`.
+pub fn validate_script_name(name: &str) -> Result<(), AppError> {
+    if name.is_empty() || name.contains('\\') || name.contains('\0') {
+        return Err(AppError::InvalidScriptName(
+            "Name must be a relative path to a file".to_string(),
+        ));
+    }
+    // `\r` остаётся в конце имени, прочитанного из файла с переводами строк Windows
+    if name.contains(char::is_control) {
+        return Err(AppError::InvalidScriptName(
+            "Name must not contain control characters such as '\\r' or '\\n'".to_string(),
+        ));
+    }
+    if name.split('/').any(|segment| segment.len() > MAX_SEGMENT_LEN) {
+        return Err(AppError::InvalidScriptName(format!(
+            "File and directory names must not exceed {} bytes",
+            MAX_SEGMENT_LEN
+        )));
+    }
+    // Полноширинные `．` и `／` и подобные символы совпадают с обычными после
+    // нормализации; такие имена принимаются только уже нормализованными
+    if !is_nfkc(name) {
+        return Err(AppError::InvalidScriptName(
+            "Name must be in Unicode normalization form NFKC".to_string(),
+        ));
+    }
+    // `CON.py` нельзя создать на Windows, поэтому имена устройств запрещены везде:
+    // скрипты переносятся между серверами архивом
+    if name.split('/').any(is_device_name) {
+        return Err(AppError::InvalidScriptName(
+            "Name must not be a Windows device name such as CON or NUL".to_string(),
+        ));
+    }
+    if cfg!(windows) && !name.split('/').all(is_windows_file_name) {
+        return Err(AppError::InvalidScriptName(
+            "Name contains characters not allowed on Windows".to_string(),
+        ));
+    }
+    let (dirs, file): (Vec<&str>, &str) = match name.rsplit_once('/') {
+        Some((dirs, file)) => (dirs.split('/').collect(), file),
+        None => (Vec::new(), name),
+    };
+    if dirs.len() > MAX_SCRIPT_DEPTH {
+        return Err(AppError::InvalidScriptName(format!(
+            "Scripts can be nested at most {} directories deep",
+            MAX_SCRIPT_DEPTH
+        )));
+    }
+    if let Some(dir) = dirs.iter().find(|dir| !is_valid_dir_name(dir)) {
+        return Err(AppError::InvalidScriptName(format!(
+            "Invalid directory name '{}': only letters, digits, '-' and '_' are allowed",
+            dir
+        )));
+    }
+    if file.is_empty() || file.starts_with('.') {
+        return Err(AppError::InvalidScriptName(
+            "File name must not be empty or start with '.'".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Путь внутри `base` по имени через `/`. Сегменты добавляются по одному,
+/// поэтому на Windows в пути получаются родные разделители `\`.
+pub fn join_name(base: &Path, name: &str) -> PathBuf {
+    let mut path = base.to_path_buf();
+    path.extend(name.split('/'));
+    path
+}
+
+/// Путь `path` относительно `base` через `/`; `None`, если `path` вне `base`
+/// или в пути есть `..` либо сегменты не в UTF-8
+pub fn relative_name(base: &Path, path: &Path) -> Option<String> {
+    let segments = path
+        .strip_prefix(base)
+        .ok()?
+        .components()
+        .map(|c| match c {
+            Component::Normal(segment) => segment.to_str(),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(segments.join("/"))
+}
+
+/// Сегмент пути, который Windows сохранит под тем же именем: без `<>:"|?*`,
+/// имён устройств (`CON`, `NUL.txt`) и точки или пробела в конце
+pub fn is_windows_file_name(segment: &str) -> bool {
+    !segment.contains(WINDOWS_RESERVED_CHARS) && !segment.ends_with(['.', ' ']) && !is_device_name(segment)
+}
+
+// Имя устройства с любым расширением: `NUL`, `con.py`, `Lpt1.txt`
+fn is_device_name(segment: &str) -> bool {
+    let stem = segment.split('.').next().unwrap_or(segment).trim_end();
+    WINDOWS_RESERVED_NAMES.iter().any(|reserved| stem.eq_ignore_ascii_case(reserved))
+}
+
+/// Проверяет, что `path` после разрешения символических ссылок остаётся
+/// внутри `base`. Для ещё не созданного файла проверяется ближайший
+/// существующий предок; висящая ссылка на месте файла отклоняется.
+pub async fn ensure_inside(base: &Path, path: &Path) -> Result<(), AppError> {
+    let base = fs::canonicalize(base).await?;
+    let mut existing = path;
+    let resolved = loop {
+        match fs::canonicalize(existing).await {
+            Ok(resolved) => break resolved,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                if fs::symlink_metadata(existing).await.is_ok() {
+                    return Err(outside_error());
+                }
+                existing = existing.parent().ok_or_else(outside_error)?;
+            }
+            Err(e) => return Err(AppError::Io(e)),
+        }
+    };
+    if resolved.starts_with(&base) {
+        Ok(())
+    } else {
+        Err(outside_error())
+    }
+}
+
+fn outside_error() -> AppError {
+    AppError::InvalidScriptName("Path resolves outside the scripts directory".to_string())
+}
+
+/// Допустимое имя поддиректории скриптов. Точки запрещены: по первому
+/// сегменту с точкой определяется конец имени скрипта в URL.
+pub fn is_valid_dir_name(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+/// Проверяет переменные окружения запуска: имя должно быть корректным
+/// и не входить в список запрещённых к переопределению.
+pub fn validate_env(env: &BTreeMap<String, String>) -> Result<(), AppError> {
+    for (key, value) in env {
+        if key.is_empty() || key.contains('=') || key.contains('\0') || value.contains('\0') {
+            return Err(AppError::BadRequest(format!(
+                "Invalid environment variable '{}'",
+                key
+            )));
+        }
+        if BLOCKED_ENV_VARS.iter().any(|blocked| key.eq_ignore_ascii_case(blocked)) {
+            return Err(AppError::BadRequest(format!(
+                "Environment variable '{}' cannot be overridden",
+                key
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Текст вывода без потерь: UTF-8 как есть, иначе base64.
+pub fn encode_output(bytes: &[u8]) -> (String, OutputEncoding) {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => (text.to_string(), OutputEncoding::Utf8),
+        Err(_) => (STANDARD.encode(bytes), OutputEncoding::Base64),
+    }
+}
+
+/// Исходные байты вывода, закодированного `encode_output`
+pub fn decode_output(text: &str, encoding: OutputEncoding) -> Vec<u8> {
+    match encoding {
+        OutputEncoding::Utf8 => text.as_bytes().to_vec(),
+        OutputEncoding::Base64 => STANDARD.decode(text).unwrap_or_default(),
+    }
+}
+
+/// Описание скрипта из начала файла: первая строка docstring (`"""`, `'''`)
+/// или комментария (`#`, `//`, `/*`). Shebang и строка кодировки пропускаются.
+pub fn extract_description(code: &str) -> Option<String> {
+    let mut lines = code.lines().map(str::trim).filter(|l| !l.is_empty());
+    while let Some(line) = lines.next() {
+        let is_coding = line.starts_with('#') && (line.contains("coding:") || line.contains("coding="));
+        if line.starts_with("#!") || is_coding {
+            continue;
+        }
+
+        if let Some(quote) = ["\"\"\"", "'''"].into_iter().find(|q| line.starts_with(q)) {
+            let rest = line[quote.len()..].trim_end_matches(quote).trim();
+            let text = if rest.is_empty() {
+                lines.next()?.trim_end_matches(quote).trim()
+            } else {
+                rest
+            };
+            return non_empty(text);
+        }
+
+        let comment = ["//", "#", "/**", "/*"]
+            .into_iter()
+            .filter(|prefix| line.starts_with(prefix))
+            .max_by_key(|prefix| prefix.len())?;
+        let text = line[comment.len()..].trim_end_matches("*/").trim();
+        if let Some(text) = non_empty(text) {
+            return Some(text);
+        }
+    }
+    None
+}
+
+fn non_empty(text: &str) -> Option<String> {
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+/// SHA-256 в шестнадцатеричной записи
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Атомарно записывает файл: данные пишутся во временный файл в той же
+/// директории и затем подменяют целевой. При `overwrite == false`
+/// существующий файл не трогается и возвращается `AlreadyExists`.
+pub async fn write_atomic(path: &Path, contents: &[u8], overwrite: bool) -> std::io::Result<()> {
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("script");
+    let tmp_path = dir.join(format!(".{}.{}.tmp", file_name, Uuid::new_v4()));
+
+    let result = async {
+        let mut file = fs::File::create(&tmp_path).await?;
+        file.write_all(contents).await?;
+        file.sync_all().await?;
+        drop(file);
+        if overwrite {
+            fs::rename(&tmp_path, path).await
+        } else {
+            // hard_link не заменяет существующий файл — проверка и запись атомарны
+            fs::hard_link(&tmp_path, path).await
+        }
+    }
+    .await;
+
+    if result.is_err() || !overwrite {
+        let _ = fs::remove_file(&tmp_path).await;
+    }
+    result
+}
+
+/// Все файлы под директорией, включая вложенные; символические ссылки пропускаются
+pub async fn files_under(root: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(mut entries) = fs::read_dir(&dir).await else {
+            continue;
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            match entry.file_type().await {
+                Ok(t) if t.is_dir() => pending.push(entry.path()),
+                Ok(t) if t.is_file() => files.push(entry.path()),
+                _ => {}
+            }
+        }
+    }
+    files
+}
+
+/// Суммарный размер файлов под директорией
+pub async fn dir_size(root: &Path) -> u64 {
+    let mut size = 0;
+    for path in files_under(root).await {
+        if let Ok(meta) = fs::metadata(&path).await {
+            size += meta.len();
+        }
+    }
+    size
+}
+
+/// Удаляет файл и оставшиеся пустыми директории над ним вплоть до `root`
+pub async fn remove_file_pruning_dirs(root: &Path, path: &Path) -> std::io::Result<()> {
+    fs::remove_file(path).await?;
+    let mut dir = path.parent();
+    while let Some(current) = dir {
+        if current == root || fs::remove_dir(current).await.is_err() {
+            break;
+        }
+        dir = current.parent();
+    }
+    Ok(())
+}
+
+/// Данные для stdin из поля `data`. Без него (и при `"data": null`) в stdin
+/// ничего не пишется, и он сразу закрывается.
+pub fn stdin_bytes(data: Option<&serde_json::Value>) -> Result<Bytes, AppError> {
+    match data {
+        Some(data) => Ok(Bytes::from(serde_json::to_vec(data)?)),
+        None => Ok(Bytes::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    fn is_rejected(name: &str) -> bool {
+        matches!(validate_script_name(name), Err(AppError::InvalidScriptName(_)))
+    }
+
+    #[test]
+    fn accepts_plain_and_nested_names() {
+        let longest = format!("{}.py", "a".repeat(MAX_SEGMENT_LEN - 3));
+        for name in ["hello.py", "etl/extract.py", "a-b/c_d/run.sh", "console.py", longest.as_str()] {
+            assert!(validate_script_name(name).is_ok(), "{:?} should be accepted", name);
+        }
+    }
+
+    #[test]
+    fn rejects_dot_segments() {
+        for name in ["../secret.py", "etl/../secret.py", "./hello.py", "etl/./hello.py", "etl/..", ".."] {
+            assert!(is_rejected(name), "{:?} should be rejected", name);
+        }
+    }
+
+    #[test]
+    fn rejects_hidden_files_and_dirs() {
+        for name in [".hello.py", "etl/.hello.py", ".versions/hello.py", ".secrets.json"] {
+            assert!(is_rejected(name), "{:?} should be rejected", name);
+        }
+    }
+
+    #[test]
+    fn rejects_nul_and_control_characters() {
+        for name in ["hello\0.py", "hello.py\0", "hello.py\r", "etl\n/hello.py"] {
+            assert!(is_rejected(name), "{:?} should be rejected", name);
+        }
+    }
+
+    #[test]
+    fn rejects_overlong_segments() {
+        let file = format!("{}.py", "a".repeat(MAX_SEGMENT_LEN));
+        let dir = format!("{}/hello.py", "a".repeat(MAX_SEGMENT_LEN + 1));
+        assert!(is_rejected(&file));
+        assert!(is_rejected(&dir));
+        let deep = format!("{}hello.py", "d/".repeat(MAX_SCRIPT_DEPTH + 1));
+        assert!(is_rejected(&deep));
+    }
+
+    #[test]
+    fn rejects_windows_device_names() {
+        for name in ["CON.py", "NUL.py", "con.py", "Lpt1.txt", "etl/aux.py", "nul/hello.py"] {
+            assert!(is_rejected(name), "{:?} should be rejected", name);
+        }
+    }
+
+    #[test]
+    fn rejects_names_that_change_under_nfkc() {
+        // Полноширинные точка и косая черта, лигатура «fi»
+        for name in ["hello\u{FF0E}py", "etl\u{FF0F}hello.py", "\u{FB01}le.py"] {
+            assert!(is_rejected(name), "{:?} should be rejected", name);
+        }
+    }
+
+    #[test]
+    fn rejects_absolute_and_backslash_paths() {
+        for name in ["", "/hello.py", "etl\\hello.py", "etl//hello.py"] {
+            assert!(is_rejected(name), "{:?} should be rejected", name);
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn ensure_inside_rejects_escape_through_symlink() {
+        let dir = TempDir::new();
+        let (base, outside) = (dir.path().join("scripts"), dir.path().join("outside"));
+        std::fs::create_dir_all(&base).expect("create base");
+        std::fs::create_dir_all(&outside).expect("create outside");
+        std::os::unix::fs::symlink(&outside, base.join("linked")).expect("symlink dir");
+        std::os::unix::fs::symlink(outside.join("missing.py"), base.join("dangling.py")).expect("symlink file");
+
+        assert!(ensure_inside(&base, &base.join("hello.py")).await.is_ok());
+        assert!(ensure_inside(&base, &base.join("etl/new.py")).await.is_ok());
+        for escape in ["linked/hello.py", "linked", "dangling.py"] {
+            let result = ensure_inside(&base, &base.join(escape)).await;
+            assert!(matches!(result, Err(AppError::InvalidScriptName(_))), "{:?} should be rejected", escape);
+        }
+    }
+}
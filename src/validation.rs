@@ -0,0 +1,65 @@
+use crate::{app_state::AppState, error::AppError, script_runner};
+use std::{path::Path, process::Stdio, time::Duration};
+use tokio::{fs, process::Command, time::timeout};
+use uuid::Uuid;
+
+// Проверка синтаксиса не должна задерживать обработчик надолго
+const SYNTAX_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Расширение -> аргументы интерпретатора для проверки без выполнения
+const SYNTAX_CHECKS: &[(&str, &[&str])] = &[
+    ("py", &["-m", "py_compile"]),
+    ("sh", &["-n"]),
+    ("js", &["--check"]),
+    ("rb", &["-c"]),
+];
+
+/// Проверяет синтаксис кода скрипта интерпретатором его типа.
+/// Код пишется во временный файл вне директории скриптов; для расширений
+/// без известной проверки ничего не делает.
+pub async fn check_syntax(state: &AppState, name: &str, code: &[u8]) -> Result<(), AppError> {
+    let Some(ext) = Path::new(name).extension().and_then(|ext| ext.to_str()) else {
+        return Ok(());
+    };
+    let Some((_, check_args)) = SYNTAX_CHECKS.iter().find(|(e, _)| *e == ext) else {
+        return Ok(());
+    };
+    let interpreter = state.interpreter_for(name)?;
+    let program = interpreter.split_whitespace().next().unwrap_or(interpreter);
+
+    let tmp_path = std::env::temp_dir().join(format!("runner-check-{}.{}", Uuid::new_v4(), ext));
+    fs::write(&tmp_path, code).await?;
+
+    let output = Command::new(program)
+        .args(*check_args)
+        .arg(&tmp_path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .output();
+    let result = timeout(SYNTAX_CHECK_TIMEOUT, output).await;
+    let _ = fs::remove_file(&tmp_path).await;
+
+    let output = match result {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => return Err(script_runner::spawn_error(interpreter, e)),
+        Err(_) => {
+            return Err(AppError::SyntaxError(format!(
+                "Syntax check timed out after {:?}",
+                SYNTAX_CHECK_TIMEOUT
+            )))
+        }
+    };
+    if output.status.success() {
+        return Ok(());
+    }
+
+    // В сообщении компилятора временный путь заменяется именем скрипта
+    let mut message = String::from_utf8_lossy(&output.stderr).into_owned();
+    if message.trim().is_empty() {
+        message = String::from_utf8_lossy(&output.stdout).into_owned();
+    }
+    let message = message.replace(&*tmp_path.to_string_lossy(), name);
+    Err(AppError::SyntaxError(message.trim().to_string()))
+}
@@ -0,0 +1,163 @@
+use crate::{
+    app_state::AppState,
+    error::AppError,
+    gc::Swept,
+    models::ScriptVersion,
+    utils::{files_under, join_name, remove_file_pruning_dirs},
+};
+use chrono::{DateTime, Utc};
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use tokio::fs;
+use tracing::{info, warn};
+
+/// Директория предыдущих версий внутри директории скриптов; сканер её пропускает
+pub const VERSIONS_DIR: &str = ".versions";
+
+fn versions_dir(state: &AppState, name: &str) -> PathBuf {
+    join_name(&state.scripts_dir.join(VERSIONS_DIR), name)
+}
+
+// Версия хранится как `<номер>.<расширение скрипта>`
+fn version_path(state: &AppState, name: &str, id: u64) -> PathBuf {
+    let ext = Path::new(name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default();
+    versions_dir(state, name).join(format!("{}.{}", id, ext))
+}
+
+// Номера сохранённых версий по возрастанию
+async fn version_ids(dir: &Path) -> Result<Vec<u64>, AppError> {
+    let mut entries = match fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut ids = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let id = entry
+            .path()
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.parse().ok());
+        if let Some(id) = id {
+            ids.push(id);
+        }
+    }
+    ids.sort_unstable();
+    Ok(ids)
+}
+
+/// Сохраняет текущее содержимое скрипта как новую версию и удаляет самые
+/// старые сверх `RUNNER_MAX_SCRIPT_VERSIONS`. Возвращает номер версии;
+/// `None`, если версии отключены или файла скрипта нет.
+pub async fn save_version(state: &AppState, name: &str) -> Result<Option<u64>, AppError> {
+    if state.max_script_versions == 0 {
+        return Ok(None);
+    }
+    let path = state.script_path(name);
+    if !fs::try_exists(&path).await? {
+        return Ok(None);
+    }
+
+    let dir = versions_dir(state, name);
+    fs::create_dir_all(&dir).await?;
+    let mut ids = version_ids(&dir).await?;
+    let id = ids.last().map_or(1, |last| last + 1);
+
+    // Жёсткая ссылка сохраняет содержимое и время изменения без копирования:
+    // скрипт затем заменяется новым файлом через rename
+    let target = version_path(state, name, id);
+    if fs::hard_link(&path, &target).await.is_err() {
+        fs::copy(&path, &target).await?;
+    }
+    ids.push(id);
+
+    let excess = ids.len().saturating_sub(state.max_script_versions);
+    for old in &ids[..excess] {
+        if let Err(e) = fs::remove_file(version_path(state, name, *old)).await {
+            warn!("Failed to remove old version {} of {}: {}", old, name, e);
+        }
+    }
+
+    info!("Saved version {} of {}", id, name);
+    Ok(Some(id))
+}
+
+/// Переносит версии переименованного скрипта. Версии хранятся с расширением
+/// скрипта, поэтому при смене расширения они остаются под старым именем.
+pub async fn rename_versions(state: &AppState, old_name: &str, new_name: &str) {
+    if Path::new(old_name).extension() != Path::new(new_name).extension() {
+        return;
+    }
+    let (from, to) = (versions_dir(state, old_name), versions_dir(state, new_name));
+    if !fs::try_exists(&from).await.unwrap_or(false) || fs::try_exists(&to).await.unwrap_or(true) {
+        return;
+    }
+    let moved = match to.parent() {
+        Some(parent) => fs::create_dir_all(parent).await.and(fs::rename(&from, &to).await),
+        None => fs::rename(&from, &to).await,
+    };
+    if let Err(e) = moved {
+        warn!("Failed to move versions of {} to {}: {}", old_name, new_name, e);
+    }
+}
+
+/// Сохранённые версии скрипта, от новых к старым
+pub async fn list_versions(state: &AppState, name: &str) -> Result<Vec<ScriptVersion>, AppError> {
+    let ids = version_ids(&versions_dir(state, name)).await?;
+    let mut versions = Vec::with_capacity(ids.len());
+    for id in ids.into_iter().rev() {
+        // Версия могла быть удалена при ротации между чтением списка и метаданных
+        let Ok(meta) = fs::metadata(version_path(state, name, id)).await else {
+            continue;
+        };
+        versions.push(ScriptVersion {
+            id,
+            size_bytes: meta.len(),
+            modified: meta.modified().map(DateTime::<Utc>::from)?,
+        });
+    }
+    Ok(versions)
+}
+
+/// Код сохранённой версии скрипта
+pub async fn read_version(state: &AppState, name: &str, id: u64) -> Result<String, AppError> {
+    fs::read_to_string(version_path(state, name, id))
+        .await
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => AppError::VersionNotFound(name.to_string(), id),
+            _ => AppError::Io(e),
+        })
+}
+
+/// Удаляет версии всех скриптов, время изменения которых старше
+/// `RUNNER_VERSION_RETENTION_DAYS`
+pub async fn prune(state: &AppState, retention: Duration) -> Swept {
+    let root = state.scripts_dir.join(VERSIONS_DIR);
+    let mut swept = Swept::default();
+    for path in files_under(&root).await {
+        let Ok(meta) = fs::metadata(&path).await else {
+            continue;
+        };
+        let expired = meta
+            .modified()
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .is_some_and(|age| age > retention);
+        if !expired {
+            continue;
+        }
+        match remove_file_pruning_dirs(&root, &path).await {
+            Ok(()) => swept.add(meta.len()),
+            Err(e) => warn!("Failed to remove version {}: {}", path.display(), e),
+        }
+    }
+    if swept.items > 0 {
+        info!("Removed {} script versions past retention", swept.items);
+    }
+    swept
+}
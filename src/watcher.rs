@@ -0,0 +1,190 @@
+use crate::{app_state::AppState, models::ScriptKind, package, script_config, script_runner, utils::is_valid_dir_name};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{fs, sync::{mpsc, Notify}};
+use tracing::{debug, info, warn};
+
+// Пауза перед синхронизацией с БД, чтобы объединить серию событий
+const SYNC_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Следит за директорией скриптов и сразу обновляет список скриптов в памяти.
+/// Наблюдатель нужно держать живым, пока требуется отслеживание.
+pub fn spawn_watcher(state: Arc<AppState>) -> notify::Result<RecommendedWatcher> {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+        let _ = tx.send(res);
+    })?;
+    watcher.watch(&state.scripts_dir, RecursiveMode::Recursive)?;
+
+    // Синхронизация метаданных в БД выполняется полным сканированием после серии событий
+    let sync = Arc::new(Notify::new());
+    let sync_state = Arc::clone(&state);
+    let sync_signal = Arc::clone(&sync);
+    tokio::spawn(async move {
+        loop {
+            sync_signal.notified().await;
+            tokio::time::sleep(SYNC_DEBOUNCE).await;
+            script_runner::scan_scripts(Arc::clone(&sync_state)).await;
+        }
+    });
+
+    let events_state = Arc::clone(&state);
+    tokio::spawn(async move {
+        while let Some(res) = rx.recv().await {
+            match res {
+                Ok(event) => {
+                    if handle_event(&events_state, event).await {
+                        sync.notify_one();
+                    }
+                }
+                Err(e) => warn!("Scripts directory watcher error: {}", e),
+            }
+        }
+    });
+
+    info!("Watching {} for changes", state.scripts_dir.display());
+    Ok(watcher)
+}
+
+// Возвращает true, если событие затронуло хотя бы один скрипт
+async fn handle_event(state: &AppState, event: Event) -> bool {
+    if matches!(event.kind, EventKind::Access(_)) {
+        return false;
+    }
+
+    let mut changed = false;
+    for path in &event.paths {
+        // Служебные скрытые директории (`.versions`, `.venvs`) скриптов не содержат;
+        // установка пакетов в окружение не должна вызывать сканирование
+        if in_hidden_dir(state, path) || package::is_bytecode(path) {
+            continue;
+        }
+        // Изменение файла настроек перечитывает настройки его скрипта
+        if let Some(script) = script_config::script_for_sidecar(path) {
+            if state.scripts.lock().await.contains_key(&script) {
+                debug!("Reloading config of {}", script.display());
+                script_runner::register_script(state, script).await;
+            } else if fs::metadata(&script).await.is_ok_and(|m| m.is_dir()) {
+                // `entrypoint` мог сделать директорию пакетом
+                changed = true;
+            }
+            continue;
+        }
+        // Любой файл пакета меняет пакет целиком
+        if let Some((package_path, name)) = containing_package(state, path).await {
+            changed = true;
+            script_runner::register_script(state, package_path).await;
+            let evicted = script_runner::invalidate_script_cache(state, &name).await;
+            debug!("Package {} changed on disk, evicted {} cache entries", name, evicted);
+            continue;
+        }
+        // Новый `__main__.py` делает директорию пакетом: его найдёт полное сканирование
+        if path.file_name().is_some_and(|n| n == package::MAIN_FILE) {
+            changed = true;
+            continue;
+        }
+        // Поддиректорию создали, переименовали или удалили целиком:
+        // её скрипты найдёт полное сканирование
+        if path.file_name().and_then(|n| n.to_str()).is_some_and(is_valid_dir_name) {
+            changed = true;
+            continue;
+        }
+        if !state.is_supported_script(path) {
+            continue;
+        }
+        // Файлы в скрытых директориях (например, `.versions`) не являются скриптами
+        let Some(name) = state.script_name(path) else {
+            continue;
+        };
+        changed = true;
+
+        let exists = fs::try_exists(path).await.unwrap_or(false);
+        update_script_list(state, path, exists).await;
+
+        let evicted = script_runner::invalidate_script_cache(state, &name).await;
+        debug!(
+            "Script {} changed on disk (exists: {}), evicted {} cache entries",
+            name, exists, evicted
+        );
+    }
+    changed
+}
+
+fn in_hidden_dir(state: &AppState, path: &Path) -> bool {
+    let Ok(relative) = path.strip_prefix(&state.scripts_dir) else {
+        return false;
+    };
+    let mut dirs = relative.components().rev().skip(1);
+    dirs.any(|dir| dir.as_os_str().to_str().is_some_and(|dir| dir.starts_with('.')))
+}
+
+// Известный сканеру пакет, в котором лежит `path`, и его имя
+async fn containing_package(state: &AppState, path: &Path) -> Option<(PathBuf, String)> {
+    let scripts = state.scripts.lock().await;
+    let package = path
+        .ancestors()
+        .skip(1)
+        .take_while(|dir| *dir != state.scripts_dir)
+        .find(|dir| scripts.get(*dir).is_some_and(|entry| entry.kind == ScriptKind::Package))?;
+    Some((package.to_path_buf(), state.script_name(package)?))
+}
+
+async fn update_script_list(state: &AppState, path: &Path, exists: bool) {
+    if exists {
+        script_runner::register_script(state, path.to_path_buf()).await;
+    } else {
+        state.scripts.lock().await.remove(path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::ScriptResult,
+        script_runner::{run_script, RunOptions},
+        test_support::TestServer,
+    };
+    use bytes::Bytes;
+    use notify::event::{CreateKind, RemoveKind};
+
+    async fn run(server: &TestServer) -> ScriptResult {
+        run_script(Arc::clone(&server.state), "greet.py", Bytes::new(), RunOptions::default())
+            .await
+            .expect("result")
+    }
+
+    #[tokio::test]
+    async fn recreated_script_with_same_mtime_is_not_served_from_cache() {
+        let server = TestServer::new(|_| {}).await;
+        let path = server.add_script("greet.py", "print('old')\n").await;
+        let mtime = std::fs::metadata(&path).and_then(|m| m.modified()).expect("mtime");
+        assert!(!run(&server).await.cached);
+        let hit = run(&server).await;
+        assert!(hit.cached);
+        assert_eq!(hit.stdout, "old\n");
+
+        std::fs::remove_file(&path).expect("delete script");
+        let removed = Event::new(EventKind::Remove(RemoveKind::File)).add_path(path.clone());
+        assert!(handle_event(&server.state, removed).await);
+        assert_eq!(script_runner::invalidate_script_cache(&server.state, "greet.py").await, 0);
+
+        // Тот же размер и тот же mtime: версии отличаются только содержимым
+        std::fs::write(&path, "print('new')\n").expect("recreate script");
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(mtime))
+            .expect("restore mtime");
+        let created = Event::new(EventKind::Create(CreateKind::File)).add_path(path.clone());
+        assert!(handle_event(&server.state, created).await);
+
+        let result = run(&server).await;
+        assert!(!result.cached);
+        assert_eq!(result.stdout, "new\n");
+    }
+}
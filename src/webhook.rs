@@ -0,0 +1,114 @@
+use crate::{
+    error::AppError,
+    models::{WebhookPayload, WebhookStats},
+};
+use reqwest::{redirect, Client, Url};
+use std::{
+    collections::HashSet,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+use tracing::{info, warn};
+
+// Попытки доставки одного уведомления, включая первую
+const MAX_ATTEMPTS: u32 = 3;
+
+// Пауза перед первым повтором; удваивается с каждой попыткой
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Отправка уведомлений о завершении запусков на адреса из разрешённого
+/// списка хостов (`RUNNER_CALLBACK_HOSTS`)
+pub struct Webhooks {
+    client: Client,
+    allowed_hosts: HashSet<String>,
+    attempts: AtomicU64,
+    delivered: AtomicU64,
+    failed: AtomicU64,
+}
+
+impl Webhooks {
+    pub fn new(allowed_hosts: HashSet<String>) -> Self {
+        // Перенаправления не выполняются: иначе разрешённый хост мог бы
+        // увести запрос на внутренний адрес
+        let client = Client::builder()
+            .redirect(redirect::Policy::none())
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .expect("Failed to build HTTP client");
+        Self {
+            client,
+            allowed_hosts,
+            attempts: AtomicU64::new(0),
+            delivered: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+        }
+    }
+
+    /// Проверяет `callback_url`: http(s) и хост из разрешённого списка
+    pub fn validate(&self, callback_url: &str) -> Result<Url, AppError> {
+        let url = Url::parse(callback_url)
+            .map_err(|e| AppError::BadRequest(format!("Invalid callback_url: {}", e)))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(AppError::BadRequest(
+                "callback_url must use http or https".to_string(),
+            ));
+        }
+        let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+        if !self.allowed_hosts.contains(&host) {
+            return Err(AppError::BadRequest(format!(
+                "Callback host '{}' is not allowed (RUNNER_CALLBACK_HOSTS)",
+                host
+            )));
+        }
+        Ok(url)
+    }
+
+    /// Отправляет уведомление, повторяя попытку при ошибке соединения или
+    /// ответе 5xx. Ответы 4xx не повторяются.
+    pub async fn deliver(&self, url: Url, payload: &WebhookPayload) {
+        // В лог попадает только хост: путь может содержать токен (Slack)
+        let host = url.host_str().unwrap_or_default().to_string();
+        let mut backoff = INITIAL_BACKOFF;
+        for attempt in 1..=MAX_ATTEMPTS {
+            self.attempts.fetch_add(1, Ordering::Relaxed);
+            let retry = match self.client.post(url.clone()).json(payload).send().await {
+                Ok(response) if response.status().is_success() => {
+                    self.delivered.fetch_add(1, Ordering::Relaxed);
+                    info!("Delivered callback for run {} to {}", payload.run_id, host);
+                    return;
+                }
+                Ok(response) => {
+                    warn!(
+                        "Callback for run {} to {} failed (attempt {}): HTTP {}",
+                        payload.run_id, host, attempt, response.status()
+                    );
+                    response.status().is_server_error()
+                }
+                Err(e) => {
+                    warn!(
+                        "Callback for run {} to {} failed (attempt {}): {}",
+                        payload.run_id, host, attempt, e.without_url()
+                    );
+                    true
+                }
+            };
+            if !retry || attempt == MAX_ATTEMPTS {
+                break;
+            }
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+        self.failed.fetch_add(1, Ordering::Relaxed);
+        warn!("Giving up on callback for run {} to {}", payload.run_id, host);
+    }
+
+    pub fn stats(&self) -> WebhookStats {
+        WebhookStats {
+            attempts: self.attempts.load(Ordering::Relaxed),
+            delivered: self.delivered.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tracing::warn;
+use uuid::Uuid;
+
+// Подпапка для файлов-результатов, которые скрипт хочет отдать клиенту;
+// из неё собираются артефакты (`artifacts::store`)
+const ARTIFACTS_DIR: &str = "artifacts";
+// Прежнее имя подпапки артефактов: файлы из неё тоже собираются, чтобы
+// скрипты, уже пишущие в `output`, не менялись
+const LEGACY_OUTPUT_DIR: &str = "output";
+
+/// Временная рабочая директория одного запуска. Удаляется при drop,
+/// поэтому очистка происходит и при таймауте, и при ошибке.
+pub struct RunDir {
+    path: PathBuf,
+    keep: bool,
+}
+
+impl RunDir {
+    /// Создаёт уникальную директорию с подпапками `artifacts` и `output`
+    pub async fn create(keep: bool) -> std::io::Result<Self> {
+        let path = std::env::temp_dir()
+            .join("script-runner")
+            .join(Uuid::new_v4().to_string());
+        fs::create_dir_all(path.join(ARTIFACTS_DIR)).await?;
+        fs::create_dir_all(path.join(LEGACY_OUTPUT_DIR)).await?;
+        Ok(Self { path, keep })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Путь директории, если её просили сохранить после запуска
+    pub fn kept_path(&self) -> Option<String> {
+        self.keep.then(|| self.path.display().to_string())
+    }
+
+    /// Подпапки для файлов-результатов (артефактов): `artifacts` и прежняя `output`
+    pub fn artifact_dirs(&self) -> [PathBuf; 2] {
+        [self.path.join(ARTIFACTS_DIR), self.path.join(LEGACY_OUTPUT_DIR)]
+    }
+}
+
+impl Drop for RunDir {
+    fn drop(&mut self) {
+        if self.keep {
+            return;
+        }
+        let path = std::mem::take(&mut self.path);
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = std::fs::remove_dir_all(&path) {
+                warn!("Failed to remove run directory {}: {}", path.display(), e);
+            }
+        });
+    }
+}
@@ -0,0 +1,334 @@
+use crate::{error::AppError, models::PoolStats, script_runner::PipeOutput};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    io,
+    path::Path,
+    process::Stdio,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+use tokio::{
+    fs,
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    process::{Child, ChildStdin, ChildStdout, Command},
+    time::timeout,
+};
+use tracing::{info, warn};
+
+/// Расширение скриптов, которые выполняются пулом
+pub const POOL_EXTENSION: &str = "py";
+
+// Цикл воркера: принимает задачи и выполняет их в дочерних процессах
+const BOOTSTRAP: &str = include_str!("pool_worker.py");
+
+// Сколько ждать ответа воркера сверх таймаута задачи, прежде чем считать его зависшим
+const REPLY_GRACE: Duration = Duration::from_secs(5);
+
+// Долгоживущий процесс интерпретатора с открытыми каналами протокола
+struct Worker {
+    _child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+fn spawn_worker(interpreter: &str) -> io::Result<Worker> {
+    let mut parts = interpreter.split_whitespace();
+    let program = parts.next().unwrap_or(interpreter);
+    let mut child = Command::new(program)
+        .args(parts)
+        .arg("-c")
+        .arg(BOOTSTRAP)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .kill_on_drop(true)
+        .spawn()?;
+    let stdin = child.stdin.take().ok_or_else(|| io::Error::other("worker stdin is closed"))?;
+    let stdout = child.stdout.take().ok_or_else(|| io::Error::other("worker stdout is closed"))?;
+    Ok(Worker {
+        _child: child,
+        stdin,
+        stdout: BufReader::new(stdout),
+    })
+}
+
+// Команда воркеру; stdin/stdout/stderr задачи передаются через файлы
+#[derive(Serialize)]
+struct TaskMessage<'a> {
+    script: &'a Path,
+    args: &'a [String],
+    cwd: &'a Path,
+    env: &'a BTreeMap<String, String>,
+    stdin: &'a Path,
+    stdout: &'a Path,
+    stderr: &'a Path,
+    max_output_bytes: usize,
+    timeout_secs: f64,
+}
+
+#[derive(Deserialize)]
+struct Started {
+    pid: i32,
+}
+
+#[derive(Deserialize)]
+struct Finished {
+    exit_code: i32,
+    #[serde(default)]
+    signal: Option<i32>,
+    timed_out: bool,
+    task_rss_kb: u64,
+    worker_rss_kb: u64,
+}
+
+async fn read_message<M: DeserializeOwned>(stdout: &mut BufReader<ChildStdout>) -> io::Result<M> {
+    let mut line = String::new();
+    if stdout.read_line(&mut line).await? == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "pool worker exited"));
+    }
+    Ok(serde_json::from_str(&line)?)
+}
+
+#[cfg(unix)]
+fn kill_task(pid: i32) {
+    // SAFETY: задача запущена воркером в собственной группе процессов (setsid)
+    unsafe {
+        libc::kill(-pid, libc::SIGKILL);
+    }
+}
+
+#[cfg(not(unix))]
+fn kill_task(_pid: i32) {}
+
+// Вывод задачи из файла, не больше `limit` байт
+async fn read_output(path: &Path, limit: usize) -> io::Result<PipeOutput> {
+    let file = match fs::File::open(path).await {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(PipeOutput::default()),
+        Err(e) => return Err(e),
+    };
+    let mut bytes = Vec::new();
+    file.take(limit as u64 + 1).read_to_end(&mut bytes).await?;
+    let truncated = bytes.len() > limit;
+    bytes.truncate(limit);
+    Ok(PipeOutput { bytes, truncated })
+}
+
+/// Параметры задачи для пула
+pub struct PoolTask<'a> {
+    pub script_path: &'a Path,
+    pub args: &'a [String],
+    pub env: &'a BTreeMap<String, String>,
+    pub workdir: &'a Path,
+    pub input: &'a [u8],
+    pub timeout: Duration,
+    pub max_output_bytes: usize,
+}
+
+/// Итог задачи; `interrupted` — значение прервавшего её future
+pub struct PoolRun<T> {
+    pub stdout: PipeOutput,
+    pub stderr: PipeOutput,
+    pub exit_code: i32,
+    /// Сигнал, завершивший задачу
+    pub signal: Option<i32>,
+    pub timed_out: bool,
+    pub interrupted: Option<T>,
+}
+
+/// Пул тёплых процессов Python: интерпретатор запускается один раз,
+/// а каждый скрипт выполняется в ответвлённом от воркера процессе.
+/// Упавший воркер и воркер, задача которого превысила порог памяти,
+/// заменяются новым.
+pub struct WorkerPool {
+    interpreter: String,
+    size: usize,
+    max_task_memory_kb: u64,
+    idle: Mutex<Vec<Worker>>,
+    alive: AtomicUsize,
+    tasks: AtomicU64,
+    fallbacks: AtomicU64,
+    recycled: AtomicU64,
+}
+
+impl WorkerPool {
+    pub fn start(interpreter: &str, size: usize, max_task_memory_mb: u64) -> Self {
+        let pool = Self {
+            interpreter: interpreter.to_string(),
+            size,
+            max_task_memory_kb: max_task_memory_mb * 1024,
+            idle: Mutex::new(Vec::with_capacity(size)),
+            alive: AtomicUsize::new(0),
+            tasks: AtomicU64::new(0),
+            fallbacks: AtomicU64::new(0),
+            recycled: AtomicU64::new(0),
+        };
+        for _ in 0..size {
+            pool.add_worker();
+        }
+        info!("Started worker pool of {} processes ({})", pool.alive.load(Ordering::Relaxed), interpreter);
+        pool
+    }
+
+    /// Выполняется ли скрипт пулом: скрипты с собственным интерпретатором
+    /// запускаются обычным способом
+    pub fn accepts(&self, script_name: &str, interpreter: &str) -> bool {
+        Path::new(script_name).extension().and_then(|ext| ext.to_str()) == Some(POOL_EXTENSION)
+            && interpreter == self.interpreter
+    }
+
+    fn add_worker(&self) {
+        match spawn_worker(&self.interpreter) {
+            Ok(worker) => {
+                self.alive.fetch_add(1, Ordering::Relaxed);
+                self.idle.lock().unwrap().push(worker);
+            }
+            Err(e) => warn!("Failed to start pool worker '{}': {}", self.interpreter, e),
+        }
+    }
+
+    // Воркер завершается при удалении (kill_on_drop), на его место запускается новый
+    fn recycle(&self, worker: Worker, reason: &str) {
+        warn!("Recycling pool worker: {}", reason);
+        drop(worker);
+        self.alive.fetch_sub(1, Ordering::Relaxed);
+        self.recycled.fetch_add(1, Ordering::Relaxed);
+        self.add_worker();
+    }
+
+    /// Выполняет задачу свободным воркером. `None` — свободных воркеров нет,
+    /// скрипт нужно запустить обычным способом.
+    pub async fn try_run<T>(
+        &self,
+        task: PoolTask<'_>,
+        interrupt: impl Future<Output = T>,
+    ) -> Option<Result<PoolRun<T>, AppError>> {
+        let worker = self.idle.lock().unwrap().pop();
+        let Some(worker) = worker else {
+            self.fallbacks.fetch_add(1, Ordering::Relaxed);
+            return None;
+        };
+        self.tasks.fetch_add(1, Ordering::Relaxed);
+        Some(self.run(worker, task, interrupt).await)
+    }
+
+    async fn run<T>(
+        &self,
+        mut worker: Worker,
+        task: PoolTask<'_>,
+        interrupt: impl Future<Output = T>,
+    ) -> Result<PoolRun<T>, AppError> {
+        let stdin_path = task.workdir.join(".stdin");
+        let stdout_path = task.workdir.join(".stdout");
+        let stderr_path = task.workdir.join(".stderr");
+        let prepared = async {
+            fs::write(&stdin_path, task.input).await?;
+            let script = std::path::absolute(task.script_path)?;
+            let workdir = std::path::absolute(task.workdir)?;
+            let message = TaskMessage {
+                script: &script,
+                args: task.args,
+                cwd: &workdir,
+                env: task.env,
+                stdin: &stdin_path,
+                stdout: &stdout_path,
+                stderr: &stderr_path,
+                max_output_bytes: task.max_output_bytes,
+                timeout_secs: task.timeout.as_secs_f64(),
+            };
+            let mut line = serde_json::to_string(&message)?;
+            line.push('\n');
+            Ok::<_, io::Error>(line)
+        };
+        let line = match prepared.await {
+            Ok(line) => line,
+            Err(e) => {
+                self.idle.lock().unwrap().push(worker);
+                return Err(e.into());
+            }
+        };
+
+        let (finished, interrupted) = match exchange(&mut worker, &line, task.timeout, interrupt).await {
+            Ok(reply) => reply,
+            Err(e) => {
+                self.recycle(worker, &e.to_string());
+                return Err(AppError::Internal(format!("Pool worker failed: {}", e)));
+            }
+        };
+
+        if finished.task_rss_kb > self.max_task_memory_kb || finished.worker_rss_kb > self.max_task_memory_kb {
+            let reason = format!(
+                "memory threshold exceeded (task {} KiB, worker {} KiB)",
+                finished.task_rss_kb, finished.worker_rss_kb
+            );
+            self.recycle(worker, &reason);
+        } else {
+            self.idle.lock().unwrap().push(worker);
+        }
+
+        let stdout = read_output(&stdout_path, task.max_output_bytes).await?;
+        let stderr = read_output(&stderr_path, task.max_output_bytes).await?;
+        // Превышение лимита вывода завершает задачу, как и при обычном запуске
+        let exit_code = if stdout.truncated || stderr.truncated { -1 } else { finished.exit_code };
+        Ok(PoolRun {
+            stdout,
+            stderr,
+            exit_code,
+            signal: finished.signal,
+            timed_out: finished.timed_out,
+            interrupted,
+        })
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        let alive = self.alive.load(Ordering::Relaxed);
+        let idle = self.idle.lock().unwrap().len();
+        PoolStats {
+            size: self.size,
+            alive,
+            busy: alive.saturating_sub(idle),
+            tasks: self.tasks.load(Ordering::Relaxed),
+            fallbacks: self.fallbacks.load(Ordering::Relaxed),
+            recycled: self.recycled.load(Ordering::Relaxed),
+        }
+    }
+}
+
+// Отправляет задачу и ждёт итога. Таймаут задачи соблюдает сам воркер;
+// при прерывании задача убивается, а воркер всё равно присылает итог.
+async fn exchange<T>(
+    worker: &mut Worker,
+    line: &str,
+    task_timeout: Duration,
+    interrupt: impl Future<Output = T>,
+) -> io::Result<(Finished, Option<T>)> {
+    worker.stdin.write_all(line.as_bytes()).await?;
+    worker.stdin.flush().await?;
+    let started: Started = read_message(&mut worker.stdout).await?;
+
+    let reply = read_message::<Finished>(&mut worker.stdout);
+    tokio::pin!(reply);
+    let interrupted = tokio::select! {
+        res = timeout(task_timeout + REPLY_GRACE, &mut reply) => {
+            return match res {
+                Ok(finished) => Ok((finished?, None)),
+                Err(_) => {
+                    kill_task(started.pid);
+                    Err(io::Error::new(io::ErrorKind::TimedOut, "pool worker did not reply"))
+                }
+            };
+        }
+        value = interrupt => value,
+    };
+
+    kill_task(started.pid);
+    let finished = timeout(REPLY_GRACE, reply)
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "pool worker did not reply"))??;
+    Ok((finished, Some(interrupted)))
+}